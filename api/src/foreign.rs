@@ -15,6 +15,7 @@
 
 //! Foreign API External Definition
 
+use crate::grinrelay::receive_tx_with_proof;
 use crate::keychain::Keychain;
 use crate::libwallet::api_impl::foreign;
 use crate::libwallet::{
@@ -24,11 +25,50 @@ use crate::util::Mutex;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-/// ForeignAPI Middleware Check callback
-pub type ForeignCheckMiddleware =
-	fn(ForeignCheckMiddlewareFn, Option<NodeVersionInfo>, Option<&Slate>) -> Result<(), Error>;
+/// ForeignAPI Middleware Check, called with the locked wallet before each
+/// Foreign API function. Returning an error aborts the call.
+///
+/// Middlewares are trait objects so they can hold their own configuration and
+/// state, like counters or a database handle. Plain functions taking the function
+/// identifier, the node version info and the slate are middlewares as well.
+pub trait ForeignCheckMiddleware<W: ?Sized, C, K>: Send + Sync
+where
+	W: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	/// Check whether the call `name` should go on
+	fn check(
+		&self,
+		name: ForeignCheckMiddlewareFn,
+		wallet: &mut W,
+		slate: Option<&Slate>,
+		context: &ForeignRequestContext,
+	) -> Result<(), Error>;
+}
+
+impl<F, W: ?Sized, C, K> ForeignCheckMiddleware<W, C, K> for F
+where
+	F: Fn(ForeignCheckMiddlewareFn, Option<NodeVersionInfo>, Option<&Slate>) -> Result<(), Error>
+		+ Send
+		+ Sync,
+	W: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	fn check(
+		&self,
+		name: ForeignCheckMiddlewareFn,
+		wallet: &mut W,
+		slate: Option<&Slate>,
+		_context: &ForeignRequestContext,
+	) -> Result<(), Error> {
+		self(name, wallet.w2n_client().get_version_info(), slate)
+	}
+}
 
 /// Middleware Identifiers for each function
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ForeignCheckMiddlewareFn {
 	/// check_version
 	CheckVersion,
@@ -42,6 +82,38 @@ pub enum ForeignCheckMiddlewareFn {
 	FinalizeInvoiceTx,
 }
 
+/// Transport a Foreign API request came in through
+#[derive(Clone, Debug, PartialEq)]
+pub enum ForeignTransport {
+	/// Called from within the wallet itself, e.g. receiving a slate file
+	Local,
+	/// The HTTP/S Foreign API listener
+	Http,
+	/// The Grin Relay listener
+	GrinRelay,
+	/// The inbox of the directory transport
+	Directory,
+	/// The keybase listener
+	Keybase,
+}
+
+impl Default for ForeignTransport {
+	fn default() -> ForeignTransport {
+		ForeignTransport::Local
+	}
+}
+
+/// What the middlewares know about the origin of a Foreign API request
+#[derive(Clone, Debug, Default)]
+pub struct ForeignRequestContext {
+	/// Transport the request came in through
+	pub transport: ForeignTransport,
+	/// Address of the HTTP peer, or Grin Relay address of the sender, when known
+	pub remote_addr: Option<String>,
+	/// Account the request operates on, `None` for the default account
+	pub account: Option<String>,
+}
+
 /// Main interface into all wallet API functions.
 /// Wallet APIs are split into two seperate blocks of functionality
 /// called the ['Owner'](struct.Owner.html) and ['Foreign'](struct.Foreign.html) APIs
@@ -71,8 +143,10 @@ where
 	phantom: PhantomData<K>,
 	/// phantom
 	phantom_c: PhantomData<C>,
-	/// foreign check middleware
-	middleware: Option<ForeignCheckMiddleware>,
+	/// foreign check middlewares, called in order
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<W, C, K>>>,
+	/// origin of the requests, handed to the middlewares
+	context: ForeignRequestContext,
 }
 
 impl<'a, W: ?Sized, C, K> Foreign<W, C, K>
@@ -92,8 +166,9 @@ where
 	/// # Arguments
 	/// * `wallet_in` - A reference-counted mutex containing an implementation of the
 	/// [`WalletBackend`](../grin_wallet_libwallet/types/trait.WalletBackend.html) trait.
-	/// * middleware - Option middleware which containts the NodeVersionInfo and can call
-	/// a predefined function with the slate to check if the operation should continue
+	/// * middleware - Option [`ForeignCheckMiddleware`](trait.ForeignCheckMiddleware.html),
+	/// called with the wallet and the slate to check if the operation should continue. More
+	/// can be chained with [`add_middleware`](struct.Foreign.html#method.add_middleware)
 	///
 	/// # Returns
 	/// * An instance of the ForeignApi holding a reference to the provided wallet
@@ -141,16 +216,48 @@ where
 	///
 	/// ```
 
-	pub fn new(wallet_in: Arc<Mutex<W>>, middleware: Option<ForeignCheckMiddleware>) -> Self {
+	pub fn new(
+		wallet_in: Arc<Mutex<W>>,
+		middleware: Option<Arc<dyn ForeignCheckMiddleware<W, C, K>>>,
+	) -> Self {
 		Foreign {
 			wallet: wallet_in,
 			doctest_mode: false,
 			phantom: PhantomData,
 			phantom_c: PhantomData,
-			middleware,
+			middlewares: middleware.into_iter().collect(),
+			context: ForeignRequestContext::default(),
 		}
 	}
 
+	/// Append a middleware, called after the ones already set. The first
+	/// middleware returning an error stops the chain and the API call.
+	pub fn add_middleware(&mut self, middleware: Arc<dyn ForeignCheckMiddleware<W, C, K>>) {
+		self.middlewares.push(middleware);
+	}
+
+	/// Set the origin of the subsequent requests, as seen by the middlewares
+	pub fn set_request_context(&mut self, context: ForeignRequestContext) {
+		self.context = context;
+	}
+
+	fn check_middlewares(
+		&self,
+		name: ForeignCheckMiddlewareFn,
+		wallet: &mut W,
+		slate: Option<&Slate>,
+		account: Option<&str>,
+	) -> Result<(), Error> {
+		let mut context = self.context.clone();
+		if let Some(a) = account {
+			context.account = Some(a.to_owned());
+		}
+		for m in &self.middlewares {
+			m.check(name, wallet, slate, &context)?;
+		}
+		Ok(())
+	}
+
 	/// Return the version capabilities of the running ForeignApi Node
 	/// # Arguments
 	/// None
@@ -168,13 +275,9 @@ where
 	/// ```
 
 	pub fn check_version(&self) -> Result<VersionInfo, Error> {
-		if let Some(m) = self.middleware.as_ref() {
+		if !self.middlewares.is_empty() {
 			let mut w = self.wallet.lock();
-			m(
-				ForeignCheckMiddlewareFn::CheckVersion,
-				w.w2n_client().get_version_info(),
-				None,
-			)?;
+			self.check_middlewares(ForeignCheckMiddlewareFn::CheckVersion, &mut *w, None, None)?;
 		}
 		Ok(foreign::check_version())
 	}
@@ -231,13 +334,7 @@ where
 
 	pub fn build_coinbase(&self, block_fees: &BlockFees) -> Result<CbData, Error> {
 		let mut w = self.wallet.lock();
-		if let Some(m) = self.middleware.as_ref() {
-			m(
-				ForeignCheckMiddlewareFn::BuildCoinbase,
-				w.w2n_client().get_version_info(),
-				None,
-			)?;
-		}
+		self.check_middlewares(ForeignCheckMiddlewareFn::BuildCoinbase, &mut *w, None, None)?;
 		w.open_with_credentials()?;
 		let res = foreign::build_coinbase(&mut *w, block_fees, self.doctest_mode);
 		w.close()?;
@@ -283,12 +380,13 @@ where
 	/// ```
 
 	pub fn verify_slate_messages(&self, slate: &Slate) -> Result<(), Error> {
-		if let Some(m) = self.middleware.as_ref() {
+		if !self.middlewares.is_empty() {
 			let mut w = self.wallet.lock();
-			m(
+			self.check_middlewares(
 				ForeignCheckMiddlewareFn::VerifySlateMessages,
-				w.w2n_client().get_version_info(),
+				&mut *w,
 				Some(slate),
+				None,
 			)?;
 		}
 		foreign::verify_slate_messages(slate)
@@ -330,11 +428,19 @@ where
 	/// * a result containing:
 	/// * `Ok`([`slate`](../grin_wallet_libwallet/slate/struct.Slate.html)`)` if successful,
 	/// containing the new slate updated with the recipient's output and public signing information.
+	/// If the sender asked for a payment proof, the slate also holds the recipient signature of
+	/// the payment statement, made with the key of the Grin Relay address at `grinrelay_key_path`,
+	/// or of the default receiving address when `None`. The payment statement is signed and
+	/// kept in the wallet in any case, see
+	/// [`Owner::get_stored_payment_proof`](struct.Owner.html#method.get_stored_payment_proof).
 	/// * or [`libwallet::Error`](../grin_wallet_libwallet/struct.Error.html) if an error is encountered.
 	///
 	/// # Remarks
 	///
 	/// * This method will store a partially completed transaction in the wallet's transaction log.
+	/// * The payment is refused if the receive policy of the wallet doesn't accept it. The sender
+	/// is the `remote_addr` of the [request context](struct.Foreign.html#method.set_request_context)
+	/// when it came in through Grin Relay, and is unknown otherwise.
 	///
 	/// # Example
 	/// Set up as in [new](struct.Foreign.html#method.new) method above.
@@ -362,17 +468,21 @@ where
 		grinrelay_key_path: Option<u64>,
 	) -> Result<Slate, Error> {
 		let mut w = self.wallet.lock();
-		if let Some(m) = self.middleware.as_ref() {
-			m(
-				ForeignCheckMiddlewareFn::ReceiveTx,
-				w.w2n_client().get_version_info(),
-				Some(slate),
-			)?;
-		}
+		self.check_middlewares(
+			ForeignCheckMiddlewareFn::ReceiveTx,
+			&mut *w,
+			Some(slate),
+			dest_acct_name,
+		)?;
+		let sender = match self.context.transport {
+			ForeignTransport::GrinRelay => self.context.remote_addr.as_ref().map(|a| a.as_str()),
+			_ => None,
+		};
 		w.open_with_credentials()?;
-		let res = foreign::receive_tx(
+		let res = receive_tx_with_proof(
 			&mut *w,
 			slate,
+			sender,
 			dest_acct_name,
 			message,
 			grinrelay_key_path,
@@ -431,13 +541,12 @@ where
 
 	pub fn finalize_invoice_tx(&self, slate: &Slate) -> Result<Slate, Error> {
 		let mut w = self.wallet.lock();
-		if let Some(m) = self.middleware.as_ref() {
-			m(
-				ForeignCheckMiddlewareFn::FinalizeInvoiceTx,
-				w.w2n_client().get_version_info(),
-				Some(slate),
-			)?;
-		}
+		self.check_middlewares(
+			ForeignCheckMiddlewareFn::FinalizeInvoiceTx,
+			&mut *w,
+			Some(slate),
+			None,
+		)?;
 		w.open_with_credentials()?;
 		let res = foreign::finalize_invoice_tx(&mut *w, slate);
 		w.close()?;
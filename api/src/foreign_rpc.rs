@@ -22,6 +22,7 @@ use crate::libwallet::{
 };
 use crate::{Foreign, ForeignCheckMiddlewareFn};
 use easy_jsonrpc;
+use std::sync::Arc;
 
 /// Public definition used to generate Foreign jsonrpc api.
 /// * When running `grin-wallet listen` with defaults, the V2 api is available at
@@ -53,6 +54,7 @@ pub trait ForeignRpc {
 			"Ok": {
 				"foreign_api_version": 2,
 				"supported_slate_versions": [
+					"V3",
 					"V2"
 				]
 			}
@@ -686,8 +688,8 @@ pub fn run_doctest_foreign(
 	}
 
 	let mut api_foreign = match init_invoice_tx {
-		false => Foreign::new(wallet1.clone(), Some(test_check_middleware)),
-		true => Foreign::new(wallet2.clone(), Some(test_check_middleware)),
+		false => Foreign::new(wallet1.clone(), Some(Arc::new(test_check_middleware))),
+		true => Foreign::new(wallet2.clone(), Some(Arc::new(test_check_middleware))),
 	};
 	api_foreign.doctest_mode = true;
 	let foreign_api = &api_foreign as &dyn ForeignRpc;
@@ -41,11 +41,19 @@ mod foreign;
 mod foreign_rpc;
 mod owner;
 mod owner_rpc;
-pub use crate::foreign::{Foreign, ForeignCheckMiddleware, ForeignCheckMiddlewareFn};
+mod wallets;
+mod wallets_rpc;
+pub use crate::foreign::{
+	Foreign, ForeignCheckMiddleware, ForeignCheckMiddlewareFn, ForeignRequestContext,
+	ForeignTransport,
+};
 pub use crate::foreign_rpc::ForeignRpc;
-pub use crate::owner::Owner;
+pub use crate::owner::{verify_tx_proofs, Owner};
 pub use crate::owner_rpc::OwnerRpc;
+pub use crate::wallets::{WalletOpener, Wallets};
+pub use crate::wallets_rpc::WalletsRpc;
 
 pub use crate::foreign_rpc::foreign_rpc as foreign_rpc_client;
 pub use crate::foreign_rpc::run_doctest_foreign;
 pub use crate::owner_rpc::run_doctest_owner;
+pub use crate::wallets_rpc::run_doctest_wallets;
@@ -15,28 +15,36 @@
 
 //! Owner API External Definition
 
-use crate::util::{Mutex, ZeroingString};
+use crate::util::{Mutex, RwLock, ZeroingString};
 use chrono::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::core::core::Transaction;
+use crate::core::core::{Transaction, TxKernelApiEntry};
 use crate::grinrelay::hasher::derive_address_key;
-use crate::grinrelay::{sign_challenge, GrinboxAddress, TxProofImpl};
-use crate::impls::{HTTPNodeClient, HTTPWalletCommAdapter, KeybaseWalletCommAdapter, WalletSeed};
+use crate::grinrelay::{
+	payment_proof_address, sign_challenge, verify_payment_proof, GrinboxAddress, TxProofImpl,
+};
+use crate::impls::{
+	HTTPNodeClient, HTTPWalletCommAdapter, HttpTransportConfig, KeybaseWalletCommAdapter,
+	WalletSeed, SEED_FILE,
+};
 use crate::keychain::{Identifier, Keychain};
 use crate::libwallet::api_impl::owner;
 use crate::libwallet::{
-	AcctPathMapping, Error, ErrorKind, InitTxArgs, IssueInvoiceTxArgs, NodeClient,
-	NodeHeightResult, OutputCommitMapping, PaymentData, Slate, TxLogEntry, TxProof,
-	TxProofVerified, WalletBackend, WalletInfo,
+	AcctPathMapping, Error, ErrorKind, InitTxArgs, IssueInvoiceTxArgs, ListenerStatus, NodeClient,
+	NodeHeightResult, OutputCommitMapping, PaymentData, PaymentInfo, PaymentProof, Slate,
+	TxLogEntry, TxProof, TxProofReport, TxProofStatus, TxProofVerified, WalletBackend, WalletInfo,
 };
 use crate::util::secp::key::PublicKey;
 use crate::util::secp::pedersen::Commitment;
 use crate::util::static_secp_instance;
 use crate::util::to_hex;
+use crate::wallets::seed_error;
 
 /// Main interface into all wallet API functions.
 /// Wallet APIs are split into two seperate blocks of functionality
@@ -63,6 +71,9 @@ where
 	pub wallet: Arc<Mutex<W>>,
 	/// Flag to normalize some output during testing. Can mostly be ignored.
 	pub doctest_mode: bool,
+	/// Status of the listeners run in the same process, kept up to date by
+	/// the listener supervisor
+	listeners: Option<Arc<RwLock<Vec<ListenerStatus>>>>,
 	phantom: PhantomData<K>,
 	phantom_c: PhantomData<C>,
 }
@@ -135,11 +146,18 @@ where
 		Owner {
 			wallet: wallet_in,
 			doctest_mode: false,
+			listeners: None,
 			phantom: PhantomData,
 			phantom_c: PhantomData,
 		}
 	}
 
+	/// Report the status of the listeners run in the same process through
+	/// [`listener_status`](struct.Owner.html#method.listener_status)
+	pub fn set_listeners(&mut self, listeners: Arc<RwLock<Vec<ListenerStatus>>>) {
+		self.listeners = Some(listeners);
+	}
+
 	/// Returns a list of accounts stored in the wallet (i.e. mappings between
 	/// user-specified labels and BIP32 derivation paths.
 	///
@@ -544,10 +562,20 @@ where
 
 	pub fn init_send_tx(&self, args: InitTxArgs) -> Result<Slate, Error> {
 		let send_args = args.send_args.clone();
+		let payment_proof =
+			args.payment_proof.unwrap_or(false) && !args.estimate_only.unwrap_or(false);
 		let mut slate = {
 			let mut w = self.wallet.lock();
 			w.open_with_credentials()?;
-			let slate = owner::init_send_tx(&mut *w, args, self.doctest_mode)?;
+			let mut slate = owner::init_send_tx(&mut *w, args, self.doctest_mode)?;
+			if payment_proof {
+				let (_, sender_address) = payment_proof_address(w.keychain(), 0)?;
+				slate.payment_proof = Some(PaymentInfo {
+					sender_address,
+					receiver_address: None,
+					receiver_signature: None,
+				});
+			}
 			w.close()?;
 			slate
 		};
@@ -556,7 +584,9 @@ where
 			Some(sa) => {
 				match sa.method.as_ref() {
 					"http" => {
-						let adapter = HTTPWalletCommAdapter::new();
+						let config = self.wallet.lock().transport_config("http");
+						let config = HttpTransportConfig::from_config(config.as_ref())?;
+						let adapter = HTTPWalletCommAdapter::with_config(config);
 						let res = adapter.send_tx_sync(&sa.dest, &slate)?;
 						slate = res.0;
 					}
@@ -816,13 +846,56 @@ where
 		grinrelay_key_path: Option<u64>,
 	) -> Result<Slate, Error> {
 		let mut w = self.wallet.lock();
+		if w.is_watch_only() {
+			return Err(ErrorKind::WatchOnly("finalize tx".to_owned()).into());
+		}
 		let mut slate = slate.clone();
 		w.open_with_credentials()?;
+		let payment_proof = Self::signed_payment_proof(&mut *w, &slate)?;
 		slate = owner::finalize_tx(&mut *w, &slate, tx_proof, grinrelay_key_path)?;
+		if let Some(proof) = payment_proof {
+			w.store_payment_proof(&slate.id.to_string(), &proof)?;
+		}
 		w.close()?;
 		Ok(slate)
 	}
 
+	/// Payment proof signed by the receiver in a slate we are about to
+	/// finalize, checked against our own address and the transaction
+	fn signed_payment_proof(w: &mut W, slate: &Slate) -> Result<Option<PaymentProof>, Error> {
+		let info = match slate.payment_proof {
+			Some(ref info) => info,
+			None => return Ok(None),
+		};
+		let (receiver_address, receiver_signature) =
+			match (&info.receiver_address, &info.receiver_signature) {
+				(Some(a), Some(s)) => (a.clone(), s.clone()),
+				_ => {
+					warn!(
+						"No payment proof signed by the receiver of transaction {}",
+						slate.id
+					);
+					return Ok(None);
+				}
+			};
+		let (_, sender_address) = payment_proof_address(w.keychain(), 0)?;
+		if info.sender_address != sender_address {
+			return Err(ErrorKind::VerifyProof(
+				"Payment proof sender address is not ours".to_owned(),
+			)
+			.into());
+		}
+		let proof = PaymentProof {
+			amount: slate.amount,
+			excess: slate.calc_excess(w.keychain())?,
+			sender_address,
+			receiver_address,
+			receiver_signature,
+		};
+		verify_payment_proof(&proof).map_err(|e| ErrorKind::VerifyProof(format!("{}", e)))?;
+		Ok(Some(proof))
+	}
+
 	/// Posts a completed transaction to the listening node for validation and inclusion in a block
 	/// for mining.
 	///
@@ -1067,6 +1140,44 @@ where
 		owner::get_stored_tx_proof(&mut *w, tx_id, tx_slate_id)
 	}
 
+	/// Retrieves the payment proof signed by the receiver of a transaction
+	/// sent with [`InitTxArgs::payment_proof`](../grin_wallet_libwallet/types/struct.InitTxArgs.html#structfield.payment_proof)
+	/// set, whatever the transport it went through.
+	pub fn get_stored_payment_proof(
+		&self,
+		tx_id: Option<u32>,
+		tx_slate_id: Option<Uuid>,
+	) -> Result<Option<PaymentProof>, Error> {
+		let mut w = self.wallet.lock();
+		owner::get_stored_payment_proof(&mut *w, tx_id, tx_slate_id)
+	}
+
+	/// Verifies a payment proof, its receiver signature and whether its
+	/// kernel is on the chain, and returns relevant information
+	pub fn verify_payment_proof(
+		&self,
+		proof: &PaymentProof,
+		check_node_api_http_addr: &str,
+		node_api_secret: Option<String>,
+	) -> Result<TxProofVerified, Error> {
+		let mut verified = check_payment_proof(proof)?;
+		let node_client = HTTPNodeClient::new(check_node_api_http_addr, node_api_secret);
+		check_confirmed(&node_client, &mut verified)?;
+		Ok(verified)
+	}
+
+	/// Verifies a payment proof without contacting any node, see
+	/// [`verify_tx_proof_offline`](struct.Owner.html#method.verify_tx_proof_offline)
+	pub fn verify_payment_proof_offline(
+		&self,
+		proof: &PaymentProof,
+		kernel: Option<&TxKernelApiEntry>,
+	) -> Result<TxProofVerified, Error> {
+		let mut verified = check_payment_proof(proof)?;
+		check_kernel_record(kernel, &mut verified)?;
+		Ok(verified)
+	}
+
 	/// Verifies a transaction proof and returns relevant information
 	pub fn verify_tx_proof(
 		&self,
@@ -1074,109 +1185,65 @@ where
 		check_node_api_http_addr: &str,
 		node_api_secret: Option<String>,
 	) -> Result<TxProofVerified, Error> {
-		// Check signature on the message
-		let slate = tx_proof.verify_extract(String::new())?;
-
-		// Inputs owned by sender
-		let inputs_ex = tx_proof.inputs.iter().collect::<HashSet<_>>();
-
-		let slate: Slate = slate.into();
-
-		// Select inputs owned by the receiver (usually none)
-		let mut inputs: Vec<Commitment> = slate
-			.tx
-			.inputs()
-			.iter()
-			.map(|i| i.commitment())
-			.filter(|c| !inputs_ex.contains(c))
-			.collect();
-
-		// Outputs owned by sender
-		let outputs_ex = tx_proof.outputs.iter().collect::<HashSet<_>>();
-
-		// Select outputs owned by the receiver
-		let outputs: Vec<Commitment> = slate
-			.tx
-			.outputs()
-			.iter()
-			.map(|o| o.commitment())
-			.filter(|c| !outputs_ex.contains(c))
-			.collect();
-
-		// Receiver's excess
-		let excess = &slate.participant_data[1].public_blind_excess;
-
-		let excess_sum_com = {
-			let secp = static_secp_instance();
-			let secp = secp.lock();
-
-			// Calculate receiver's excess from their inputs and inputs
-			let commit_amount = secp.commit_value(tx_proof.amount)?;
-			inputs.push(commit_amount);
-
-			let commit_excess = secp.commit_sum(outputs.clone(), inputs)?;
-			let pubkey_excess = commit_excess.to_pubkey(&secp)?;
-
-			// Verify receiver's excess with their inputs and outputs
-			if excess != &pubkey_excess {
-				return Err(ErrorKind::VerifyProof(
-					"Verify receiver's excess with their inputs and outputs".to_string(),
-				)
-				.into());
-			}
-
-			// Calculate kernel excess from inputs and outputs
-			let excess_parts: Vec<&PublicKey> = slate
-				.participant_data
-				.iter()
-				.map(|p| &p.public_blind_excess)
-				.collect();
-			let excess_sum = PublicKey::from_combination(&secp, excess_parts)?;
-
-			let mut input_com: Vec<Commitment> =
-				slate.tx.inputs().iter().map(|i| i.commitment()).collect();
-
-			let mut output_com: Vec<Commitment> =
-				slate.tx.outputs().iter().map(|o| o.commitment()).collect();
-
-			input_com.push(secp.commit(0, slate.tx.offset.secret_key(&secp)?)?);
-
-			output_com.push(secp.commit_value(slate.fee)?);
-
-			let excess_sum_com = secp.commit_sum(output_com, input_com)?;
-
-			// Verify kernel excess with all inputs and outputs
-			if excess_sum_com.to_pubkey(&secp)? != excess_sum {
-				return Err(ErrorKind::VerifyProof(
-					"Verify kernel excess with all inputs and outputs".to_string(),
-				)
-				.into());
-			}
-
-			excess_sum_com
-		};
-
-		// Check whether this transaction has been confirmed on the chain
-		let excess = to_hex(excess_sum_com.as_ref().to_vec());
+		let mut verified = check_tx_proof(tx_proof)?;
 		let node_client = HTTPNodeClient::new(check_node_api_http_addr, node_api_secret);
-		let api_tx_kernels = node_client.get_tx_kernel_from_node(excess)?;
+		check_confirmed(&node_client, &mut verified)?;
+		Ok(verified)
+	}
 
-		let (confirmed, height) = if api_tx_kernels.len() > 0 {
-			let tx_kernel = api_tx_kernels.get(&excess_sum_com).unwrap();
-			(true, Some(tx_kernel.height))
-		} else {
-			(false, None)
-		};
+	/// Verifies a transaction proof without contacting any node, e.g. on an
+	/// air-gapped machine. The signature and the excess reconstruction are
+	/// checked as in [`verify_tx_proof`](struct.Owner.html#method.verify_tx_proof),
+	/// but whether the transaction is confirmed stays unknown unless a kernel
+	/// inclusion record is supplied.
+	///
+	/// # Arguments
+	/// * `tx_proof` - The transaction proof to verify.
+	/// * `kernel` - An optional kernel inclusion record, as returned for the
+	/// proof excess by the node `v1/chain/kernels/byids` API on another machine.
+	/// Its excess must match the proof and its signature must be valid, its
+	/// height is then reported as is, it can't be checked against the chain.
+	///
+	/// # Returns
+	/// * Ok with a [`TxProofVerified`](../grin_wallet_libwallet/types/struct.TxProofVerified.html)
+	/// which has `offline` set, and `confirmed` only if a kernel record was supplied.
+	/// * or [`libwallet::Error`](../grin_wallet_libwallet/struct.Error.html) if the
+	/// proof or the kernel record is not valid.
+	pub fn verify_tx_proof_offline(
+		&self,
+		tx_proof: &TxProof,
+		kernel: Option<&TxKernelApiEntry>,
+	) -> Result<TxProofVerified, Error> {
+		let mut verified = check_tx_proof(tx_proof)?;
+		check_kernel_record(kernel, &mut verified)?;
+		Ok(verified)
+	}
 
-		return Ok(TxProofVerified {
-			sender: tx_proof.sender_address.clone(),
-			receiver: tx_proof.recipient_address.clone(),
-			amount: tx_proof.amount,
-			outputs,
-			excess: excess_sum_com,
-			confirmed,
-			height,
-		});
+	/// Verifies every transaction proof or payment proof file found in a
+	/// directory, and reports the result of each of them. All the kernels are
+	/// looked up on the node at once, so this scales to many proofs.
+	///
+	/// # Arguments
+	/// * `proof_dir` - The directory holding the proof files, as exported by
+	/// `grin-wallet proof export`. Sub-directories are ignored.
+	/// * `check_node_api_http_addr` - The node to look the kernels up on, or
+	/// none to only do the cryptographic checks offline, leaving the
+	/// confirmation of every proof unknown.
+	/// * `node_api_secret` - The node API secret, if any.
+	///
+	/// # Returns
+	/// * Ok with one [`TxProofReport`](../grin_wallet_libwallet/types/struct.TxProofReport.html)
+	/// per file, ordered by file name. A file which is not a valid proof has its
+	/// `error` set, it doesn't fail the whole batch.
+	/// * or [`libwallet::Error`](../grin_wallet_libwallet/struct.Error.html) if the
+	/// directory can't be read or the node can't be reached.
+	pub fn verify_tx_proofs(
+		&self,
+		proof_dir: &str,
+		check_node_api_http_addr: Option<&str>,
+		node_api_secret: Option<String>,
+	) -> Result<Vec<TxProofReport>, Error> {
+		verify_tx_proofs(proof_dir, check_node_api_http_addr, node_api_secret)
 	}
 
 	/// Sign a transaction proof and update relevant information: prover_msg, prover_signature
@@ -1201,6 +1268,9 @@ where
 
 		let (sec_key, pub_key) = {
 			let mut w = self.wallet.lock();
+			if w.is_watch_only() {
+				return Err(ErrorKind::WatchOnly("sign tx proof".to_owned()).into());
+			}
 			w.open_with_credentials()?;
 			let keychain = w.keychain();
 			let sec_key = derive_address_key(keychain, path, index)?;
@@ -1559,6 +1629,9 @@ where
 		}
 
 		let mut w = self.wallet.lock();
+		if w.is_watch_only() {
+			return Err(ErrorKind::WatchOnly("change password".to_owned()).into());
+		}
 		w.open_with_credentials()?;
 
 		//TODO: move these out of 'grin_wallet_api' crate, since it's not an owner_api.
@@ -1579,6 +1652,427 @@ where
 		w.close()?;
 		Ok(())
 	}
+
+	/// Creates the seed of this wallet, when its data dir doesn't hold one
+	/// yet, with a random seed of the given length, and returns its recovery
+	/// phrase. The wallet instance only needs to be created, not opened, so a
+	/// front-end can onboard its user through the Owner API.
+	///
+	/// # Arguments
+	///
+	/// * `seed_length` - the seed length in bytes, one of 16, 20, 24, 28 or 32,
+	/// for a recovery phrase of 12, 15, 18, 21 or 24 words
+	/// * `passphrase` - optional BIP39 passphrase, used along with the recovery
+	/// phrase to derive the wallet keys
+	/// * `password` - the password encrypting the seed, used by the following
+	/// calls on this instance
+	///
+	/// # Returns
+	///
+	/// * `Ok(phrase)` with the BIP39 recovery phrase of the seed if successful.
+	/// This is the only time the phrase is given out, callers must make sure the
+	/// user backs it up.
+	/// * or [`libwallet::Error`](../grin_wallet_libwallet/struct.Error.html) if an error is encountered,
+	/// including when the wallet already has a seed.
+	///
+	/// # Remarks
+	///
+	/// * The seed is written to the data dir of the wallet instance, never to a
+	/// path given by the caller.
+	///
+	/// # Example
+	/// Set up as in [`new`](struct.Owner.html#method.new) method above.
+	/// ```
+	/// # grin_wallet_api::doctest_helper_setup_doc_env!(wallet, wallet_config);
+	///
+	/// let api_owner = Owner::new(wallet.clone());
+	/// let result = api_owner.create_wallet(32, None, "password");
+	/// if let Ok(phrase) = result {
+	///		// show the phrase to the user, once
+	/// }
+	/// assert!(api_owner.wallet_exists().unwrap());
+	/// ```
+	pub fn create_wallet(
+		&self,
+		seed_length: usize,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<ZeroingString, Error> {
+		if ![16, 20, 24, 28, 32].contains(&seed_length) {
+			return Err(ErrorKind::GenericError(format!(
+				"invalid seed length {}, must be one of 16, 20, 24, 28 or 32",
+				seed_length
+			))
+			.into());
+		}
+		// normalize the seed for the doc tests
+		let recovery_phrase = match self.doctest_mode {
+			true => Some(ZeroingString::from(
+				WalletSeed::from_bytes(&vec![0; seed_length])
+					.to_mnemonic()
+					.map_err(seed_error)?,
+			)),
+			false => None,
+		};
+		let seed = self.init_seed(seed_length, recovery_phrase, passphrase, password)?;
+		Ok(ZeroingString::from(seed.to_mnemonic().map_err(seed_error)?))
+	}
+
+	/// Recreates the seed of this wallet from its recovery phrase, when its
+	/// data dir doesn't hold one yet.
+	///
+	/// # Arguments
+	///
+	/// * `recovery_phrase` - the BIP39 recovery phrase of the seed
+	/// * `passphrase` - the BIP39 passphrase the wallet was created with, if any.
+	/// A different passphrase recovers a different wallet.
+	/// * `password` - the password encrypting the seed, used by the following
+	/// calls on this instance
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` if successful
+	/// * or [`libwallet::Error`](../grin_wallet_libwallet/struct.Error.html) if an error is encountered,
+	/// including when the wallet already has a seed.
+	///
+	/// # Remarks
+	///
+	/// * Only the seed is recovered. The outputs of the wallet are found by a
+	/// [`restore`](struct.Owner.html#method.restore) afterwards.
+	///
+	/// # Example
+	/// Set up as in [`new`](struct.Owner.html#method.new) method above.
+	/// ```
+	/// # grin_wallet_api::doctest_helper_setup_doc_env!(wallet, wallet_config);
+	///
+	/// let api_owner = Owner::new(wallet.clone());
+	/// let phrase = "fat twenty mean degree forget shell check candy immense awful \
+	///		flame next during february bulb bike sun wink theory day kiwi embrace peace lunch";
+	/// let result = api_owner.recover_wallet(phrase, None, "password");
+	/// if let Ok(_) = result {
+	///		// restore the wallet outputs
+	/// }
+	/// ```
+	pub fn recover_wallet(
+		&self,
+		recovery_phrase: &str,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<(), Error> {
+		let phrase = ZeroingString::from(recovery_phrase);
+		self.init_seed(0, Some(phrase), passphrase, password)?;
+		Ok(())
+	}
+
+	/// Whether this wallet has a seed yet, i.e. whether it has been created
+	/// or recovered. A wallet without a seed only serves
+	/// [`create_wallet`](struct.Owner.html#method.create_wallet) and
+	/// [`recover_wallet`](struct.Owner.html#method.recover_wallet).
+	///
+	/// # Example
+	/// Set up as in [`new`](struct.Owner.html#method.new) method above.
+	/// ```
+	/// # grin_wallet_api::doctest_helper_setup_doc_env!(wallet, wallet_config);
+	///
+	/// let api_owner = Owner::new(wallet.clone());
+	/// assert_eq!(api_owner.wallet_exists().unwrap(), false);
+	/// ```
+	pub fn wallet_exists(&self) -> Result<bool, Error> {
+		let w = self.wallet.lock();
+		Ok(Path::new(w.wallet_data_dir()).join(SEED_FILE).exists())
+	}
+
+	/// Writes the seed of this wallet, and has the wallet instance use its
+	/// password
+	fn init_seed(
+		&self,
+		seed_length: usize,
+		recovery_phrase: Option<ZeroingString>,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<WalletSeed, Error> {
+		// hold the lock until the password is set, so no other call sees the
+		// seed before the instance can open it
+		let mut w = self.wallet.lock();
+		if w.is_watch_only() {
+			return Err(ErrorKind::WatchOnly("create a wallet seed".to_owned()).into());
+		}
+		let data_dir = w.wallet_data_dir().to_owned();
+		if Path::new(&data_dir).join(SEED_FILE).exists() {
+			return Err(ErrorKind::WalletSeedExists.into());
+		}
+		let seed = WalletSeed::init_file(
+			&data_dir,
+			seed_length,
+			recovery_phrase,
+			passphrase.map(ZeroingString::from),
+			password,
+			false,
+		)
+		.map_err(seed_error)?;
+		w.update_passphrase(password);
+		Ok(seed)
+	}
+
+	/// Status of the transport listeners (HTTP, Grin Relay, Keybase) run by
+	/// `grin-wallet listen` in the same process as this owner API.
+	///
+	/// # Returns
+	/// * Ok with a [`ListenerStatus`](../grin_wallet_libwallet/api_impl/types/struct.ListenerStatus.html)
+	/// for each listener, empty if the owner API runs on its own
+	///
+	/// # Example
+	/// Set up as in [`new`](struct.Owner.html#method.new) method above.
+	/// ```
+	/// # grin_wallet_api::doctest_helper_setup_doc_env!(wallet, wallet_config);
+	///
+	/// let api_owner = Owner::new(wallet.clone());
+	/// assert!(api_owner.listener_status().unwrap().is_empty());
+	/// ```
+	pub fn listener_status(&self) -> Result<Vec<ListenerStatus>, Error> {
+		Ok(self
+			.listeners
+			.as_ref()
+			.map(|l| l.read().clone())
+			.unwrap_or_default())
+	}
+}
+
+/// Verifies every transaction proof or payment proof file found in a
+/// directory, and reports the result of each of them, see
+/// [`Owner::verify_tx_proofs`](struct.Owner.html#method.verify_tx_proofs).
+/// No wallet is needed, e.g. on an air-gapped machine.
+pub fn verify_tx_proofs(
+	proof_dir: &str,
+	check_node_api_http_addr: Option<&str>,
+	node_api_secret: Option<String>,
+) -> Result<Vec<TxProofReport>, Error> {
+	let mut files = vec![];
+	let entries = fs::read_dir(proof_dir).map_err(|e| {
+		ErrorKind::GenericError(format!(
+			"Unable to read proof directory {}: {}",
+			proof_dir, e
+		))
+	})?;
+	for entry in entries {
+		let path = entry
+			.map_err(|e| ErrorKind::GenericError(format!("{}", e)))?
+			.path();
+		if path.is_file() {
+			files.push(path);
+		}
+	}
+	files.sort();
+
+	let mut checked = vec![];
+	for path in files {
+		let file = path
+			.file_name()
+			.map(|f| f.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		let verified = fs::read_to_string(&path)
+			.map_err(|e| Error::from(ErrorKind::GenericError(format!("{}", e))))
+			.and_then(|content| match serde_json::from_str::<TxProof>(&content) {
+				Ok(tx_proof) => check_tx_proof(&tx_proof),
+				Err(_) => match serde_json::from_str::<PaymentProof>(&content) {
+					Ok(proof) => check_payment_proof(&proof),
+					Err(_) => Err(ErrorKind::VerifyProof(
+						"not a transaction proof nor a payment proof".to_owned(),
+					)
+					.into()),
+				},
+			});
+		checked.push((file, verified));
+	}
+
+	// One node query for all the kernels of the valid proofs
+	let excesses = checked
+		.iter()
+		.filter_map(|(_, v)| v.as_ref().ok())
+		.map(|v| to_hex(v.excess.as_ref().to_vec()))
+		.collect::<Vec<_>>();
+	let api_tx_kernels = match check_node_api_http_addr {
+		Some(addr) if !excesses.is_empty() => {
+			let node_client = HTTPNodeClient::new(addr, node_api_secret);
+			node_client.get_tx_kernels_from_node(excesses)?
+		}
+		_ => HashMap::new(),
+	};
+
+	Ok(checked
+		.into_iter()
+		.map(|(file, verified)| match verified {
+			Ok(v) => {
+				let height = api_tx_kernels.get(&v.excess).map(|k| k.height);
+				let status = match (check_node_api_http_addr, height) {
+					(None, _) => TxProofStatus::ConfirmationUnknown,
+					(Some(_), Some(_)) => TxProofStatus::Confirmed,
+					(Some(_), None) => TxProofStatus::Unconfirmed,
+				};
+				TxProofReport {
+					file,
+					status,
+					sender: Some(v.sender),
+					receiver: Some(v.receiver),
+					amount: Some(v.amount),
+					excess: Some(to_hex(v.excess.as_ref().to_vec())),
+					height,
+					error: None,
+				}
+			}
+			Err(e) => TxProofReport::failed(&file, format!("{}", e)),
+		})
+		.collect())
+}
+
+/// Checks the receiver signature of a payment proof, without asking the
+/// node whether its kernel is on the chain
+fn check_payment_proof(proof: &PaymentProof) -> Result<TxProofVerified, Error> {
+	verify_payment_proof(proof).map_err(|e| ErrorKind::VerifyProof(format!("{}", e)))?;
+
+	Ok(TxProofVerified {
+		sender: proof.sender_address.clone(),
+		receiver: proof.receiver_address.clone(),
+		amount: proof.amount,
+		outputs: vec![],
+		excess: proof.excess.clone(),
+		confirmed: false,
+		height: None,
+		offline: false,
+	})
+}
+
+/// Checks a kernel inclusion record supplied by the user against a
+/// verified proof, in place of a node lookup
+fn check_kernel_record(
+	kernel: Option<&TxKernelApiEntry>,
+	verified: &mut TxProofVerified,
+) -> Result<(), Error> {
+	verified.offline = true;
+	if let Some(kernel) = kernel {
+		if kernel.kernel.excess != verified.excess {
+			return Err(ErrorKind::VerifyProof(
+				"Kernel inclusion record doesn't match the proof excess".to_string(),
+			)
+			.into());
+		}
+		kernel.kernel.verify().map_err(|e| {
+			ErrorKind::VerifyProof(format!("Invalid kernel inclusion record: {:?}", e))
+		})?;
+		verified.confirmed = true;
+		verified.height = Some(kernel.height);
+	}
+	Ok(())
+}
+
+/// Looks up the kernel of a verified proof on the node, and fills in
+/// whether and where it has been confirmed
+fn check_confirmed(
+	node_client: &HTTPNodeClient,
+	verified: &mut TxProofVerified,
+) -> Result<(), Error> {
+	let excess = to_hex(verified.excess.as_ref().to_vec());
+	let api_tx_kernels = node_client.get_tx_kernel_from_node(excess)?;
+	verified.height = api_tx_kernels.get(&verified.excess).map(|k| k.height);
+	verified.confirmed = verified.height.is_some();
+	Ok(())
+}
+
+/// Checks the signature and the excess of a transaction proof, without
+/// asking the node whether its kernel is on the chain
+fn check_tx_proof(tx_proof: &TxProof) -> Result<TxProofVerified, Error> {
+	// Check signature on the message
+	let slate = tx_proof.verify_extract(String::new())?;
+
+	// Inputs owned by sender
+	let inputs_ex = tx_proof.inputs.iter().collect::<HashSet<_>>();
+
+	let slate: Slate = slate.into();
+
+	// Select inputs owned by the receiver (usually none)
+	let mut inputs: Vec<Commitment> = slate
+		.tx
+		.inputs()
+		.iter()
+		.map(|i| i.commitment())
+		.filter(|c| !inputs_ex.contains(c))
+		.collect();
+
+	// Outputs owned by sender
+	let outputs_ex = tx_proof.outputs.iter().collect::<HashSet<_>>();
+
+	// Select outputs owned by the receiver
+	let outputs: Vec<Commitment> = slate
+		.tx
+		.outputs()
+		.iter()
+		.map(|o| o.commitment())
+		.filter(|c| !outputs_ex.contains(c))
+		.collect();
+
+	// Receiver's excess
+	let excess = &slate.participant_data[1].public_blind_excess;
+
+	let excess_sum_com = {
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+
+		// Calculate receiver's excess from their inputs and inputs
+		let commit_amount = secp.commit_value(tx_proof.amount)?;
+		inputs.push(commit_amount);
+
+		let commit_excess = secp.commit_sum(outputs.clone(), inputs)?;
+		let pubkey_excess = commit_excess.to_pubkey(&secp)?;
+
+		// Verify receiver's excess with their inputs and outputs
+		if excess != &pubkey_excess {
+			return Err(ErrorKind::VerifyProof(
+				"Verify receiver's excess with their inputs and outputs".to_string(),
+			)
+			.into());
+		}
+
+		// Calculate kernel excess from inputs and outputs
+		let excess_parts: Vec<&PublicKey> = slate
+			.participant_data
+			.iter()
+			.map(|p| &p.public_blind_excess)
+			.collect();
+		let excess_sum = PublicKey::from_combination(&secp, excess_parts)?;
+
+		let mut input_com: Vec<Commitment> =
+			slate.tx.inputs().iter().map(|i| i.commitment()).collect();
+
+		let mut output_com: Vec<Commitment> =
+			slate.tx.outputs().iter().map(|o| o.commitment()).collect();
+
+		input_com.push(secp.commit(0, slate.tx.offset.secret_key(&secp)?)?);
+
+		output_com.push(secp.commit_value(slate.fee)?);
+
+		let excess_sum_com = secp.commit_sum(output_com, input_com)?;
+
+		// Verify kernel excess with all inputs and outputs
+		if excess_sum_com.to_pubkey(&secp)? != excess_sum {
+			return Err(ErrorKind::VerifyProof(
+				"Verify kernel excess with all inputs and outputs".to_string(),
+			)
+			.into());
+		}
+
+		excess_sum_com
+	};
+
+	Ok(TxProofVerified {
+		sender: tx_proof.sender_address.clone(),
+		receiver: tx_proof.recipient_address.clone(),
+		amount: tx_proof.amount,
+		outputs,
+		excess: excess_sum_com,
+		confirmed: false,
+		height: None,
+		offline: false,
+	})
 }
 
 #[doc(hidden)]
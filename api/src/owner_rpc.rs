@@ -20,9 +20,9 @@ use crate::core::core::Transaction;
 use crate::keychain::{Identifier, Keychain};
 use crate::libwallet::slate_versions::v2::TransactionV2;
 use crate::libwallet::{
-	AcctPathMapping, ErrorKind, InitTxArgs, IssueInvoiceTxArgs, NodeClient, NodeHeightResult,
-	OutputCommitMapping, Slate, SlateVersion, TxLogEntry, VersionedSlate, WalletBackend,
-	WalletInfo,
+	AcctPathMapping, ErrorKind, InitTxArgs, IssueInvoiceTxArgs, ListenerStatus, NodeClient,
+	NodeHeightResult, OutputCommitMapping, Slate, SlateVersion, TxLogEntry, VersionedSlate,
+	WalletBackend, WalletInfo,
 };
 use crate::Owner;
 use easy_jsonrpc;
@@ -1261,6 +1261,135 @@ pub trait OwnerRpc {
 	```
 	 */
 	fn node_height(&self) -> Result<NodeHeightResult, ErrorKind>;
+
+	/**
+	Networked version of [Owner::create_wallet](struct.Owner.html#method.create_wallet).
+
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "create_wallet",
+		"params": [16, null, "password"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+		}
+	}
+	# "#
+	# , 0, false, false, false);
+	```
+	 */
+	fn create_wallet(
+		&self,
+		seed_length: usize,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<String, ErrorKind>;
+
+	/**
+	Networked version of [Owner::recover_wallet](struct.Owner.html#method.recover_wallet).
+
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "recover_wallet",
+		"params": [
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+			null,
+			"password"
+		],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# , 0, false, false, false);
+	```
+	 */
+	fn recover_wallet(
+		&self,
+		recovery_phrase: String,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::wallet_exists](struct.Owner.html#method.wallet_exists).
+
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "wallet_exists",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": true
+		}
+	}
+	# "#
+	# , 0, false, false, false);
+	```
+	 */
+	fn wallet_exists(&self) -> Result<bool, ErrorKind>;
+
+	/**
+	Networked version of [Owner::listener_status](struct.Owner.html#method.listener_status).
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "listener_status",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": []
+		}
+	}
+	# "#
+	# , 0, false, false, false);
+	```
+	 */
+	fn listener_status(&self) -> Result<Vec<ListenerStatus>, ErrorKind>;
 }
 
 impl<W: ?Sized, C, K> OwnerRpc for Owner<W, C, K>
@@ -1380,6 +1509,45 @@ where
 	fn node_height(&self) -> Result<NodeHeightResult, ErrorKind> {
 		Owner::node_height(self).map_err(|e| e.kind())
 	}
+
+	fn create_wallet(
+		&self,
+		seed_length: usize,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<String, ErrorKind> {
+		Owner::create_wallet(
+			self,
+			seed_length,
+			passphrase.as_ref().map(|p| p.as_str()),
+			&password,
+		)
+		.map(|phrase| phrase.to_string())
+		.map_err(|e| e.kind())
+	}
+
+	fn recover_wallet(
+		&self,
+		recovery_phrase: String,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<(), ErrorKind> {
+		Owner::recover_wallet(
+			self,
+			&recovery_phrase,
+			passphrase.as_ref().map(|p| p.as_str()),
+			&password,
+		)
+		.map_err(|e| e.kind())
+	}
+
+	fn wallet_exists(&self) -> Result<bool, ErrorKind> {
+		Owner::wallet_exists(self).map_err(|e| e.kind())
+	}
+
+	fn listener_status(&self) -> Result<Vec<ListenerStatus>, ErrorKind> {
+		Owner::listener_status(self).map_err(|e| e.kind())
+	}
 }
 
 /// helper to set up a real environment to run integrated doctests
@@ -1392,8 +1560,10 @@ pub fn run_doctest_owner(
 	finalize_tx: bool,
 ) -> Result<Option<serde_json::Value>, String> {
 	use easy_jsonrpc::Handler;
+	use grin_wallet_config::WalletConfig;
 	use grin_wallet_impls::test_framework::{self, LocalWalletClient, WalletProxy};
-	use grin_wallet_libwallet::api_impl;
+	use grin_wallet_impls::LMDBBackend;
+	use grin_wallet_libwallet::{api_impl, WalletInst};
 	use grin_wallet_util::grin_keychain::ExtKeychain;
 
 	use crate::core::global;
@@ -1401,7 +1571,9 @@ pub fn run_doctest_owner(
 	use grin_wallet_util::grin_util as util;
 
 	use std::fs;
+	use std::sync::Arc;
 	use std::thread;
+	use util::Mutex;
 
 	util::init_test_logger();
 	let _ = fs::remove_dir_all(test_dir);
@@ -1470,8 +1642,8 @@ pub fn run_doctest_owner(
 		{
 			let mut w2 = wallet2.lock();
 			w2.open_with_credentials().unwrap();
-			slate =
-				api_impl::foreign::receive_tx(&mut *w2, &slate, None, None, None, true).unwrap();
+			slate = api_impl::foreign::receive_tx(&mut *w2, &slate, None, None, None, None, true)
+				.unwrap();
 			w2.close().unwrap();
 		}
 		// Spit out slate for input to finalize_tx
@@ -1493,7 +1665,20 @@ pub fn run_doctest_owner(
 		let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 3 as usize, false);
 	}
 
-	let mut api_owner = Owner::new(wallet1.clone());
+	// the wallet creation calls run on a wallet without a seed yet
+	let wallet: Arc<Mutex<dyn WalletInst<LocalWalletClient, ExtKeychain>>> =
+		match request["method"].as_str() {
+			Some("create_wallet") | Some("recover_wallet") => {
+				let mut wallet_config = WalletConfig::default();
+				wallet_config.data_file_dir = format!("{}/wallet3", test_dir);
+				Arc::new(Mutex::new(
+					LMDBBackend::new(wallet_config, "", client1.clone()).unwrap(),
+				))
+			}
+			_ => wallet1.clone(),
+		};
+
+	let mut api_owner = Owner::new(wallet);
 	api_owner.doctest_mode = true;
 	let owner_api = &api_owner as &dyn OwnerRpc;
 	Ok(owner_api.handle_request(request).as_option())
@@ -0,0 +1,252 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hosting of several named wallets by a single Owner API process
+
+use crate::util::{Mutex, ZeroingString};
+use std::collections::HashMap;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::impls::{self, WalletSeed, SEED_FILE};
+use crate::keychain::Keychain;
+use crate::libwallet::{Error, ErrorKind, NodeClient, WalletBackend, WalletListing};
+
+/// Opens the wallet stored in a data dir, given the data dir and the wallet
+/// password. This is where the caller decides on the node client and the
+/// storage backend of the hosted wallets.
+pub type WalletOpener<T> = Box<dyn Fn(&str, &str) -> Result<Arc<Mutex<T>>, Error> + Send + Sync>;
+
+/// Set of wallets hosted by one Owner API process. Each wallet lives in its
+/// own sub directory of the wallets dir, named after the wallet, with its own
+/// seed and database.
+///
+/// A wallet has to be opened with its password before it can serve any
+/// [`Owner`](struct.Owner.html) call, and stays open until it is closed or
+/// the process exits. Calls on different open wallets don't wait on each
+/// other.
+pub struct Wallets<T: ?Sized, C, K>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	wallets_dir: PathBuf,
+	opener: WalletOpener<T>,
+	open_wallets: Mutex<HashMap<String, Arc<Mutex<T>>>>,
+	phantom: PhantomData<K>,
+	phantom_c: PhantomData<C>,
+	/// Whether the recovery phrases are normalized for the doc tests
+	pub doctest_mode: bool,
+}
+
+impl<T: ?Sized, C, K> Wallets<T, C, K>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	/// Create a new set of hosted wallets under `wallets_dir`, opening each
+	/// of them with `opener`
+	pub fn new(wallets_dir: &str, opener: WalletOpener<T>) -> Self {
+		Wallets {
+			wallets_dir: PathBuf::from(wallets_dir),
+			opener,
+			open_wallets: Mutex::new(HashMap::new()),
+			phantom: PhantomData,
+			phantom_c: PhantomData,
+			doctest_mode: false,
+		}
+	}
+
+	/// Creates a new wallet with a random seed of `seed_length` bytes,
+	/// encrypted with `password`, and leaves it open. Returns the BIP39
+	/// recovery phrase of the seed, which is only given out here: the caller
+	/// must have the user back it up.
+	///
+	/// The seed length is one of 16, 20, 24, 28 or 32, for a recovery phrase
+	/// of 12, 15, 18, 21 or 24 words. The optional BIP39 `passphrase` is used
+	/// along with the recovery phrase to derive the wallet keys.
+	pub fn create_wallet(
+		&self,
+		name: &str,
+		seed_length: usize,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<ZeroingString, Error> {
+		if ![16, 20, 24, 28, 32].contains(&seed_length) {
+			return Err(ErrorKind::GenericError(format!(
+				"invalid seed length {}, must be one of 16, 20, 24, 28 or 32",
+				seed_length
+			))
+			.into());
+		}
+		// normalize the seed for the doc tests
+		let recovery_phrase = match self.doctest_mode {
+			true => Some(ZeroingString::from(
+				WalletSeed::from_bytes(&vec![0; seed_length])
+					.to_mnemonic()
+					.map_err(seed_error)?,
+			)),
+			false => None,
+		};
+		let seed = self.init_wallet(name, seed_length, recovery_phrase, passphrase, password)?;
+		info!("Hosted wallet '{}' created", name);
+		Ok(ZeroingString::from(seed.to_mnemonic().map_err(seed_error)?))
+	}
+
+	/// Recreates a wallet from its BIP39 recovery phrase, with the
+	/// `passphrase` it was created with if any, encrypted with `password`, and
+	/// leaves it open. A different passphrase recovers a different wallet.
+	///
+	/// Only the seed is recovered: the outputs are found by a
+	/// [`restore`](struct.Owner.html#method.restore) on the recovered wallet.
+	pub fn recover_wallet(
+		&self,
+		name: &str,
+		recovery_phrase: &str,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<(), Error> {
+		let phrase = ZeroingString::from(recovery_phrase);
+		self.init_wallet(name, 0, Some(phrase), passphrase, password)?;
+		info!("Hosted wallet '{}' recovered", name);
+		Ok(())
+	}
+
+	/// Whether a wallet of that name exists in the wallets dir
+	pub fn wallet_exists(&self, name: &str) -> Result<bool, Error> {
+		Ok(self.data_dir(name)?.join(SEED_FILE).exists())
+	}
+
+	/// Writes the seed of a new wallet, and opens it
+	fn init_wallet(
+		&self,
+		name: &str,
+		seed_length: usize,
+		recovery_phrase: Option<ZeroingString>,
+		passphrase: Option<&str>,
+		password: &str,
+	) -> Result<WalletSeed, Error> {
+		let data_dir = self.data_dir(name)?;
+		// hold the lock until the wallet is open, so no other call creates or
+		// opens a second database on the same data dir
+		let mut open_wallets = self.open_wallets.lock();
+		if data_dir.join(SEED_FILE).exists() {
+			return Err(ErrorKind::WalletSeedExists.into());
+		}
+		let data_dir = data_dir.to_str().unwrap();
+		let seed = WalletSeed::init_file(
+			data_dir,
+			seed_length,
+			recovery_phrase,
+			passphrase.map(ZeroingString::from),
+			password,
+			false,
+		)
+		.map_err(seed_error)?;
+		let wallet = (self.opener)(data_dir, password)?;
+		open_wallets.insert(name.to_owned(), wallet);
+		Ok(seed)
+	}
+
+	/// Opens an existing wallet with its password, so it can serve calls.
+	/// Opening a wallet which is already open is a no-op.
+	pub fn open_wallet(&self, name: &str, password: &str) -> Result<(), Error> {
+		let data_dir = self.data_dir(name)?;
+		if !data_dir.join(SEED_FILE).exists() {
+			return Err(ErrorKind::WalletSeedDoesntExist.into());
+		}
+		// hold the lock until the wallet is open, so no other call opens a
+		// second database on the same data dir
+		let mut open_wallets = self.open_wallets.lock();
+		if open_wallets.contains_key(name) {
+			return Ok(());
+		}
+		let wallet = (self.opener)(data_dir.to_str().unwrap(), password)?;
+		open_wallets.insert(name.to_owned(), wallet);
+		info!("Hosted wallet '{}' opened", name);
+		Ok(())
+	}
+
+	/// Closes an open wallet. Calls already running on it are finished first.
+	pub fn close_wallet(&self, name: &str) -> Result<(), Error> {
+		let wallet = self
+			.open_wallets
+			.lock()
+			.remove(name)
+			.ok_or(ErrorKind::WalletNotOpen(name.to_owned()))?;
+		// wait for the wallet to be released by any call in flight
+		wallet.lock().close()?;
+		info!("Hosted wallet '{}' closed", name);
+		Ok(())
+	}
+
+	/// Lists all the wallets in the wallets dir, sorted by name
+	pub fn list_wallets(&self) -> Result<Vec<WalletListing>, Error> {
+		let open_wallets = self.open_wallets.lock();
+		let mut res = vec![];
+		if !self.wallets_dir.exists() {
+			return Ok(res);
+		}
+		for entry in fs::read_dir(&self.wallets_dir)? {
+			let path = entry?.path();
+			if !path.join(SEED_FILE).exists() {
+				continue;
+			}
+			if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+				res.push(WalletListing {
+					name: name.to_owned(),
+					open: open_wallets.contains_key(name),
+				});
+			}
+		}
+		res.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(res)
+	}
+
+	/// Returns the open wallet with the given name
+	pub fn wallet(&self, name: &str) -> Result<Arc<Mutex<T>>, Error> {
+		match self.open_wallets.lock().get(name) {
+			Some(w) => Ok(w.clone()),
+			None => Err(ErrorKind::WalletNotOpen(name.to_owned()).into()),
+		}
+	}
+
+	/// Data dir of a wallet. Names are used as directory names, so they are
+	/// restricted to a plain set of characters.
+	fn data_dir(&self, name: &str) -> Result<PathBuf, Error> {
+		let valid = !name.is_empty()
+			&& name.len() <= 64
+			&& name
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+		if !valid {
+			return Err(ErrorKind::InvalidWalletName(name.to_owned()).into());
+		}
+		Ok(Path::new(&self.wallets_dir).join(name))
+	}
+}
+
+/// Maps the errors of the wallet seed handling
+pub(crate) fn seed_error(e: impls::Error) -> Error {
+	match e.kind() {
+		impls::ErrorKind::WalletSeedExists(_) => ErrorKind::WalletSeedExists.into(),
+		impls::ErrorKind::Encryption => ErrorKind::WalletSeedDecryption.into(),
+		impls::ErrorKind::Mnemonic => ErrorKind::Mnemonic.into(),
+		_ => ErrorKind::GenericError(format!("{}", e)).into(),
+	}
+}
@@ -0,0 +1,367 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON-RPC Stub generation for the management of hosted wallets
+
+use crate::keychain::Keychain;
+use crate::libwallet::{ErrorKind, NodeClient, WalletBackend, WalletListing};
+use crate::Wallets;
+use easy_jsonrpc;
+
+/// Public definition used to generate the hosted wallets jsonrpc api.
+/// * When running `grin-wallet owner_api` with `owner_api_wallets_dir` set, the api
+/// is available at `localhost:3420/v2/wallets`
+/// * The endpoint only supports POST operations, with the json-rpc request as the body
+/// * Calls to the `localhost:3420/v2/owner` endpoint select an open wallet with a
+/// `"wallet"` member next to `"method"`, i.e.
+/// `{"jsonrpc": "2.0", "wallet": "alice", "method": "accounts", "params": [], "id": 1}`.
+/// Calls without it are served by the wallet the process was started with, if any.
+/// * Wallets are created, recovered and opened here, so the process can be started
+/// with only a wallets dir and no wallet of its own.
+#[easy_jsonrpc::rpc]
+pub trait WalletsRpc {
+	/**
+	Networked version of [Wallets::create_wallet](struct.Wallets.html#method.create_wallet).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "create_wallet",
+		"params": ["bob", 32, null, "password"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art"
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn create_wallet(
+		&self,
+		name: String,
+		seed_length: usize,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<String, ErrorKind>;
+
+	/**
+	Networked version of [Wallets::recover_wallet](struct.Wallets.html#method.recover_wallet).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "recover_wallet",
+		"params": ["carol", "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about", null, "password"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn recover_wallet(
+		&self,
+		name: String,
+		recovery_phrase: String,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Wallets::wallet_exists](struct.Wallets.html#method.wallet_exists).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "wallet_exists",
+		"params": ["alice"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": true
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn wallet_exists(&self, name: String) -> Result<bool, ErrorKind>;
+
+	/**
+	Networked version of [Wallets::open_wallet](struct.Wallets.html#method.open_wallet).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "open_wallet",
+		"params": ["alice", "password"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn open_wallet(&self, name: String, password: String) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Wallets::close_wallet](struct.Wallets.html#method.close_wallet).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "close_wallet",
+		"params": ["alice"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn close_wallet(&self, name: String) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Wallets::list_wallets](struct.Wallets.html#method.list_wallets).
+
+	# Json rpc example
+
+	```
+	# grin_wallet_api::doctest_helper_json_rpc_wallets_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "list_wallets",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+				{
+					"name": "alice",
+					"open": true
+				}
+			]
+		},
+		"id": 1
+	}
+	# "#
+	# );
+	```
+	*/
+	fn list_wallets(&self) -> Result<Vec<WalletListing>, ErrorKind>;
+}
+
+impl<W: ?Sized, C, K> WalletsRpc for Wallets<W, C, K>
+where
+	W: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	fn create_wallet(
+		&self,
+		name: String,
+		seed_length: usize,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<String, ErrorKind> {
+		Wallets::create_wallet(
+			self,
+			&name,
+			seed_length,
+			passphrase.as_ref().map(|p| p.as_str()),
+			&password,
+		)
+		.map(|phrase| phrase.to_string())
+		.map_err(|e| e.kind())
+	}
+
+	fn recover_wallet(
+		&self,
+		name: String,
+		recovery_phrase: String,
+		passphrase: Option<String>,
+		password: String,
+	) -> Result<(), ErrorKind> {
+		Wallets::recover_wallet(
+			self,
+			&name,
+			&recovery_phrase,
+			passphrase.as_ref().map(|p| p.as_str()),
+			&password,
+		)
+		.map_err(|e| e.kind())
+	}
+
+	fn wallet_exists(&self, name: String) -> Result<bool, ErrorKind> {
+		Wallets::wallet_exists(self, &name).map_err(|e| e.kind())
+	}
+
+	fn open_wallet(&self, name: String, password: String) -> Result<(), ErrorKind> {
+		Wallets::open_wallet(self, &name, &password).map_err(|e| e.kind())
+	}
+
+	fn close_wallet(&self, name: String) -> Result<(), ErrorKind> {
+		Wallets::close_wallet(self, &name).map_err(|e| e.kind())
+	}
+
+	fn list_wallets(&self) -> Result<Vec<WalletListing>, ErrorKind> {
+		Wallets::list_wallets(self).map_err(|e| e.kind())
+	}
+}
+
+/// helper to set up a real environment to run integrated doctests
+pub fn run_doctest_wallets(
+	request: serde_json::Value,
+	test_dir: &str,
+) -> Result<Option<serde_json::Value>, String> {
+	use easy_jsonrpc::Handler;
+	use grin_wallet_config::WalletConfig;
+	use grin_wallet_impls::{HTTPNodeClient, LMDBBackend, WalletSeed};
+	use grin_wallet_libwallet::WalletInst;
+	use grin_wallet_util::grin_keychain::ExtKeychain;
+
+	use crate::core::global;
+	use crate::core::global::ChainTypes;
+	use crate::util::Mutex;
+	use crate::WalletOpener;
+	use grin_wallet_util::grin_util as util;
+
+	use std::fs;
+	use std::sync::Arc;
+
+	util::init_test_logger();
+	let _ = fs::remove_dir_all(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+
+	let opener: WalletOpener<WalletInst<HTTPNodeClient, ExtKeychain>> =
+		Box::new(|data_dir, password| {
+			WalletSeed::from_file(data_dir, password)
+				.map_err(|_| ErrorKind::WalletSeedDecryption)?;
+			let mut wallet_config = WalletConfig::default();
+			wallet_config.data_file_dir = data_dir.to_owned();
+			let node_client = HTTPNodeClient::new(&wallet_config.check_node_api_http_addr, None);
+			let wallet: LMDBBackend<HTTPNodeClient, ExtKeychain> =
+				LMDBBackend::new(wallet_config, password, node_client)?;
+			Ok(Arc::new(Mutex::new(wallet)))
+		});
+
+	let mut wallets = Wallets::new(test_dir, opener);
+	wallets.doctest_mode = true;
+	wallets
+		.create_wallet("alice", 32, None, "password")
+		.map_err(|e| format!("{}", e))?;
+	let wallets_api = &wallets as &dyn WalletsRpc;
+	Ok(wallets_api.handle_request(request).as_option())
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! doctest_helper_json_rpc_wallets_assert_response {
+	($request:expr, $expected_response:expr) => {
+		// create temporary wallets dir, run jsonrpc request on the hosted wallets api,
+		// delete the dir, return json response.
+		// In order to prevent leaking tempdirs, This function should not panic.
+		use grin_wallet_api::run_doctest_wallets;
+		use serde_json;
+		use serde_json::Value;
+		use tempfile::tempdir;
+
+		let dir = tempdir().map_err(|e| format!("{:#?}", e)).unwrap();
+		let dir = dir
+			.path()
+			.to_str()
+			.ok_or("Failed to convert tmpdir path to string.".to_owned())
+			.unwrap();
+
+		let request_val: Value = serde_json::from_str($request).unwrap();
+		let expected_response: Value = serde_json::from_str($expected_response).unwrap();
+
+		let response = run_doctest_wallets(request_val, dir).unwrap().unwrap();
+
+		if response != expected_response {
+			panic!(
+				"(left != right) \nleft: {}\nright: {}",
+				serde_json::to_string_pretty(&response).unwrap(),
+				serde_json::to_string_pretty(&expected_response).unwrap()
+			);
+		}
+	};
+}
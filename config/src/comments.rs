@@ -87,6 +87,16 @@ fn comments() -> HashMap<String, String> {
 #include the foreign API endpoints on the same port as the owner
 #API. Useful for networking environments like AWS ECS that make
 #it difficult to access multiple ports on a single service.
+"
+		.to_string(),
+	);
+	retval.insert(
+		"owner_api_wallets_dir".to_string(),
+		"
+#directory of the wallets hosted by the owner API, one sub directory
+#per wallet. When set, the owner API also serves the /v2/wallets
+#endpoint to create, open, close and list them, and owner API calls
+#select one of them with a \"wallet\" member in the request.
 "
 		.to_string(),
 	);
@@ -124,6 +134,16 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"wallet_backend".to_string(),
+		"
+#Storage backend of the wallet database: \"Lmdb\" or \"Sqlite\".
+#Sqlite requires a wallet built with the 'sqlite' feature. Use the
+#'convert' command to migrate an existing wallet between backends.
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[wallet.grinrelay_config]".to_string(),
 		"
@@ -172,6 +192,120 @@ fn comments() -> HashMap<String, String> {
 		"grinrelay_sending_address_random".to_string(),
 		"
 #Whether to use random address for Grin Relay sending. If 'false', use above index+1
+"
+		.to_string(),
+	);
+	retval.insert(
+		"grinrelay_message_version".to_string(),
+		"
+#Grin Relay message encryption for the peers not heard from yet, the others are
+#answered in the newest format they advertise or use. 1 (the default) derives the
+#keys from the static relay keys, which every wallet reads, 2 from ephemeral sender
+#keys, only for peers known to read it
+"
+		.to_string(),
+	);
+	retval.insert(
+		"grinrelay_receive_workers".to_string(),
+		"
+#Number of threads receiving the incoming slates
+"
+		.to_string(),
+	);
+	retval.insert(
+		"grinrelay_receive_queue".to_string(),
+		"
+#Number of incoming slates waiting for a receiving thread, the next ones are
+#dropped until the queue drains
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[wallet.stale_tx_policy]".to_string(),
+		"
+#########################################
+###       Stale Transactions          ###
+#########################################
+"
+		.to_string(),
+	);
+	retval.insert(
+		"unconfirmed_blocks".to_string(),
+		"
+#Cancel a sent transaction which was posted but is still unconfirmed this many
+#blocks after its creation, and whose inputs the node shows unspent, unlocking
+#them. Remove to never cancel.
+"
+		.to_string(),
+	);
+	retval.insert(
+		"unposted_hours".to_string(),
+		"
+#Cancel a sent transaction which was not posted this many hours after its
+#creation, unlocking its inputs. Remove to never cancel.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[wallet.receive_policy]".to_string(),
+		"
+#########################################
+###     Incoming Payments Policy      ###
+#########################################
+
+#Payments refused by the Foreign API, before any output is added to the wallet.
+#Amounts are in nanogrins, Grin Relay addresses as given by 'grin-wallet address'.
+#Only the payments coming through Grin Relay have a known sender: with
+#allowed_senders set, the payments from the other transports are refused.
+#min_amount = 1000000
+#max_amount = 1000000000000
+#daily_cap = 10000000000000
+#require_message = true
+#max_pending_receives = 100
+#allowed_senders = [\"tn1q...\"]
+#denied_senders = [\"tn1q...\"]
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[wallet.foreign_api_limits]".to_string(),
+		"
+#########################################
+###     Foreign API Request Limits    ###
+#########################################
+"
+		.to_string(),
+	);
+	retval.insert(
+		"max_body_size".to_string(),
+		"
+#Largest request body accepted by the Foreign API listener, in bytes
+"
+		.to_string(),
+	);
+	retval.insert(
+		"requests_per_minute".to_string(),
+		"
+#Most requests per minute accepted from a single peer address
+"
+		.to_string(),
+	);
+	retval.insert(
+		"max_concurrent_receives".to_string(),
+		"
+#Most incoming payments (receive_tx calls) processed at the same time
+"
+		.to_string(),
+	);
+	retval.insert(
+		"trust_forwarded_for".to_string(),
+		"
+#Take the peer address from the X-Forwarded-For header, as the last address
+#appended by the reverse proxy. Only set it to true behind a reverse proxy.
+#When false, the address of the connection is used.
 "
 		.to_string(),
 	);
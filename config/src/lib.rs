@@ -35,4 +35,7 @@ pub mod types;
 pub use crate::config::{
 	initial_setup_wallet, select_node_server, GRIN_WALLET_DIR, WALLET_CONFIG_FILE_NAME,
 };
-pub use crate::types::{ConfigError, GlobalWalletConfig, GrinRelayConfig, WalletConfig};
+pub use crate::types::{
+	ConfigError, ForeignApiLimits, GlobalWalletConfig, GrinRelayConfig, ReceivePolicy,
+	StaleTxPolicy, TransportConfig, WalletBackendType, WalletConfig,
+};
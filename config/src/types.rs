@@ -15,6 +15,7 @@
 
 //! Public types for config modules
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io;
 use std::path::PathBuf;
@@ -43,6 +44,9 @@ pub struct WalletConfig {
 	pub check_node_api_http_addr: String,
 	/// Whether to include foreign API endpoints on the Owner API
 	pub owner_api_include_foreign: Option<bool>,
+	/// Directory of the wallets hosted by the Owner API, one sub directory
+	/// per wallet. The Owner API only serves this wallet if none
+	pub owner_api_wallets_dir: Option<String>,
 	/// The directory in which wallet files are stored
 	pub data_file_dir: String,
 	/// If Some(true), don't cache commits alongside output data
@@ -57,8 +61,19 @@ pub struct WalletConfig {
 	pub dark_background_color_scheme: Option<bool>,
 	/// The exploding lifetime (minutes) for keybase notification on coins received
 	pub keybase_notify_ttl: Option<u16>,
+	/// Storage backend for the wallet database (LMDB if none)
+	pub wallet_backend: Option<WalletBackendType>,
 	/// Grin Relay config
 	pub grinrelay_config: Option<GrinRelayConfig>,
+	/// Automatic cancellation of the stale outgoing transactions, none if missing
+	pub stale_tx_policy: Option<StaleTxPolicy>,
+	/// Acceptance policy of the incoming payments, any payment is accepted if missing
+	pub receive_policy: Option<ReceivePolicy>,
+	/// Limits on the requests to the Foreign API HTTP/S listener, the defaults if missing
+	pub foreign_api_limits: Option<ForeignApiLimits>,
+	/// Config sections of the transports, by transport name, each read by
+	/// its own transport
+	pub transports: Option<BTreeMap<String, TransportConfig>>,
 }
 
 impl Default for WalletConfig {
@@ -72,13 +87,19 @@ impl Default for WalletConfig {
 			node_api_secret: Some("change-me-to-your-node-api-secret".to_string()),
 			check_node_api_http_addr: "http://127.0.0.1:3413".to_string(),
 			owner_api_include_foreign: Some(false),
+			owner_api_wallets_dir: None,
 			data_file_dir: ".".to_string(),
 			no_commit_cache: Some(false),
 			tls_certificate_file: None,
 			tls_certificate_key: None,
 			dark_background_color_scheme: Some(true),
 			keybase_notify_ttl: Some(1440),
+			wallet_backend: Some(WalletBackendType::Lmdb),
 			grinrelay_config: Some(GrinRelayConfig::default()),
+			stale_tx_policy: None,
+			receive_policy: Some(ReceivePolicy::default()),
+			foreign_api_limits: Some(ForeignApiLimits::default()),
+			transports: None,
 		}
 	}
 }
@@ -104,6 +125,35 @@ impl WalletConfig {
 	pub fn owner_api_listen_addr(&self) -> String {
 		format!("127.0.0.1:{}", self.owner_api_listen_port())
 	}
+
+	/// Use value from config file, defaulting to LMDB if missing.
+	pub fn wallet_backend(&self) -> WalletBackendType {
+		self.wallet_backend
+			.clone()
+			.unwrap_or(WalletBackendType::Lmdb)
+	}
+}
+
+/// Config section of a transport, the `[wallet.transports.<name>]` table,
+/// which the transport deserializes into its own type
+pub type TransportConfig = toml::Value;
+
+/// Storage backends available for the wallet database
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum WalletBackendType {
+	/// LMDB key/value store (default)
+	Lmdb,
+	/// SQLite relational database, requires the `sqlite` feature
+	Sqlite,
+}
+
+impl fmt::Display for WalletBackendType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match *self {
+			WalletBackendType::Lmdb => write!(f, "lmdb"),
+			WalletBackendType::Sqlite => write!(f, "sqlite"),
+		}
+	}
 }
 
 /// Grin Relay configuration
@@ -121,6 +171,15 @@ pub struct GrinRelayConfig {
 	pub grinrelay_receiving_address_index: u32,
 	/// Whether to use random address for Grin Relay sending
 	pub grinrelay_sending_address_random: bool,
+	/// Grin Relay message format for the peers not heard from yet: 1 (the
+	/// default) for the legacy static keys, 2 for ephemeral keys. The others
+	/// get the newest format they advertise or use.
+	pub grinrelay_message_version: Option<u8>,
+	/// Number of threads receiving the incoming Grin Relay slates, 2 if none
+	pub grinrelay_receive_workers: Option<usize>,
+	/// Number of incoming Grin Relay slates waiting for a receiving thread
+	/// before the next ones get dropped, 32 if none
+	pub grinrelay_receive_queue: Option<usize>,
 }
 
 impl Default for GrinRelayConfig {
@@ -132,6 +191,92 @@ impl Default for GrinRelayConfig {
 			grinrelay_protocol_unsecure: true,
 			grinrelay_receiving_address_index: 0,
 			grinrelay_sending_address_random: true,
+			grinrelay_message_version: None,
+			grinrelay_receive_workers: Some(2),
+			grinrelay_receive_queue: Some(32),
+		}
+	}
+}
+
+/// Automatic cancellation of the stale outgoing transactions, applied on
+/// each refresh once configured. Cancelling a transaction unlocks its inputs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StaleTxPolicy {
+	/// Cancel a posted transaction still unconfirmed this many blocks after
+	/// its creation, counted at the target block time, if the node shows its
+	/// inputs still unspent
+	pub unconfirmed_blocks: Option<u64>,
+	/// Cancel a transaction not posted this many hours after its creation
+	pub unposted_hours: Option<u64>,
+}
+
+/// Acceptance policy of the incoming payments, enforced by the Foreign API
+/// before an output is added to the wallet for the payment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ReceivePolicy {
+	/// Smallest amount accepted, in nanogrins
+	pub min_amount: Option<u64>,
+	/// Largest amount accepted, in nanogrins
+	pub max_amount: Option<u64>,
+	/// Largest total amount received over the last 24 hours, in nanogrins
+	pub daily_cap: Option<u64>,
+	/// Whether the sender must include a participant message
+	pub require_message: Option<bool>,
+	/// Largest number of received transactions not confirmed yet
+	pub max_pending_receives: Option<u32>,
+	/// Only accept the payments from these Grin Relay addresses. The payments
+	/// coming through the other transports have no known sender, and are refused.
+	pub allowed_senders: Option<Vec<String>>,
+	/// Refuse the payments from these Grin Relay addresses
+	pub denied_senders: Option<Vec<String>>,
+}
+
+impl ReceivePolicy {
+	/// Whether the payments from this Grin Relay address are accepted
+	pub fn accepts_sender(&self, sender: &str) -> bool {
+		let sender = ReceivePolicy::relay_address_key(sender);
+		let listed = |list: &Option<Vec<String>>| {
+			list.as_ref().map(|l| {
+				l.iter()
+					.any(|a| ReceivePolicy::relay_address_key(a) == sender)
+			})
+		};
+		listed(&self.denied_senders) != Some(true) && listed(&self.allowed_senders) != Some(false)
+	}
+
+	/// The public key part of a relay address, which can be written with
+	/// the scheme and the relay server, or without
+	fn relay_address_key(address: &str) -> &str {
+		let address = address.trim();
+		let address = address.trim_start_matches("grinrelay://");
+		address.split('@').next().unwrap_or(address)
+	}
+}
+
+/// Limits on the requests to the Foreign API HTTP/S listener, protecting
+/// the wallet from a client flooding it with requests. A limit set to none
+/// doesn't apply.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForeignApiLimits {
+	/// Largest request body, in bytes
+	pub max_body_size: Option<u64>,
+	/// Most requests per minute from a single peer address
+	pub requests_per_minute: Option<u32>,
+	/// Most `receive_tx` calls processed at the same time
+	pub max_concurrent_receives: Option<u32>,
+	/// Whether the peer address is the last one of the `X-Forwarded-For`
+	/// header, only to be set behind a reverse proxy. Otherwise it is the
+	/// address of the connection.
+	pub trust_forwarded_for: Option<bool>,
+}
+
+impl Default for ForeignApiLimits {
+	fn default() -> ForeignApiLimits {
+		ForeignApiLimits {
+			max_body_size: Some(1_048_576),
+			requests_per_minute: Some(60),
+			max_concurrent_receives: Some(4),
+			trust_forwarded_for: Some(false),
 		}
 	}
 }
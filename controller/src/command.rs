@@ -18,11 +18,9 @@
 use crate::util::{Mutex, ZeroingString};
 use chrono::NaiveDateTime as DateTime;
 use colored::*;
-use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{stdin, stdout, Read, Write};
-use std::sync::mpsc::channel;
-use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::{channel, sync_channel, RecvTimeoutError};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -31,20 +29,25 @@ use serde_json as json;
 use uuid::Uuid;
 
 use crate::api::TLSConfig;
-use crate::core::core;
+use crate::apiwallet::{verify_tx_proofs, WalletOpener, Wallets};
+use crate::core::core::{self, TxKernelApiEntry};
 use crate::keychain;
+use crate::{impls, libwallet};
 
-use crate::config::{GrinRelayConfig, WalletConfig};
+use crate::config::{GrinRelayConfig, WalletBackendType, WalletConfig};
 use crate::error::{Error, ErrorKind};
+use crate::grinrelay::{armor, GrinboxAddress};
 use crate::impls::{
-	instantiate_wallet, FileWalletCommAdapter, GrinrelayWalletCommAdapter, HTTPWalletCommAdapter,
-	KeybaseWalletCommAdapter, LMDBBackend, NullWalletCommAdapter,
+	convert_wallet_db, init_wallet_db, instantiate_wallet, DirConfig, DirTransport,
+	FileWalletCommAdapter, GrinrelayWalletCommAdapter, KeybaseApi, KeybaseWalletCommAdapter,
+	TorConfig, Transport, TransportRegistry,
 };
 use crate::impls::{HTTPNodeClient, WalletSeed};
 use crate::libwallet::{
-	InitTxArgs, IssueInvoiceTxArgs, Listener, NodeClient, OutputStatus, TxLogEntryType, TxProof,
-	WalletInst,
+	InitTxArgs, IssueInvoiceTxArgs, NodeClient, OutputStatus, PaymentProof, Slate, TxLogEntryType,
+	TxProof, TxProofReport, TxProofStatus, WalletInst,
 };
+use crate::supervisor::ListenerSupervisor;
 use crate::{controller, display};
 
 /// Arguments common to all wallet commands
@@ -55,6 +58,7 @@ pub struct GlobalArgs {
 	pub show_spent: bool,
 	pub password: Option<ZeroingString>,
 	pub tls_conf: Option<TLSConfig>,
+	pub watch_only: bool,
 }
 
 /// Arguments for init command
@@ -64,6 +68,8 @@ pub struct InitArgs {
 	pub password: ZeroingString,
 	pub config: WalletConfig,
 	pub recovery_phrase: Option<ZeroingString>,
+	/// BIP39 passphrase
+	pub mnemonic_passphrase: Option<ZeroingString>,
 	pub restore: bool,
 }
 
@@ -72,6 +78,7 @@ pub fn init(g_args: &GlobalArgs, args: InitArgs) -> Result<(), Error> {
 		args.config.data_file_dir.as_str(),
 		args.list_length,
 		args.recovery_phrase,
+		args.mnemonic_passphrase,
 		&args.password,
 		true,
 	)?;
@@ -80,15 +87,19 @@ pub fn init(g_args: &GlobalArgs, args: InitArgs) -> Result<(), Error> {
 		&args.config.check_node_api_http_addr,
 		g_args.node_api_secret.clone(),
 	);
-	let _: LMDBBackend<HTTPNodeClient, keychain::ExtKeychain> =
-		LMDBBackend::new(args.config.clone(), &args.password, client_n)?;
-	info!("Wallet database backend created");
+	init_wallet_db(&args.config, client_n, &args.password)?;
+	info!(
+		"Wallet database backend created, on {} backend",
+		args.config.wallet_backend()
+	);
 	Ok(())
 }
 
 /// Argument for recover
 pub struct RecoverArgs {
 	pub recovery_phrase: Option<ZeroingString>,
+	/// BIP39 passphrase
+	pub mnemonic_passphrase: Option<ZeroingString>,
 	pub passphrase: ZeroingString,
 }
 
@@ -104,6 +115,7 @@ pub fn recover(config: &WalletConfig, args: RecoverArgs) -> Result<(), Error> {
 		let res = WalletSeed::recover_from_phrase(
 			config.data_file_dir.as_str(),
 			&args.recovery_phrase.as_ref().unwrap(),
+			args.mnemonic_passphrase,
 			&args.passphrase,
 		);
 		if let Err(e) = res {
@@ -116,103 +128,190 @@ pub fn recover(config: &WalletConfig, args: RecoverArgs) -> Result<(), Error> {
 
 /// Arguments for listen command
 pub struct ListenArgs {
-	pub method: String,
+	/// Transports to listen on: "http", "relay" and "keybase"
+	pub methods: Vec<String>,
+	/// Whether to also run the owner API, reporting the status of the listeners
+	pub owner_api: bool,
 }
 
 pub fn listen(config: &WalletConfig, args: &ListenArgs, g_args: &GlobalArgs) -> Result<(), Error> {
-	let mut params = HashMap::new();
-	params.insert("api_listen_addr".to_owned(), config.api_listen_addr());
-	if let Some(t) = g_args.tls_conf.as_ref() {
-		params.insert("certificate".to_owned(), t.certificate.clone());
-		params.insert("private_key".to_owned(), t.private_key.clone());
-	}
-	let res = match args.method.as_str() {
-		"http" => {
-			// HTTP adapter can't use the listen trait method because of the
-			// crate structure. May be able to fix when V1 API is deprecated
-			let node_client = HTTPNodeClient::new(
-				&config.check_node_api_http_addr,
-				g_args.node_api_secret.clone(),
-			);
-			let wallet = instantiate_wallet(
-				config.clone(),
-				node_client,
-				&g_args.password.clone().unwrap(),
-				&g_args.account,
-			)?;
-			let listen_addr = params.get("api_listen_addr").unwrap();
-			let tls_conf = match params.get("certificate") {
-				Some(s) => Some(TLSConfig::new(
-					s.to_owned(),
-					params.get("private_key").unwrap().to_owned(),
-				)),
-				None => None,
-			};
+	let node_client = HTTPNodeClient::new(
+		&config.check_node_api_http_addr,
+		g_args.node_api_secret.clone(),
+	);
+	let wallet = instantiate_wallet(
+		config.clone(),
+		node_client,
+		&g_args.password.clone().unwrap(),
+		&g_args.account,
+	)?;
 
-			let grinrelay_config = config.grinrelay_config.clone().unwrap_or_default();
-			let (grinrelay_key_path, relay_rx, grinrelay_listener) =
-				if grinrelay_config.enable_grinrelay {
-					// The streaming channel between 'grinrelay_listener' and 'foreign_listener'
-					let (relay_tx_as_payee, relay_rx) = channel();
+	let grinrelay_config = config.grinrelay_config.clone().unwrap_or_default();
+	let mut methods = args.methods.clone();
+	// the HTTP listener has always received over Grin Relay as well, when enabled
+	let has = |methods: &Vec<String>, m: &str| methods.iter().any(|n| n == m);
+	if has(&methods, "http") && !has(&methods, "relay") && grinrelay_config.enable_grinrelay {
+		methods.push("relay".to_owned());
+	}
+	// the onion service forwards to the HTTP listener
+	if has(&methods, "tor") && !has(&methods, "http") {
+		methods.push("http".to_owned());
+	}
 
+	let mut supervisor = ListenerSupervisor::new();
+	for method in methods {
+		let wallet = wallet.clone();
+		let account = g_args.account.clone();
+		match method.as_str() {
+			"http" => {
+				let listen_addr = config.api_listen_addr();
+				let tls_conf = g_args.tls_conf.clone();
+				let limits = config.foreign_api_limits.clone().unwrap_or_default();
+				supervisor.spawn("http", move || {
+					controller::foreign_listener(
+						wallet.clone(),
+						&listen_addr,
+						tls_conf.clone(),
+						vec![],
+						limits.clone(),
+					)
+				});
+			}
+			"relay" => {
+				let grinrelay_config = grinrelay_config.clone();
+				let workers = grinrelay_config.grinrelay_receive_workers.unwrap_or(2);
+				let queue = grinrelay_config.grinrelay_receive_queue.unwrap_or(32);
+				supervisor.spawn("relay", move || {
+					// The bounded channel between 'grinrelay_listener' and the receiving workers
+					let (relay_tx_as_payee, relay_rx) = sync_channel(queue);
 					let (grinrelay_key_path, grinrelay_listener) = controller::grinrelay_listener(
 						wallet.clone(),
-						config.grinrelay_config.clone().unwrap_or_default(),
+						grinrelay_config.clone(),
 						None,
 						Some(relay_tx_as_payee),
 						None,
 					)?;
-
-					(
+					controller::grinrelay_receive_loop(
+						wallet.clone(),
+						relay_rx,
+						grinrelay_listener,
 						Some(grinrelay_key_path),
-						Some(relay_rx),
-						Some(grinrelay_listener),
+						&account,
+						vec![],
+						workers,
 					)
-				} else {
-					(None, None, None)
-				};
-
-			controller::foreign_listener(
-				wallet.clone(),
-				&listen_addr,
-				tls_conf,
-				relay_rx,
-				grinrelay_listener,
-				grinrelay_key_path,
-				&g_args.account,
-			)?;
-			Ok(())
-		}
-		"keybase" => {
-			let adapter = KeybaseWalletCommAdapter::new();
-			adapter.listen(
-				params,
-				config.clone(),
-				&g_args.password.clone().unwrap(),
-				&g_args.account,
-				g_args.node_api_secret.clone(),
-			)
+				});
+			}
+			"keybase" => {
+				// check keybase is installed before going on
+				let _ = KeybaseWalletCommAdapter::new();
+				let notify_ttl = config.keybase_notify_ttl.unwrap_or(1440);
+				let keybase = KeybaseWalletCommAdapter::with_api(KeybaseApi::new("keybase"));
+				supervisor.spawn("keybase", move || {
+					controller::keybase_listener(
+						wallet.clone(),
+						&keybase,
+						notify_ttl,
+						&account,
+						vec![],
+					)
+				});
+			}
+			"tor" => {
+				let tor_config = config.transports.as_ref().and_then(|t| t.get("tor"));
+				let tor_config = TorConfig::from_config(tor_config)?;
+				let listen_addr = config.api_listen_addr();
+				supervisor.spawn("tor", move || {
+					controller::tor_listener(wallet.clone(), &tor_config, &listen_addr)
+				});
+			}
+			"dir" => {
+				let dir_config = config.transports.as_ref().and_then(|t| t.get("dir"));
+				let transport = DirTransport::new(DirConfig::from_config(dir_config)?);
+				supervisor.spawn("dir", move || {
+					controller::dir_listener(wallet.clone(), &transport, &account, vec![])
+				});
+			}
+			m => {
+				return Err(
+					ErrorKind::ArgumentError(format!("Unknown listen method: {}", m)).into(),
+				);
+			}
 		}
-		_ => Ok(()),
-	};
+	}
 
-	if let Err(e) = res {
-		return Err(ErrorKind::LibWallet(e.kind(), e.cause_string()).into());
+	if args.owner_api {
+		let wallet = wallet.clone();
+		let owner_addr = config.owner_api_listen_addr();
+		let api_secret = g_args.node_api_secret.clone();
+		let include_foreign = config.owner_api_include_foreign.clone();
+		let foreign_limits = config.foreign_api_limits.clone().unwrap_or_default();
+		let listeners = supervisor.statuses();
+		supervisor.spawn("owner_api", move || {
+			controller::owner_listener(
+				Some(wallet.clone()),
+				&owner_addr,
+				api_secret.clone(),
+				None,
+				include_foreign.clone(),
+				foreign_limits.clone(),
+				None,
+				Some(listeners.clone()),
+			)
+		});
 	}
+
+	supervisor.wait();
 	Ok(())
 }
 
-pub fn owner_api(
-	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
+/// Runs the Owner API. Without a default `wallet`, the process only serves
+/// the wallets hosted in the configured wallets dir.
+pub fn owner_api<C>(
+	wallet: Option<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>>,
+	node_client: C,
 	config: &WalletConfig,
 	g_args: &GlobalArgs,
-) -> Result<(), Error> {
+) -> Result<(), Error>
+where
+	C: NodeClient + 'static,
+{
+	let wallets = match config.owner_api_wallets_dir.clone() {
+		Some(dir) => {
+			let wallet_config = config.clone();
+			let opener: WalletOpener<WalletInst<C, keychain::ExtKeychain>> =
+				Box::new(move |data_dir, password| {
+					let mut c = wallet_config.clone();
+					c.data_file_dir = data_dir.to_owned();
+					instantiate_wallet(c, node_client.clone(), password, "default").map_err(|e| {
+						match e.kind() {
+							impls::ErrorKind::Encryption => {
+								libwallet::ErrorKind::WalletSeedDecryption.into()
+							}
+							_ => libwallet::ErrorKind::GenericError(format!("{}", e)).into(),
+						}
+					})
+				});
+			info!("Hosting the wallets found in {}", dir);
+			Some(Arc::new(Wallets::new(&dir, opener)))
+		}
+		None if wallet.is_none() => {
+			return Err(ErrorKind::ArgumentError(
+				"No wallet to serve: create a wallet or set owner_api_wallets_dir".to_owned(),
+			)
+			.into());
+		}
+		None => None,
+	};
 	let res = controller::owner_listener(
 		wallet,
 		config.owner_api_listen_addr().as_str(),
 		g_args.node_api_secret.clone(),
 		g_args.tls_conf.clone(),
 		config.owner_api_include_foreign.clone(),
+		config.foreign_api_limits.clone().unwrap_or_default(),
+		wallets,
+		None,
 	);
 	if let Err(e) = res {
 		return Err(ErrorKind::LibWallet(e.kind(), e.cause_string()).into());
@@ -271,6 +370,12 @@ pub struct SendArgs {
 	pub fluff: bool,
 	pub max_outputs: usize,
 	pub target_slate_version: Option<u16>,
+	/// Write an armored slate, for the file method
+	pub armor: bool,
+	/// Relay address to encrypt the armored slate to
+	pub encrypt_to: Option<String>,
+	/// Number of blocks the transaction stays valid for
+	pub ttl_blocks: Option<u64>,
 }
 
 /// Prompts for a password on STDOUT and reads it from STDIN
@@ -306,17 +411,27 @@ pub fn send(
 	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
 	args: SendArgs,
 	wallet_config: &WalletConfig,
+	transports: &TransportRegistry,
 ) -> Result<(), Error> {
 	let dark_scheme = wallet_config.dark_background_color_scheme.unwrap_or(true);
 
-	// The streaming channel between 'grinrelay_listener' and 'GrinrelayWalletCommAdapter'
-	let (relay_tx_as_payer, relay_rx) = channel();
+	let mut transports = transports.clone();
+	let (method, mut dest) = {
+		let (transport, dest) = transports.resolve(&args.method, &args.dest)?;
+		if !transport.capabilities.send_sync && !transport.capabilities.send_async {
+			return Err(ErrorKind::ArgumentError(format!(
+				"The {} method can't send",
+				transport.name
+			)))?;
+		}
+		(transport.name.clone(), dest)
+	};
 
-	let mut grinrelay_listener: Option<Box<Listener>> = None;
 	let mut grinrelay_key_path: Option<u64> = None;
-	let mut dest = args.dest.clone();
 
-	if "relay" == args.method.as_str() {
+	if "relay" == method.as_str() {
+		// The streaming channel between 'grinrelay_listener' and 'GrinrelayWalletCommAdapter'
+		let (relay_tx_as_payer, relay_rx) = channel();
 		let (relay_addr_query_sender, relay_addr_query_rx) = channel();
 
 		// Start a Grin Relay service firstly
@@ -341,8 +456,21 @@ pub fn send(
 				std::process::exit(1);
 			}
 		}
-		grinrelay_listener = Some(listener.clone());
 		grinrelay_key_path = Some(key_path);
+		let relay = Mutex::new(Some((listener.clone(), relay_rx)));
+		transports.register(Transport::new(
+			"relay",
+			&["relay", "grinrelay"],
+			transports.get("relay").unwrap().capabilities,
+			move |_| match relay.lock().take() {
+				Some((listener, relay_rx)) => {
+					Ok(GrinrelayWalletCommAdapter::new(listener, relay_rx))
+				}
+				None => Err(libwallet::ErrorKind::GenericError(
+					"The relay transport was already used".to_owned(),
+				))?,
+			},
+		));
 
 		// Conversion the abbreviation address to the full address
 		if 6 == dest.len() {
@@ -357,21 +485,11 @@ pub fn send(
 			}
 
 			const TTL: u16 = 10;
-			let mut addresses: Option<Vec<String>> = None;
-			let mut cnt = 0;
-			loop {
-				match relay_addr_query_rx.try_recv() {
-					Ok((_abbr, addrs)) => {
-						if !addrs.is_empty() {
-							addresses = Some(addrs);
-						}
-						break;
-					}
-					Err(TryRecvError::Disconnected) => break,
-					Err(TryRecvError::Empty) => {}
-				}
-				cnt += 1;
-				if cnt > TTL * 10 {
+			let addresses = match relay_addr_query_rx.recv_timeout(Duration::from_secs(TTL as u64))
+			{
+				Ok((_abbr, addrs)) if !addrs.is_empty() => Some(addrs),
+				Ok(_) | Err(RecvTimeoutError::Disconnected) => None,
+				Err(RecvTimeoutError::Timeout) => {
 					info!(
 						"{} from relay server for address query. {}s timeout",
 						"No response".bright_blue(),
@@ -381,8 +499,7 @@ pub fn send(
 						"relay server no response, please try again later".into(),
 					))?;
 				}
-				thread::sleep(Duration::from_millis(100));
-			}
+			};
 
 			if let Some(addresses) = addresses {
 				match addresses.len() {
@@ -419,6 +536,32 @@ pub fn send(
 		}
 	}
 
+	// armored slates, encrypted or not, are written by a file transport of
+	// their own
+	let armored = args.armor || args.encrypt_to.is_some();
+	if armored {
+		let recipient = match args.encrypt_to {
+			Some(ref addr) => Some(GrinboxAddress::from_str(addr).map_err(|_| {
+				ErrorKind::ArgumentError(format!(
+					"Invalid Grin Relay address to encrypt to: {}",
+					addr
+				))
+			})?),
+			None => None,
+		};
+		transports.register(Transport::new(
+			"file",
+			&["file"],
+			transports.get("file").unwrap().capabilities,
+			move |_| {
+				Ok(FileWalletCommAdapter::new_armored(
+					recipient.clone(),
+					vec![],
+				))
+			},
+		));
+	}
+
 	controller::owner_single_use(wallet.clone(), |api| {
 		if args.estimate_selection_strategies {
 			let strategies = vec!["smallest", "biggest", "all"]
@@ -449,6 +592,8 @@ pub fn send(
 				selection_strategy: args.selection_strategy.clone(),
 				message: args.message.clone(),
 				target_slate_version: args.target_slate_version,
+				payment_proof: Some(method != "self"),
+				ttl_blocks: args.ttl_blocks,
 				send_args: None,
 				..Default::default()
 			};
@@ -468,20 +613,13 @@ pub fn send(
 					return Err(e);
 				}
 			};
-			let adapter = match args.method.as_str() {
-				"http" => HTTPWalletCommAdapter::new(),
-				"file" => FileWalletCommAdapter::new(),
-				"relay" => GrinrelayWalletCommAdapter::new(grinrelay_listener.unwrap(), relay_rx),
-				"keybase" => KeybaseWalletCommAdapter::new(),
-				"self" => NullWalletCommAdapter::new(),
-				_ => NullWalletCommAdapter::new(),
-			};
+			let adapter = transports.adapter(&method)?;
 
 			if adapter.supports_sync() {
 				let (returned_slate, tx_proof) = adapter.send_tx_sync(dest.as_str(), &slate)?;
 				slate = returned_slate;
 				api.tx_lock_outputs(&slate, 0)?;
-				if args.method == "self" {
+				if method == "self" {
 					controller::foreign_single_use(wallet, |api| {
 						slate = api.receive_tx(&slate, Some(&args.dest), None, None)?;
 						Ok(())
@@ -495,6 +633,9 @@ pub fn send(
 			} else {
 				adapter.send_tx_async(dest.as_str(), &slate)?;
 				api.tx_lock_outputs(&slate, 0)?;
+				if armored {
+					println!("{}", fs::read_to_string(dest.as_str())?);
+				}
 			}
 			if adapter.supports_sync() {
 				let result = api.post_tx(Some(slate.id), &slate.tx, args.fluff);
@@ -535,9 +676,10 @@ pub fn receive(
 	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
 	g_args: &GlobalArgs,
 	args: ReceiveArgs,
+	wallet_config: &WalletConfig,
 ) -> Result<(), Error> {
-	let adapter = FileWalletCommAdapter::new();
-	let mut slate = adapter.receive_tx_async(&args.input)?;
+	let (mut slate, armored, encrypted) =
+		read_slate_file(wallet.clone(), wallet_config, &args.input)?;
 	controller::foreign_single_use(wallet, |api| {
 		if let Err(e) = api.verify_slate_messages(&slate) {
 			error!("Error validating participant messages: {}", e);
@@ -546,8 +688,37 @@ pub fn receive(
 		slate = api.receive_tx(&slate, Some(&g_args.account), args.message.clone(), None)?;
 		Ok(())
 	})?;
+	// answer the way we were sent the slate, encrypted to the payment proof
+	// address of the sender if the slate was encrypted to us
+	let adapter = if encrypted {
+		let recipient = match slate.payment_proof {
+			Some(ref info) => {
+				Some(GrinboxAddress::from_str(&info.sender_address).map_err(|_| {
+					ErrorKind::ArgumentError(format!(
+						"Invalid sender address {}",
+						info.sender_address
+					))
+				})?)
+			}
+			None => {
+				warn!("No sender address in the slate, the response is not encrypted");
+				None
+			}
+		};
+		FileWalletCommAdapter::new_armored(recipient, vec![])
+	} else if armored {
+		FileWalletCommAdapter::new_armored(None, vec![])
+	} else {
+		FileWalletCommAdapter::new()
+	};
 	let send_tx = format!("{}.response", args.input);
 	adapter.send_tx_async(&send_tx, &slate)?;
+	if armored {
+		println!(
+			"{}",
+			fs::read_to_string(&send_tx).map_err(|_| ErrorKind::IO)?
+		);
+	}
 	info!(
 		"Response file {}.response generated, and can be sent back to the transaction originator.",
 		args.input
@@ -564,9 +735,9 @@ pub struct FinalizeArgs {
 pub fn finalize(
 	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
 	args: FinalizeArgs,
+	wallet_config: &WalletConfig,
 ) -> Result<(), Error> {
-	let adapter = FileWalletCommAdapter::new();
-	let mut slate = adapter.receive_tx_async(&args.input)?;
+	let (mut slate, _, _) = read_slate_file(wallet.clone(), wallet_config, &args.input)?;
 	// Rather than duplicating the entire command, we'll just
 	// try to determine what kind of finalization this is
 	// based on the slate contents
@@ -622,6 +793,32 @@ pub fn finalize(
 	Ok(())
 }
 
+/// Read a slate file, armored or not. An encrypted slate is decrypted with
+/// the relay keys of the wallet. Returns the slate, whether it was armored and
+/// whether it was encrypted.
+fn read_slate_file(
+	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
+	wallet_config: &WalletConfig,
+	input: &str,
+) -> Result<(Slate, bool, bool), libwallet::Error> {
+	let content = fs::read_to_string(input)?;
+	if !armor::is_armored(&content) {
+		let slate = FileWalletCommAdapter::new().receive_tx_async(input)?;
+		return Ok((slate, false, false));
+	}
+	let encrypted = armor::is_encrypted(&content);
+	let secret_keys = if encrypted {
+		controller::grinrelay_secret_keys(
+			wallet,
+			wallet_config.grinrelay_config.clone().unwrap_or_default(),
+		)?
+	} else {
+		vec![]
+	};
+	let adapter = FileWalletCommAdapter::new_armored(None, secret_keys);
+	Ok((adapter.receive_tx_async(input)?, true, encrypted))
+}
+
 /// Issue Invoice Args
 pub struct IssueInvoiceArgs {
 	/// output file
@@ -661,7 +858,12 @@ pub fn process_invoice(
 	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
 	args: ProcessInvoiceArgs,
 	dark_scheme: bool,
+	transports: &TransportRegistry,
 ) -> Result<(), Error> {
+	let (method, dest) = {
+		let (transport, dest) = transports.resolve(&args.method, &args.dest)?;
+		(transport.name.clone(), dest)
+	};
 	let adapter = FileWalletCommAdapter::new();
 	let slate = adapter.receive_tx_async(&args.input)?;
 	controller::owner_single_use(wallet.clone(), |api| {
@@ -716,23 +918,18 @@ pub fn process_invoice(
 					return Err(e);
 				}
 			};
-			let adapter = match args.method.as_str() {
-				"http" => HTTPWalletCommAdapter::new(),
-				"file" => FileWalletCommAdapter::new(),
-				"self" => NullWalletCommAdapter::new(),
-				_ => NullWalletCommAdapter::new(),
-			};
+			let adapter = transports.adapter(&method)?;
 			if adapter.supports_sync() {
-				slate = adapter.send_tx_sync(&args.dest, &slate)?.0;
+				slate = adapter.send_tx_sync(&dest, &slate)?.0;
 				api.tx_lock_outputs(&slate, 0)?;
-				if args.method == "self" {
+				if method == "self" {
 					controller::foreign_single_use(wallet, |api| {
 						slate = api.finalize_invoice_tx(&slate)?;
 						Ok(())
 					})?;
 				}
 			} else {
-				adapter.send_tx_async(&args.dest, &slate)?;
+				adapter.send_tx_async(&dest, &slate)?;
 				api.tx_lock_outputs(&slate, 0)?;
 			}
 		}
@@ -967,7 +1164,8 @@ pub fn txs(
 #[derive(Clone, Debug)]
 pub enum ProofArgs<'a> {
 	Export(u32, &'a str, &'a str),
-	Verify(&'a str),
+	/// Proof file, offline, kernel inclusion record file (implies offline)
+	Verify(&'a str, bool, Option<&'a str>),
 }
 
 /// Proof
@@ -1008,20 +1206,55 @@ pub fn proof(
 					file.write_all(serde_json::to_string(&tx_proof)?.as_bytes())?;
 					println!("Proof exported to {}", filename.bright_green());
 					let _ = display::proof(verified, dark_scheme);
+				} else if let Some(proof) = api.get_stored_payment_proof(Some(index), None)? {
+					let verified = api.verify_payment_proof(
+						&proof,
+						&config.check_node_api_http_addr,
+						config.node_api_secret.clone(),
+					)?;
+					let mut file = File::create(filename.replace("~", &home_dir))?;
+					file.write_all(serde_json::to_string(&proof)?.as_bytes())?;
+					println!("Proof exported to {}", filename.bright_green());
+					let _ = display::proof(verified, dark_scheme);
 				} else {
 					println!("No proof for this transaction");
 				}
 			}
-			ProofArgs::Verify(filename) => {
+			ProofArgs::Verify(filename, offline, kernel_file) => {
 				let mut file = File::open(filename.replace("~", &home_dir))?;
 				let mut tx_proof = String::new();
 				file.read_to_string(&mut tx_proof)?;
-				let tx_proof: TxProof = serde_json::from_str(&tx_proof)?;
-				let verified = api.verify_tx_proof(
-					&tx_proof,
-					&config.check_node_api_http_addr,
-					config.node_api_secret.clone(),
-				)?;
+				let verified = if offline || kernel_file.is_some() {
+					let kernel = match kernel_file {
+						Some(kernel_file) => {
+							Some(read_kernel_record(&kernel_file.replace("~", &home_dir))?)
+						}
+						None => None,
+					};
+					match serde_json::from_str::<TxProof>(&tx_proof) {
+						Ok(tx_proof) => api.verify_tx_proof_offline(&tx_proof, kernel.as_ref())?,
+						Err(_) => {
+							let proof: PaymentProof = serde_json::from_str(&tx_proof)?;
+							api.verify_payment_proof_offline(&proof, kernel.as_ref())?
+						}
+					}
+				} else {
+					match serde_json::from_str::<TxProof>(&tx_proof) {
+						Ok(tx_proof) => api.verify_tx_proof(
+							&tx_proof,
+							&config.check_node_api_http_addr,
+							config.node_api_secret.clone(),
+						)?,
+						Err(_) => {
+							let proof: PaymentProof = serde_json::from_str(&tx_proof)?;
+							api.verify_payment_proof(
+								&proof,
+								&config.check_node_api_http_addr,
+								config.node_api_secret.clone(),
+							)?
+						}
+					}
+				};
 				let _ = display::proof(verified, dark_scheme);
 			}
 		};
@@ -1030,6 +1263,123 @@ pub fn proof(
 	Ok(())
 }
 
+/// Proof batch command args
+pub struct ProofBatchArgs<'a> {
+	/// Directory of the proof files
+	pub dir: &'a str,
+	/// Report format, csv or json
+	pub format: &'a str,
+	/// Report file, the report is printed without one
+	pub output: Option<&'a str>,
+	/// Whether to verify the proofs without a node
+	pub offline: bool,
+}
+
+/// Verifies a directory of proof files. No wallet is needed.
+pub fn proof_batch(args: ProofBatchArgs, config: &WalletConfig) -> Result<(), Error> {
+	let home_dir = dirs::home_dir()
+		.map(|p| p.to_str().unwrap().to_string())
+		.unwrap_or("~".to_string());
+
+	let node_addr = match args.offline {
+		true => None,
+		false => Some(config.check_node_api_http_addr.as_str()),
+	};
+	let reports = verify_tx_proofs(
+		&args.dir.replace("~", &home_dir),
+		node_addr,
+		config.node_api_secret.clone(),
+	)?;
+	write_proof_report(&reports, args.format, args.output, &home_dir)?;
+	let count = |status: TxProofStatus| reports.iter().filter(|r| r.status == status).count();
+	let failed = count(TxProofStatus::Invalid);
+	if args.offline {
+		println!(
+			"{} proofs verified offline: {} cryptographically valid with confirmation unknown, {} failed",
+			reports.len(),
+			count(TxProofStatus::ConfirmationUnknown),
+			failed,
+		);
+	} else {
+		println!(
+			"{} proofs verified: {} confirmed, {} unconfirmed, {} failed",
+			reports.len(),
+			count(TxProofStatus::Confirmed),
+			count(TxProofStatus::Unconfirmed),
+			failed,
+		);
+	}
+	Ok(())
+}
+
+/// Writes a batch proof verification report to a file, or prints it
+fn write_proof_report(
+	reports: &[TxProofReport],
+	format: &str,
+	output: Option<&str>,
+	home_dir: &str,
+) -> Result<(), libwallet::Error> {
+	let report = match format {
+		"json" => serde_json::to_string_pretty(&reports)?,
+		_ => proof_report_csv(&reports),
+	};
+	match output {
+		Some(filename) => {
+			let mut file = File::create(filename.replace("~", home_dir))?;
+			file.write_all(report.as_bytes())?;
+			println!("Report written to {}", filename.bright_green());
+		}
+		None => println!("{}", report),
+	}
+	Ok(())
+}
+
+/// Reads a kernel inclusion record, either a single kernel entry or the whole
+/// response of the node `v1/chain/kernels/byids` API
+fn read_kernel_record(filename: &str) -> Result<TxKernelApiEntry, libwallet::Error> {
+	let mut file = File::open(filename)?;
+	let mut content = String::new();
+	file.read_to_string(&mut content)?;
+	match serde_json::from_str::<TxKernelApiEntry>(&content) {
+		Ok(kernel) => Ok(kernel),
+		Err(_) => serde_json::from_str::<Vec<TxKernelApiEntry>>(&content)?
+			.pop()
+			.ok_or_else(|| {
+				libwallet::ErrorKind::GenericError("Empty kernel inclusion record".to_owned())
+					.into()
+			}),
+	}
+}
+
+/// Renders a batch proof verification report as CSV, one line per proof file
+fn proof_report_csv(reports: &[TxProofReport]) -> String {
+	fn field(value: Option<String>) -> String {
+		let value = value.unwrap_or_default();
+		if value.contains(|c| c == ',' || c == '"' || c == '\n') {
+			format!("\"{}\"", value.replace("\"", "\"\""))
+		} else {
+			value
+		}
+	}
+
+	let mut csv = "file,status,sender,receiver,amount,excess,height,error\n".to_owned();
+	for r in reports {
+		let line = vec![
+			field(Some(r.file.clone())),
+			field(Some(r.status.to_string())),
+			field(r.sender.clone()),
+			field(r.receiver.clone()),
+			field(r.amount.map(|a| a.to_string())),
+			field(r.excess.clone()),
+			field(r.height.map(|h| h.to_string())),
+			field(r.error.clone()),
+		];
+		csv.push_str(&line.join(","));
+		csv.push('\n');
+	}
+	csv
+}
+
 /// Address
 pub fn address(
 	wallet: Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>,
@@ -1190,3 +1540,32 @@ pub fn change_password(
 	})?;
 	Ok(())
 }
+
+/// Arguments for convert command
+pub struct ConvertArgs {
+	pub to: WalletBackendType,
+}
+
+pub fn convert(config: &WalletConfig, g_args: &GlobalArgs, args: ConvertArgs) -> Result<(), Error> {
+	if config.wallet_backend() == args.to {
+		return Err(ErrorKind::ArgumentError(format!(
+			"Wallet is already configured to use the {} backend",
+			args.to
+		)))?;
+	}
+	let client_n = HTTPNodeClient::new(
+		&config.check_node_api_http_addr,
+		g_args.node_api_secret.clone(),
+	);
+	convert_wallet_db(
+		config,
+		client_n,
+		&g_args.password.clone().unwrap(),
+		args.to.clone(),
+	)?;
+	warn!(
+		"Wallet database copied to the {} backend. Set 'wallet_backend' in grin-wallet.toml to use it.",
+		args.to
+	);
+	Ok(())
+}
@@ -15,17 +15,22 @@
 
 //! Controller for wallet.. instantiates and handles listeners (or single-run
 //! invocations) as needed.
-use crate::api::{self, ApiServer, BasicAuthMiddleware, ResponseFuture, Router, TLSConfig};
-use crate::config::GrinRelayConfig;
-use crate::keychain::Keychain;
+use crate::api::{self, BasicAuthMiddleware, ResponseFuture, Router, TLSConfig};
+use crate::config::{ForeignApiLimits, GrinRelayConfig};
+use crate::impls::{
+	onion_service_key, DirScan, DirTransport, KeybaseWalletCommAdapter, TorConfig, TorControl,
+};
+use crate::keychain::{Keychain, SwitchCommitmentType};
 use crate::libwallet::{
-	Error, ErrorKind, Listener, NodeClient, NodeVersionInfo, Slate, SlateVersion, TxProof,
-	VersionedSlate, WalletBackend, CURRENT_SLATE_VERSION, GRIN_BLOCK_HEADER_VERSION,
+	Error, ErrorKind, Listener, ListenerStatus, NodeClient, NodeVersionInfo, Slate, TxProof,
+	VersionedSlate, WalletBackend, GRIN_BLOCK_HEADER_VERSION, MIN_SLATE_VERSION,
 };
 
-use crate::util::secp::key::PublicKey;
+use crate::limits::{ForeignLimiter, LimitError};
+use crate::server::start_api_server;
+use crate::util::secp::key::{PublicKey, SecretKey};
 use crate::util::to_base64;
-use crate::util::Mutex;
+use crate::util::{Mutex, RwLock};
 use colored::*;
 use failure::ResultExt;
 use futures::future::{err, ok};
@@ -33,22 +38,27 @@ use futures::{Future, Stream};
 use hyper::header::HeaderValue;
 use hyper::{Body, Request, Response, StatusCode};
 use rand::prelude::*;
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use crate::grinrelay::hasher::derive_address_key;
+use crate::grinrelay::message::MESSAGE_VERSION;
 use crate::grinrelay::GrinboxAddress;
 use crate::grinrelay::{
 	Controller, GrinboxListener, GrinboxPublisher, GrinboxSubscriber, Subscriber,
 };
 
-use crate::apiwallet::{Foreign, ForeignCheckMiddlewareFn, ForeignRpc, Owner, OwnerRpc};
+use crate::apiwallet::{
+	Foreign, ForeignCheckMiddleware, ForeignCheckMiddlewareFn, ForeignRequestContext, ForeignRpc,
+	ForeignTransport, Owner, OwnerRpc, Wallets, WalletsRpc,
+};
 use easy_jsonrpc;
 use easy_jsonrpc::{Handler, MaybeReply};
 
@@ -71,7 +81,7 @@ fn check_middleware(
 				bhv = n.block_header_version;
 			}
 			if let Some(s) = slate {
-				if s.version_info.version < CURRENT_SLATE_VERSION
+				if s.version_info.version < MIN_SLATE_VERSION
 					|| (bhv == 1 && s.version_info.block_header_version != 1)
 					|| (bhv > 1 && s.version_info.block_header_version < GRIN_BLOCK_HEADER_VERSION)
 				{
@@ -109,25 +119,32 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
-	f(&mut Foreign::new(wallet.clone(), Some(check_middleware)))?;
+	f(&mut Foreign::new(
+		wallet.clone(),
+		Some(Arc::new(check_middleware)),
+	))?;
 	Ok(())
 }
 
 /// Listener version, providing same API but listening for requests on a
-/// port and wrapping the calls
+/// port and wrapping the calls. Without a default `wallet`, the calls have to
+/// select one of the hosted `wallets`.
 pub fn owner_listener<T: ?Sized, C, K>(
-	wallet: Arc<Mutex<T>>,
+	wallet: Option<Arc<Mutex<T>>>,
 	addr: &str,
 	api_secret: Option<String>,
 	tls_config: Option<TLSConfig>,
 	owner_api_include_foreign: Option<bool>,
+	foreign_limits: ForeignApiLimits,
+	wallets: Option<Arc<Wallets<T, C, K>>>,
+	listeners: Option<Arc<RwLock<Vec<ListenerStatus>>>>,
 ) -> Result<(), Error>
 where
 	T: WalletBackend<C, K> + Send + Sync + 'static,
 	C: NodeClient + 'static,
 	K: Keychain + 'static,
 {
-	let api_handler_v2 = OwnerAPIHandlerV2::new(wallet.clone());
+	let api_handler_v2 = OwnerAPIHandlerV2::new(wallet.clone(), wallets.clone(), listeners);
 
 	let mut router = Router::new();
 	if api_secret.is_some() {
@@ -144,45 +161,56 @@ where
 		.add_route("/v2/owner", Arc::new(api_handler_v2))
 		.map_err(|_| ErrorKind::GenericError("Router failed to add route".to_string()))?;
 
-	// If so configured, add the foreign API to the same port
-	if owner_api_include_foreign.unwrap_or(false) {
-		info!("Starting HTTP Foreign API on Owner server at {}.", addr);
-		let foreign_api_handler_v2 = ForeignAPIHandlerV2::new(wallet.clone());
+	// If so configured, host more wallets on the same port
+	if let Some(wallets) = wallets {
+		info!("Starting HTTP Wallets API on Owner server at {}.", addr);
+		let wallets_api_handler_v2 = WalletsAPIHandlerV2::new(wallets);
 		router
-			.add_route("/v2/foreign", Arc::new(foreign_api_handler_v2))
+			.add_route("/v2/wallets", Arc::new(wallets_api_handler_v2))
 			.map_err(|_| ErrorKind::GenericError("Router failed to add route".to_string()))?;
 	}
 
-	let mut apis = ApiServer::new();
+	// If so configured, add the foreign API of the default wallet to the same port
+	if owner_api_include_foreign.unwrap_or(false) {
+		match wallet {
+			Some(wallet) => {
+				info!("Starting HTTP Foreign API on Owner server at {}.", addr);
+				let foreign_api_handler_v2 =
+					ForeignAPIHandlerV2::new(wallet, vec![], foreign_limits);
+				router
+					.add_route("/v2/foreign", Arc::new(foreign_api_handler_v2))
+					.map_err(|_| {
+						ErrorKind::GenericError("Router failed to add route".to_string())
+					})?;
+			}
+			None => warn!("No default wallet, not starting the Foreign API on Owner server"),
+		}
+	}
+
 	info!("Starting HTTP Owner API server at {}.", addr);
 	let socket_addr: SocketAddr = addr.parse().expect("unable to parse socket address");
-	let api_thread =
-		apis.start(socket_addr, router, tls_config)
-			.context(ErrorKind::GenericError(
-				"API thread failed to start".to_string(),
-			))?;
+	let api_thread = start_api_server(socket_addr, router, tls_config)?;
 	api_thread
 		.join()
 		.map_err(|e| ErrorKind::GenericError(format!("API thread panicked :{:?}", e)).into())
 }
 
 /// Listener version, providing same API but listening for requests on a
-/// port and wrapping the calls
+/// port and wrapping the calls. The `middlewares` are called, in order, on
+/// each request, which the `limits` apply to
 pub fn foreign_listener<T: ?Sized, C, K>(
 	wallet: Arc<Mutex<T>>,
 	addr: &str,
 	tls_config: Option<TLSConfig>,
-	relay_rx_as_payee: Option<Receiver<(String, Slate)>>,
-	grinrelay_listener: Option<Box<dyn Listener>>,
-	grinrelay_key_path: Option<u64>,
-	account: &str,
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+	limits: ForeignApiLimits,
 ) -> Result<(), Error>
 where
 	T: WalletBackend<C, K> + Send + Sync + 'static,
 	C: NodeClient + 'static,
 	K: Keychain + 'static,
 {
-	let api_handler_v2 = ForeignAPIHandlerV2::new(wallet.clone());
+	let api_handler_v2 = ForeignAPIHandlerV2::new(wallet, middlewares, limits);
 
 	let mut router = Router::new();
 
@@ -190,64 +218,228 @@ where
 		.add_route("/v2/foreign", Arc::new(api_handler_v2))
 		.map_err(|_| ErrorKind::GenericError("Router failed to add route".to_string()))?;
 
-	let mut apis = ApiServer::new();
 	debug!("Starting HTTP/S Foreign listener API server at {}", addr);
 	let socket_addr: SocketAddr = addr.parse().expect("unable to parse socket address");
-	let api_thread =
-		apis.start(socket_addr, router, tls_config)
-			.context(ErrorKind::GenericError(
-				"API thread failed to start".to_string(),
-			))?;
+	let api_thread = start_api_server(socket_addr, router, tls_config)?;
 	info!(
 		"HTTP/S Foreign listener started.  Ready to receive Grin at {}",
 		addr.bright_green()
 	);
 
-	if let Some(relay_rx_as_payee) = relay_rx_as_payee {
-		let api = Foreign::new(wallet, None);
-		let grinrelay_listener = grinrelay_listener.unwrap();
-		loop {
-			match relay_rx_as_payee.try_recv() {
-				Ok((addr, slate)) => {
-					let slate_id = slate.id;
-					if api.verify_slate_messages(&slate).is_ok() {
-						let slate_rx =
-							api.receive_tx(&slate, Some(account), None, grinrelay_key_path);
-						if let Ok(slate_rx) = slate_rx {
-							let versioned_slate =
-								VersionedSlate::into_version(slate_rx.clone(), SlateVersion::V2);
-							let res =
-								grinrelay_listener.publish(&versioned_slate, &addr.to_owned());
-							match res {
-								Ok(_) => {
-									info!(
-										"Slate [{}] sent back to {} successfully",
-										slate_id.to_string().bright_green(),
-										addr.bright_green(),
-									);
-								}
-								Err(e) => {
-									error!(
-										"Slate [{}] fail to sent back to {} for {}",
-										slate_id.to_string().bright_green(),
-										addr.bright_green(),
-										e,
-									);
-								}
-							}
-						}
-					}
+	api_thread
+		.join()
+		.map_err(|e| ErrorKind::GenericError(format!("API thread panicked :{:?}", e)).into())
+}
+
+/// Receive the slates coming from Grin Relay into the wallet on `workers`
+/// threads, sending the signed slates back to their senders, until the relay
+/// listener goes away. The `middlewares` are called, in order, on each slate.
+pub fn grinrelay_receive_loop<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	relay_rx_as_payee: Receiver<(String, Slate)>,
+	grinrelay_listener: Box<dyn Listener>,
+	grinrelay_key_path: Option<u64>,
+	account: &str,
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+	workers: usize,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let relay_rx_as_payee = Arc::new(Mutex::new(relay_rx_as_payee));
+	let mut handles = vec![];
+	for i in 0..workers.max(1) {
+		let wallet = wallet.clone();
+		let relay_rx_as_payee = relay_rx_as_payee.clone();
+		let grinrelay_listener = grinrelay_listener.box_clone();
+		let account = account.to_owned();
+		let middlewares = middlewares.clone();
+		let handle = thread::Builder::new()
+			.name(format!("grinrelay-receive-{}", i))
+			.spawn(move || {
+				let mut api = Foreign::new(wallet, None);
+				for m in middlewares {
+					api.add_middleware(m);
+				}
+				loop {
+					// the lock is only held by the idle workers, waiting for a slate
+					let received = relay_rx_as_payee.lock().recv();
+					let (addr, slate) = match received {
+						Ok(r) => r,
+						Err(_) => break,
+					};
+					grinrelay_receive_slate(
+						&mut api,
+						&*grinrelay_listener,
+						grinrelay_key_path,
+						&account,
+						addr,
+						slate,
+					);
+				}
+			})
+			.context(ErrorKind::GenericError(
+				"Grin Relay receive thread failed to start".to_string(),
+			))?;
+		handles.push(handle);
+	}
+	for handle in handles {
+		handle.join().map_err(|e| {
+			ErrorKind::GenericError(format!("Grin Relay receive thread panicked :{:?}", e))
+		})?;
+	}
+	Ok(())
+}
+
+/// Receive a slate coming from Grin Relay, and send it back signed
+fn grinrelay_receive_slate<T: ?Sized, C, K>(
+	api: &mut Foreign<T, C, K>,
+	grinrelay_listener: &dyn Listener,
+	grinrelay_key_path: Option<u64>,
+	account: &str,
+	addr: String,
+	slate: Slate,
+) where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let slate_id = slate.id;
+	api.set_request_context(ForeignRequestContext {
+		transport: ForeignTransport::GrinRelay,
+		remote_addr: Some(addr.clone()),
+		account: Some(account.to_owned()),
+	});
+	if api.verify_slate_messages(&slate).is_err() {
+		return;
+	}
+	match api.receive_tx(&slate, Some(account), None, grinrelay_key_path) {
+		Ok(slate_rx) => {
+			let versioned_slate = VersionedSlate::into_orig_version(slate_rx);
+			match grinrelay_listener.publish(&versioned_slate, &addr) {
+				Ok(_) => {
+					info!(
+						"Slate [{}] sent back to {} successfully",
+						slate_id.to_string().bright_green(),
+						addr.bright_green(),
+					);
+				}
+				Err(e) => {
+					error!(
+						"Slate [{}] fail to sent back to {} for {}",
+						slate_id.to_string().bright_green(),
+						addr.bright_green(),
+						e,
+					);
 				}
-				Err(TryRecvError::Disconnected) => break,
-				Err(TryRecvError::Empty) => {}
 			}
-			thread::sleep(Duration::from_millis(100));
+		}
+		Err(e) => {
+			warn!("Slate [{}] from {} not received: {}", slate_id, addr, e);
 		}
 	}
+}
 
-	api_thread
-		.join()
-		.map_err(|e| ErrorKind::GenericError(format!("API thread panicked :{:?}", e)).into())
+/// Receive the slates sent over keybase into the wallet, through the Foreign
+/// API for the `middlewares` to be called on each slate, until keybase stops.
+pub fn keybase_listener<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	keybase: &KeybaseWalletCommAdapter,
+	notify_ttl: u16,
+	account: &str,
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let mut foreign = Foreign::new(wallet, None);
+	for m in middlewares {
+		foreign.add_middleware(m);
+	}
+	let receive = |channel: &str, slate: &Slate| {
+		foreign.set_request_context(ForeignRequestContext {
+			transport: ForeignTransport::Keybase,
+			remote_addr: Some(channel.to_owned()),
+			account: Some(account.to_owned()),
+		});
+		foreign.receive_tx(slate, Some(account), None, None)
+	};
+	keybase.receive_loop(receive, notify_ttl)
+}
+
+/// Receive or finalize the slates dropped into the inbox of the directory
+/// transport, scanning it every `poll_secs` of its config. Only returns on
+/// an error of the directories themselves.
+pub fn dir_listener<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	transport: &DirTransport,
+	account: &str,
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let poll = Duration::from_secs(transport.config().poll_secs.unwrap_or(5).max(1));
+	info!(
+		"Listening for slates in {}",
+		transport.config().inbox.bright_green()
+	);
+	loop {
+		dir_process_inbox(wallet.clone(), transport, account, middlewares.clone())?;
+		thread::sleep(poll);
+	}
+}
+
+/// Scan the inbox of the directory transport once. The new slates are
+/// received, their responses written to the outbox, and the responses to the
+/// slates we sent are finalized and posted.
+pub fn dir_process_inbox<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	transport: &DirTransport,
+	account: &str,
+	middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+) -> Result<DirScan, Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let mut foreign = Foreign::new(wallet.clone(), None);
+	for m in middlewares {
+		foreign.add_middleware(m);
+	}
+	foreign.set_request_context(ForeignRequestContext {
+		transport: ForeignTransport::Directory,
+		remote_addr: None,
+		account: Some(account.to_owned()),
+	});
+	let owner = Owner::new(wallet);
+	let scan = transport.scan(
+		|slate| {
+			foreign.verify_slate_messages(slate)?;
+			foreign.receive_tx(slate, Some(account), None, None)
+		},
+		|slate| {
+			let slate = owner.finalize_tx(slate, None, None)?;
+			owner.post_tx(Some(slate.id), &slate.tx, false)
+		},
+	)?;
+	if scan != DirScan::default() {
+		info!(
+			"Inbox {}: {} slates processed, {} already processed, {} failed",
+			transport.config().inbox,
+			scan.processed,
+			scan.duplicates,
+			scan.failed,
+		);
+	}
+	Ok(scan)
 }
 
 /// Get the Grin Relay Address
@@ -264,6 +456,9 @@ where
 
 	let pub_key = {
 		let mut w = wallet.lock();
+		if w.is_watch_only() {
+			return Err(ErrorKind::WatchOnly("grinrelay address".to_owned()).into());
+		}
 		w.open_with_credentials()?;
 		let keychain = w.keychain();
 		let sec_key = derive_address_key(keychain, 0, index)?;
@@ -279,12 +474,96 @@ where
 	Ok(address.stripped())
 }
 
+/// Domain of the hash deriving the Tor onion service seed from the wallet
+/// root key, apart from the BIP32 tree of the Grin Relay addresses
+const TOR_KEY_DOMAIN: &[u8] = b"GottsWalletTorOnionServiceKey";
+
+/// Get the key of the Tor onion service of the wallet, derived from its seed
+/// so the onion address stays the same over restores
+pub fn tor_onion_key<T: ?Sized, C, K>(wallet: Arc<Mutex<T>>) -> Result<String, Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let mut w = wallet.lock();
+	if w.is_watch_only() {
+		return Err(ErrorKind::WatchOnly("onion address".to_owned()).into());
+	}
+	w.open_with_credentials()?;
+	let root = w
+		.keychain()
+		.derive_key(0, &K::root_key_id(), &SwitchCommitmentType::None)?;
+	let mut seed = [0; 32];
+	seed.copy_from_slice(
+		digest::digest(&digest::SHA256, &[TOR_KEY_DOMAIN, &root.0[..]].concat()).as_ref(),
+	);
+	Ok(onion_service_key(&seed))
+}
+
+/// Publish the onion service of the wallet through the control port of Tor,
+/// forwarding it to the Foreign API listening on `listen_addr`. Blocks while
+/// Tor keeps the service up.
+pub fn tor_listener<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	tor_config: &TorConfig,
+	listen_addr: &str,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let key = tor_onion_key(wallet)?;
+	// Tor reaches the listener locally, whatever interface it is bound to
+	let target = match listen_addr.rfind(':') {
+		Some(i) if listen_addr.starts_with("0.0.0.0") => {
+			format!("127.0.0.1{}", &listen_addr[i..])
+		}
+		_ => listen_addr.to_owned(),
+	};
+	let mut control = TorControl::connect(tor_config)?;
+	let onion = control.add_onion(&key, tor_config.onion_port(), &target)?;
+	warn!(
+		"Listening on the onion address {}, forwarded to {}",
+		onion.bright_green(),
+		target
+	);
+	control.wait()
+}
+
+/// Get the Grin Relay keys an armored slate for this wallet can be encrypted
+/// to: the key of the receiving address, and the key of the payment proof
+/// address, which the receivers of our slates answer to
+pub fn grinrelay_secret_keys<T: ?Sized, C, K>(
+	wallet: Arc<Mutex<T>>,
+	grinrelay_config: GrinRelayConfig,
+) -> Result<Vec<SecretKey>, Error>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	let mut w = wallet.lock();
+	if w.is_watch_only() {
+		return Err(ErrorKind::WatchOnly("encrypted slate".to_owned()).into());
+	}
+	w.open_with_credentials()?;
+	let keychain = w.keychain();
+	let mut keys = vec![derive_address_key(keychain, 0, 0)?];
+	let index = grinrelay_config.grinrelay_receiving_address_index;
+	if index != 0 {
+		keys.push(derive_address_key(keychain, 0, index)?);
+	}
+	Ok(keys)
+}
+
 /// Grin Relay Listener
 pub fn grinrelay_listener<T: ?Sized, C, K>(
 	wallet: Arc<Mutex<T>>,
 	grinrelay_config: GrinRelayConfig,
 	relay_tx_as_payer: Option<Sender<(Slate, Option<TxProof>)>>,
-	relay_tx_as_payee: Option<Sender<(String, Slate)>>,
+	relay_tx_as_payee: Option<SyncSender<(String, Slate)>>,
 	relay_addr_query: Option<Sender<(String, Vec<String>)>>,
 ) -> Result<(u64, Box<dyn Listener>), Error>
 where
@@ -324,6 +603,9 @@ where
 
 	let (sec_key, pub_key) = {
 		let mut w = wallet.lock();
+		if w.is_watch_only() {
+			return Err(ErrorKind::WatchOnly("grinrelay listener".to_owned()).into());
+		}
 		w.open_with_credentials()?;
 		let keychain = w.keychain();
 		let sec_key = derive_address_key(keychain, path, index)?;
@@ -341,6 +623,9 @@ where
 		&address,
 		&sec_key,
 		grinrelay_config.grinrelay_protocol_unsecure,
+		grinrelay_config
+			.grinrelay_message_version
+			.unwrap_or(MESSAGE_VERSION),
 	)?;
 
 	let subscriber = GrinboxSubscriber::new(&publisher)?;
@@ -384,8 +669,12 @@ where
 	C: NodeClient + 'static,
 	K: Keychain + 'static,
 {
-	/// Wallet instance
-	pub wallet: Arc<Mutex<T>>,
+	/// Wallet instance serving the calls without a wallet selector, if any
+	pub wallet: Option<Arc<Mutex<T>>>,
+	/// Hosted wallets, selected with a "wallet" member in the request
+	pub wallets: Option<Arc<Wallets<T, C, K>>>,
+	/// Status of the listeners run in the same process
+	pub listeners: Option<Arc<RwLock<Vec<ListenerStatus>>>>,
 	phantom: PhantomData<K>,
 	phantom_c: PhantomData<C>,
 }
@@ -397,20 +686,65 @@ where
 	K: Keychain + 'static,
 {
 	/// Create a new owner API handler for GET methods
-	pub fn new(wallet: Arc<Mutex<T>>) -> OwnerAPIHandlerV2<T, C, K> {
+	pub fn new(
+		wallet: Option<Arc<Mutex<T>>>,
+		wallets: Option<Arc<Wallets<T, C, K>>>,
+		listeners: Option<Arc<RwLock<Vec<ListenerStatus>>>>,
+	) -> OwnerAPIHandlerV2<T, C, K> {
 		OwnerAPIHandlerV2 {
 			wallet,
+			wallets,
+			listeners,
 			phantom: PhantomData,
 			phantom_c: PhantomData,
 		}
 	}
 
+	/// Wallet the request is addressed to, taking the wallet selector out of
+	/// the request
+	fn select_wallet(
+		wallet: Option<Arc<Mutex<T>>>,
+		wallets: Option<Arc<Wallets<T, C, K>>>,
+		val: &mut serde_json::Value,
+	) -> Result<Arc<Mutex<T>>, Error> {
+		let selector = match val.as_object_mut().and_then(|o| o.remove("wallet")) {
+			Some(s) => s,
+			None => {
+				return wallet.ok_or(
+					ErrorKind::GenericError(
+						"Invalid request body: a wallet selector is required".to_owned(),
+					)
+					.into(),
+				)
+			}
+		};
+		let name = selector.as_str().ok_or(ErrorKind::GenericError(
+			"Invalid request body: wallet selector must be a string".to_owned(),
+		))?;
+		match wallets {
+			Some(w) => w.wallet(name),
+			None => Err(ErrorKind::GenericError(
+				"Invalid request body: this owner API doesn't host other wallets".to_owned(),
+			)
+			.into()),
+		}
+	}
+
 	fn call_api(
 		&self,
 		req: Request<Body>,
-		api: Owner<T, C, K>,
 	) -> Box<dyn Future<Item = serde_json::Value, Error = Error> + Send> {
-		Box::new(parse_body(req).and_then(move |val: serde_json::Value| {
+		let wallet = self.wallet.clone();
+		let wallets = self.wallets.clone();
+		let listeners = self.listeners.clone();
+		Box::new(parse_body(req).and_then(move |mut val: serde_json::Value| {
+			let mut api = match Self::select_wallet(wallet, wallets, &mut val) {
+				Ok(w) => Owner::new(w),
+				Err(e) => return err(e),
+			};
+			if let Some(l) = listeners {
+				api.set_listeners(l);
+			}
 			let owner_api = &api as &dyn OwnerRpc;
 			match owner_api.handle_request(val) {
 				MaybeReply::Reply(r) => ok(r),
@@ -424,9 +758,8 @@ where
 	}
 
 	fn handle_post_request(&self, req: Request<Body>) -> WalletResponseFuture {
-		let api = Owner::new(self.wallet.clone());
 		Box::new(
-			self.call_api(req, api)
+			self.call_api(req)
 				.and_then(|resp| ok(json_response_pretty(&resp))),
 		)
 	}
@@ -454,6 +787,76 @@ where
 	}
 }
 
+/// V2 API Handler/Wrapper for the management of hosted wallets
+pub struct WalletsAPIHandlerV2<T: ?Sized, C, K>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	/// Hosted wallets
+	pub wallets: Arc<Wallets<T, C, K>>,
+}
+
+impl<T: ?Sized, C, K> WalletsAPIHandlerV2<T, C, K>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	/// Create a new hosted wallets API handler
+	pub fn new(wallets: Arc<Wallets<T, C, K>>) -> WalletsAPIHandlerV2<T, C, K> {
+		WalletsAPIHandlerV2 { wallets }
+	}
+
+	fn call_api(
+		&self,
+		req: Request<Body>,
+	) -> Box<dyn Future<Item = serde_json::Value, Error = Error> + Send> {
+		let wallets = self.wallets.clone();
+		Box::new(parse_body(req).and_then(move |val: serde_json::Value| {
+			let wallets_api = &*wallets as &dyn WalletsRpc;
+			match wallets_api.handle_request(val) {
+				MaybeReply::Reply(r) => ok(r),
+				MaybeReply::DontReply => {
+					// Since it's http, we need to return something. We return [] because jsonrpc
+					// clients will parse it as an empty batch response.
+					ok(serde_json::json!([]))
+				}
+			}
+		}))
+	}
+
+	fn handle_post_request(&self, req: Request<Body>) -> WalletResponseFuture {
+		Box::new(
+			self.call_api(req)
+				.and_then(|resp| ok(json_response_pretty(&resp))),
+		)
+	}
+}
+
+impl<T: ?Sized, C, K> api::Handler for WalletsAPIHandlerV2<T, C, K>
+where
+	T: WalletBackend<C, K> + Send + Sync + 'static,
+	C: NodeClient + 'static,
+	K: Keychain + 'static,
+{
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		Box::new(
+			self.handle_post_request(req)
+				.and_then(|r| ok(r))
+				.or_else(|e| {
+					error!("Request Error: {:?}", e);
+					ok(create_error_response(e))
+				}),
+		)
+	}
+
+	fn options(&self, _req: Request<Body>) -> ResponseFuture {
+		Box::new(ok(create_ok_response("{}")))
+	}
+}
+
 /// V2 API Handler/Wrapper for foreign functions
 pub struct ForeignAPIHandlerV2<T: ?Sized, C, K>
 where
@@ -463,6 +866,9 @@ where
 {
 	/// Wallet instance
 	pub wallet: Arc<Mutex<T>>,
+	/// Middlewares called after the compatibility check
+	pub middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+	limiter: Arc<ForeignLimiter>,
 	phantom: PhantomData<K>,
 	phantom_c: PhantomData<C>,
 }
@@ -474,9 +880,15 @@ where
 	K: Keychain + 'static,
 {
 	/// Create a new foreign API handler for GET methods
-	pub fn new(wallet: Arc<Mutex<T>>) -> ForeignAPIHandlerV2<T, C, K> {
+	pub fn new(
+		wallet: Arc<Mutex<T>>,
+		middlewares: Vec<Arc<dyn ForeignCheckMiddleware<T, C, K>>>,
+		limits: ForeignApiLimits,
+	) -> ForeignAPIHandlerV2<T, C, K> {
 		ForeignAPIHandlerV2 {
 			wallet,
+			middlewares,
+			limiter: Arc::new(ForeignLimiter::new(limits)),
 			phantom: PhantomData,
 			phantom_c: PhantomData,
 		}
@@ -486,8 +898,42 @@ where
 		&self,
 		req: Request<Body>,
 		api: Foreign<T, C, K>,
+		peer: Option<String>,
 	) -> Box<dyn Future<Item = serde_json::Value, Error = Error> + Send> {
-		Box::new(parse_body(req).and_then(move |val: serde_json::Value| {
+		let limiter = self.limiter.clone();
+		Box::new(limiter.read_body(req).and_then(move |body| {
+			let peer_name = peer.clone().unwrap_or("unknown peer".to_owned());
+			let reject = |e: LimitError, id: serde_json::Value| {
+				warn!("Foreign API request from {} refused: {}", peer_name, e);
+				ok::<_, Error>(e.to_response(id))
+			};
+			let body = match body {
+				Ok(body) => body,
+				Err(e) => return reject(e, serde_json::Value::Null),
+			};
+			let val: serde_json::Value = match serde_json::from_slice(&body) {
+				Ok(val) => val,
+				Err(e) => {
+					return err(
+						ErrorKind::GenericError(format!("Invalid request body: {}", e)).into(),
+					)
+				}
+			};
+			let id = val.get("id").cloned().unwrap_or(serde_json::Value::Null);
+			if let Err(e) = limiter.check_rate(
+				peer.as_ref().map(|p| p.as_str()),
+				ForeignLimiter::call_count(&val),
+			) {
+				return reject(e, id);
+			}
+			// held until the call returns
+			let mut _slot = None;
+			if ForeignLimiter::calls_receive(&val) {
+				match limiter.receive_slot() {
+					Ok(slot) => _slot = Some(slot),
+					Err(e) => return reject(e, id),
+				}
+			}
 			let foreign_api = &api as &dyn ForeignRpc;
 			match foreign_api.handle_request(val) {
 				MaybeReply::Reply(r) => ok(r),
@@ -501,9 +947,18 @@ where
 	}
 
 	fn handle_post_request(&self, req: Request<Body>) -> WalletResponseFuture {
-		let api = Foreign::new(self.wallet.clone(), Some(check_middleware));
+		let mut api = Foreign::new(self.wallet.clone(), Some(Arc::new(check_middleware)));
+		for m in &self.middlewares {
+			api.add_middleware(m.clone());
+		}
+		let peer = self.limiter.peer_addr(&req);
+		api.set_request_context(ForeignRequestContext {
+			transport: ForeignTransport::Http,
+			remote_addr: peer.clone(),
+			account: None,
+		});
 		Box::new(
-			self.call_api(req, api)
+			self.call_api(req, api, peer)
 				.and_then(|resp| ok(json_response_pretty(&resp))),
 		)
 	}
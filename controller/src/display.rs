@@ -742,9 +742,14 @@ pub fn proof(verified: TxProofVerified, _dark_background_color_scheme: bool) ->
 		format!("{}", verified.sender).bright_green()
 	);
 
-	println!("\nOutputs:");
-	for output in outputs {
-		println!("\t{}", output.bright_magenta());
+	// payment proofs signed by the receiver don't list outputs
+	if !outputs.is_empty() {
+		println!("\nOutputs:");
+		for output in outputs {
+			println!("\t{}", output.bright_magenta());
+		}
+	} else {
+		println!();
 	}
 	println!("Kernel Excess:");
 	println!("\t{}", excess.bright_magenta());
@@ -760,8 +765,18 @@ pub fn proof(verified: TxProofVerified, _dark_background_color_scheme: bool) ->
 				// impossible here
 				None => println!("\nTransaction {}", "confirmed".bright_green()),
 			}
+			if verified.offline {
+				println!("(according to the supplied kernel inclusion record, not checked against a node)");
+			}
 		}
-		false => println!("\nTransaction {}", "unconfirmed".bright_red()),
+		false => match verified.offline {
+			true => println!(
+				"\nTransaction {}, {}",
+				"cryptographically valid".bright_green(),
+				"confirmation unknown".bright_yellow()
+			),
+			false => println!("\nTransaction {}", "unconfirmed".bright_red()),
+		},
 	}
 
 	Ok(())
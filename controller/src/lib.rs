@@ -39,7 +39,10 @@ pub mod command;
 pub mod controller;
 pub mod display;
 mod error;
+mod limits;
+mod server;
+pub mod supervisor;
 
 pub use crate::error::{Error, ErrorKind};
 pub use chrono::NaiveDateTime as DateTime;
-pub use controller::{grinrelay_address, grinrelay_listener};
+pub use controller::{grinrelay_address, grinrelay_listener, grinrelay_secret_keys};
@@ -0,0 +1,280 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request limits of the Foreign API HTTP/S listener
+
+use crate::config::ForeignApiLimits;
+use crate::libwallet::{Error, ErrorKind};
+use crate::server::PeerAddr;
+use crate::util::Mutex;
+use futures::future::ok;
+use futures::{Future, Stream};
+use hyper::{Body, Request};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Window of the request rate
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Number of peers tracked before the stale ones get dropped
+const MAX_TRACKED_PEERS: usize = 10_000;
+
+/// Peer key used when the peer address is unknown
+const UNKNOWN_PEER: &str = "unknown";
+
+/// A request refused by the listener limits
+#[derive(Clone, Debug, PartialEq)]
+pub enum LimitError {
+	/// The peer sent too many requests in the current window
+	RateLimited(u32),
+	/// The request body is larger than the limit
+	BodyTooLarge(u64),
+	/// Too many `receive_tx` calls are already being processed
+	TooManyReceives(u32),
+}
+
+impl LimitError {
+	/// JSON-RPC error code of the rejection
+	pub fn code(&self) -> i64 {
+		match self {
+			LimitError::RateLimited(_) => -32001,
+			LimitError::BodyTooLarge(_) => -32002,
+			LimitError::TooManyReceives(_) => -32003,
+		}
+	}
+
+	/// JSON-RPC error response to the request `id`
+	pub fn to_response(&self, id: Value) -> Value {
+		json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"error": {
+				"code": self.code(),
+				"message": self.to_string(),
+			}
+		})
+	}
+}
+
+impl fmt::Display for LimitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LimitError::RateLimited(n) => {
+				write!(f, "Too many requests, at most {} per minute", n)
+			}
+			LimitError::BodyTooLarge(n) => write!(f, "Request larger than {} bytes", n),
+			LimitError::TooManyReceives(n) => {
+				write!(f, "Too many payments being received, at most {} at once", n)
+			}
+		}
+	}
+}
+
+/// A `receive_tx` call being processed, released on drop
+pub struct ReceiveSlot(Arc<AtomicUsize>);
+
+impl Drop for ReceiveSlot {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Enforces the [`ForeignApiLimits`](../../grin_wallet_config/types/struct.ForeignApiLimits.html)
+/// of a Foreign API listener
+pub struct ForeignLimiter {
+	limits: ForeignApiLimits,
+	/// Start of the current window and number of requests in it, per peer
+	requests: Mutex<HashMap<String, (Instant, u32)>>,
+	receives: Arc<AtomicUsize>,
+}
+
+impl ForeignLimiter {
+	/// New limiter, with no request seen yet
+	pub fn new(limits: ForeignApiLimits) -> ForeignLimiter {
+		ForeignLimiter {
+			limits,
+			requests: Mutex::new(HashMap::new()),
+			receives: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+
+	/// Address of the peer which sent the request: the address the reverse
+	/// proxy appended last to `X-Forwarded-For` if trusted, the address of
+	/// the connection otherwise
+	pub fn peer_addr(&self, req: &Request<Body>) -> Option<String> {
+		if self.limits.trust_forwarded_for == Some(true) {
+			let forwarded = req
+				.headers()
+				.get("x-forwarded-for")
+				.and_then(|v| v.to_str().ok())
+				.and_then(|v| v.rsplit(',').next())
+				.map(|v| v.trim().to_owned())
+				.filter(|v| !v.is_empty());
+			if forwarded.is_some() {
+				return forwarded;
+			}
+		}
+		req.extensions()
+			.get::<PeerAddr>()
+			.map(|p| p.0.ip().to_string())
+	}
+
+	/// Count `count` requests from the peer, failing if it goes over its rate.
+	/// The requests of unknown peers share the same rate.
+	pub fn check_rate(&self, peer: Option<&str>, count: u32) -> Result<(), LimitError> {
+		let max = match self.limits.requests_per_minute {
+			Some(m) => m,
+			None => return Ok(()),
+		};
+		let now = Instant::now();
+		let mut requests = self.requests.lock();
+		if requests.len() >= MAX_TRACKED_PEERS {
+			requests.retain(|_, (start, _)| now.duration_since(*start) < RATE_WINDOW);
+		}
+		let entry = requests
+			.entry(peer.unwrap_or(UNKNOWN_PEER).to_owned())
+			.or_insert((now, 0));
+		if now.duration_since(entry.0) >= RATE_WINDOW {
+			*entry = (now, 0);
+		}
+		entry.1 = entry.1.saturating_add(count);
+		if entry.1 > max {
+			return Err(LimitError::RateLimited(max));
+		}
+		Ok(())
+	}
+
+	/// Take a slot for a `receive_tx` call, failing if none is left
+	pub fn receive_slot(&self) -> Result<ReceiveSlot, LimitError> {
+		let slot = ReceiveSlot(self.receives.clone());
+		let taken = self.receives.fetch_add(1, Ordering::SeqCst);
+		match self.limits.max_concurrent_receives {
+			Some(max) if taken >= max as usize => Err(LimitError::TooManyReceives(max)),
+			_ => Ok(slot),
+		}
+	}
+
+	/// Read the request body, up to the size limit. The body of a request
+	/// going over it is drained but not kept.
+	pub fn read_body(
+		&self,
+		req: Request<Body>,
+	) -> Box<dyn Future<Item = Result<Vec<u8>, LimitError>, Error = Error> + Send> {
+		let max = self.limits.max_body_size;
+		Box::new(
+			req.into_body()
+				.map_err(|_| {
+					Error::from(ErrorKind::GenericError("Failed to read request".to_owned()))
+				})
+				.fold(
+					Ok(vec![]),
+					move |body: Result<Vec<u8>, LimitError>, chunk| {
+						let body = body.and_then(|mut body| match max {
+							Some(max) if (body.len() + chunk.len()) as u64 > max => {
+								Err(LimitError::BodyTooLarge(max))
+							}
+							_ => {
+								body.extend_from_slice(&chunk);
+								Ok(body)
+							}
+						});
+						ok::<_, Error>(body)
+					},
+				),
+		)
+	}
+
+	/// Whether the request, or a request of the batch, calls `receive_tx`
+	pub fn calls_receive(request: &Value) -> bool {
+		let is_receive = |r: &Value| r.get("method").and_then(|m| m.as_str()) == Some("receive_tx");
+		match request {
+			Value::Array(batch) => batch.iter().any(is_receive),
+			r => is_receive(r),
+		}
+	}
+
+	/// Number of calls in the request, a batch counting for each of its calls
+	pub fn call_count(request: &Value) -> u32 {
+		match request {
+			Value::Array(batch) => batch.len().max(1) as u32,
+			_ => 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn foreign_limits() {
+		let limiter = ForeignLimiter::new(ForeignApiLimits {
+			max_body_size: Some(16),
+			requests_per_minute: Some(3),
+			max_concurrent_receives: Some(1),
+			trust_forwarded_for: Some(true),
+		});
+
+		let req = Request::builder()
+			.header("x-forwarded-for", "10.0.0.1, 192.168.0.1")
+			.body(Body::from("{}"))
+			.unwrap();
+		assert_eq!(limiter.peer_addr(&req), Some("192.168.0.1".to_owned()));
+		assert_eq!(limiter.read_body(req).wait().unwrap(), Ok(b"{}".to_vec()));
+		let req = Request::new(Body::from(vec![b' '; 17]));
+		assert_eq!(
+			limiter.read_body(req).wait().unwrap(),
+			Err(LimitError::BodyTooLarge(16))
+		);
+
+		// a batch counts for each of its calls, and the peers are counted apart
+		let batch = json!([{"method": "check_version"}, {"method": "receive_tx"}]);
+		assert!(ForeignLimiter::calls_receive(&batch));
+		assert!(limiter
+			.check_rate(Some("10.0.0.1"), ForeignLimiter::call_count(&batch))
+			.is_ok());
+		assert!(limiter.check_rate(Some("10.0.0.1"), 1).is_ok());
+		assert_eq!(
+			limiter.check_rate(Some("10.0.0.1"), 1),
+			Err(LimitError::RateLimited(3))
+		);
+		assert!(limiter.check_rate(Some("10.0.0.2"), 1).is_ok());
+
+		// the address of the connection is used unless the header is trusted
+		let untrusted = ForeignLimiter::new(ForeignApiLimits::default());
+		let mut req = Request::builder()
+			.header("x-forwarded-for", "10.0.0.1")
+			.body(Body::from("{}"))
+			.unwrap();
+		assert_eq!(untrusted.peer_addr(&req), None);
+		req.extensions_mut()
+			.insert(PeerAddr("10.0.0.3:3415".parse().unwrap()));
+		assert_eq!(untrusted.peer_addr(&req), Some("10.0.0.3".to_owned()));
+		assert!(untrusted.check_rate(None, 60).is_ok());
+		assert!(untrusted.check_rate(None, 1).is_err());
+
+		// a receive slot is given back once the call is done
+		let slot = limiter.receive_slot().unwrap();
+		assert!(limiter.receive_slot().is_err());
+		drop(slot);
+		assert!(limiter.receive_slot().is_ok());
+
+		let response = LimitError::TooManyReceives(1).to_response(json!(1));
+		assert_eq!(response["error"]["code"], json!(-32003));
+	}
+}
@@ -0,0 +1,97 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP/S server of the wallet APIs, passing the address of the connection
+//! on to the handlers
+
+use crate::api::{Router, TLSConfig};
+use crate::libwallet::{Error, ErrorKind};
+use futures::{Future, Stream};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn, Service};
+use hyper::{Body, Request, Server};
+use std::net::SocketAddr;
+use std::thread;
+use tokio::net::TcpListener;
+use tokio_rustls::{TlsAcceptor, TlsStream};
+
+/// Address of the peer at the other end of the connection a request came
+/// in through, kept in the request extensions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerAddr(pub SocketAddr);
+
+/// Route the request, after recording the address of its peer
+fn route(
+	router: &Router,
+	peer: Option<SocketAddr>,
+	mut req: Request<Body>,
+) -> <Router as Service>::Future {
+	if let Some(peer) = peer {
+		req.extensions_mut().insert(PeerAddr(peer));
+	}
+	router.clone().call(req)
+}
+
+/// Serve the router on `addr`, over TLS if configured, on a thread of its own
+pub fn start_api_server(
+	addr: SocketAddr,
+	router: Router,
+	tls_config: Option<TLSConfig>,
+) -> Result<thread::JoinHandle<()>, Error> {
+	let acceptor = match tls_config {
+		Some(conf) => {
+			let server_config = conf.build_server_config().map_err(|e| {
+				ErrorKind::GenericError(format!("Invalid TLS certificate or key: {}", e))
+			})?;
+			Some(TlsAcceptor::from(server_config))
+		}
+		None => None,
+	};
+	let thread = thread::Builder::new()
+		.name("apis".to_string())
+		.spawn(move || match acceptor {
+			None => {
+				let server = Server::bind(&addr)
+					.serve(make_service_fn(move |conn: &AddrStream| {
+						let (router, peer) = (router.clone(), Some(conn.remote_addr()));
+						service_fn(move |req| route(&router, peer, req))
+					}))
+					.map_err(|e| error!("HTTP API server error: {}", e));
+				hyper::rt::run(server);
+			}
+			Some(acceptor) => {
+				let listener = TcpListener::bind(&addr).expect("failed to bind");
+				let tls = listener
+					.incoming()
+					.and_then(move |s| acceptor.accept(s))
+					.then(|r| match r {
+						Ok(s) => Ok::<_, std::io::Error>(Some(s)),
+						Err(e) => {
+							error!("TLS connection refused: {}", e);
+							Ok(None)
+						}
+					})
+					.filter_map(|s| s);
+				let server = Server::builder(tls)
+					.serve(make_service_fn(move |conn: &TlsStream<_, _>| {
+						let (router, peer) = (router.clone(), conn.get_ref().0.peer_addr().ok());
+						service_fn(move |req| route(&router, peer, req))
+					}))
+					.map_err(|e| error!("HTTPS API server error: {}", e));
+				hyper::rt::run(server);
+			}
+		})
+		.map_err(|e| ErrorKind::GenericError(format!("API thread failed to start: {}", e)))?;
+	Ok(thread)
+}
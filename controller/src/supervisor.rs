@@ -0,0 +1,130 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Supervisor running the transport listeners of a wallet side by side,
+//! restarting the ones which fail
+
+use crate::libwallet::{Error, ListenerState, ListenerStatus};
+use crate::util::RwLock;
+use chrono::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// First delay before restarting a failed listener, doubled on each failure
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay before restarting a failed listener
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// Runs each listener in its own thread, and restarts it when it fails or
+/// stops. The status of the listeners can be shared with the owner API.
+pub struct ListenerSupervisor {
+	statuses: Arc<RwLock<Vec<ListenerStatus>>>,
+	handles: Vec<JoinHandle<()>>,
+}
+
+impl Default for ListenerSupervisor {
+	fn default() -> ListenerSupervisor {
+		ListenerSupervisor::new()
+	}
+}
+
+impl ListenerSupervisor {
+	/// New supervisor, with no listener yet
+	pub fn new() -> ListenerSupervisor {
+		ListenerSupervisor {
+			statuses: Arc::new(RwLock::new(vec![])),
+			handles: vec![],
+		}
+	}
+
+	/// Status of the listeners, kept up to date
+	pub fn statuses(&self) -> Arc<RwLock<Vec<ListenerStatus>>> {
+		self.statuses.clone()
+	}
+
+	/// Start the listener of the `transport`. `run` is expected to block while
+	/// the listener works, and is called again once it returns.
+	pub fn spawn<F>(&mut self, transport: &str, mut run: F)
+	where
+		F: FnMut() -> Result<(), Error> + Send + 'static,
+	{
+		let index = {
+			let mut statuses = self.statuses.write();
+			statuses.push(ListenerStatus {
+				transport: transport.to_owned(),
+				state: ListenerState::Running,
+				since: Utc::now(),
+				restarts: 0,
+				last_error: None,
+			});
+			statuses.len() - 1
+		};
+		let statuses = self.statuses.clone();
+		let transport = transport.to_owned();
+		let handle = thread::Builder::new()
+			.name(format!("listener-{}", transport))
+			.spawn(move || {
+				let mut delay = RESTART_DELAY;
+				loop {
+					let started = Instant::now();
+					let error = match panic::catch_unwind(AssertUnwindSafe(|| run())) {
+						Ok(Ok(())) => "stopped".to_owned(),
+						Ok(Err(e)) => format!("{}", e),
+						Err(_) => "panicked".to_owned(),
+					};
+					// a listener which ran for a while starts over with a short delay
+					if started.elapsed() > MAX_RESTART_DELAY {
+						delay = RESTART_DELAY;
+					}
+					warn!(
+						"The {} listener failed: {}, restarting it in {}s",
+						transport,
+						error,
+						delay.as_secs()
+					);
+					ListenerSupervisor::update(&statuses, index, |s| {
+						s.state = ListenerState::Restarting;
+						s.last_error = Some(error);
+					});
+					thread::sleep(delay);
+					delay = (delay * 2).min(MAX_RESTART_DELAY);
+					ListenerSupervisor::update(&statuses, index, |s| {
+						s.state = ListenerState::Running;
+						s.restarts += 1;
+					});
+				}
+			})
+			.expect("failed to start the listener thread");
+		self.handles.push(handle);
+	}
+
+	/// Block for as long as the listeners run, that is until the process ends
+	pub fn wait(self) {
+		for handle in self.handles {
+			let _ = handle.join();
+		}
+	}
+
+	fn update<F>(statuses: &RwLock<Vec<ListenerStatus>>, index: usize, f: F)
+	where
+		F: FnOnce(&mut ListenerStatus),
+	{
+		let mut statuses = statuses.write();
+		f(&mut statuses[index]);
+		statuses[index].since = Utc::now();
+	}
+}
@@ -18,6 +18,7 @@
 extern crate log;
 extern crate grin_wallet_controller as wallet;
 extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_relay as grinrelay;
 
 use grin_wallet_util::grin_core as core;
 use grin_wallet_util::grin_keychain as keychain;
@@ -26,7 +27,9 @@ use grin_wallet_util::grin_util as util;
 use self::core::global;
 use self::core::global::ChainTypes;
 use self::keychain::ExtKeychain;
+use grin_wallet_config::GrinRelayConfig;
 use grin_wallet_libwallet as libwallet;
+use grinrelay::{armor, GrinboxAddress};
 use impls::test_framework::{self, LocalWalletClient, WalletProxy};
 use impls::FileWalletCommAdapter;
 use std::fs;
@@ -221,6 +224,122 @@ fn file_exchange_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
 	Ok(())
 }
 
+/// armored file exchange impl
+fn file_exchange_armored_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	// new slates go out as V2 to files and the relay, older wallets can't read V3
+	let send_file = format!("{}/part_tx_1.tx", test_dir);
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			..Default::default()
+		};
+		let slate = api.init_send_tx(args)?;
+		FileWalletCommAdapter::new().send_tx_async(&send_file, &slate)?;
+		let content = fs::read_to_string(&send_file)?;
+		assert_eq!(libwallet::Slate::parse_slate_version(&content)?, 2);
+		assert_eq!(
+			libwallet::VersionedSlate::into_orig_version(slate.clone()).version(),
+			libwallet::SlateVersion::V2
+		);
+		Ok(())
+	})?;
+
+	// armored slates, encrypted to each other
+	let send_file = format!("{}/part_tx_2.tx", test_dir);
+	let receive_file = format!("{}/part_tx_3.tx", test_dir);
+	let grinrelay_config = GrinRelayConfig::default();
+	let wallet1_keys =
+		wallet::controller::grinrelay_secret_keys(wallet1.clone(), grinrelay_config.clone())?;
+	let wallet2_keys =
+		wallet::controller::grinrelay_secret_keys(wallet2.clone(), grinrelay_config.clone())?;
+	let wallet2_address =
+		wallet::controller::grinrelay_address(wallet2.clone(), grinrelay_config.clone())?;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			payment_proof: Some(true),
+			..Default::default()
+		};
+		let slate = api.init_send_tx(args)?;
+		let recipient = GrinboxAddress::from_str(&wallet2_address)?;
+		FileWalletCommAdapter::new_armored(Some(recipient), vec![])
+			.send_tx_async(&send_file, &slate)?;
+		api.tx_lock_outputs(&slate, 0)?;
+		Ok(())
+	})?;
+
+	// only the recipient reads it
+	let content = fs::read_to_string(&send_file)?;
+	assert!(armor::is_encrypted(&content));
+	assert!(FileWalletCommAdapter::new()
+		.receive_tx_async(&send_file)
+		.is_err());
+	assert!(
+		FileWalletCommAdapter::new_armored(None, wallet1_keys.clone())
+			.receive_tx_async(&send_file)
+			.is_err()
+	);
+	let mut slate =
+		FileWalletCommAdapter::new_armored(None, wallet2_keys).receive_tx_async(&send_file)?;
+
+	// wallet 2 answers, encrypted to the payment proof address of the sender
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		slate = api.receive_tx(&slate, None, None, None)?;
+		let sender =
+			GrinboxAddress::from_str(&slate.payment_proof.clone().unwrap().sender_address)?;
+		FileWalletCommAdapter::new_armored(Some(sender), vec![])
+			.send_tx_async(&receive_file, &slate)?;
+		Ok(())
+	})?;
+
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let mut slate = FileWalletCommAdapter::new_armored(None, wallet1_keys)
+			.receive_tx_async(&receive_file)?;
+		slate = api.finalize_tx(&slate, None, None)?;
+		api.post_tx(Some(slate.id), &slate.tx, false)?;
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
 #[test]
 fn wallet_file_exchange() {
 	let test_dir = "test_output/file_exchange";
@@ -228,3 +347,11 @@ fn wallet_file_exchange() {
 		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
 	}
 }
+
+#[test]
+fn wallet_file_exchange_armored() {
+	let test_dir = "test_output/file_exchange_armored";
+	if let Err(e) = file_exchange_armored_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
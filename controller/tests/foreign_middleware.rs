@@ -0,0 +1,176 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test stateful Foreign API middlewares and their request context
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::apiwallet::{
+	Foreign, ForeignCheckMiddleware, ForeignCheckMiddlewareFn, ForeignRequestContext,
+	ForeignTransport,
+};
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::{ExtKeychain, Keychain};
+use self::libwallet::{ErrorKind, InitTxArgs, NodeClient, NodeVersionInfo, Slate, WalletBackend};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use util::Mutex;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// Accepts a limited number of receives, and keeps where they came from
+struct ReceiveCap {
+	max: usize,
+	count: AtomicUsize,
+	senders: Mutex<Vec<Option<String>>>,
+}
+
+impl<W: ?Sized, C, K> ForeignCheckMiddleware<W, C, K> for ReceiveCap
+where
+	W: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	fn check(
+		&self,
+		name: ForeignCheckMiddlewareFn,
+		_wallet: &mut W,
+		_slate: Option<&Slate>,
+		context: &ForeignRequestContext,
+	) -> Result<(), libwallet::Error> {
+		if name != ForeignCheckMiddlewareFn::ReceiveTx {
+			return Ok(());
+		}
+		assert_eq!(context.transport, ForeignTransport::GrinRelay);
+		self.senders.lock().push(context.remote_addr.clone());
+		if self.count.fetch_add(1, Ordering::SeqCst) >= self.max {
+			return Err(ErrorKind::GenericError("Too many receives".to_owned()).into());
+		}
+		Ok(())
+	}
+}
+
+fn allow_all(
+	_name: ForeignCheckMiddlewareFn,
+	_node_version_info: Option<NodeVersionInfo>,
+	_slate: Option<&Slate>,
+) -> Result<(), libwallet::Error> {
+	Ok(())
+}
+
+/// foreign middleware impl
+fn foreign_middleware_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	let mut slates = vec![];
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		for _ in 0..2 {
+			let args = InitTxArgs {
+				src_acct_name: None,
+				amount: reward / 2,
+				minimum_confirmations: 2,
+				max_outputs: 500,
+				num_change_outputs: 1,
+				selection_strategy: "smallest".to_owned(),
+				..Default::default()
+			};
+			slates.push(api.init_send_tx(args)?);
+		}
+		Ok(())
+	})?;
+
+	// a plain function and a stateful middleware, chained
+	let cap = Arc::new(ReceiveCap {
+		max: 1,
+		count: AtomicUsize::new(0),
+		senders: Mutex::new(vec![]),
+	});
+	let mut api: Foreign<_, LocalWalletClient, ExtKeychain> =
+		Foreign::new(wallet2.clone(), Some(Arc::new(allow_all)));
+	api.add_middleware(cap.clone());
+	api.set_request_context(ForeignRequestContext {
+		transport: ForeignTransport::GrinRelay,
+		remote_addr: Some("tn1-alice".to_owned()),
+		account: None,
+	});
+	api.receive_tx(&slates[0], None, None, None)?;
+	assert!(api.receive_tx(&slates[1], None, None, None).is_err());
+	assert_eq!(
+		*cap.senders.lock(),
+		vec![Some("tn1-alice".to_owned()), Some("tn1-alice".to_owned())]
+	);
+
+	// only the first payment got in
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 1);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn foreign_middleware() {
+	let test_dir = "test_output/foreign_middleware";
+	if let Err(e) = foreign_middleware_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
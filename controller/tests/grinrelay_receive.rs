@@ -0,0 +1,176 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the Grin Relay receiving workers
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::apiwallet::ForeignCheckMiddleware;
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{InitTxArgs, Listener, Slate, VersionedSlate};
+use self::util::Mutex;
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::sync::mpsc::sync_channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// Relay listener keeping the slates it publishes
+#[derive(Clone)]
+struct FakeListener {
+	published: Arc<Mutex<Vec<(String, Slate)>>>,
+}
+
+impl Listener for FakeListener {
+	fn address(&self) -> String {
+		"fake".to_owned()
+	}
+	fn publish(&self, slate: &VersionedSlate, to: &String) -> Result<(), failure::Error> {
+		self.published
+			.lock()
+			.push((to.clone(), slate.clone().into()));
+		Ok(())
+	}
+	fn stop(self: Box<Self>) -> Result<(), failure::Error> {
+		Ok(())
+	}
+	fn box_clone(&self) -> Box<dyn Listener> {
+		Box::new(self.clone())
+	}
+	fn retrieve_relay_addr(&self, _abbr: String) -> Result<(), failure::Error> {
+		Ok(())
+	}
+	fn is_connected(&self) -> bool {
+		true
+	}
+}
+
+/// grinrelay receive impl
+fn grinrelay_receive_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	let mut slates = vec![];
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		for _ in 0..3 {
+			let args = InitTxArgs {
+				src_acct_name: None,
+				amount: reward / 4,
+				minimum_confirmations: 2,
+				max_outputs: 500,
+				num_change_outputs: 1,
+				selection_strategy: "smallest".to_owned(),
+				..Default::default()
+			};
+			slates.push(api.init_send_tx(args)?);
+		}
+		Ok(())
+	})?;
+
+	// the queue holds all the slates, the workers stop once it is closed
+	let (relay_tx, relay_rx) = sync_channel(3);
+	for slate in slates.iter() {
+		relay_tx
+			.send(("tn1-sender".to_owned(), slate.clone()))
+			.unwrap();
+	}
+	assert!(relay_tx
+		.try_send(("tn1-sender".to_owned(), slates[0].clone()))
+		.is_err());
+	drop(relay_tx);
+
+	let listener = FakeListener {
+		published: Arc::new(Mutex::new(vec![])),
+	};
+	let middlewares: Vec<Arc<dyn ForeignCheckMiddleware<_, LocalWalletClient, ExtKeychain>>> =
+		vec![];
+	wallet::controller::grinrelay_receive_loop(
+		wallet2.clone(),
+		relay_rx,
+		Box::new(listener.clone()),
+		None,
+		"default",
+		middlewares,
+		2,
+	)?;
+
+	// each slate was received once, and sent back to its sender
+	let published = listener.published.lock();
+	assert_eq!(published.len(), 3);
+	for slate in slates.iter() {
+		assert!(published
+			.iter()
+			.any(|(to, s)| to == "tn1-sender" && s.id == slate.id));
+	}
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 3);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn grinrelay_receive() {
+	let test_dir = "test_output/grinrelay_receive";
+	if let Err(e) = grinrelay_receive_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
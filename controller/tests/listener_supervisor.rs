@@ -0,0 +1,91 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the listener supervisor restarts, and their report over the owner API
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::apiwallet::Owner;
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{ErrorKind, ListenerState};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use wallet::supervisor::ListenerSupervisor;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn listener_supervisor() {
+	let test_dir = "test_output/listener_supervisor";
+	setup(test_dir);
+	let wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+
+	// one listener fails at its first run, the other one keeps running
+	let mut supervisor = ListenerSupervisor::new();
+	let runs = Arc::new(AtomicUsize::new(0));
+	let flaky_runs = runs.clone();
+	supervisor.spawn("flaky", move || {
+		if flaky_runs.fetch_add(1, Ordering::SeqCst) == 0 {
+			return Err(ErrorKind::GenericError("connection lost".to_owned()).into());
+		}
+		thread::sleep(Duration::from_secs(60));
+		Ok(())
+	});
+	supervisor.spawn("steady", || {
+		thread::sleep(Duration::from_secs(60));
+		Ok(())
+	});
+	thread::sleep(Duration::from_millis(1500));
+	assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+	let mut api: Owner<_, LocalWalletClient, ExtKeychain> = Owner::new(wallet1.clone());
+	assert!(api.listener_status().unwrap().is_empty());
+	api.set_listeners(supervisor.statuses());
+	let statuses = api.listener_status().unwrap();
+	assert_eq!(statuses.len(), 2);
+	assert_eq!(statuses[0].transport, "flaky");
+	assert_eq!(statuses[0].state, ListenerState::Running);
+	assert_eq!(statuses[0].restarts, 1);
+	assert!(statuses[0]
+		.last_error
+		.as_ref()
+		.unwrap()
+		.contains("connection lost"));
+	assert_eq!(statuses[1].transport, "steady");
+	assert_eq!(statuses[1].restarts, 0);
+	assert_eq!(statuses[1].last_error, None);
+}
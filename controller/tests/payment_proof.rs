@@ -0,0 +1,223 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test payment proofs signed by the receiver outside of Grin Relay
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+extern crate grin_wallet_relay as grinrelay;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{InitTxArgs, TxProofStatus};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// payment proof impl
+fn payment_proof_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	// sender asks for a payment proof
+	let mut slate = None;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward * 2,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			payment_proof: Some(true),
+			..Default::default()
+		};
+		let s = api.init_send_tx(args)?;
+		assert!(s.payment_proof.is_some());
+		api.tx_lock_outputs(&s, 0)?;
+		slate = Some(s);
+		Ok(())
+	})?;
+	let mut slate = slate.unwrap();
+
+	// receiver signs it, whatever the way the slate came in
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		slate = api.receive_tx(&slate, None, None, None)?;
+		Ok(())
+	})?;
+	let info = slate.payment_proof.clone().unwrap();
+	assert!(info.receiver_address.is_some());
+	assert!(info.receiver_signature.is_some());
+
+	// a tampered amount is refused on finalize
+	let mut naughty_slate = slate.clone();
+	naughty_slate.amount += 1;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		assert!(api.finalize_tx(&naughty_slate, None, None).is_err());
+		Ok(())
+	})?;
+
+	// sender finalizes and keeps the proof
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		slate = api.finalize_tx(&slate, None, None)?;
+		api.post_tx(Some(slate.id), &slate.tx, false)?;
+		let proof = api.get_stored_payment_proof(None, Some(slate.id))?.unwrap();
+		assert_eq!(proof.amount, reward * 2);
+		assert_eq!(proof.excess, slate.tx.kernels()[0].excess);
+		assert_eq!(Some(proof.receiver_address.clone()), info.receiver_address);
+		assert!(grinrelay::verify_payment_proof(&proof).is_ok());
+
+		let mut naughty_proof = proof.clone();
+		naughty_proof.amount = reward;
+		assert!(grinrelay::verify_payment_proof(&naughty_proof).is_err());
+
+		// batch verification reports the failing files without asking the node
+		let proof_dir = format!("{}/proofs", test_dir);
+		fs::create_dir_all(&proof_dir).unwrap();
+		fs::write(
+			format!("{}/a.proof", proof_dir),
+			serde_json::to_string(&naughty_proof).unwrap(),
+		)
+		.unwrap();
+		fs::write(format!("{}/b.proof", proof_dir), "not a proof").unwrap();
+		let reports = api.verify_tx_proofs(&proof_dir, Some("http://127.0.0.1:1"), None)?;
+		assert_eq!(reports.len(), 2);
+		assert_eq!(reports[0].file, "a.proof");
+		assert_eq!(reports[1].file, "b.proof");
+		assert!(reports.iter().all(|r| r.error.is_some()
+			&& r.height.is_none()
+			&& r.status == TxProofStatus::Invalid));
+
+		// offline batch verification needs no wallet, and tells the valid
+		// proofs with an unknown confirmation apart
+		fs::write(
+			format!("{}/c.proof", proof_dir),
+			serde_json::to_string(&proof).unwrap(),
+		)
+		.unwrap();
+		let reports = apiwallet::verify_tx_proofs(&proof_dir, None, None)?;
+		assert_eq!(reports.len(), 3);
+		assert_eq!(reports[2].file, "c.proof");
+		assert_eq!(reports[2].status, TxProofStatus::ConfirmationUnknown);
+		assert!(reports[2].error.is_none() && reports[2].height.is_none());
+
+		// offline verification does every check but the confirmation
+		let verified = api.verify_payment_proof_offline(&proof, None)?;
+		assert!(verified.offline);
+		assert!(!verified.confirmed);
+		assert_eq!(verified.excess, proof.excess);
+		assert!(api
+			.verify_payment_proof_offline(&naughty_proof, None)
+			.is_err());
+		Ok(())
+	})?;
+
+	// receiver kept the same proof, to prove the receipt
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let received = api.get_stored_payment_proof(None, Some(slate.id))?.unwrap();
+		assert_eq!(received.amount, reward * 2);
+		assert_eq!(received.excess, slate.tx.kernels()[0].excess);
+		assert_eq!(
+			Some(received.receiver_address.clone()),
+			info.receiver_address
+		);
+		assert!(grinrelay::verify_payment_proof(&received).is_ok());
+		Ok(())
+	})?;
+
+	// the receiver keeps a proof of a payment without any proof requested,
+	// whatever the way the slate came in
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 3, false);
+	let mut slate = None;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			..Default::default()
+		};
+		let s = api.init_send_tx(args)?;
+		assert!(s.payment_proof.is_none());
+		let s = client1.send_tx_slate_direct("wallet2", &s)?;
+		assert!(s.payment_proof.is_none());
+		api.tx_lock_outputs(&s, 0)?;
+		slate = Some(api.finalize_tx(&s, None, None)?);
+		Ok(())
+	})?;
+	let slate = slate.unwrap();
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let received = api.get_stored_payment_proof(None, Some(slate.id))?.unwrap();
+		assert_eq!(received.amount, reward);
+		assert_eq!(received.excess, slate.tx.kernels()[0].excess);
+		assert_eq!(received.sender_address, "");
+		assert!(grinrelay::verify_payment_proof(&received).is_ok());
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn payment_proof() {
+	let test_dir = "test_output/payment_proof";
+	if let Err(e) = payment_proof_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,175 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the receive policy checks on incoming payments
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_config as config;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::apiwallet::{Foreign, ForeignRequestContext, ForeignTransport};
+use self::config::ReceivePolicy;
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{InitTxArgs, Slate};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// receive policy impl
+fn receive_policy_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	let send = |amount: u64, message: Option<String>| -> Result<Slate, libwallet::Error> {
+		let mut slate = None;
+		wallet::controller::owner_single_use(wallet1.clone(), |api| {
+			let args = InitTxArgs {
+				src_acct_name: None,
+				amount,
+				minimum_confirmations: 2,
+				max_outputs: 500,
+				num_change_outputs: 1,
+				selection_strategy: "smallest".to_owned(),
+				message: message.clone(),
+				..Default::default()
+			};
+			slate = Some(api.init_send_tx(args)?);
+			Ok(())
+		})?;
+		Ok(slate.unwrap())
+	};
+
+	wallet2.lock().set_receive_policy(Some(ReceivePolicy {
+		min_amount: Some(1_000_000),
+		max_pending_receives: Some(1),
+		..Default::default()
+	}));
+
+	// dust is refused, a regular payment goes through, and the next one
+	// hits the pending cap until the first one is confirmed
+	let dust = send(1_000, None)?;
+	let first = send(reward / 2, None)?;
+	let second = send(reward / 2, None)?;
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		assert!(api.receive_tx(&dust, None, None, None).is_err());
+		api.receive_tx(&first, None, None, None)?;
+		assert!(api.receive_tx(&second, None, None, None).is_err());
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 1);
+		Ok(())
+	})?;
+
+	// a policy asking for a message refuses anonymous payments
+	wallet2.lock().set_receive_policy(Some(ReceivePolicy {
+		require_message: Some(true),
+		..Default::default()
+	}));
+	let anonymous = send(reward / 2, None)?;
+	let signed = send(reward / 2, Some("for the coffee".to_owned()))?;
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		assert!(api.receive_tx(&anonymous, None, None, None).is_err());
+		api.receive_tx(&signed, None, None, None)?;
+		Ok(())
+	})?;
+
+	// sender lists match on the bare relay address
+	let policy = ReceivePolicy {
+		allowed_senders: Some(vec!["tn1-alice".to_owned(), "tn1-bob".to_owned()]),
+		denied_senders: Some(vec!["grinrelay://tn1-bob".to_owned()]),
+		..Default::default()
+	};
+	assert!(policy.accepts_sender("grinrelay://tn1-alice@relay.example.com"));
+	assert!(!policy.accepts_sender("tn1-bob"));
+	assert!(!policy.accepts_sender("tn1-carol"));
+	assert!(ReceivePolicy::default().accepts_sender("tn1-carol"));
+
+	// the Foreign API only knows the sender of the payments coming through
+	// Grin Relay, the others are refused once some senders are allowed
+	wallet2.lock().set_receive_policy(Some(policy));
+	let from = |transport: ForeignTransport, addr: &str| ForeignRequestContext {
+		transport,
+		remote_addr: Some(addr.to_owned()),
+		account: None,
+	};
+	let mut api: Foreign<_, LocalWalletClient, ExtKeychain> = Foreign::new(wallet2.clone(), None);
+	let slate = send(reward / 2, None)?;
+	api.set_request_context(from(ForeignTransport::GrinRelay, "tn1-carol"));
+	assert!(api.receive_tx(&slate, None, None, None).is_err());
+	api.set_request_context(from(ForeignTransport::Http, "tn1-alice"));
+	assert!(api.receive_tx(&slate, None, None, None).is_err());
+	api.set_request_context(from(ForeignTransport::GrinRelay, "tn1-alice"));
+	api.receive_tx(&slate, None, None, None)?;
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 3);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn receive_policy() {
+	let test_dir = "test_output/receive_policy";
+	if let Err(e) = receive_policy_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,255 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the SQLite wallet backend, and the conversion of the wallet data
+//! between LMDB and SQLite
+#![cfg(feature = "sqlite")]
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use grin_wallet_config::{WalletBackendType, WalletConfig};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use impls::{copy_wallet_data, LMDBBackend, SqliteBackend, SEED_FILE};
+use libwallet::{InitTxArgs, NodeClient, WalletBackend};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+fn send_args(amount: u64) -> InitTxArgs {
+	InitTxArgs {
+		src_acct_name: None,
+		amount,
+		minimum_confirmations: 2,
+		max_outputs: 500,
+		num_change_outputs: 1,
+		selection_strategy: "all".to_owned(),
+		..Default::default()
+	}
+}
+
+/// The wallet data of a backend, each kind sorted, for comparison
+fn wallet_data<W: ?Sized, C, K>(w: &mut W) -> Result<Vec<Vec<String>>, libwallet::Error>
+where
+	W: WalletBackend<C, K>,
+	C: NodeClient,
+	K: keychain::Keychain,
+{
+	fn sorted<T: Serialize>(items: Vec<T>) -> Vec<String> {
+		let mut items: Vec<String> = items
+			.iter()
+			.map(|i| serde_json::to_string(i).unwrap())
+			.collect();
+		items.sort();
+		items
+	}
+	let accounts: Vec<_> = w.acct_path_iter()?.collect();
+	let tx_entries: Vec<_> = w.tx_log_iter()?.collect();
+	let mut child_indices = vec![];
+	for a in &accounts {
+		child_indices.push((a.label.clone(), w.current_child_index(&a.path)?));
+	}
+	let mut contexts = vec![];
+	for t in &tx_entries {
+		if let Some(slate_id) = t.tx_slate_id {
+			if let Ok(ctx) = w.get_private_context(slate_id.as_bytes(), 0) {
+				contexts.push(ctx);
+			}
+		}
+	}
+	Ok(vec![
+		sorted(accounts),
+		sorted(w.iter()?.collect()),
+		sorted(tx_entries),
+		sorted(child_indices),
+		sorted(contexts),
+	])
+}
+
+/// send and receive between two SQLite wallets
+fn sqlite_transaction_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 = test_framework::create_wallet_with_backend(
+		&format!("{}/wallet1", test_dir),
+		client1.clone(),
+		None,
+		WalletBackendType::Sqlite,
+	);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 = test_framework::create_wallet_with_backend(
+		&format!("{}/wallet2", test_dir),
+		client2.clone(),
+		None,
+		WalletBackendType::Sqlite,
+	);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let cm = global::coinbase_maturity();
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let (refreshed, info) = api.retrieve_summary_info(true, 1)?;
+		assert!(refreshed);
+		assert_eq!(
+			info.amount_currently_spendable,
+			(info.last_confirmed_height - cm) * reward
+		);
+		Ok(())
+	})?;
+
+	let amount = reward / 2;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let slate = api.init_send_tx(send_args(amount))?;
+		let slate = client1.send_tx_slate_direct("wallet2", &slate)?;
+		api.tx_lock_outputs(&slate, 0)?;
+		let slate = api.finalize_tx(&slate, None, None)?;
+		api.post_tx(Some(slate.id), &slate.tx, false)?;
+		Ok(())
+	})?;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 3, false);
+
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (refreshed, info) = api.retrieve_summary_info(true, 1)?;
+		assert!(refreshed);
+		assert_eq!(info.amount_currently_spendable, amount);
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 1);
+		assert!(txs[0].confirmed);
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert!(txs
+			.iter()
+			.filter(|t| t.amount_debited > 0)
+			.all(|t| t.confirmed));
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+/// convert a wallet from LMDB to SQLite and back
+fn sqlite_round_trip_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let lmdb_dir = format!("{}/wallet1", test_dir);
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 = test_framework::create_wallet(&lmdb_dir, client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	// outputs, an account, and a send still in flight with its context
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 5, false);
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		api.create_account_path("account1")?;
+		let slate = api.init_send_tx(send_args(reward))?;
+		api.tx_lock_outputs(&slate, 0)?;
+		Ok(())
+	})?;
+
+	let mut lmdb = wallet1.lock();
+	let expected = wallet_data(&mut *lmdb)?;
+	assert_eq!(expected[4].len(), 1);
+
+	// LMDB -> SQLite, in the same data dir
+	let mut config = WalletConfig::default();
+	config.data_file_dir = lmdb_dir.clone();
+	let mut sqlite: SqliteBackend<LocalWalletClient, ExtKeychain> =
+		SqliteBackend::new(config.clone(), "", client1.clone())?;
+	sqlite.open_with_credentials()?;
+	copy_wallet_data(&mut *lmdb, &mut sqlite)?;
+	assert_eq!(wallet_data(&mut sqlite)?, expected);
+	// only into an empty database
+	assert!(copy_wallet_data(&mut *lmdb, &mut sqlite).is_err());
+
+	// SQLite -> LMDB, in a new data dir with the same seed
+	let copy_dir = format!("{}/wallet1_copy", test_dir);
+	fs::create_dir_all(&copy_dir)?;
+	fs::copy(
+		Path::new(&lmdb_dir).join(SEED_FILE),
+		Path::new(&copy_dir).join(SEED_FILE),
+	)?;
+	config.data_file_dir = copy_dir;
+	let mut lmdb_copy: LMDBBackend<LocalWalletClient, ExtKeychain> =
+		LMDBBackend::new(config, "", client1.clone())?;
+	lmdb_copy.open_with_credentials()?;
+	copy_wallet_data(&mut sqlite, &mut lmdb_copy)?;
+	assert_eq!(wallet_data(&mut lmdb_copy)?, expected);
+	drop(lmdb);
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn sqlite_transaction() {
+	let test_dir = "test_output/sqlite_transaction";
+	if let Err(e) = sqlite_transaction_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
+
+#[test]
+fn sqlite_round_trip() {
+	let test_dir = "test_output/sqlite_round_trip";
+	if let Err(e) = sqlite_round_trip_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,120 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the automatic cancellation of stale outgoing transactions
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_config as config;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::config::StaleTxPolicy;
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{InitTxArgs, TxCancellationReason, TxLogEntryType};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// stale tx policy impl
+fn stale_tx_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	// no policy unless configured, an unanswered send stays locked
+	assert!(wallet1.lock().stale_tx_policy().is_none());
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "smallest".to_owned(),
+			..Default::default()
+		};
+		let s = api.init_send_tx(args)?;
+		api.tx_lock_outputs(&s, 0)?;
+		let (_, wallet_info) = api.retrieve_summary_info(true, 1)?;
+		assert!(wallet_info.amount_locked > 0);
+		Ok(())
+	})?;
+
+	// a policy giving up on unposted sends at once cancels it on refresh
+	wallet1.lock().set_stale_tx_policy(Some(StaleTxPolicy {
+		unconfirmed_blocks: None,
+		unposted_hours: Some(0),
+	}));
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let (_, wallet_info) = api.retrieve_summary_info(true, 1)?;
+		assert_eq!(wallet_info.amount_locked, 0);
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		let cancelled: Vec<_> = txs
+			.iter()
+			.filter(|t| t.tx_type == TxLogEntryType::TxSentCancelled)
+			.collect();
+		assert_eq!(cancelled.len(), 1);
+		assert_eq!(
+			cancelled[0].cancellation_reason,
+			Some(TxCancellationReason::NotPosted(0))
+		);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn stale_tx_policy() {
+	let test_dir = "test_output/stale_tx_policy";
+	if let Err(e) = stale_tx_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,80 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the derivation of the Tor onion service key of a wallet
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+extern crate grin_wallet_relay as grinrelay;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::grinrelay::hasher::derive_address_key;
+use self::keychain::ExtKeychain;
+use impls::onion_service_key;
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// onion key derivation impl
+fn tor_onion_key_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let phrase = "fat twenty mean degree forget shell check candy immense awful \
+	              flame next during february bulb bike sun wink theory day kiwi embrace peace lunch";
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1, Some(phrase));
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2, Some(phrase));
+
+	// the same seed gives the same onion address
+	let key = wallet::controller::tor_onion_key(wallet1.clone())?;
+	assert!(key.starts_with("ED25519-V3:"));
+	assert_eq!(wallet::controller::tor_onion_key(wallet2)?, key);
+
+	// and no Grin Relay address key is used for it
+	let mut w = wallet1.lock();
+	w.open_with_credentials()?;
+	for path in 0..2 {
+		for index in 0..2 {
+			let sec_key = derive_address_key(w.keychain(), path, index).unwrap();
+			assert_ne!(onion_service_key(&sec_key.0), key);
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn tor_onion_key() {
+	let test_dir = "test_output/tor_onion_key";
+	if let Err(e) = tor_onion_key_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
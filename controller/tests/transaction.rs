@@ -28,7 +28,7 @@ use self::core::core::transaction;
 use self::core::global;
 use self::core::global::ChainTypes;
 use self::keychain::ExtKeychain;
-use self::libwallet::{InitTxArgs, OutputStatus, Slate};
+use self::libwallet::{InitTxArgs, IssueInvoiceTxArgs, OutputStatus, Slate};
 use impls::test_framework::{self, LocalWalletClient, WalletProxy};
 use std::fs;
 use std::thread;
@@ -513,6 +513,106 @@ fn tx_rollback(test_dir: &str) -> Result<(), libwallet::Error> {
 	Ok(())
 }
 
+/// Send and invoice with V3 slates, whose initial leg leaves the range
+/// proofs out for the party finalizing to rebuild
+fn v3_transaction(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+	let round_trip = |slate: &Slate| -> Result<Slate, libwallet::Error> {
+		Ok(serde_json::from_str(&serde_json::to_string(slate)?)?)
+	};
+
+	// the sender rebuilds the range proof of its change
+	wallet::controller::owner_single_use(wallet1.clone(), |sender_api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			target_slate_version: Some(3),
+			..Default::default()
+		};
+		let slate_i = sender_api.init_send_tx(args)?;
+		let sent = serde_json::to_value(&slate_i)?;
+		assert!(sent["tx"]["body"]["outputs"][0].get("proof").is_none());
+		let slate = client1.send_tx_slate_direct("wallet2", &slate_i)?;
+		sender_api.tx_lock_outputs(&slate, 0)?;
+		let slate = sender_api.finalize_tx(&slate, None, None)?;
+		sender_api.post_tx(Some(slate.id), &slate.tx, false)?;
+		Ok(())
+	})?;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 3, false);
+
+	// the invoicer rebuilds the range proof of its output
+	let mut slate = Slate::blank(2);
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let args = IssueInvoiceTxArgs {
+			amount: reward,
+			target_slate_version: Some(3),
+			..Default::default()
+		};
+		slate = round_trip(&api.issue_invoice_tx(args)?)?;
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: slate.amount,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			..Default::default()
+		};
+		slate = api.process_invoice_tx(&slate, args)?;
+		api.tx_lock_outputs(&slate, 0)?;
+		slate = round_trip(&slate)?;
+		Ok(())
+	})?;
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		slate = api.finalize_invoice_tx(&slate)?;
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		api.post_tx(Some(slate.id), &slate.tx, false)?;
+		Ok(())
+	})?;
+
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 3, false);
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (refreshed, info) = api.retrieve_summary_info(true, 1)?;
+		assert!(refreshed);
+		assert_eq!(info.amount_currently_spendable, reward * 2);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
 #[test]
 fn db_wallet_basic_transaction_api() {
 	let test_dir = "test_output/basic_transaction_api";
@@ -528,3 +628,11 @@ fn db_wallet_tx_rollback() {
 		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
 	}
 }
+
+#[test]
+fn db_wallet_v3_transaction() {
+	let test_dir = "test_output/v3_transaction";
+	if let Err(e) = v3_transaction(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,147 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test slate expiry, and the cancellation of expired transactions
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{InitTxArgs, TxLogEntryType};
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// slate ttl impl
+fn ttl_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	let reward = core::consensus::REWARD;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 10, false);
+
+	// two sends valid for 3 blocks, only the first one gets received in time
+	let mut slates = vec![];
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		for _ in 0..2 {
+			let args = InitTxArgs {
+				src_acct_name: None,
+				amount: reward,
+				minimum_confirmations: 2,
+				max_outputs: 500,
+				num_change_outputs: 1,
+				selection_strategy: "smallest".to_owned(),
+				ttl_blocks: Some(3),
+				..Default::default()
+			};
+			let s = api.init_send_tx(args)?;
+			assert_eq!(s.ttl_cutoff_height, Some(s.height + 3));
+			// sent as V3, the version carrying the TTL
+			assert_eq!(s.version_info.orig_version, 3);
+			assert!(serde_json::to_value(&s)?.get("ttl_cutoff_height").is_some());
+			api.tx_lock_outputs(&s, 0)?;
+			slates.push(s);
+		}
+		Ok(())
+	})?;
+	let mut received = None;
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		received = Some(api.receive_tx(&slates[0], None, None, None)?);
+		Ok(())
+	})?;
+	let received = received.unwrap();
+
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 5, false);
+
+	// past the cutoff, the receiver refuses the slate and the sender doesn't finalize
+	wallet::controller::foreign_single_use(wallet2.clone(), |api| {
+		assert!(api.receive_tx(&slates[1], None, None, None).is_err());
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		assert!(api.finalize_tx(&received, None, None).is_err());
+		Ok(())
+	})?;
+
+	// and both wallets cancel the expired transactions on refresh
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let (_, wallet_info) = api.retrieve_summary_info(true, 1)?;
+		assert_eq!(wallet_info.amount_locked, 0);
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		let cancelled = txs
+			.iter()
+			.filter(|t| t.tx_type == TxLogEntryType::TxSentCancelled)
+			.count();
+		assert_eq!(cancelled, 2);
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallet2.clone(), |api| {
+		let (_, wallet_info) = api.retrieve_summary_info(true, 1)?;
+		assert_eq!(wallet_info.amount_awaiting_confirmation, 0);
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 1);
+		assert_eq!(txs[0].tx_type, TxLogEntryType::TxReceivedCancelled);
+		Ok(())
+	})?;
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn slate_ttl() {
+	let test_dir = "test_output/slate_ttl";
+	if let Err(e) = ttl_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
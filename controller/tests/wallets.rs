@@ -0,0 +1,298 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test hosting several wallets in one owner API, and creating the wallet
+//! of a single wallet owner API
+#[macro_use]
+extern crate serde_json;
+extern crate grin_wallet_api as apiwallet;
+extern crate grin_wallet_config as config;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::apiwallet::{Owner, OwnerRpc, WalletOpener, Wallets};
+use self::config::WalletConfig;
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::{ExtKeychain, Keychain, SwitchCommitmentType};
+use self::libwallet::{ErrorKind, WalletInst, WalletListing};
+use easy_jsonrpc::Handler;
+use impls::test_framework::{LocalWalletClient, WalletProxy};
+use impls::{LMDBBackend, WalletSeed};
+use std::fs;
+use std::sync::Arc;
+use util::secp::key::SecretKey;
+use util::Mutex;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// hosted wallets impl
+fn wallets_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let client = LocalWalletClient::new("hosted", wallet_proxy.tx.clone());
+
+	let opener: WalletOpener<WalletInst<LocalWalletClient, ExtKeychain>> =
+		Box::new(move |data_dir, password| {
+			WalletSeed::from_file(data_dir, password)
+				.map_err(|_| ErrorKind::WalletSeedDecryption)?;
+			let mut wallet_config = WalletConfig::default();
+			wallet_config.data_file_dir = data_dir.to_owned();
+			let wallet: LMDBBackend<LocalWalletClient, ExtKeychain> =
+				LMDBBackend::new(wallet_config, password, client.clone())?;
+			Ok(Arc::new(Mutex::new(wallet)))
+		});
+	let wallets = Wallets::new(&format!("{}/wallets", test_dir), opener);
+
+	assert!(wallets.list_wallets()?.is_empty());
+	// the recovery phrase is given out on creation
+	let phrase = wallets
+		.create_wallet("alice", 32, None, "alice_pass")?
+		.to_string();
+	assert_eq!(phrase.split_whitespace().count(), 24);
+	assert_ne!(
+		wallets
+			.create_wallet("bob", 32, None, "bob_pass")?
+			.to_string(),
+		phrase
+	);
+	assert!(wallets.wallet_exists("alice")?);
+	assert!(!wallets.wallet_exists("carol")?);
+	assert!(wallets
+		.create_wallet("alice", 32, None, "alice_pass")
+		.is_err());
+	assert!(wallets
+		.create_wallet("carol", 15, None, "carol_pass")
+		.is_err());
+	// wallets only live in the wallets dir
+	assert!(wallets
+		.create_wallet("../carol", 32, None, "carol_pass")
+		.is_err());
+	assert!(wallets
+		.recover_wallet("/tmp/carol", &phrase, None, "carol_pass")
+		.is_err());
+	assert!(wallets.wallet_exists("../alice").is_err());
+
+	// each wallet has its own accounts
+	wallet::controller::owner_single_use(wallets.wallet("alice")?, |api| {
+		api.create_account_path("savings")?;
+		Ok(())
+	})?;
+	wallet::controller::owner_single_use(wallets.wallet("bob")?, |api| {
+		let accounts = api.accounts()?;
+		assert_eq!(accounts.len(), 1);
+		assert_eq!(accounts[0].label, "default");
+		Ok(())
+	})?;
+
+	wallets.close_wallet("alice")?;
+	assert!(wallets.wallet("alice").is_err());
+	assert_eq!(
+		wallets.list_wallets()?,
+		vec![
+			WalletListing {
+				name: "alice".to_owned(),
+				open: false,
+			},
+			WalletListing {
+				name: "bob".to_owned(),
+				open: true,
+			},
+		]
+	);
+
+	// wrong password leaves the wallet closed
+	assert!(wallets.open_wallet("alice", "bob_pass").is_err());
+	assert!(wallets.wallet("alice").is_err());
+	wallets.open_wallet("alice", "alice_pass")?;
+	wallet::controller::owner_single_use(wallets.wallet("alice")?, |api| {
+		let accounts = api.accounts()?;
+		assert_eq!(accounts.len(), 2);
+		Ok(())
+	})?;
+
+	assert!(wallets.open_wallet("dave", "dave_pass").is_err());
+	Ok(())
+}
+
+/// Key at the root of a wallet's keychain
+fn root_key(wallet: Arc<Mutex<WalletInst<LocalWalletClient, ExtKeychain>>>) -> SecretKey {
+	let mut w = wallet.lock();
+	w.open_with_credentials().unwrap();
+	w.keychain()
+		.derive_key(
+			0,
+			&ExtKeychain::root_key_id(),
+			&SwitchCommitmentType::Regular,
+		)
+		.unwrap()
+}
+
+/// hosted wallets recovery impl
+fn wallets_recover_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let client = LocalWalletClient::new("hosted", wallet_proxy.tx.clone());
+
+	let opener: WalletOpener<WalletInst<LocalWalletClient, ExtKeychain>> =
+		Box::new(move |data_dir, password| {
+			WalletSeed::from_file(data_dir, password)
+				.map_err(|_| ErrorKind::WalletSeedDecryption)?;
+			let mut wallet_config = WalletConfig::default();
+			wallet_config.data_file_dir = data_dir.to_owned();
+			let wallet: LMDBBackend<LocalWalletClient, ExtKeychain> =
+				LMDBBackend::new(wallet_config, password, client.clone())?;
+			Ok(Arc::new(Mutex::new(wallet)))
+		});
+	let wallets = Wallets::new(&format!("{}/wallets", test_dir), opener);
+
+	// a 12 words wallet, with a passphrase
+	let phrase = wallets
+		.create_wallet("alice", 16, Some("secret"), "alice_pass")?
+		.to_string();
+	assert_eq!(phrase.split_whitespace().count(), 12);
+	let alice_key = root_key(wallets.wallet("alice")?);
+
+	// the same phrase and passphrase recover the same keys
+	wallets.recover_wallet("bob", &phrase, Some("secret"), "bob_pass")?;
+	assert_eq!(root_key(wallets.wallet("bob")?), alice_key);
+
+	// without the passphrase, another wallet
+	wallets.recover_wallet("carol", &phrase, None, "carol_pass")?;
+	assert_ne!(root_key(wallets.wallet("carol")?), alice_key);
+
+	// the recovered wallet opens with its new password
+	wallets.close_wallet("bob")?;
+	assert!(wallets.open_wallet("bob", "alice_pass").is_err());
+	wallets.open_wallet("bob", "bob_pass")?;
+	assert_eq!(root_key(wallets.wallet("bob")?), alice_key);
+
+	// an existing wallet isn't overwritten, and a bad phrase is refused
+	match wallets.recover_wallet("alice", &phrase, None, "alice_pass") {
+		Err(e) => assert_eq!(e.kind(), ErrorKind::WalletSeedExists),
+		Ok(_) => panic!("recovered over an existing wallet"),
+	}
+	match wallets.recover_wallet("dave", "not a recovery phrase", None, "dave_pass") {
+		Err(e) => assert_eq!(e.kind(), ErrorKind::Mnemonic),
+		Ok(_) => panic!("recovered from a bad phrase"),
+	}
+	assert!(!wallets.wallet_exists("dave")?);
+	Ok(())
+}
+
+/// A wallet instance without a seed yet, in its own data dir
+fn wallet_without_seed(
+	data_dir: &str,
+	client: LocalWalletClient,
+) -> Result<Arc<Mutex<WalletInst<LocalWalletClient, ExtKeychain>>>, libwallet::Error> {
+	let mut wallet_config = WalletConfig::default();
+	wallet_config.data_file_dir = data_dir.to_owned();
+	let wallet: LMDBBackend<LocalWalletClient, ExtKeychain> =
+		LMDBBackend::new(wallet_config, "", client)?;
+	Ok(Arc::new(Mutex::new(wallet)))
+}
+
+/// owner API wallet creation impl, without hosting
+fn owner_create_wallet_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	let wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let client = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+
+	// create the seed of the wallet the owner API runs on
+	let wallet1 = wallet_without_seed(&format!("{}/wallet1", test_dir), client.clone())?;
+	let api = Owner::new(wallet1.clone());
+	assert!(!api.wallet_exists()?);
+	assert!(api.retrieve_txs(false, None, None).is_err());
+	let phrase = api.create_wallet(24, None, "password")?.to_string();
+	assert_eq!(phrase.split_whitespace().count(), 18);
+	assert!(api.wallet_exists()?);
+	// the instance now opens with the password
+	assert!(api.retrieve_txs(false, None, None)?.1.is_empty());
+	match api.create_wallet(32, None, "password") {
+		Err(e) => assert_eq!(e.kind(), ErrorKind::WalletSeedExists),
+		Ok(_) => panic!("created over an existing wallet"),
+	}
+	match api.recover_wallet(&phrase, None, "password") {
+		Err(e) => assert_eq!(e.kind(), ErrorKind::WalletSeedExists),
+		Ok(_) => panic!("recovered over an existing wallet"),
+	}
+	let key1 = root_key(wallet1);
+
+	// recover it through the JSON-RPC API of another wallet
+	let wallet2 = wallet_without_seed(&format!("{}/wallet2", test_dir), client.clone())?;
+	let api = Owner::new(wallet2.clone());
+	let owner_rpc = &api as &dyn OwnerRpc;
+	let res = owner_rpc.handle_request(json!({
+		"jsonrpc": "2.0",
+		"method": "recover_wallet",
+		"params": [phrase, null, "password2"],
+		"id": 1
+	}));
+	assert_eq!(res.as_option().unwrap()["result"]["Ok"], json!(null));
+	let res = owner_rpc.handle_request(json!({
+		"jsonrpc": "2.0",
+		"method": "wallet_exists",
+		"params": [],
+		"id": 1
+	}));
+	assert_eq!(res.as_option().unwrap()["result"]["Ok"], json!(true));
+	assert_eq!(root_key(wallet2), key1);
+
+	// a bad phrase leaves the wallet without a seed
+	let wallet3 = wallet_without_seed(&format!("{}/wallet3", test_dir), client)?;
+	let api = Owner::new(wallet3);
+	match api.recover_wallet("not a recovery phrase", None, "password") {
+		Err(e) => assert_eq!(e.kind(), ErrorKind::Mnemonic),
+		Ok(_) => panic!("recovered from a bad phrase"),
+	}
+	assert!(!api.wallet_exists()?);
+	Ok(())
+}
+
+#[test]
+fn wallet_hosting() {
+	let test_dir = "test_output/wallet_hosting";
+	if let Err(e) = wallets_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
+
+#[test]
+fn wallet_hosting_recover() {
+	let test_dir = "test_output/wallet_hosting_recover";
+	if let Err(e) = wallets_recover_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
+
+#[test]
+fn owner_create_wallet() {
+	let test_dir = "test_output/owner_create_wallet";
+	if let Err(e) = owner_create_wallet_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
@@ -0,0 +1,149 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test a wallet opened in watch-only mode
+#[macro_use]
+extern crate log;
+extern crate grin_wallet_controller as wallet;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_core as core;
+use grin_wallet_util::grin_keychain as keychain;
+use grin_wallet_util::grin_util as util;
+
+use self::core::global;
+use self::core::global::ChainTypes;
+use self::keychain::ExtKeychain;
+use self::libwallet::{ErrorKind, InitTxArgs, PaymentInfo, Slate};
+use self::util::secp;
+use impls::test_framework::{self, LocalWalletClient, WalletProxy};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn setup(test_dir: &str) {
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+}
+
+/// watch-only impl
+fn watch_only_test_impl(test_dir: &str) -> Result<(), libwallet::Error> {
+	setup(test_dir);
+	// Create a new proxy to simulate server and wallet responses
+	let mut wallet_proxy: WalletProxy<LocalWalletClient, ExtKeychain> = WalletProxy::new(test_dir);
+	let chain = wallet_proxy.chain.clone();
+
+	let client1 = LocalWalletClient::new("wallet1", wallet_proxy.tx.clone());
+	let wallet1 =
+		test_framework::create_wallet(&format!("{}/wallet1", test_dir), client1.clone(), None);
+	wallet_proxy.add_wallet("wallet1", client1.get_send_instance(), wallet1.clone());
+
+	let client2 = LocalWalletClient::new("wallet2", wallet_proxy.tx.clone());
+	let wallet2 =
+		test_framework::create_wallet(&format!("{}/wallet2", test_dir), client2.clone(), None);
+	wallet_proxy.add_wallet("wallet2", client2.get_send_instance(), wallet2.clone());
+
+	// Set the wallet proxy listener running
+	thread::spawn(move || {
+		if let Err(e) = wallet_proxy.run() {
+			error!("Wallet Proxy error: {}", e);
+		}
+	});
+
+	// few values to keep things shorter
+	let reward = core::consensus::REWARD;
+	let cm = global::coinbase_maturity();
+
+	// Get some mining done, while the wallet still has its keys
+	let mut bh = 5u64;
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), bh as usize, false);
+
+	wallet1.lock().set_watch_only(true);
+
+	// The chain moves on, a watch-only wallet still follows its outputs
+	let _ = test_framework::award_blocks_to_wallet(&chain, wallet2.clone(), cm as usize, false);
+	bh += cm;
+
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let (wallet1_refreshed, wallet1_info) = api.retrieve_summary_info(true, 1)?;
+		assert!(wallet1_refreshed);
+		assert_eq!(wallet1_info.last_confirmed_height, bh);
+		assert_eq!(wallet1_info.total, 5 * reward);
+
+		let (_, outputs) = api.retrieve_outputs(false, true, None)?;
+		assert_eq!(outputs.len(), 5);
+		let (_, txs) = api.retrieve_txs(true, None, None)?;
+		assert_eq!(txs.len(), 5);
+		Ok(())
+	})?;
+
+	// Anything needing the keys is refused
+	wallet::controller::owner_single_use(wallet1.clone(), |api| {
+		let args = InitTxArgs {
+			src_acct_name: None,
+			amount: reward,
+			minimum_confirmations: 2,
+			max_outputs: 500,
+			num_change_outputs: 1,
+			selection_strategy: "all".to_owned(),
+			..Default::default()
+		};
+		match api.init_send_tx(args) {
+			Err(e) => match e.kind() {
+				ErrorKind::WatchOnly(_) => {}
+				k => panic!("unexpected error: {}", k),
+			},
+			Ok(_) => panic!("watch-only wallet created a transaction"),
+		}
+		assert!(api.check_repair(false).is_err());
+
+		// including a slate carrying a signed payment proof to finalize
+		let mut slate = Slate::blank(2);
+		slate.payment_proof = Some(PaymentInfo {
+			sender_address: String::new(),
+			receiver_address: Some(String::new()),
+			receiver_signature: Some(
+				secp::Signature::from_raw_data(&[0; secp::constants::AGG_SIGNATURE_SIZE]).unwrap(),
+			),
+		});
+		match api.finalize_tx(&slate, None, None) {
+			Err(e) => match e.kind() {
+				ErrorKind::WatchOnly(_) => {}
+				k => panic!("unexpected error: {}", k),
+			},
+			Ok(_) => panic!("watch-only wallet finalized a transaction"),
+		}
+		Ok(())
+	})?;
+	let res = test_framework::award_blocks_to_wallet(&chain, wallet1.clone(), 1, false);
+	assert!(res.is_err());
+
+	// let logging finish
+	thread::sleep(Duration::from_millis(200));
+	Ok(())
+}
+
+#[test]
+fn wallet_watch_only() {
+	let test_dir = "test_output/watch_only";
+	if let Err(e) = watch_only_test_impl(test_dir) {
+		panic!("Libwallet Error: {} - {}", e, e.backtrace().unwrap());
+	}
+}
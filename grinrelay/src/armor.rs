@@ -0,0 +1,172 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Armored Slates
+//!
+//! Text form of a slate between header and footer lines, to be copied and
+//! pasted through a chat or an email. The slate can be encrypted to the Grin
+//! Relay address of its recipient, with the ephemeral message format, so
+//! that only the recipient can read it.
+
+use rand::thread_rng;
+use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use secp256k1zkp::Secp256k1;
+
+use crate::error::ErrorKind;
+use crate::grin_util::secp::key::{PublicKey, SecretKey};
+use crate::grinrelay_address::GrinboxAddress;
+use crate::libwallet::{Slate, VersionedSlate};
+use crate::message::{EncryptedMessage, MESSAGE_VERSION_EPHEMERAL};
+use crate::Result;
+
+pub const ARMOR_HEADER: &str = "-----BEGIN GRIN SLATE-----";
+pub const ARMOR_FOOTER: &str = "-----END GRIN SLATE-----";
+pub const ENCRYPTED_ARMOR_HEADER: &str = "-----BEGIN GRIN ENCRYPTED SLATE-----";
+pub const ENCRYPTED_ARMOR_FOOTER: &str = "-----END GRIN ENCRYPTED SLATE-----";
+
+const LINE_LENGTH: usize = 64;
+
+/// Armor a slate, encrypted to `recipient` if any
+pub fn armor_slate(slate: &Slate, recipient: Option<&GrinboxAddress>) -> Result<String> {
+	let armored = VersionedSlate::into_orig_version(slate.clone())
+		.to_armored()
+		.map_err(|e| ErrorKind::ArmoredSlate(e.to_string()))?;
+	let (header, body, footer) = match recipient {
+		None => (ARMOR_HEADER, armored, ARMOR_FOOTER),
+		Some(recipient) => {
+			// the ephemeral format doesn't use our relay key, any key will do
+			let secp = Secp256k1::new();
+			let secret_key = SecretKey::new(&secp, &mut thread_rng());
+			let message = EncryptedMessage::new(
+				armored,
+				recipient,
+				&recipient.public_key()?,
+				&secret_key,
+				MESSAGE_VERSION_EPHEMERAL,
+			)?;
+			let message = serde_json::to_string(&message).map_err(|_| ErrorKind::Encryption)?;
+			(
+				ENCRYPTED_ARMOR_HEADER,
+				message.as_bytes().to_base64(STANDARD),
+				ENCRYPTED_ARMOR_FOOTER,
+			)
+		}
+	};
+
+	let mut text = format!("{}\n", header);
+	for line in body.as_bytes().chunks(LINE_LENGTH) {
+		text.push_str(&String::from_utf8_lossy(line));
+		text.push('\n');
+	}
+	text.push_str(footer);
+	text.push('\n');
+	Ok(text)
+}
+
+/// Read an armored slate. An encrypted one is decrypted with whichever of
+/// `secret_keys` matches the relay address it was encrypted to.
+pub fn dearmor_slate(text: &str, secret_keys: &[SecretKey]) -> Result<Slate> {
+	let armored = if let Some(body) = armor_body(text, ARMOR_HEADER, ARMOR_FOOTER) {
+		body
+	} else if let Some(body) = armor_body(text, ENCRYPTED_ARMOR_HEADER, ENCRYPTED_ARMOR_FOOTER) {
+		let message = body
+			.from_base64()
+			.map_err(|_| ErrorKind::ArmoredSlate("invalid encrypted slate".to_owned()))?;
+		let message: EncryptedMessage = serde_json::from_slice(&message)
+			.map_err(|_| ErrorKind::ArmoredSlate("invalid encrypted slate".to_owned()))?;
+		if message.version() != MESSAGE_VERSION_EPHEMERAL {
+			return Err(ErrorKind::Decryption.into());
+		}
+		let recipient = message.destination.public_key()?;
+		let secp = Secp256k1::new();
+		let secret_key = secret_keys
+			.iter()
+			.find(|k| PublicKey::from_secret_key(&secp, k).ok() == Some(recipient))
+			.ok_or_else(|| {
+				ErrorKind::ArmoredSlate(format!(
+					"slate encrypted to {}, not to this wallet",
+					message.destination.stripped()
+				))
+			})?;
+		// the sender key is not used by the ephemeral format
+		let key = message.key(&recipient, secret_key)?;
+		message.decrypt_with_key(&key)?
+	} else {
+		return Err(ErrorKind::ArmoredSlate("no armored slate found".to_owned()).into());
+	};
+
+	let slate = VersionedSlate::from_armored(&armored)
+		.map_err(|e| ErrorKind::ArmoredSlate(e.to_string()))?;
+	Ok(slate.into())
+}
+
+/// Whether this text holds an armored slate, encrypted or not
+pub fn is_armored(text: &str) -> bool {
+	text.contains(ARMOR_HEADER) || is_encrypted(text)
+}
+
+/// Whether this text holds an encrypted armored slate
+pub fn is_encrypted(text: &str) -> bool {
+	text.contains(ENCRYPTED_ARMOR_HEADER)
+}
+
+/// Lines between the header and the footer, joined. Anything around them,
+/// a mail signature for instance, is ignored.
+fn armor_body(text: &str, header: &str, footer: &str) -> Option<String> {
+	let mut lines = text.lines().map(str::trim);
+	lines.find(|l| *l == header)?;
+	let mut body = String::new();
+	for line in lines {
+		if line == footer {
+			return Some(body);
+		}
+		body.push_str(line);
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn armored_slates() {
+		let secp = Secp256k1::new();
+		let receiver_secret = SecretKey::new(&secp, &mut thread_rng());
+		let receiver_public = PublicKey::from_secret_key(&secp, &receiver_secret).unwrap();
+		let receiver = GrinboxAddress::new(receiver_public, None, None);
+		let other_secret = SecretKey::new(&secp, &mut thread_rng());
+
+		let slate = Slate::blank(2);
+		let text = armor_slate(&slate, None).unwrap();
+		assert!(is_armored(&text) && !is_encrypted(&text));
+		assert!(text.lines().all(|l| l.len() <= LINE_LENGTH));
+		let pasted = format!("Hi, here is the slate:\n\n{}\nCheers", text);
+		assert_eq!(dearmor_slate(&pasted, &[]).unwrap().id, slate.id);
+
+		let text = armor_slate(&slate, Some(&receiver)).unwrap();
+		assert!(is_armored(&text) && is_encrypted(&text));
+		assert!(dearmor_slate(&text, &[]).is_err());
+		assert!(dearmor_slate(&text, &[other_secret.clone()]).is_err());
+		let read = dearmor_slate(&text, &[other_secret, receiver_secret.clone()]).unwrap();
+		assert_eq!(read.id, slate.id);
+
+		// a truncated paste is refused
+		let truncated: Vec<&str> = text
+			.lines()
+			.filter(|l| *l != ENCRYPTED_ARMOR_FOOTER)
+			.collect();
+		assert!(dearmor_slate(&truncated.join("\n"), &[receiver_secret]).is_err());
+	}
+}
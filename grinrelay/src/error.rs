@@ -36,6 +36,8 @@ pub enum ErrorKind {
 	Encryption,
 	#[fail(display = "unable to decrypt message")]
 	Decryption,
+	#[fail(display = "armored slate: {}", 0)]
+	ArmoredSlate(String),
 	#[fail(display = "unable to verify proof")]
 	VerifyProof,
 	#[fail(display = "grinrelay websocket terminated unexpectedly!")]
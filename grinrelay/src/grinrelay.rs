@@ -17,13 +17,14 @@
 
 use chrono::prelude::Utc;
 use dns_lookup::lookup_addr;
+use std::collections::HashMap;
 use std::net::ToSocketAddrs;
 use std::net::{SocketAddr, TcpStream};
 use std::sync::Arc;
 
 use crate::grinrelay_address::GrinboxAddress;
 use crate::libwallet::{Listener, VersionedSlate};
-use crate::message::EncryptedMessage;
+use crate::message::{peer_message_version, EncryptedMessage, MESSAGE_VERSION_LATEST};
 #[cfg(feature = "ssl")]
 use openssl::ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode};
 #[cfg(feature = "ssl")]
@@ -96,14 +97,17 @@ pub struct GrinboxPublisher {
 }
 
 impl GrinboxPublisher {
+	/// `message_version` is the format of the messages sent to the peers we
+	/// haven't heard from yet, the others get the newest format they read.
 	pub fn new(
 		address: &GrinboxAddress,
 		secret_key: &SecretKey,
 		protocol_unsecure: bool,
+		message_version: u8,
 	) -> Result<Self> {
 		Ok(Self {
 			address: address.clone(),
-			broker: GrinboxBroker::new(protocol_unsecure)?,
+			broker: GrinboxBroker::new(protocol_unsecure, message_version)?,
 			secret_key: secret_key.clone(),
 		})
 	}
@@ -176,6 +180,9 @@ struct GrinboxBroker {
 	inner: Arc<Mutex<Option<Sender>>>,
 	protocol_unsecure: bool,
 	pub selected_server: Option<String>,
+	message_version: u8,
+	/// Newest message format each peer reads, by public key
+	peer_versions: Arc<Mutex<HashMap<String, u8>>>,
 }
 
 struct ConnectionMetadata {
@@ -193,11 +200,13 @@ impl ConnectionMetadata {
 }
 
 impl GrinboxBroker {
-	fn new(protocol_unsecure: bool) -> Result<Self> {
+	fn new(protocol_unsecure: bool, message_version: u8) -> Result<Self> {
 		Ok(Self {
 			inner: Arc::new(Mutex::new(None)),
 			protocol_unsecure,
 			selected_server: None,
+			message_version,
+			peer_versions: Arc::new(Mutex::new(HashMap::new())),
 		})
 	}
 
@@ -232,8 +241,15 @@ impl GrinboxBroker {
 
 		let pkey = to.public_key()?;
 		let skey = secret_key.clone();
-		let message = EncryptedMessage::new(serde_json::to_string(&slate)?, &to, &pkey, &skey)
-			.map_err(|_| WsError::new(WsErrorKind::Protocol, "could not encrypt slate!"))?;
+		let version = self
+			.peer_versions
+			.lock()
+			.get(&to.public_key)
+			.cloned()
+			.unwrap_or(self.message_version);
+		let message =
+			EncryptedMessage::new(serde_json::to_string(&slate)?, &to, &pkey, &skey, version)
+				.map_err(|_| WsError::new(WsErrorKind::Protocol, "could not encrypt slate!"))?;
 		let message_ser = serde_json::to_string(&message)?;
 
 		let mut challenge = String::new();
@@ -245,6 +261,7 @@ impl GrinboxBroker {
 			to: to.stripped(),
 			str: message_ser,
 			signature,
+			message_version: Some(MESSAGE_VERSION_LATEST),
 		};
 
 		if let Some(ref sender) = *self.inner.lock() {
@@ -283,6 +300,7 @@ impl GrinboxBroker {
 		let cloned_inner = self.inner.clone();
 		let cloned_handler = handler.clone();
 		let connection_meta_data = Arc::new(Mutex::new(ConnectionMetadata::new()));
+		let peer_versions = self.peer_versions.clone();
 		loop {
 			let cloned_address = cloned_address.clone();
 			let cloned_handler = cloned_handler.clone();
@@ -301,6 +319,7 @@ impl GrinboxBroker {
 					address: cloned_address.clone(),
 					secret_key: secret_key.clone(),
 					connection_meta_data: cloned_connection_meta_data.clone(),
+					peer_versions: peer_versions.clone(),
 				};
 				client
 			});
@@ -423,6 +442,7 @@ where
 	address: GrinboxAddress,
 	secret_key: SecretKey,
 	connection_meta_data: Arc<Mutex<ConnectionMetadata>>,
+	peer_versions: Arc<Mutex<HashMap<String, u8>>>,
 }
 
 impl<P> GrinboxClient<P>
@@ -489,7 +509,9 @@ where
 				str,
 				challenge,
 				signature,
+				message_version,
 			} => {
+				let version = serde_json::from_str::<EncryptedMessage>(&str).map(|m| m.version());
 				let (slate, tx_proof) = match TxProof::from_response(
 					from.clone(),
 					str,
@@ -505,6 +527,12 @@ where
 					}
 				};
 
+				// answer each peer in the newest message format it reads
+				if let (Ok(version), Ok(peer)) = (version, GrinboxAddress::from_str(&from)) {
+					let version = peer_message_version(version, message_version);
+					self.peer_versions.lock().insert(peer.public_key, version);
+				}
+
 				self.handler.lock().on_slate(&from, &slate, Some(tx_proof));
 			}
 			ProtocolResponse::RelayAddr { abbr, relay_addr } => {
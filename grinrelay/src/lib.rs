@@ -28,6 +28,7 @@ extern crate serde_derive;
 extern crate colored;
 extern crate serde;
 
+pub mod armor;
 pub mod bech32;
 pub mod crypto;
 pub mod error;
@@ -51,7 +52,10 @@ pub use crate::crypto::{sign_challenge, verify_signature};
 pub use crate::error::ErrorKind;
 pub use crate::grinrelay::{GrinboxListener, GrinboxPublisher, GrinboxSubscriber};
 pub use crate::grinrelay_address::GrinboxAddress;
-pub use crate::tx_proof::TxProofImpl;
+pub use crate::tx_proof::{
+	payment_proof_address, receive_tx_with_proof, sign_payment_proof, verify_payment_proof,
+	TxProofImpl,
+};
 pub use crate::types::{CloseReason, Controller, Publisher, Subscriber, SubscriptionHandler};
 
 pub use failure::Error;
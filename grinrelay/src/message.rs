@@ -19,7 +19,7 @@ use crate::grin_util::secp::key::{PublicKey, SecretKey};
 use rand::thread_rng;
 use rand::Rng;
 use ring::aead;
-use ring::{digest, pbkdf2};
+use ring::{digest, hkdf, hmac, pbkdf2};
 use secp256k1zkp::Secp256k1;
 
 use crate::crypto::{from_hex, to_hex};
@@ -27,33 +27,74 @@ use crate::error::ErrorKind;
 use crate::grinrelay_address::GrinboxAddress;
 use crate::Result;
 
+/// Legacy message format, whose key comes from a static ECDH of the relay
+/// keys of both parties, stretched with PBKDF2
+pub const MESSAGE_VERSION_STATIC: u8 = 1;
+/// Message format whose key comes from an ECDH of a fresh ephemeral key of the
+/// sender and the relay key of the receiver, expanded with HKDF. A leaked relay
+/// key no longer decrypts the past messages it sent.
+pub const MESSAGE_VERSION_EPHEMERAL: u8 = 2;
+/// Format of the messages sent to the peers not heard from yet: the legacy
+/// one, which every wallet reads. A peer gets the ephemeral format once it has
+/// used it, or advertised it along a message.
+pub const MESSAGE_VERSION: u8 = MESSAGE_VERSION_STATIC;
+/// Newest format this wallet reads, advertised along the messages it posts
+pub const MESSAGE_VERSION_LATEST: u8 = MESSAGE_VERSION_EPHEMERAL;
+
+const HKDF_INFO: &[u8] = b"grinrelay message v2";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EncryptedMessage {
 	pub destination: GrinboxAddress,
 	pub encrypted_message: String,
 	salt: String,
 	nonce: String,
+	/// Format of this message, none for the legacy static one
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	version: Option<u8>,
+	/// Ephemeral public key of the sender, for the ephemeral format
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	ephemeral_key: Option<String>,
 }
 
 impl EncryptedMessage {
+	/// Encrypts a message for the owner of `receiver_public_key`, in the
+	/// given format. `secret_key` is our relay key, only used by the legacy format.
 	pub fn new(
 		message: String,
 		destination: &GrinboxAddress,
 		receiver_public_key: &PublicKey,
 		secret_key: &SecretKey,
+		version: u8,
 	) -> Result<EncryptedMessage> {
 		let secp = Secp256k1::new();
-		let mut common_secret = receiver_public_key.clone();
-		common_secret
-			.mul_assign(&secp, secret_key)
-			.map_err(|_| ErrorKind::Encryption)?;
-		let common_secret_ser = common_secret.serialize_vec(&secp, true);
-		let common_secret_slice = &common_secret_ser[1..33];
+		let (key, salt, ephemeral_key) = match version {
+			MESSAGE_VERSION_STATIC => {
+				let salt: [u8; 8] = thread_rng().gen();
+				let key = static_key(&secp, receiver_public_key, secret_key, &salt)
+					.map_err(|_| ErrorKind::Encryption)?;
+				(key, salt.to_vec(), None)
+			}
+			MESSAGE_VERSION_EPHEMERAL => {
+				let ephemeral_secret = SecretKey::new(&secp, &mut thread_rng());
+				let ephemeral_public = PublicKey::from_secret_key(&secp, &ephemeral_secret)
+					.map_err(|_| ErrorKind::Encryption)?;
+				let ephemeral_public = ephemeral_public.serialize_vec(&secp, true).to_vec();
+				let salt: [u8; 32] = thread_rng().gen();
+				let key = ephemeral_key(
+					&secp,
+					receiver_public_key,
+					&ephemeral_secret,
+					&ephemeral_public,
+					&salt,
+				)
+				.map_err(|_| ErrorKind::Encryption)?;
+				(key, salt.to_vec(), Some(to_hex(ephemeral_public)))
+			}
+			_ => return Err(ErrorKind::Encryption.into()),
+		};
 
-		let salt: [u8; 8] = thread_rng().gen();
 		let nonce: [u8; 12] = thread_rng().gen();
-		let mut key = [0; 32];
-		pbkdf2::derive(&digest::SHA512, 10000, &salt, common_secret_slice, &mut key);
 		let mut enc_bytes = message.as_bytes().to_vec();
 		let suffix_len = aead::CHACHA20_POLY1305.tag_len();
 		for _ in 0..suffix_len {
@@ -67,26 +108,49 @@ impl EncryptedMessage {
 		Ok(EncryptedMessage {
 			destination: destination.clone(),
 			encrypted_message: to_hex(enc_bytes),
-			salt: to_hex(salt.to_vec()),
+			salt: to_hex(salt),
 			nonce: to_hex(nonce.to_vec()),
+			version: match version {
+				MESSAGE_VERSION_STATIC => None,
+				v => Some(v),
+			},
+			ephemeral_key,
 		})
 	}
 
+	/// Format of this message
+	pub fn version(&self) -> u8 {
+		self.version.unwrap_or(MESSAGE_VERSION_STATIC)
+	}
+
+	/// Decryption key of this message, for its receiver. `sender_public_key`
+	/// is the relay key of the sender, only used by the legacy format.
 	pub fn key(&self, sender_public_key: &PublicKey, secret_key: &SecretKey) -> Result<[u8; 32]> {
 		let salt = from_hex(self.salt.clone()).map_err(|_| ErrorKind::Decryption)?;
 
 		let secp = Secp256k1::new();
-		let mut common_secret = sender_public_key.clone();
-		common_secret
-			.mul_assign(&secp, secret_key)
-			.map_err(|_| ErrorKind::Decryption)?;
-		let common_secret_ser = common_secret.serialize_vec(&secp, true);
-		let common_secret_slice = &common_secret_ser[1..33];
-
-		let mut key = [0; 32];
-		pbkdf2::derive(&digest::SHA512, 10000, &salt, common_secret_slice, &mut key);
-
-		Ok(key)
+		match self.version() {
+			MESSAGE_VERSION_STATIC => static_key(&secp, sender_public_key, secret_key, &salt)
+				.map_err(|_| ErrorKind::Decryption.into()),
+			MESSAGE_VERSION_EPHEMERAL => {
+				let ephemeral_public = self
+					.ephemeral_key
+					.clone()
+					.ok_or(ErrorKind::Decryption)
+					.and_then(|k| from_hex(k).map_err(|_| ErrorKind::Decryption))?;
+				let ephemeral_public_key = PublicKey::from_slice(&secp, &ephemeral_public)
+					.map_err(|_| ErrorKind::Decryption)?;
+				ephemeral_key(
+					&secp,
+					&ephemeral_public_key,
+					secret_key,
+					&ephemeral_public,
+					&salt,
+				)
+				.map_err(|_| ErrorKind::Decryption.into())
+			}
+			_ => Err(ErrorKind::Decryption.into()),
+		}
 	}
 
 	pub fn decrypt_with_key(&self, key: &[u8; 32]) -> Result<String> {
@@ -109,16 +173,70 @@ impl EncryptedMessage {
 			message: self.decrypt_with_key(key)?,
 			salt: self.salt.clone(),
 			nonce: self.nonce.clone(),
+			version: self.version,
+			ephemeral_key: self.ephemeral_key.clone(),
 		})
 	}
 }
 
+/// Format to answer a peer in, from the format of its last message and the
+/// newest format it advertised along, if any: the newest both sides read
+pub fn peer_message_version(used: u8, advertised: Option<u8>) -> u8 {
+	let read = advertised.unwrap_or(used).min(MESSAGE_VERSION_LATEST);
+	read.max(used)
+}
+
+/// x coordinate of the ECDH point of a public key and a secret key
+fn ecdh(secp: &Secp256k1, public_key: &PublicKey, secret_key: &SecretKey) -> Result<Vec<u8>> {
+	let mut common_secret = public_key.clone();
+	common_secret
+		.mul_assign(secp, secret_key)
+		.map_err(|_| ErrorKind::Encryption)?;
+	let common_secret_ser = common_secret.serialize_vec(secp, true);
+	Ok(common_secret_ser[1..33].to_vec())
+}
+
+/// Legacy key: PBKDF2 of the static ECDH of both relay keys
+fn static_key(
+	secp: &Secp256k1,
+	public_key: &PublicKey,
+	secret_key: &SecretKey,
+	salt: &[u8],
+) -> Result<[u8; 32]> {
+	let common_secret = ecdh(secp, public_key, secret_key)?;
+	let mut key = [0; 32];
+	pbkdf2::derive(&digest::SHA512, 10000, salt, &common_secret, &mut key);
+	Ok(key)
+}
+
+/// Ephemeral key: HKDF of the ECDH of the ephemeral key of the sender and the
+/// relay key of the receiver, bound to the ephemeral public key
+fn ephemeral_key(
+	secp: &Secp256k1,
+	public_key: &PublicKey,
+	secret_key: &SecretKey,
+	ephemeral_public: &[u8],
+	salt: &[u8],
+) -> Result<[u8; 32]> {
+	let common_secret = ecdh(secp, public_key, secret_key)?;
+	let mut info = HKDF_INFO.to_vec();
+	info.extend_from_slice(ephemeral_public);
+	let mut key = [0; 32];
+	let salt = hmac::SigningKey::new(&digest::SHA256, salt);
+	hkdf::extract_and_expand(&salt, &common_secret, &info, &mut key);
+	Ok(key)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DecryptedMessage {
 	pub destination: GrinboxAddress,
 	pub message: String,
 	pub salt: String,
 	pub nonce: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<u8>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ephemeral_key: Option<String>,
 }
 
 impl DecryptedMessage {
@@ -141,6 +259,87 @@ impl DecryptedMessage {
 			encrypted_message,
 			salt: self.salt.clone(),
 			nonce: self.nonce.clone(),
+			version: self.version,
+			ephemeral_key: self.ephemeral_key.clone(),
 		})
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn message_versions() {
+		let secp = Secp256k1::new();
+		let sender_secret = SecretKey::new(&secp, &mut thread_rng());
+		let sender_public = PublicKey::from_secret_key(&secp, &sender_secret).unwrap();
+		let receiver_secret = SecretKey::new(&secp, &mut thread_rng());
+		let receiver_public = PublicKey::from_secret_key(&secp, &receiver_secret).unwrap();
+		let destination = GrinboxAddress::new(receiver_public.clone(), None, None);
+
+		for version in vec![MESSAGE_VERSION_STATIC, MESSAGE_VERSION_EPHEMERAL] {
+			let message = EncryptedMessage::new(
+				"slate".to_owned(),
+				&destination,
+				&receiver_public,
+				&sender_secret,
+				version,
+			)
+			.unwrap();
+			let message: EncryptedMessage =
+				serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+			assert_eq!(message.version(), version);
+
+			let key = message.key(&sender_public, &receiver_secret).unwrap();
+			let decrypted = message.get_decrypted_message(&key).unwrap();
+			assert_eq!(decrypted.message, "slate");
+
+			// a tx proof re-encrypts the message with the key, to check its signature
+			let reencrypted = decrypted.encrypt_with_key(&key).unwrap();
+			assert_eq!(
+				serde_json::to_string(&reencrypted).unwrap(),
+				serde_json::to_string(&message).unwrap()
+			);
+		}
+
+		// the ephemeral format doesn't depend on the relay key of the sender
+		let message = EncryptedMessage::new(
+			"slate".to_owned(),
+			&destination,
+			&receiver_public,
+			&sender_secret,
+			MESSAGE_VERSION_EPHEMERAL,
+		)
+		.unwrap();
+		let other_secret = SecretKey::new(&secp, &mut thread_rng());
+		let other_public = PublicKey::from_secret_key(&secp, &other_secret).unwrap();
+		assert_eq!(
+			message.key(&sender_public, &receiver_secret).unwrap(),
+			message.key(&other_public, &receiver_secret).unwrap()
+		);
+		// and a leaked relay key of the sender doesn't decrypt it
+		let leaked = message.key(&receiver_public, &sender_secret).unwrap();
+		assert!(message.decrypt_with_key(&leaked).is_err());
+
+		// the default format is the legacy one, without the fields added since
+		let message = EncryptedMessage::new(
+			"slate".to_owned(),
+			&destination,
+			&receiver_public,
+			&sender_secret,
+			MESSAGE_VERSION,
+		)
+		.unwrap();
+		let message = serde_json::to_value(&message).unwrap();
+		assert!(message.get("version").is_none());
+		assert!(message.get("ephemeral_key").is_none());
+
+		// a legacy peer is answered in the legacy format, the others in the
+		// newest format both sides read
+		assert_eq!(peer_message_version(1, None), MESSAGE_VERSION_STATIC);
+		assert_eq!(peer_message_version(1, Some(2)), MESSAGE_VERSION_EPHEMERAL);
+		assert_eq!(peer_message_version(2, None), MESSAGE_VERSION_EPHEMERAL);
+		assert_eq!(peer_message_version(1, Some(9)), MESSAGE_VERSION_LATEST);
+	}
+}
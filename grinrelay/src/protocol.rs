@@ -53,6 +53,10 @@ pub enum ProtocolRequest {
 		to: String,
 		str: String,
 		signature: String,
+		/// Newest message format the sender reads, outside the signed message
+		/// so that the tx proofs of the legacy wallets still verify
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		message_version: Option<u8>,
 	},
 	Unsubscribe {
 		address: String,
@@ -83,6 +87,7 @@ impl Display for ProtocolRequest {
 				ref to,
 				str: _,
 				signature: _,
+				message_version: _,
 			} => write!(
 				f,
 				"{} from {} to {}",
@@ -116,6 +121,9 @@ pub enum ProtocolResponse {
 		str: String,
 		signature: String,
 		challenge: String,
+		/// Newest message format the sender reads, when the relay passes it on
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		message_version: Option<u8>,
 	},
 	RelayAddr {
 		abbr: String,
@@ -139,6 +147,7 @@ impl Display for ProtocolResponse {
 				str: _,
 				signature: _,
 				challenge: _,
+				message_version: _,
 			} => write!(f, "{} from {}", "Slate".cyan(), from.bright_green()),
 			ProtocolResponse::RelayAddr {
 				ref abbr,
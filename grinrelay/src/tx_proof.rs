@@ -15,14 +15,20 @@
 
 //! Grin Relay Tx Proof
 
-use crate::grin_util::secp::key::SecretKey;
+use crate::grin_util::secp::key::{PublicKey, SecretKey};
 use crate::grin_util::secp::Signature;
 use crate::Result;
 use failure::Fail;
+use grin_wallet_util::grin_keychain::Keychain;
 
-use crate::crypto::verify_signature;
 use crate::crypto::Hex;
-use crate::libwallet::{TxProof, VersionedSlate};
+use crate::crypto::{sign_challenge, verify_signature};
+use crate::hasher::derive_address_key;
+use crate::libwallet::api_impl::foreign;
+use crate::libwallet::{
+	self, payment_proof_message, NodeClient, PaymentProof, Slate, TxProof, VersionedSlate,
+	WalletBackend,
+};
 use crate::message::{DecryptedMessage, EncryptedMessage};
 use crate::GrinboxAddress;
 
@@ -177,3 +183,120 @@ impl TxProofImpl for TxProof {
 		Ok((slate, proof))
 	}
 }
+
+/// Grin Relay address of the wallet at a key path (`path << 32 | index`),
+/// with its secret key. Payment proofs are signed with these keys, the
+/// address at key path 0 being the default receiving address.
+pub fn payment_proof_address<K: Keychain>(
+	keychain: &K,
+	key_path: u64,
+) -> Result<(SecretKey, String)> {
+	let index = key_path as u32;
+	let path = (key_path >> 32) as u32;
+	let sec_key = derive_address_key(keychain, path, index)?;
+	let pub_key = PublicKey::from_secret_key(keychain.secp(), &sec_key)?;
+	let address = GrinboxAddress::new(pub_key, None, None);
+	Ok((sec_key, address.stripped()))
+}
+
+/// Sign the payment statement of a slate as its receiver, with the key of our
+/// address at `key_path`. The slate must hold all the inputs and outputs of
+/// the transaction, for its kernel excess to be known. When the sender asked
+/// for a payment proof, the signature is added to the slate for the sender to
+/// keep. The signed proof is returned either way, for the receiver to keep,
+/// with an empty sender address if none was given.
+pub fn sign_payment_proof<K: Keychain>(
+	keychain: &K,
+	slate: &mut Slate,
+	key_path: u64,
+) -> Result<PaymentProof> {
+	let excess = slate.calc_excess(keychain)?;
+	let (sec_key, address) = payment_proof_address(keychain, key_path)?;
+	let sender_address = slate
+		.payment_proof
+		.as_ref()
+		.map(|info| info.sender_address.clone())
+		.unwrap_or_default();
+	let message = payment_proof_message(slate.amount, &excess, &sender_address);
+	let signature = sign_challenge(&message, &sec_key)?;
+	if let Some(ref mut info) = slate.payment_proof {
+		info.receiver_signature = Some(signature.clone());
+		info.receiver_address = Some(address.clone());
+	}
+	Ok(PaymentProof {
+		amount: slate.amount,
+		excess,
+		sender_address,
+		receiver_address: address,
+		receiver_signature: signature,
+	})
+}
+
+/// Receive a slate as its recipient, whatever the transport it came in
+/// through, and sign its payment statement with the key of our address at
+/// `key_path`, see [`sign_payment_proof`](fn.sign_payment_proof.html). The
+/// wallet keeps the signed proof, to prove the receipt later on. The proof
+/// key is derived first, so that a requested proof which can't be signed
+/// fails the receive before anything is saved, and the receive is cancelled
+/// if the signature still fails. Failing to sign a proof the sender didn't
+/// ask for, or to keep the proof, only logs an error. `sender` is the Grin
+/// Relay address of the sender, checked against the receive policy.
+pub fn receive_tx_with_proof<T: ?Sized, C, K>(
+	w: &mut T,
+	slate: &Slate,
+	sender: Option<&str>,
+	dest_acct_name: Option<&str>,
+	message: Option<String>,
+	key_path: Option<u64>,
+	use_test_rng: bool,
+) -> std::result::Result<Slate, libwallet::Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	if slate.payment_proof.is_some() && !w.is_watch_only() {
+		payment_proof_address(w.keychain(), key_path.unwrap_or(0))
+			.map_err(|e| libwallet::ErrorKind::SignProof(format!("{}", e)))?;
+	}
+	let mut slate = foreign::receive_tx(
+		&mut *w,
+		slate,
+		sender,
+		dest_acct_name,
+		message,
+		key_path,
+		use_test_rng,
+	)?;
+	match sign_payment_proof(w.keychain(), &mut slate, key_path.unwrap_or(0)) {
+		Ok(proof) => {
+			if let Err(e) = w.store_payment_proof(&slate.id.to_string(), &proof) {
+				error!("Failed to keep the payment proof of tx {}: {}", slate.id, e);
+			}
+		}
+		Err(e) if slate.payment_proof.is_some() => {
+			if let Err(e) = foreign::cancel_receive_tx(&mut *w, &slate, dest_acct_name) {
+				error!("Failed to cancel the receive of tx {}: {}", slate.id, e);
+			}
+			return Err(libwallet::ErrorKind::SignProof(format!("{}", e)).into());
+		}
+		Err(e) => error!("Failed to sign the payment proof of tx {}: {}", slate.id, e),
+	}
+	Ok(slate)
+}
+
+/// Verify the receiver signature of a payment proof
+pub fn verify_payment_proof(proof: &PaymentProof) -> Result<()> {
+	let receiver_address =
+		GrinboxAddress::from_str(&proof.receiver_address).map_err(|_| ErrorKind::ParseAddress)?;
+	let receiver_public_key = receiver_address
+		.public_key()
+		.map_err(|_| ErrorKind::ParsePublicKey)?;
+	verify_signature(
+		&proof.message(),
+		&proof.receiver_signature,
+		&receiver_public_key,
+	)
+	.map_err(|_| ErrorKind::VerifySignature)?;
+	Ok(())
+}
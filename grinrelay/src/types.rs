@@ -23,7 +23,7 @@ use crate::libwallet::TxProof;
 use crate::libwallet::{Slate, VersionedSlate};
 use crate::ErrorKind;
 use crate::Result;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Sender, SyncSender, TrySendError};
 
 pub enum CloseReason {
 	Normal,
@@ -61,7 +61,7 @@ where
 	name: String,
 	publisher: P,
 	relay_tx_as_payer: Option<Sender<(Slate, Option<TxProof>)>>,
-	relay_tx_as_payee: Option<Sender<(String, Slate)>>,
+	relay_tx_as_payee: Option<SyncSender<(String, Slate)>>,
 	relay_addr_query: Option<Sender<(String, Vec<String>)>>,
 }
 
@@ -73,7 +73,7 @@ where
 		name: &str,
 		publisher: P,
 		relay_tx_as_payer: Option<Sender<(Slate, Option<TxProof>)>>,
-		relay_tx_as_payee: Option<Sender<(String, Slate)>>,
+		relay_tx_as_payee: Option<SyncSender<(String, Slate)>>,
 		relay_addr_query: Option<Sender<(String, Vec<String>)>>,
 	) -> Result<Self> {
 		Ok(Self {
@@ -102,17 +102,32 @@ where
 					address.bright_green(),
 				);
 				//*slate = self.foreign.receive_tx(slate, None, address, None)?;
-				if self.relay_tx_as_payee.is_some() {
-					let _ = self
-						.relay_tx_as_payee
-						.clone()
-						.unwrap()
-						.send((address, slate.clone()));
-				} else {
-					return Err(ErrorKind::GenericError(
-						"relay mpsc sender (as payee) missed".to_string(),
-					)
-					.into());
+				// the receiving workers are busy, drop the slate rather than
+				// holding the relay connection
+				match &self.relay_tx_as_payee {
+					Some(tx) => match tx.try_send((address, slate.clone())) {
+						Ok(()) => {}
+						Err(TrySendError::Full(_)) => {
+							return Err(ErrorKind::GenericError(format!(
+								"slate [{}] dropped, too many slates waiting to be received",
+								slate.id
+							))
+							.into());
+						}
+						Err(TrySendError::Disconnected(_)) => {
+							return Err(ErrorKind::GenericError(format!(
+								"slate [{}] dropped, no receiving worker left",
+								slate.id
+							))
+							.into());
+						}
+					},
+					None => {
+						return Err(ErrorKind::GenericError(
+							"relay mpsc sender (as payee) missed".to_string(),
+						)
+						.into());
+					}
 				}
 			}
 			Ok(())
@@ -0,0 +1,306 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Directory transport: slates exchanged as files through an inbox and an
+//! outbox directory, shared with the other party
+
+use crate::config::{TransportConfig, WalletConfig};
+use crate::libwallet::{Error, ErrorKind, Slate, TxProof};
+use crate::WalletCommAdapter;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Extension of the files being written, which are skipped until renamed
+const PARTIAL_EXT: &str = "part";
+
+/// Config of the directory transport, the `[wallet.transports.dir]` section
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DirConfig {
+	/// Directory the other party drops the slates into
+	pub inbox: String,
+	/// Directory the slates sent and the responses are written to
+	pub outbox: String,
+	/// Directory the processed slates are moved to, `archive` in the inbox
+	/// if none
+	pub archive: Option<String>,
+	/// Seconds between two scans of the inbox, 5 if none
+	pub poll_secs: Option<u64>,
+}
+
+impl DirConfig {
+	/// Read the config out of the section of the transport
+	pub fn from_config(config: Option<&TransportConfig>) -> Result<DirConfig, Error> {
+		let config = config.ok_or_else(|| {
+			ErrorKind::GenericError(
+				"The dir transport needs a [wallet.transports.dir] config section".to_owned(),
+			)
+		})?;
+		config.clone().try_into().map_err(|e| {
+			ErrorKind::GenericError(format!("Invalid [wallet.transports.dir] config: {}", e)).into()
+		})
+	}
+}
+
+/// What was done with a slate of the inbox
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DirStage {
+	/// A new slate, received and answered in the outbox
+	Received,
+	/// The answer to a slate we sent, finalized and posted
+	Finalized,
+}
+
+impl DirStage {
+	/// Stage of a slate found in the inbox
+	pub fn of(slate: &Slate) -> DirStage {
+		if slate.num_participants > slate.participant_data.len() {
+			DirStage::Received
+		} else {
+			DirStage::Finalized
+		}
+	}
+
+	fn ext(&self) -> &'static str {
+		match self {
+			DirStage::Received => "received",
+			DirStage::Finalized => "finalized",
+		}
+	}
+}
+
+/// Outcome of a scan of the inbox
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirScan {
+	/// Slates received or finalized
+	pub processed: usize,
+	/// Slates already processed, answered again if needed and archived
+	pub duplicates: usize,
+	/// Slates which failed, moved to the `failed` directory of the archive
+	pub failed: usize,
+}
+
+/// The inbox, outbox and archive directories of the transport
+#[derive(Clone, Debug)]
+pub struct DirTransport {
+	config: DirConfig,
+}
+
+impl DirTransport {
+	/// Transport over the directories of `config`
+	pub fn new(config: DirConfig) -> DirTransport {
+		DirTransport { config }
+	}
+
+	/// The config of the transport
+	pub fn config(&self) -> &DirConfig {
+		&self.config
+	}
+
+	fn archive_dir(&self) -> PathBuf {
+		match self.config.archive {
+			Some(ref a) => PathBuf::from(a),
+			None => Path::new(&self.config.inbox).join("archive"),
+		}
+	}
+
+	/// Write a slate into the `dir` directory, under the slate id and `ext`.
+	/// The file only shows up once complete.
+	fn write(dir: &Path, slate: &Slate, ext: &str) -> Result<PathBuf, Error> {
+		fs::create_dir_all(dir)?;
+		let content = serde_json::to_string(slate).map_err(|_| ErrorKind::SlateSer)?;
+		let path = dir.join(format!("{}.{}", slate.id, ext));
+		let partial = path.with_extension(format!("{}.{}", ext, PARTIAL_EXT));
+		fs::write(&partial, content)?;
+		fs::rename(&partial, &path)?;
+		Ok(path)
+	}
+
+	/// Write a slate for the other party into the outbox, or into its `dest`
+	/// sub directory if not empty. `dest` can't lead out of the outbox.
+	pub fn send(&self, dest: &str, slate: &Slate) -> Result<PathBuf, Error> {
+		let outbox = Path::new(&self.config.outbox);
+		let dest = Path::new(dest.trim_matches('/'));
+		let inside = dest.components().all(|c| match c {
+			Component::Normal(_) | Component::CurDir => true,
+			_ => false,
+		});
+		if !inside {
+			return Err(ErrorKind::GenericError(format!(
+				"Destination {} is outside of the outbox",
+				dest.display()
+			))
+			.into());
+		}
+		DirTransport::write(&outbox.join(dest), slate, "tx")
+	}
+
+	/// The slate files waiting in the inbox, oldest name first
+	pub fn pending(&self) -> Result<Vec<PathBuf>, Error> {
+		let mut files = vec![];
+		for entry in fs::read_dir(&self.config.inbox)? {
+			let path = entry?.path();
+			let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+			let partial = path.extension().map_or(false, |e| e == PARTIAL_EXT);
+			if path.is_file() && !name.starts_with('.') && !partial {
+				files.push(path);
+			}
+		}
+		files.sort();
+		Ok(files)
+	}
+
+	/// Process the slates waiting in the inbox, each once per slate id and
+	/// stage. `receive` signs a new slate, whose response is written to the
+	/// outbox, and `finalize` completes a slate we sent. The processed files
+	/// are moved to the archive.
+	pub fn scan<R, F>(&self, mut receive: R, mut finalize: F) -> Result<DirScan, Error>
+	where
+		R: FnMut(&Slate) -> Result<Slate, Error>,
+		F: FnMut(&Slate) -> Result<(), Error>,
+	{
+		let archive = self.archive_dir();
+		fs::create_dir_all(&archive)?;
+		let mut scan = DirScan::default();
+		for path in self.pending()? {
+			let slate = fs::read(&path)
+				.map_err(Error::from)
+				.and_then(|content| Slate::deserialize_upgrade_bytes(&content));
+			let slate = match slate {
+				Ok(s) => s,
+				Err(e) => {
+					self.fail(&path, &e)?;
+					scan.failed += 1;
+					continue;
+				}
+			};
+			let stage = DirStage::of(&slate);
+			let archived = archive.join(format!("{}.{}", slate.id, stage.ext()));
+			if archived.exists() {
+				info!(
+					"Slate [{}] of {} already {}",
+					slate.id,
+					path.display(),
+					stage.ext()
+				);
+				if stage == DirStage::Received {
+					// the other party may have missed the response
+					let response = archive.join(format!("{}.response", slate.id));
+					if let Ok(content) = fs::read_to_string(&response) {
+						let response = Slate::deserialize_upgrade(&content)?;
+						DirTransport::write(Path::new(&self.config.outbox), &response, "response")?;
+					}
+				}
+				let duplicate = format!(
+					"{}.{}.{}.duplicate",
+					slate.id,
+					stage.ext(),
+					Utc::now().timestamp_nanos()
+				);
+				fs::rename(&path, archive.join(duplicate))?;
+				scan.duplicates += 1;
+				continue;
+			}
+			let res = match stage {
+				DirStage::Received => receive(&slate).and_then(|response| {
+					DirTransport::write(&archive, &response, "response")?;
+					DirTransport::write(Path::new(&self.config.outbox), &response, "response")
+				}),
+				DirStage::Finalized => finalize(&slate).map(|_| archived.clone()),
+			};
+			match res {
+				Ok(_) => {
+					info!("Slate [{}] of {} {}", slate.id, path.display(), stage.ext());
+					fs::rename(&path, &archived)?;
+					scan.processed += 1;
+				}
+				Err(e) => {
+					self.fail(&path, &e)?;
+					scan.failed += 1;
+				}
+			}
+		}
+		Ok(scan)
+	}
+
+	/// Move a slate file which failed to the `failed` directory of the
+	/// archive, next to the error
+	fn fail(&self, path: &Path, e: &Error) -> Result<(), Error> {
+		warn!("Slate file {} failed: {}", path.display(), e);
+		let failed = self.archive_dir().join("failed");
+		fs::create_dir_all(&failed)?;
+		let name = format!(
+			"{}.{}",
+			path.file_name().and_then(|n| n.to_str()).unwrap_or("slate"),
+			Utc::now().timestamp_nanos()
+		);
+		fs::write(failed.join(format!("{}.error", name)), e.to_string())?;
+		fs::rename(path, failed.join(name))?;
+		Ok(())
+	}
+}
+
+/// Sends the slates into the outbox of the directory transport, the
+/// responses coming back through the inbox
+#[derive(Clone)]
+pub struct DirWalletCommAdapter {
+	transport: DirTransport,
+}
+
+impl DirWalletCommAdapter {
+	/// Create
+	pub fn new(config: DirConfig) -> Box<dyn WalletCommAdapter> {
+		Box::new(DirWalletCommAdapter {
+			transport: DirTransport::new(config),
+		})
+	}
+}
+
+impl WalletCommAdapter for DirWalletCommAdapter {
+	fn supports_sync(&self) -> bool {
+		false
+	}
+
+	fn send_tx_sync(&self, _dest: &str, _slate: &Slate) -> Result<(Slate, Option<TxProof>), Error> {
+		Err(ErrorKind::GenericError(
+			"The directory transport only sends asynchronously".to_owned(),
+		))?
+	}
+
+	fn send_tx_async(&self, dest: &str, slate: &Slate) -> Result<(), Error> {
+		let path = self.transport.send(dest, slate)?;
+		info!("Slate [{}] written to {}", slate.id, path.display());
+		Ok(())
+	}
+
+	fn receive_tx_async(&self, params: &str) -> Result<Slate, Error> {
+		let content = fs::read(params)?;
+		Ok(Slate::deserialize_upgrade_bytes(&content)?)
+	}
+
+	fn listen(
+		&self,
+		_params: HashMap<String, String>,
+		_config: WalletConfig,
+		_passphrase: &str,
+		_account: &str,
+		_node_api_secret: Option<String>,
+	) -> Result<(), Error> {
+		Err(ErrorKind::GenericError(
+			"The directory transport is listened on by the wallet listener, `listen -m dir`"
+				.to_owned(),
+		))?
+	}
+}
@@ -18,17 +18,44 @@ use std::fs::File;
 use std::io::{Read, Write};
 
 use crate::config::WalletConfig;
+use crate::grinrelay::armor::{armor_slate, dearmor_slate, is_armored};
+use crate::grinrelay::GrinboxAddress;
 use crate::libwallet::{Error, ErrorKind, Slate, TxProof};
+use crate::util::secp::key::SecretKey;
 use crate::WalletCommAdapter;
 use std::collections::HashMap;
 
 #[derive(Clone)]
-pub struct FileWalletCommAdapter {}
+pub struct FileWalletCommAdapter {
+	/// Write armored slates instead of json ones
+	armored: bool,
+	/// Relay address the armored slates are encrypted to
+	recipient: Option<GrinboxAddress>,
+	/// Relay keys to decrypt the encrypted slates we read
+	secret_keys: Vec<SecretKey>,
+}
 
 impl FileWalletCommAdapter {
 	/// Create
 	pub fn new() -> Box<dyn WalletCommAdapter> {
-		Box::new(FileWalletCommAdapter {})
+		Box::new(FileWalletCommAdapter {
+			armored: false,
+			recipient: None,
+			secret_keys: vec![],
+		})
+	}
+
+	/// Create, writing armored slates, encrypted to `recipient` if any, and
+	/// decrypting the slates we read with one of `secret_keys`
+	pub fn new_armored(
+		recipient: Option<GrinboxAddress>,
+		secret_keys: Vec<SecretKey>,
+	) -> Box<dyn WalletCommAdapter> {
+		Box::new(FileWalletCommAdapter {
+			armored: true,
+			recipient,
+			secret_keys,
+		})
 	}
 }
 
@@ -38,25 +65,33 @@ impl WalletCommAdapter for FileWalletCommAdapter {
 	}
 
 	fn send_tx_sync(&self, _dest: &str, _slate: &Slate) -> Result<(Slate, Option<TxProof>), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The file transport only sends asynchronously, to a file".to_owned(),
+		))?
 	}
 
 	fn send_tx_async(&self, dest: &str, slate: &Slate) -> Result<(), Error> {
+		let content = if self.armored {
+			armor_slate(slate, self.recipient.as_ref())?
+		} else {
+			serde_json::to_string(slate).map_err(|_| ErrorKind::SlateSer)?
+		};
 		let mut pub_tx = File::create(dest)?;
-		pub_tx.write_all(
-			serde_json::to_string(slate)
-				.map_err(|_| ErrorKind::SlateSer)?
-				.as_bytes(),
-		)?;
+		pub_tx.write_all(content.as_bytes())?;
 		pub_tx.sync_all()?;
 		Ok(())
 	}
 
 	fn receive_tx_async(&self, params: &str) -> Result<Slate, Error> {
 		let mut pub_tx_f = File::open(params)?;
-		let mut content = String::new();
-		pub_tx_f.read_to_string(&mut content)?;
-		Ok(Slate::deserialize_upgrade(&content)?)
+		let mut content = Vec::new();
+		pub_tx_f.read_to_end(&mut content)?;
+		if let Ok(text) = std::str::from_utf8(&content) {
+			if is_armored(text) {
+				return Ok(dearmor_slate(text, &self.secret_keys)?);
+			}
+		}
+		Ok(Slate::deserialize_upgrade_bytes(&content)?)
 	}
 
 	fn listen(
@@ -67,6 +102,9 @@ impl WalletCommAdapter for FileWalletCommAdapter {
 		_account: &str,
 		_node_api_secret: Option<String>,
 	) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The file transport has no listener, the slate files are received with `receive`"
+				.to_owned(),
+		))?
 	}
 }
@@ -15,12 +15,11 @@
 /// Grin Relay 'plugin' implementation
 use crate::config::WalletConfig;
 use crate::libwallet::Listener;
-use crate::libwallet::{Error, ErrorKind, Slate, SlateVersion, TxProof, VersionedSlate};
+use crate::libwallet::{Error, ErrorKind, Slate, TxProof, VersionedSlate};
 use crate::WalletCommAdapter;
 use colored::*;
 use std::collections::HashMap;
-use std::sync::mpsc::{Receiver, TryRecvError};
-use std::thread;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::time::Duration;
 
 const TTL: u16 = 10; // TODO: Pass this as a parameter
@@ -50,42 +49,36 @@ impl WalletCommAdapter for GrinrelayWalletCommAdapter {
 			"Posting transaction slate to {} via Grin Relay service",
 			dest
 		);
-		let versioned_slate = VersionedSlate::into_version(slate.clone(), SlateVersion::V2);
+		let versioned_slate = VersionedSlate::into_orig_version(slate.clone());
 		self.listener.publish(&versioned_slate, &dest.to_owned())?;
 
 		// Wait for response from recipient via Grin Relay
 		info!("Waiting for recipient to response ...");
-		let mut cnt = 0;
-		loop {
-			match self.relay_rx.try_recv() {
-				Ok(s) => {
-					return Ok(s);
-				}
-				Err(TryRecvError::Disconnected) => {
-					return Err(ErrorKind::ClientCallback(
-						"TryRecvError::Disconnected".to_owned(),
-					))?
-				}
-				Err(TryRecvError::Empty) => {}
+		match self.relay_rx.recv_timeout(Duration::from_secs(TTL as u64)) {
+			Ok(s) => Ok(s),
+			Err(RecvTimeoutError::Disconnected) => {
+				Err(ErrorKind::ClientCallback("RecvTimeoutError::Disconnected".to_owned()).into())
 			}
-			cnt += 1;
-			if cnt > TTL * 10 {
-				return Err(ErrorKind::ClientCallback(format!(
-					"{} from recipient. {}s timeout",
-					"No response".bright_blue(),
-					TTL
-				)))?;
-			}
-			thread::sleep(Duration::from_millis(100));
+			Err(RecvTimeoutError::Timeout) => Err(ErrorKind::ClientCallback(format!(
+				"{} from recipient. {}s timeout",
+				"No response".bright_blue(),
+				TTL
+			))
+			.into()),
 		}
 	}
 
 	fn send_tx_async(&self, _dest: &str, _slate: &Slate) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The Grin Relay transport only sends synchronously".to_owned(),
+		))?
 	}
 
 	fn receive_tx_async(&self, _params: &str) -> Result<Slate, Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The Grin Relay transport has no slate to read, slates come in through its listener"
+				.to_owned(),
+		))?
 	}
 
 	fn listen(
@@ -96,6 +89,9 @@ impl WalletCommAdapter for GrinrelayWalletCommAdapter {
 		_account: &str,
 		_node_api_secret: Option<String>,
 	) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The Grin Relay transport is listened on by the wallet listener, `listen -m grinrelay`"
+				.to_owned(),
+		))?
 	}
 }
@@ -14,25 +14,61 @@
 // limitations under the License.
 
 /// HTTP Wallet 'plugin' implementation
+use crate::adapters::tls::{tls_post, HttpTransportConfig};
+use crate::adapters::tor::{socks_post, TorConfig};
 use crate::api;
-use crate::libwallet::{Error, ErrorKind, Slate, TxProof};
+use crate::libwallet::{Error, ErrorKind, Slate, SlateVersion, TxProof, VersionedSlate};
 use crate::WalletCommAdapter;
 use config::WalletConfig;
 use serde::Serialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::io::{self, Read, Write};
 
 #[derive(Clone)]
-pub struct HTTPWalletCommAdapter {}
+pub struct HTTPWalletCommAdapter {
+	/// TLS settings of the HTTPS destinations
+	config: HttpTransportConfig,
+	/// Post through the SOCKS proxy of Tor, to the onion addresses
+	tor: Option<TorConfig>,
+}
 
 impl HTTPWalletCommAdapter {
 	/// Create
 	pub fn new() -> Box<dyn WalletCommAdapter> {
-		Box::new(HTTPWalletCommAdapter {})
+		HTTPWalletCommAdapter::with_config(HttpTransportConfig::default())
+	}
+
+	/// Create, with the TLS settings of the HTTPS destinations
+	pub fn with_config(config: HttpTransportConfig) -> Box<dyn WalletCommAdapter> {
+		Box::new(HTTPWalletCommAdapter { config, tor: None })
+	}
+
+	/// Create, posting through Tor
+	pub fn new_tor(config: TorConfig) -> Box<dyn WalletCommAdapter> {
+		Box::new(HTTPWalletCommAdapter {
+			config: HttpTransportConfig::default(),
+			tor: Some(config),
+		})
+	}
+
+	/// Post a request to the other wallet, through Tor if set, or with the
+	/// TLS settings of its destination if any
+	fn post(&self, url: &str, req: &Value) -> Result<String, Error> {
+		if let Some(ref tor) = self.tor {
+			return socks_post(tor, url, req);
+		}
+		match self.config.tls_for(url) {
+			Some(tls) => tls_post(tls, url, req),
+			None => {
+				post(url, None, req).map_err(|e| ErrorKind::ClientCallback(format!("{}", e)).into())
+			}
+		}
 	}
 
-	/// Check version of the other wallet
-	fn check_other_version(&self, url: &str) -> Result<(), Error> {
+	/// Check version of the other wallet, returning the newest slate
+	/// version both wallets understand
+	fn check_other_version(&self, url: &str) -> Result<SlateVersion, Error> {
 		let req = json!({
 			"jsonrpc": "2.0",
 			"method": "check_version",
@@ -40,7 +76,7 @@ impl HTTPWalletCommAdapter {
 			"params": []
 		});
 
-		let res: String = post(url, None, &req).map_err(|e| {
+		let res: String = self.post(url, &req).map_err(|e| {
 			let report = format!("Performing version check (is recipient listening?): {}", e);
 			error!("{}", report);
 			ErrorKind::ClientCallback(report)
@@ -71,13 +107,16 @@ impl HTTPWalletCommAdapter {
 			return Err(ErrorKind::ClientCallback(report).into());
 		}
 
+		if supported_slate_versions.contains(&"V3".to_owned()) {
+			return Ok(SlateVersion::V3);
+		}
 		if !supported_slate_versions.contains(&"V2".to_owned()) {
 			let report = format!("Unable to negotiate slate format with other wallet.");
 			error!("{}", report);
 			return Err(ErrorKind::ClientCallback(report).into());
 		}
 
-		Ok(())
+		Ok(SlateVersion::V2)
 	}
 }
 
@@ -87,7 +126,12 @@ impl WalletCommAdapter for HTTPWalletCommAdapter {
 	}
 
 	fn send_tx_sync(&self, dest: &str, slate: &Slate) -> Result<(Slate, Option<TxProof>), Error> {
-		if &dest[..4] != "http" {
+		// onion addresses may be given without their scheme
+		let dest = match self.tor {
+			Some(_) if !dest.starts_with("http") => format!("http://{}", dest),
+			_ => dest.to_owned(),
+		};
+		if !dest.starts_with("http") {
 			let err_str = format!(
 				"dest formatted as {} but send -d expected stdout or http://IP:port",
 				dest
@@ -98,7 +142,20 @@ impl WalletCommAdapter for HTTPWalletCommAdapter {
 		let url = format!("{}/v2/foreign", dest);
 		debug!("Posting transaction slate to {}", url);
 
-		self.check_other_version(&url)?;
+		// the receiver answers in the version it was sent, which it advertised
+		let version = self.check_other_version(&url)?;
+		if version == SlateVersion::V2 && slate.ttl_cutoff_height.is_some() {
+			let report =
+				"Other wallet doesn't read V3 slates, the only ones with a TTL.".to_owned();
+			error!("{}", report);
+			return Err(ErrorKind::ClientCallback(report).into());
+		}
+		let mut slate = slate.clone();
+		slate.version_info.orig_version = match version {
+			SlateVersion::V3 => 3,
+			SlateVersion::V2 => 2,
+		};
+		let slate = VersionedSlate::into_version(slate, version);
 
 		// Note: not using easy-jsonrpc as don't want the dependencies in this crate
 		let req = json!({
@@ -113,7 +170,7 @@ impl WalletCommAdapter for HTTPWalletCommAdapter {
 		});
 		trace!("Sending receive_tx request: {}", req);
 
-		let res: String = post(url.as_str(), None, &req).map_err(|e| {
+		let res: String = self.post(url.as_str(), &req).map_err(|e| {
 			let report = format!("Posting transaction slate (is recipient listening?): {}", e);
 			error!("{}", report);
 			ErrorKind::ClientCallback(report)
@@ -139,11 +196,16 @@ impl WalletCommAdapter for HTTPWalletCommAdapter {
 	}
 
 	fn send_tx_async(&self, _dest: &str, _slate: &Slate) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The http transport only sends synchronously".to_owned(),
+		))?
 	}
 
 	fn receive_tx_async(&self, _params: &str) -> Result<Slate, Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The http transport has no slate to read, slates come in through its listener"
+				.to_owned(),
+		))?
 	}
 
 	fn listen(
@@ -154,7 +216,9 @@ impl WalletCommAdapter for HTTPWalletCommAdapter {
 		_account: &str,
 		_node_api_secret: Option<String>,
 	) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The http transport is listened on by the wallet listener, `listen -m http`".to_owned(),
+		))?
 	}
 }
 
@@ -166,3 +230,122 @@ where
 	let res = api::client::send_request(req)?;
 	Ok(res)
 }
+
+/// Map an error of the connection to the other wallet
+pub(crate) fn client_error<E: std::fmt::Display>(what: &str) -> impl Fn(E) -> Error + '_ {
+	move |e| ErrorKind::ClientCallback(format!("{}: {}", what, e)).into()
+}
+
+/// Parts of an `http(s)://host[:port][/path]` URL
+pub(crate) struct UrlParts {
+	pub url: String,
+	pub https: bool,
+	pub host: String,
+	pub port: u16,
+	pub path: String,
+}
+
+impl UrlParts {
+	pub fn parse(url: &str) -> Result<UrlParts, Error> {
+		let (https, rest) = match url.find("://") {
+			Some(i) if &url[..i] == "http" => (false, &url[i + 3..]),
+			Some(i) if &url[..i] == "https" => (true, &url[i + 3..]),
+			_ => return Err(ErrorKind::Uri.into()),
+		};
+		let (authority, path) = match rest.find('/') {
+			Some(i) => (&rest[..i], &rest[i..]),
+			None => (rest, "/"),
+		};
+		let (host, port) = match authority.rfind(':') {
+			Some(i) => {
+				let port = authority[i + 1..]
+					.parse::<u16>()
+					.map_err(|_| ErrorKind::ClientCallback(format!("Invalid port in {}", url)))?;
+				(&authority[..i], port)
+			}
+			None if https => (authority, 443),
+			None => (authority, 80),
+		};
+		Ok(UrlParts {
+			url: url.to_owned(),
+			https,
+			host: host.to_owned(),
+			port,
+			path: path.to_owned(),
+		})
+	}
+}
+
+/// Post a json `body` over a connection to the other wallet, closed once
+/// answered, returning the body of the response
+pub(crate) fn post_over<S>(stream: &mut S, url: &UrlParts, body: &str) -> Result<String, Error>
+where
+	S: Read + Write,
+{
+	let request = format!(
+		"POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+		 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+		url.path,
+		url.host,
+		body.len(),
+		body
+	);
+	let mut response = vec![];
+	let res = stream
+		.write_all(request.as_bytes())
+		.and_then(|_| stream.read_to_end(&mut response));
+	match res {
+		// TLS peers end the response with a close notify alert
+		Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted && !response.is_empty() => {}
+		r => {
+			r.map_err(client_error(&format!("Posting to {}", url.url)))?;
+		}
+	}
+	let response = String::from_utf8_lossy(&response);
+
+	let split = response
+		.find("\r\n\r\n")
+		.ok_or_else(|| ErrorKind::ClientCallback(format!("Truncated response from {}", url.url)))?;
+	let (head, body) = (&response[..split], &response[split + 4..]);
+	let mut lines = head.lines();
+	let status = lines
+		.next()
+		.and_then(|l| l.split_whitespace().nth(1))
+		.and_then(|s| s.parse::<u16>().ok())
+		.unwrap_or(0);
+	if !(200..300).contains(&status) {
+		return Err(ErrorKind::ClientCallback(format!(
+			"{} answered {}",
+			url.url,
+			head.lines().next().unwrap_or("nothing")
+		))
+		.into());
+	}
+	let chunked = lines.any(|l| {
+		let l = l.to_lowercase();
+		l.starts_with("transfer-encoding:") && l.contains("chunked")
+	});
+	if chunked {
+		dechunk(body).ok_or_else(|| {
+			ErrorKind::ClientCallback(format!("Invalid chunked response from {}", url.url)).into()
+		})
+	} else {
+		Ok(body.to_owned())
+	}
+}
+
+/// Body of a chunked HTTP response
+fn dechunk(mut body: &str) -> Option<String> {
+	let mut content = String::new();
+	loop {
+		let i = body.find("\r\n")?;
+		let size = body[..i].split(';').next()?.trim();
+		let size = usize::from_str_radix(size, 16).ok()?;
+		if size == 0 {
+			return Some(content);
+		}
+		let chunk = body.get(i + 2..i + 2 + size)?;
+		content.push_str(chunk);
+		body = body.get(i + 4 + size..)?;
+	}
+}
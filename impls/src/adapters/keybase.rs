@@ -16,269 +16,587 @@
 // Keybase Wallet Plugin
 
 use crate::config::WalletConfig;
-use crate::libwallet::api_impl::foreign;
+use crate::grinrelay::receive_tx_with_proof;
 use crate::libwallet::{Error, ErrorKind, Slate, TxProof};
+use crate::util::Mutex;
 use crate::{instantiate_wallet, HTTPNodeClient, WalletCommAdapter};
 use failure::ResultExt;
 use serde::Serialize;
-use serde_json::{from_str, json, to_string, Value};
+use serde_json::de::IoRead;
+use serde_json::{json, to_string, Deserializer, StreamDeserializer, Value};
 use std::collections::{HashMap, HashSet};
-use std::process::{Command, Stdio};
-use std::str::from_utf8;
-use std::thread::sleep;
+use std::io::{BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant};
 
 const TTL: u16 = 60; // TODO: Pass this as a parameter
-const LISTEN_SLEEP_DURATION: Duration = Duration::from_millis(5000);
-const POLL_SLEEP_DURATION: Duration = Duration::from_millis(1000);
 
 // Which topic names to use for communication
 const SLATE_NEW: &str = "grin_slate_new";
 const SLATE_SIGNED: &str = "grin_slate_signed";
 
-#[derive(Clone)]
-pub struct KeybaseWalletCommAdapter {}
-
-impl KeybaseWalletCommAdapter {
-	/// Check if keybase is installed and return an adapter object.
-	pub fn new() -> Box<WalletCommAdapter> {
-		let mut proc = if cfg!(target_os = "windows") {
-			Command::new("where")
-		} else {
-			Command::new("which")
-		};
-		proc.arg("keybase")
-			.stdout(Stdio::null())
-			.status()
-			.expect("Keybase executable not found, make sure it is installed and in your PATH");
+/// A text message of a keybase chat channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeybaseMessage {
+	/// Id of the message in its channel
+	pub id: u64,
+	/// Channel name, the comma separated usernames of its members
+	pub channel: String,
+	/// Topic of the channel
+	pub topic: String,
+	/// Username of the sender
+	pub sender: String,
+	/// Message text
+	pub body: String,
+}
 
-		Box::new(KeybaseWalletCommAdapter {})
+impl KeybaseMessage {
+	/// Read a message out of an `api-listen` notification, or of a `read`
+	/// result entry. Anything but a text message is skipped.
+	fn from_json(value: &Value) -> Option<KeybaseMessage> {
+		if value.get("type").map_or(false, |t| t != "chat") {
+			return None;
+		}
+		let msg = &value["msg"];
+		if msg["content"]["type"] != "text" {
+			return None;
+		}
+		Some(KeybaseMessage {
+			id: msg["id"].as_u64().unwrap_or(0),
+			channel: msg["channel"]["name"].as_str()?.to_owned(),
+			topic: msg["channel"]["topic_name"]
+				.as_str()
+				.unwrap_or("")
+				.to_owned(),
+			sender: msg["sender"]["username"].as_str().unwrap_or("").to_owned(),
+			body: msg["content"]["text"]["body"].as_str()?.to_owned(),
+		})
 	}
 }
 
-/// Send a json object to the keybase process. Type `keybase chat api --help` for a list of available methods.
-fn api_send(payload: &str) -> Result<Value, Error> {
-	let mut proc = Command::new("keybase");
-	proc.args(&["chat", "api", "-m", &payload]);
-	let output = proc.output().expect("No output");
-	if !output.status.success() {
-		error!(
-			"keybase api fail: {} {}",
-			String::from_utf8_lossy(&output.stdout),
-			String::from_utf8_lossy(&output.stderr)
-		);
-		Err(ErrorKind::GenericError("keybase api fail".to_owned()))?
-	} else {
-		let response: Value =
-			from_str(from_utf8(&output.stdout).expect("Bad output")).expect("Bad output");
-		let err_msg = format!("{}", response["error"]["message"]);
-		if err_msg.len() > 0 && err_msg != "null" {
-			error!("api_send got error: {}", err_msg);
+/// The `keybase chat api` process, answering a JSON request per JSON response
+struct ChatProcess {
+	child: Child,
+	stdin: ChildStdin,
+	stdout: StreamDeserializer<'static, IoRead<BufReader<ChildStdout>>, Value>,
+}
+
+impl ChatProcess {
+	fn call(&mut self, request: &Value) -> Result<Value, Error> {
+		let fail = |e: String| ErrorKind::GenericError(format!("keybase api fail: {}", e));
+		let mut line = to_string(request).map_err(|e| fail(e.to_string()))?;
+		line.push('\n');
+		self.stdin
+			.write_all(line.as_bytes())
+			.and_then(|_| self.stdin.flush())
+			.map_err(|e| fail(e.to_string()))?;
+		match self.stdout.next() {
+			Some(Ok(response)) => Ok(response),
+			Some(Err(e)) => Err(fail(e.to_string()).into()),
+			None => Err(fail("keybase exited".to_owned()).into()),
 		}
+	}
+}
 
-		Ok(response)
+impl Drop for ChatProcess {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
 	}
 }
 
-/// Get keybase username
-fn whoami() -> Result<String, Error> {
-	let mut proc = Command::new("keybase");
-	proc.args(&["status", "-json"]);
-	let output = proc.output().expect("No output");
-	if !output.status.success() {
-		error!(
-			"keybase api fail: {} {}",
-			String::from_utf8_lossy(&output.stdout),
-			String::from_utf8_lossy(&output.stderr)
-		);
-		Err(ErrorKind::GenericError("keybase api fail".to_owned()))?
-	} else {
-		let response: Value =
-			from_str(from_utf8(&output.stdout).expect("Bad output")).expect("Bad output");
-		let err_msg = format!("{}", response["error"]["message"]);
-		if err_msg.len() > 0 && err_msg != "null" {
-			error!("status query got error: {}", err_msg);
+/// Messages pushed by a `keybase chat api-listen` process, which is stopped
+/// on drop
+pub struct KeybaseListener {
+	child: Child,
+	messages: Receiver<KeybaseMessage>,
+}
+
+impl KeybaseListener {
+	/// Wait for the next message, `None` once keybase stopped
+	pub fn recv(&self) -> Option<KeybaseMessage> {
+		self.messages.recv().ok()
+	}
+
+	/// Wait for the next message, at most for `timeout`
+	pub fn recv_timeout(&self, timeout: Duration) -> Result<KeybaseMessage, RecvTimeoutError> {
+		self.messages.recv_timeout(timeout)
+	}
+}
+
+impl Drop for KeybaseListener {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+/// Client of the keybase chat JSON API. The requests go through a single
+/// long-running `keybase chat api` process, and the incoming messages are
+/// pushed by `keybase chat api-listen`.
+pub struct KeybaseApi {
+	/// Keybase executable, or a stand-in speaking the same protocol
+	program: String,
+	chat: Mutex<Option<ChatProcess>>,
+}
+
+impl KeybaseApi {
+	/// Client running the `program` keybase executable
+	pub fn new(program: &str) -> KeybaseApi {
+		KeybaseApi {
+			program: program.to_owned(),
+			chat: Mutex::new(None),
 		}
+	}
 
-		let username = response["Username"].as_str();
-		if let Some(s) = username {
-			Ok(s.to_string())
-		} else {
-			error!("keybase username query fail");
-			Err(ErrorKind::GenericError(
-				"keybase username query fail".to_owned(),
-			))?
+	fn command(&self, args: &[&str]) -> Command {
+		let mut cmd = Command::new(&self.program);
+		cmd.args(args).stdin(Stdio::piped()).stdout(Stdio::piped());
+		cmd
+	}
+
+	fn spawn(&self, args: &[&str]) -> Result<(Child, ChildStdin, ChildStdout), Error> {
+		let mut child = self.command(args).spawn().map_err(|e| {
+			ErrorKind::GenericError(format!("failed to start {}: {}", self.program, e))
+		})?;
+		let stdin = child.stdin.take().unwrap();
+		let stdout = child.stdout.take().unwrap();
+		Ok((child, stdin, stdout))
+	}
+
+	/// Send a json request to the keybase chat API. Type `keybase chat api --help`
+	/// for a list of available methods. The keybase process is started again
+	/// on the next request when it fails.
+	pub fn call(&self, request: &Value) -> Result<Value, Error> {
+		let mut chat = self.chat.lock();
+		if chat.is_none() {
+			let (child, stdin, stdout) = self.spawn(&["chat", "api"])?;
+			*chat = Some(ChatProcess {
+				child,
+				stdin,
+				stdout: Deserializer::from_reader(BufReader::new(stdout)).into_iter(),
+			});
+		}
+		let response = chat.as_mut().unwrap().call(request);
+		match response {
+			Ok(response) => {
+				let err_msg = format!("{}", response["error"]["message"]);
+				if err_msg.len() > 0 && err_msg != "null" {
+					error!("keybase api got error: {}", err_msg);
+				}
+				Ok(response)
+			}
+			Err(e) => {
+				error!("{}", e);
+				*chat = None;
+				Err(e)
+			}
 		}
 	}
-}
 
-/// Get all unread messages from a specific channel/topic and mark as read.
-fn read_from_channel(channel: &str, topic: &str) -> Result<Vec<String>, Error> {
-	let payload = to_string(&json!({
-		"method": "read",
-		"params": {
-			"options": {
-				"channel": {
+	/// Start listening for the messages of all the channels, dev topics included
+	pub fn listen(&self) -> Result<KeybaseListener, Error> {
+		let (child, stdin, stdout) = self.spawn(&["chat", "api-listen", "--dev"])?;
+		drop(stdin);
+		let (tx, messages) = channel();
+		thread::Builder::new()
+			.name("keybase-listen".to_owned())
+			.spawn(move || {
+				let stream = Deserializer::from_reader(BufReader::new(stdout)).into_iter::<Value>();
+				for notification in stream {
+					match notification {
+						Ok(n) => {
+							if let Some(msg) = KeybaseMessage::from_json(&n) {
+								if tx.send(msg).is_err() {
+									break;
+								}
+							}
+						}
+						Err(e) => {
+							if !e.is_eof() {
+								error!("keybase api-listen sent a bad notification: {}", e);
+							}
+							break;
+						}
+					}
+				}
+			})
+			.context(ErrorKind::GenericError(
+				"keybase listen thread failed to start".to_owned(),
+			))?;
+		Ok(KeybaseListener { child, messages })
+	}
+
+	/// Get keybase username
+	pub fn whoami(&self) -> Result<String, Error> {
+		let output = self
+			.command(&["status", "-json"])
+			.stdin(Stdio::null())
+			.output()
+			.map_err(|e| ErrorKind::GenericError(format!("keybase status fail: {}", e)))?;
+		if !output.status.success() {
+			error!(
+				"keybase api fail: {} {}",
+				String::from_utf8_lossy(&output.stdout),
+				String::from_utf8_lossy(&output.stderr)
+			);
+			return Err(ErrorKind::GenericError("keybase api fail".to_owned()).into());
+		}
+		let response: Value = serde_json::from_slice(&output.stdout)
+			.map_err(|e| ErrorKind::GenericError(format!("keybase status fail: {}", e)))?;
+		match response["Username"].as_str() {
+			Some(s) => Ok(s.to_string()),
+			None => {
+				error!("keybase username query fail");
+				Err(ErrorKind::GenericError(
+					"keybase username query fail".to_owned(),
+				))?
+			}
+		}
+	}
+
+	/// Get all unread messages from a specific channel/topic and mark as read.
+	fn read_from_channel(&self, channel: &str, topic: &str) -> Result<Vec<KeybaseMessage>, Error> {
+		let res = self.call(&json!({
+			"method": "read",
+			"params": {
+				"options": {
+					"channel": {
 						"name": channel, "topic_type": "dev", "topic_name": topic
 					},
 					"unread_only": true, "peek": false
 				},
 			}
-		}
-	))
-	.unwrap();
-
-	let response = api_send(&payload);
-	if let Ok(res) = response {
-		let mut unread: Vec<String> = Vec::new();
+		}))?;
+		let mut unread = vec![];
 		for msg in res["result"]["messages"]
 			.as_array()
-			.unwrap_or(&vec![json!({})])
+			.unwrap_or(&vec![])
 			.iter()
 		{
-			if (msg["msg"]["content"]["type"] == "text") && (msg["msg"]["unread"] == true) {
-				let message = msg["msg"]["content"]["text"]["body"].as_str().unwrap_or("");
-				unread.push(message.to_owned());
+			if msg["msg"]["unread"] == true {
+				if let Some(m) = KeybaseMessage::from_json(msg) {
+					unread.push(m);
+				}
 			}
 		}
 		Ok(unread)
-	} else {
-		Err(ErrorKind::GenericError("keybase api fail".to_owned()))?
 	}
-}
 
-/// Get unread messages from all channels and mark as read.
-fn get_unread(topic: &str) -> Result<HashMap<String, String>, Error> {
-	let payload = to_string(&json!({
-		"method": "list",
-		"params": {
-			"options": {
-				"topic_type": "dev",
-			},
-		}
-	}))
-	.unwrap();
-	let response = api_send(&payload);
-
-	if let Ok(res) = response {
+	/// Get unread messages from all channels of the topic and mark as read.
+	pub fn get_unread(&self, topic: &str) -> Result<Vec<KeybaseMessage>, Error> {
+		let res = self.call(&json!({
+			"method": "list",
+			"params": {
+				"options": {
+					"topic_type": "dev",
+				},
+			}
+		}))?;
 		let mut channels = HashSet::new();
 		// Unfortunately the response does not contain the message body
 		// and a separate call is needed for each channel
 		for msg in res["result"]["conversations"]
 			.as_array()
-			.unwrap_or(&vec![json!({})])
+			.unwrap_or(&vec![])
 			.iter()
 		{
 			if (msg["unread"] == true) && (msg["channel"]["topic_name"] == topic) {
-				let channel = msg["channel"]["name"].as_str().unwrap();
-				channels.insert(channel.to_string());
+				if let Some(channel) = msg["channel"]["name"].as_str() {
+					channels.insert(channel.to_string());
+				}
 			}
 		}
-		let mut unread: HashMap<String, String> = HashMap::new();
+		let mut unread = vec![];
 		for channel in channels.iter() {
-			let messages = read_from_channel(channel, topic);
-			if messages.is_err() {
-				break;
-			}
-			for msg in messages.unwrap() {
-				unread.insert(msg, channel.to_string());
-			}
+			unread.extend(self.read_from_channel(channel, topic)?);
 		}
 		Ok(unread)
-	} else {
-		Err(ErrorKind::GenericError("keybase api fail".to_owned()))?
 	}
-}
 
-/// Send a message to a keybase channel that self-destructs after ttl seconds.
-fn send<T: Serialize>(message: T, channel: &str, topic: &str, ttl: u16) -> bool {
-	let seconds = format!("{}s", ttl);
-	let serialized = to_string(&message).unwrap();
-	let payload = to_string(&json!({
-		"method": "send",
-		"params": {
-			"options": {
-				"channel": {
-					"name": channel, "topic_name": topic, "topic_type": "dev"
-				},
-				"message": {
-					"body": serialized
-				},
-				"exploding_lifetime": seconds
+	/// Mark a message pushed by `api-listen` as read
+	pub fn mark_read(&self, msg: &KeybaseMessage) -> Result<(), Error> {
+		self.call(&json!({
+			"method": "mark",
+			"params": {
+				"options": {
+					"channel": {
+						"name": msg.channel, "topic_type": "dev", "topic_name": msg.topic
+					},
+					"message_id": msg.id
+				}
 			}
-		}
-	}))
-	.unwrap();
-	let response = api_send(&payload);
-	if let Ok(res) = response {
-		match res["result"]["message"].as_str() {
-			Some("message sent") => {
+		}))?;
+		Ok(())
+	}
+
+	/// Send a message to a keybase channel that self-destructs after ttl seconds.
+	pub fn send<T: Serialize>(&self, message: T, channel: &str, topic: &str, ttl: u16) -> bool {
+		let seconds = format!("{}s", ttl);
+		let serialized = to_string(&message).unwrap();
+		let response = self.call(&json!({
+			"method": "send",
+			"params": {
+				"options": {
+					"channel": {
+						"name": channel, "topic_name": topic, "topic_type": "dev"
+					},
+					"message": {
+						"body": serialized
+					},
+					"exploding_lifetime": seconds
+				}
+			}
+		}));
+		match response {
+			Ok(ref res) if res["result"]["message"] == "message sent" => {
 				debug!("Message sent to {}: {}", channel, serialized);
 				true
 			}
 			_ => false,
 		}
-	} else {
-		false
 	}
-}
 
-/// Send a notify to self that self-destructs after ttl minutes.
-fn notify(message: &str, channel: &str, ttl: u16) -> bool {
-	let minutes = format!("{}m", ttl);
-	let payload = to_string(&json!({
-		"method": "send",
-		"params": {
-			"options": {
-				"channel": {
-					"name": channel
-				},
-				"message": {
-					"body": message
-				},
-				"exploding_lifetime": minutes
+	/// Send a notify to self that self-destructs after ttl minutes.
+	fn notify(&self, message: &str, channel: &str, ttl: u16) -> bool {
+		let minutes = format!("{}m", ttl);
+		let response = self.call(&json!({
+			"method": "send",
+			"params": {
+				"options": {
+					"channel": {
+						"name": channel
+					},
+					"message": {
+						"body": message
+					},
+					"exploding_lifetime": minutes
+				}
 			}
+		}));
+		match response {
+			Ok(ref res) => res["result"]["message"] == "message sent",
+			Err(_) => false,
 		}
-	}))
-	.unwrap();
-	let response = api_send(&payload);
-	if let Ok(res) = response {
-		match res["result"]["message"].as_str() {
-			Some("message sent") => true,
-			_ => false,
+	}
+
+	/// Notify in keybase on receiving a transaction
+	fn notify_on_receive(&self, keybase_notify_ttl: u16, channel: &str, tx_uuid: String) {
+		if keybase_notify_ttl == 0 {
+			return;
+		}
+		let username = match self.whoami() {
+			Ok(username) => username,
+			Err(_) => {
+				error!("keybase notification fail on whoami query");
+				return;
+			}
+		};
+		let vec: Vec<&str> = channel.split(",").collect();
+		if vec.len() > 1 {
+			let receiver = username;
+			let sender = if vec[0] == receiver {
+				vec[1]
+			} else {
+				if vec[1] != receiver {
+					error!(
+						"keybase - channel doesn't include my username! channel: {}, username: {}",
+						channel, receiver
+					);
+				}
+				vec[0]
+			};
+
+			let msg = format!(
+				"[grin wallet notice]: \
+				 you could have some coins received from @{}\n\
+				 Transaction Id: {}",
+				sender, tx_uuid
+			);
+			self.notify(&msg, &receiver, keybase_notify_ttl);
+			info!(
+				"tx from @{} is done, please check on grin wallet. tx uuid: {}",
+				sender, tx_uuid,
+			);
 		}
-	} else {
-		false
 	}
 }
 
-/// Listen for a message from a specific channel with topic SLATE_SIGNED for nseconds and return the first valid slate.
-fn poll(nseconds: u64, channel: &str) -> Option<Slate> {
-	let start = Instant::now();
-	info!("Waiting for response message from @{}...", channel);
-	while start.elapsed().as_secs() < nseconds {
-		let unread = read_from_channel(channel, SLATE_SIGNED);
-		for msg in unread.unwrap().iter() {
-			let blob = Slate::deserialize_upgrade(&msg);
-			match blob {
-				Ok(slate) => {
-					let slate: Slate = slate.into();
-					info!(
-						"keybase response message received from @{}, tx uuid: {}",
-						channel, slate.id,
+#[derive(Clone)]
+pub struct KeybaseWalletCommAdapter {
+	api: Arc<KeybaseApi>,
+}
+
+impl KeybaseWalletCommAdapter {
+	/// Check if keybase is installed and return an adapter object.
+	pub fn new() -> Box<WalletCommAdapter> {
+		let mut proc = if cfg!(target_os = "windows") {
+			Command::new("where")
+		} else {
+			Command::new("which")
+		};
+		proc.arg("keybase")
+			.stdout(Stdio::null())
+			.status()
+			.expect("Keybase executable not found, make sure it is installed and in your PATH");
+
+		Box::new(KeybaseWalletCommAdapter::with_api(KeybaseApi::new(
+			"keybase",
+		)))
+	}
+
+	/// Adapter talking to keybase through `api`
+	pub fn with_api(api: KeybaseApi) -> KeybaseWalletCommAdapter {
+		KeybaseWalletCommAdapter { api: Arc::new(api) }
+	}
+
+	/// Receive the slates sent over keybase with `receive`, called with the
+	/// channel and the slate, replying with the signed slates, until keybase
+	/// stops. The messages left unread while the wallet was not listening are
+	/// received first. The messages sent by our own user, which `api-listen`
+	/// pushes too, are skipped.
+	pub fn receive_loop<F>(&self, mut receive: F, keybase_notify_ttl: u16) -> Result<(), Error>
+	where
+		F: FnMut(&str, &Slate) -> Result<Slate, Error>,
+	{
+		let username = self.api.whoami()?;
+		// listen first, for no message to fall in between
+		let listener = self.api.listen()?;
+		info!("Listening for transactions on keybase ...");
+		for msg in self.api.get_unread(SLATE_NEW)? {
+			if msg.sender == username {
+				continue;
+			}
+			self.receive_message(&mut receive, &msg, keybase_notify_ttl)?;
+		}
+		while let Some(msg) = listener.recv() {
+			if msg.topic != SLATE_NEW || msg.sender == username {
+				continue;
+			}
+			let res = self.receive_message(&mut receive, &msg, keybase_notify_ttl);
+			if let Err(e) = self.api.mark_read(&msg) {
+				debug!("Failed to mark keybase message {} as read: {}", msg.id, e);
+			}
+			res?;
+		}
+		error!("Listening exited for some keybase api failure");
+		Err(ErrorKind::GenericError(
+			"keybase api-listen stopped".to_owned(),
+		))?
+	}
+
+	/// Receive the slate of a message into the wallet, and reply with the
+	/// signed slate
+	fn receive_message<F>(
+		&self,
+		receive: &mut F,
+		msg: &KeybaseMessage,
+		keybase_notify_ttl: u16,
+	) -> Result<(), Error>
+	where
+		F: FnMut(&str, &Slate) -> Result<Slate, Error>,
+	{
+		let channel = &msg.channel;
+		let slate: Slate = match Slate::deserialize_upgrade(&msg.body) {
+			Ok(s) => s.into(),
+			Err(_) => {
+				debug!("Failed to deserialize keybase message: {}", msg.body);
+				return Ok(());
+			}
+		};
+		let tx_uuid = slate.id;
+
+		// Reject multiple recipients channel for safety
+		if channel.matches(",").count() > 1 {
+			error!(
+				"Incoming tx initiated on channel \"{}\" is rejected, multiple recipients channel! amount: {}(g), tx uuid: {}",
+				channel,
+				slate.amount as f64 / 1000000000.0,
+				tx_uuid,
+			);
+			return Ok(());
+		}
+
+		info!(
+			"tx initiated on channel \"{}\", to send you {}(g). tx uuid: {}",
+			channel,
+			slate.amount as f64 / 1000000000.0,
+			tx_uuid,
+		);
+		if let Err(e) = slate.verify_messages() {
+			error!(
+				"Error validating participant messages: {}. Incoming tx failed",
+				e
+			);
+			return Ok(());
+		}
+		match receive(channel, &slate) {
+			// Reply to the same channel with topic SLATE_SIGNED
+			Ok(s) => {
+				if self.api.send(s, channel, SLATE_SIGNED, TTL) {
+					self.api
+						.notify_on_receive(keybase_notify_ttl, channel, tx_uuid.to_string());
+					debug!("Returned slate to @{} via keybase", channel);
+				} else {
+					error!(
+						"Failed to return slate to @{} via keybase. Incoming tx failed",
+						channel
 					);
-					return Some(slate);
 				}
-				Err(_) => (),
+			}
+			Err(e) => {
+				error!(
+					"Error on receiving tx via keybase: {}. Incoming tx failed",
+					e
+				);
 			}
 		}
-		sleep(POLL_SLEEP_DURATION);
+		Ok(())
+	}
+
+	/// Wait for a message from a specific channel with topic SLATE_SIGNED for
+	/// nseconds and return the first valid slate not sent by `username`, our
+	/// own user.
+	fn poll(
+		&self,
+		listener: &KeybaseListener,
+		nseconds: u64,
+		channel: &str,
+		username: &str,
+	) -> Option<Slate> {
+		let deadline = Instant::now() + Duration::from_secs(nseconds);
+		info!("Waiting for response message from @{}...", channel);
+		loop {
+			let now = Instant::now();
+			if now >= deadline {
+				break;
+			}
+			let msg = match listener.recv_timeout(deadline - now) {
+				Ok(msg) => msg,
+				Err(_) => break,
+			};
+			if msg.channel != channel || msg.topic != SLATE_SIGNED || msg.sender == username {
+				continue;
+			}
+			if let Ok(slate) = Slate::deserialize_upgrade(&msg.body) {
+				let slate: Slate = slate.into();
+				info!(
+					"keybase response message received from @{}, tx uuid: {}",
+					channel, slate.id,
+				);
+				let _ = self.api.mark_read(&msg);
+				return Some(slate);
+			}
+		}
+		error!(
+			"No response from @{} in {} seconds. Grin send failed!",
+			channel, nseconds
+		);
+		None
 	}
-	error!(
-		"No response from @{} in {} seconds. Grin send failed!",
-		channel, nseconds
-	);
-	None
 }
 
 impl WalletCommAdapter for KeybaseWalletCommAdapter {
@@ -295,9 +613,13 @@ impl WalletCommAdapter for KeybaseWalletCommAdapter {
 		}
 
 		let id = slate.id;
+		let username = self.api.whoami()?;
+
+		// Listen before sending, for the response not to be missed
+		let listener = self.api.listen()?;
 
 		// Send original slate to recipient with the SLATE_NEW topic
-		match send(&slate, addr, SLATE_NEW, TTL) {
+		match self.api.send(&slate, addr, SLATE_NEW, TTL) {
 			true => (),
 			false => {
 				return Err(ErrorKind::ClientCallback(
@@ -307,7 +629,7 @@ impl WalletCommAdapter for KeybaseWalletCommAdapter {
 		}
 		info!("tx request has been sent to @{}, tx uuid: {}", addr, id);
 		// Wait for response from recipient with SLATE_SIGNED topic
-		match poll(TTL as u64, addr) {
+		match self.poll(&listener, TTL as u64, addr, &username) {
 			Some(slate) => return Ok((slate, None)),
 			None => {
 				return Err(ErrorKind::ClientCallback(
@@ -319,16 +641,20 @@ impl WalletCommAdapter for KeybaseWalletCommAdapter {
 
 	/// Send a transaction asynchronously (result will be returned via the listener)
 	fn send_tx_async(&self, _addr: &str, _slate: &Slate) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The keybase transport only sends synchronously".to_owned(),
+		))?
 	}
 
 	/// Receive a transaction async. (Actually just read it from wherever and return the slate)
 	fn receive_tx_async(&self, _params: &str) -> Result<Slate, Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The keybase transport has no slate to read, slates come in through its listener"
+				.to_owned(),
+		))?
 	}
 
 	/// Start a listener, passing received messages to the wallet api directly
-	#[allow(unreachable_code)]
 	fn listen(
 		&self,
 		_params: HashMap<String, String>,
@@ -340,120 +666,13 @@ impl WalletCommAdapter for KeybaseWalletCommAdapter {
 		let node_client = HTTPNodeClient::new(&config.check_node_api_http_addr, node_api_secret);
 		let wallet = instantiate_wallet(config.clone(), node_client, passphrase, account)
 			.context(ErrorKind::WalletSeedDecryption)?;
-
-		info!("Listening for transactions on keybase ...");
-		loop {
-			// listen for messages from all channels with topic SLATE_NEW
-			let unread = get_unread(SLATE_NEW);
-			if unread.is_err() {
-				error!("Listening exited for some keybase api failure");
-				break;
-			}
-			for (msg, channel) in &unread.unwrap() {
-				let blob = Slate::deserialize_upgrade(&msg);
-				match blob {
-					Ok(message) => {
-						let slate: Slate = message.clone().into();
-						let tx_uuid = slate.id;
-
-						// Reject multiple recipients channel for safety
-						{
-							if channel.matches(",").count() > 1 {
-								error!(
-									"Incoming tx initiated on channel \"{}\" is rejected, multiple recipients channel! amount: {}(g), tx uuid: {}",
-									channel,
-									slate.amount as f64 / 1000000000.0,
-									tx_uuid,
-								);
-								continue;
-							}
-						}
-
-						info!(
-							"tx initiated on channel \"{}\", to send you {}(g). tx uuid: {}",
-							channel,
-							slate.amount as f64 / 1000000000.0,
-							tx_uuid,
-						);
-						if let Err(e) = slate.verify_messages() {
-							error!("Error validating participant messages: {}", e);
-							return Err(e);
-						}
-						let res = {
-							let mut w = wallet.lock();
-							w.open_with_credentials()?;
-							let r = foreign::receive_tx(&mut *w, &slate, None, None, None, false);
-							w.close()?;
-							r
-						};
-						match res {
-							// Reply to the same channel with topic SLATE_SIGNED
-							Ok(s) => {
-								let success = send(s, channel, SLATE_SIGNED, TTL);
-
-								if success {
-									notify_on_receive(
-										config.keybase_notify_ttl.unwrap_or(1440),
-										channel.to_string(),
-										tx_uuid.to_string(),
-									);
-									debug!("Returned slate to @{} via keybase", channel);
-								} else {
-									error!("Failed to return slate to @{} via keybase. Incoming tx failed", channel);
-								}
-							}
-
-							Err(e) => {
-								error!(
-									"Error on receiving tx via keybase: {}. Incoming tx failed",
-									e
-								);
-							}
-						}
-					}
-					Err(_) => debug!("Failed to deserialize keybase message: {}", msg),
-				}
-			}
-			sleep(LISTEN_SLEEP_DURATION);
-		}
-		Ok(())
-	}
-}
-
-/// Notify in keybase on receiving a transaction
-fn notify_on_receive(keybase_notify_ttl: u16, channel: String, tx_uuid: String) {
-	if keybase_notify_ttl > 0 {
-		let my_username = whoami();
-		if let Ok(username) = my_username {
-			let split = channel.split(",");
-			let vec: Vec<&str> = split.collect();
-			if vec.len() > 1 {
-				let receiver = username;
-				let sender = if vec[0] == receiver {
-					vec[1]
-				} else {
-					if vec[1] != receiver {
-						error!("keybase - channel doesn't include my username! channel: {}, username: {}",
-							   channel, receiver
-						);
-					}
-					vec[0]
-				};
-
-				let msg = format!(
-					"[grin wallet notice]: \
-					 you could have some coins received from @{}\n\
-					 Transaction Id: {}",
-					sender, tx_uuid
-				);
-				notify(&msg, &receiver, keybase_notify_ttl);
-				info!(
-					"tx from @{} is done, please check on grin wallet. tx uuid: {}",
-					sender, tx_uuid,
-				);
-			}
-		} else {
-			error!("keybase notification fail on whoami query");
-		}
+		let receive = |_: &str, slate: &Slate| {
+			let mut w = wallet.lock();
+			w.open_with_credentials()?;
+			let res = receive_tx_with_proof(&mut *w, slate, None, None, None, None, false);
+			w.close()?;
+			res
+		};
+		self.receive_loop(receive, config.keybase_notify_ttl.unwrap_or(1440))
 	}
 }
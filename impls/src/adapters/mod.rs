@@ -13,17 +13,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod dir;
 mod file;
 mod grinrelay;
 mod http;
 mod keybase;
 mod null;
+mod registry;
+mod tls;
+mod tor;
 
+pub use self::dir::{DirConfig, DirScan, DirStage, DirTransport, DirWalletCommAdapter};
 pub use self::file::FileWalletCommAdapter;
 pub use self::grinrelay::GrinrelayWalletCommAdapter;
 pub use self::http::HTTPWalletCommAdapter;
-pub use self::keybase::KeybaseWalletCommAdapter;
+pub use self::keybase::{KeybaseApi, KeybaseListener, KeybaseMessage, KeybaseWalletCommAdapter};
 pub use self::null::NullWalletCommAdapter;
+pub use self::registry::{Transport, TransportCapabilities, TransportFactory, TransportRegistry};
+pub use self::tls::{tls_post, HttpTlsConfig, HttpTransportConfig};
+pub use self::tor::{onion_service_key, socks5_connect, socks_post, TorConfig, TorControl};
 
 use crate::config::WalletConfig;
 use crate::libwallet::{Error, Slate, TxProof};
@@ -15,7 +15,7 @@
 
 use crate::config::WalletConfig;
 /// Null Output 'plugin' implementation
-use crate::libwallet::{Error, Slate, TxProof};
+use crate::libwallet::{Error, ErrorKind, Slate, TxProof};
 use crate::WalletCommAdapter;
 
 use std::collections::HashMap;
@@ -44,7 +44,9 @@ impl WalletCommAdapter for NullWalletCommAdapter {
 	}
 
 	fn receive_tx_async(&self, _params: &str) -> Result<Slate, Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The null transport has no slate to read".to_owned(),
+		))?
 	}
 
 	fn listen(
@@ -55,6 +57,8 @@ impl WalletCommAdapter for NullWalletCommAdapter {
 		_account: &str,
 		_node_api_secret: Option<String>,
 	) -> Result<(), Error> {
-		unimplemented!();
+		Err(ErrorKind::GenericError(
+			"The null transport has no listener".to_owned(),
+		))?
 	}
 }
@@ -0,0 +1,329 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Registry of the transports slates are sent over, by method name
+
+use crate::adapters::{
+	DirConfig, DirWalletCommAdapter, FileWalletCommAdapter, HTTPWalletCommAdapter,
+	HttpTransportConfig, KeybaseWalletCommAdapter, NullWalletCommAdapter, TorConfig,
+	WalletCommAdapter,
+};
+use crate::config::TransportConfig;
+use crate::libwallet::{Error, ErrorKind};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Builds the adapter of a transport out of its config section, if any
+pub type TransportFactory = Arc<
+	dyn Fn(Option<&TransportConfig>) -> Result<Box<dyn WalletCommAdapter>, Error> + Send + Sync,
+>;
+
+/// What a transport is able to do
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransportCapabilities {
+	/// Sends a slate and waits for the response
+	pub send_sync: bool,
+	/// Sends a slate, the response coming back another way
+	pub send_async: bool,
+	/// Listens for the incoming slates, as a method of `grin-wallet listen`
+	pub listen: bool,
+}
+
+/// A transport, registered under its method name
+#[derive(Clone)]
+pub struct Transport {
+	/// Method name, as given to `--method`
+	pub name: String,
+	/// URI schemes of the destinations of the transport
+	pub schemes: Vec<String>,
+	/// Whether the destinations are given to the adapter as full URIs, rather
+	/// than without their scheme
+	pub keeps_scheme: bool,
+	/// What the transport is able to do
+	pub capabilities: TransportCapabilities,
+	/// Builds the adapter of the transport
+	pub factory: TransportFactory,
+}
+
+impl Transport {
+	/// New transport, whose destinations are given to the adapter without
+	/// their scheme
+	pub fn new<F>(
+		name: &str,
+		schemes: &[&str],
+		capabilities: TransportCapabilities,
+		factory: F,
+	) -> Transport
+	where
+		F: Fn(Option<&TransportConfig>) -> Result<Box<dyn WalletCommAdapter>, Error>
+			+ Send
+			+ Sync
+			+ 'static,
+	{
+		Transport {
+			name: name.to_owned(),
+			schemes: schemes.iter().map(|s| (*s).to_owned()).collect(),
+			keeps_scheme: false,
+			capabilities,
+			factory: Arc::new(factory),
+		}
+	}
+}
+
+/// The transports known to the wallet, and their config sections
+#[derive(Clone)]
+pub struct TransportRegistry {
+	transports: Vec<Transport>,
+	config: BTreeMap<String, TransportConfig>,
+}
+
+impl Default for TransportRegistry {
+	fn default() -> TransportRegistry {
+		TransportRegistry::new()
+	}
+}
+
+impl TransportRegistry {
+	/// Registry of the transports built in the wallet. The Grin Relay
+	/// transport needs a running relay listener, and has to be registered
+	/// again with it before being used.
+	pub fn new() -> TransportRegistry {
+		let sync = TransportCapabilities {
+			send_sync: true,
+			..Default::default()
+		};
+		let mut registry = TransportRegistry::empty();
+		let mut http = Transport::new(
+			"http",
+			&["http", "https"],
+			TransportCapabilities {
+				listen: true,
+				..sync
+			},
+			|config| {
+				Ok(HTTPWalletCommAdapter::with_config(
+					HttpTransportConfig::from_config(config)?,
+				))
+			},
+		);
+		http.keeps_scheme = true;
+		registry.register(http);
+		registry.register(Transport::new(
+			"file",
+			&["file"],
+			TransportCapabilities {
+				send_async: true,
+				..Default::default()
+			},
+			|_| Ok(FileWalletCommAdapter::new()),
+		));
+		registry.register(Transport::new(
+			"dir",
+			&["dir"],
+			TransportCapabilities {
+				send_async: true,
+				listen: true,
+				..Default::default()
+			},
+			|config| Ok(DirWalletCommAdapter::new(DirConfig::from_config(config)?)),
+		));
+		registry.register(Transport::new(
+			"relay",
+			&["relay", "grinrelay"],
+			TransportCapabilities {
+				listen: true,
+				..sync
+			},
+			|_| {
+				Err(ErrorKind::GenericError(
+					"The relay transport needs a running relay listener".to_owned(),
+				))?
+			},
+		));
+		registry.register(Transport::new(
+			"keybase",
+			&["keybase"],
+			TransportCapabilities {
+				listen: true,
+				..sync
+			},
+			|_| Ok(KeybaseWalletCommAdapter::new()),
+		));
+		registry.register(Transport::new(
+			"tor",
+			&["tor"],
+			TransportCapabilities {
+				listen: true,
+				..sync
+			},
+			|config| {
+				Ok(HTTPWalletCommAdapter::new_tor(TorConfig::from_config(
+					config,
+				)?))
+			},
+		));
+		registry.register(Transport::new("self", &[], sync, |_| {
+			Ok(NullWalletCommAdapter::new())
+		}));
+		registry
+	}
+
+	/// Registry with no transport
+	pub fn empty() -> TransportRegistry {
+		TransportRegistry {
+			transports: vec![],
+			config: BTreeMap::new(),
+		}
+	}
+
+	/// Set the config sections of the transports, by transport name
+	pub fn set_config(&mut self, config: BTreeMap<String, TransportConfig>) {
+		self.config = config;
+	}
+
+	/// Add a transport, in place of the one of the same name if any
+	pub fn register(&mut self, transport: Transport) {
+		match self
+			.transports
+			.iter_mut()
+			.find(|t| t.name == transport.name)
+		{
+			Some(t) => *t = transport,
+			None => self.transports.push(transport),
+		}
+	}
+
+	/// The transport of this method name
+	pub fn get(&self, name: &str) -> Option<&Transport> {
+		self.transports.iter().find(|t| t.name == name)
+	}
+
+	/// Names of the registered transports
+	pub fn names(&self) -> Vec<&str> {
+		self.transports.iter().map(|t| t.name.as_str()).collect()
+	}
+
+	/// Build the adapter of a transport, out of its config section
+	pub fn adapter(&self, name: &str) -> Result<Box<dyn WalletCommAdapter>, Error> {
+		let transport = self.get(name).ok_or_else(|| self.unknown(name))?;
+		(transport.factory)(self.config.get(name))
+	}
+
+	/// The transport of the scheme of a destination URI, if it has one
+	pub fn by_scheme(&self, dest: &str) -> Option<&Transport> {
+		let scheme = split_scheme(dest)?.0;
+		self.transports
+			.iter()
+			.find(|t| t.schemes.iter().any(|s| s == scheme))
+	}
+
+	/// The transport of the `method`, with the destination as expected by
+	/// its adapter. Fails on an unknown method, or on a destination URI of
+	/// another transport.
+	pub fn resolve(&self, method: &str, dest: &str) -> Result<(&Transport, String), Error> {
+		let transport = self.get(method).ok_or_else(|| self.unknown(method))?;
+		let dest = match split_scheme(dest) {
+			Some((scheme, rest)) if transport.schemes.iter().any(|s| s == scheme) => {
+				if transport.keeps_scheme {
+					dest.to_owned()
+				} else {
+					rest.to_owned()
+				}
+			}
+			Some((scheme, _)) => {
+				return Err(ErrorKind::GenericError(format!(
+					"Destination scheme {}:// not supported by the {} method",
+					scheme, method
+				)))?;
+			}
+			None => dest.to_owned(),
+		};
+		Ok((transport, dest))
+	}
+
+	fn unknown(&self, method: &str) -> Error {
+		ErrorKind::GenericError(format!(
+			"Unknown method {}, expected one of {}",
+			method,
+			self.names().join(", ")
+		))
+		.into()
+	}
+}
+
+/// Split a destination URI into its scheme and the rest
+fn split_scheme(dest: &str) -> Option<(&str, &str)> {
+	let i = dest.find("://")?;
+	let scheme = &dest[..i];
+	if scheme.is_empty()
+		|| !scheme
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+	{
+		return None;
+	}
+	Some((scheme, &dest[i + 3..]))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transport_registry() {
+		let mut registry = TransportRegistry::new();
+		assert!(registry.resolve("carrier-pigeon", "bob").is_err());
+
+		let (t, dest) = registry.resolve("http", "https://127.0.0.1:3415").unwrap();
+		assert_eq!(
+			(t.name.as_str(), dest.as_str()),
+			("http", "https://127.0.0.1:3415")
+		);
+		let (t, dest) = registry.resolve("file", "file:///tmp/tx.slate").unwrap();
+		assert_eq!((t.name.as_str(), dest.as_str()), ("file", "/tmp/tx.slate"));
+		let (_, dest) = registry.resolve("file", "tx.slate").unwrap();
+		assert_eq!(dest, "tx.slate");
+		assert!(registry.resolve("file", "relay://tn1-bob").is_err());
+		assert_eq!(registry.by_scheme("relay://tn1-bob").unwrap().name, "relay");
+		assert!(registry.by_scheme("tn1-bob").is_none());
+		assert!(registry.adapter("relay").is_err());
+		let (t, dest) = registry.resolve("tor", "tor://abc.onion:3415").unwrap();
+		assert_eq!((t.name.as_str(), dest.as_str()), ("tor", "abc.onion:3415"));
+		assert!(registry.adapter("tor").unwrap().supports_sync());
+
+		// a transport of our own, reading its config section
+		registry.register(Transport::new(
+			"bus",
+			&["bus"],
+			TransportCapabilities {
+				send_async: true,
+				..Default::default()
+			},
+			|config| match config {
+				Some(c) if c.get("queue").is_some() => Ok(NullWalletCommAdapter::new()),
+				_ => Err(ErrorKind::GenericError("no queue".to_owned()))?,
+			},
+		));
+		let (_, dest) = registry.resolve("bus", "bus://payments/bob").unwrap();
+		assert_eq!(dest, "payments/bob");
+		assert!(registry.adapter("bus").is_err());
+		let mut config = BTreeMap::new();
+		config.insert(
+			"bus".to_owned(),
+			"queue = \"payments\"".parse::<TransportConfig>().unwrap(),
+		);
+		registry.set_config(config);
+		assert!(registry.adapter("bus").is_ok());
+	}
+}
@@ -0,0 +1,302 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! TLS settings of the HTTPS destinations: trusted CAs, certificate pinning
+//! and client certificates for mutual TLS
+
+use crate::adapters::http::{client_error, post_over, UrlParts};
+use crate::config::TransportConfig;
+use crate::libwallet::{Error, ErrorKind};
+use crate::util;
+use ring::digest;
+use rustls::internal::pemfile;
+use rustls::{ClientConfig, ClientSession, Session, StreamOwned};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Config of the HTTP transport, the `[wallet.transports.http]` section
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct HttpTransportConfig {
+	/// TLS settings of the destinations, the `[[wallet.transports.http.tls]]`
+	/// entries
+	pub tls: Option<Vec<HttpTlsConfig>>,
+}
+
+impl HttpTransportConfig {
+	/// Read the config out of the section of the transport, the defaults
+	/// when there is none
+	pub fn from_config(config: Option<&TransportConfig>) -> Result<HttpTransportConfig, Error> {
+		match config {
+			Some(c) => c.clone().try_into().map_err(|e| {
+				ErrorKind::GenericError(format!("Invalid [wallet.transports.http] config: {}", e))
+					.into()
+			}),
+			None => Ok(HttpTransportConfig::default()),
+		}
+	}
+
+	/// TLS settings of a destination URL, from the entry of the longest URL
+	/// it starts with, up to a path or port boundary
+	pub fn tls_for(&self, url: &str) -> Option<&HttpTlsConfig> {
+		let matches = |prefix: &str| {
+			url.starts_with(prefix)
+				&& match url[prefix.len()..].chars().next() {
+					None | Some('/') | Some(':') => true,
+					_ => false,
+				}
+		};
+		self.tls
+			.as_ref()?
+			.iter()
+			.filter(|t| matches(t.url.trim_end_matches('/')))
+			.max_by_key(|t| t.url.trim_end_matches('/').len())
+	}
+}
+
+/// TLS settings of the destinations starting with an URL
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct HttpTlsConfig {
+	/// URL the destinations start with, e.g. `https://wallet.exchange.com`
+	pub url: String,
+	/// PEM bundle of the CAs trusted for the destination, in place of the
+	/// usual web roots
+	pub ca_file: Option<String>,
+	/// SHA-256 fingerprints, in hex, of the certificates accepted for the
+	/// destination, checked on top of the CAs
+	pub pins: Option<Vec<String>>,
+	/// PEM certificate chain presented to the destination, for mutual TLS
+	pub client_cert_file: Option<String>,
+	/// PEM private key of the client certificate, PKCS8 or RSA
+	pub client_key_file: Option<String>,
+	/// Seconds to wait for the destination, 60 if none
+	pub timeout_secs: Option<u64>,
+}
+
+impl HttpTlsConfig {
+	/// Build the TLS client config of the destination
+	pub fn client_config(&self) -> Result<ClientConfig, Error> {
+		let mut config = ClientConfig::new();
+		match self.ca_file {
+			Some(ref file) => {
+				let (valid, _) = config
+					.root_store
+					.add_pem_file(&mut open(file)?)
+					.map_err(|_| tls_error(format!("Invalid CA file {}", file)))?;
+				if valid == 0 {
+					return Err(tls_error(format!("No CA certificate in {}", file)));
+				}
+			}
+			None => config
+				.root_store
+				.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+		}
+		match (&self.client_cert_file, &self.client_key_file) {
+			(Some(cert_file), Some(key_file)) => {
+				let certs = pemfile::certs(&mut open(cert_file)?)
+					.map_err(|_| tls_error(format!("Invalid certificate file {}", cert_file)))?;
+				if certs.is_empty() {
+					return Err(tls_error(format!("No certificate in {}", cert_file)));
+				}
+				let mut keys = pemfile::pkcs8_private_keys(&mut open(key_file)?)
+					.map_err(|_| tls_error(format!("Invalid key file {}", key_file)))?;
+				if keys.is_empty() {
+					keys = pemfile::rsa_private_keys(&mut open(key_file)?)
+						.map_err(|_| tls_error(format!("Invalid key file {}", key_file)))?;
+				}
+				if keys.is_empty() {
+					return Err(tls_error(format!("No private key in {}", key_file)));
+				}
+				config.set_single_client_cert(certs, keys.remove(0));
+			}
+			(None, None) => {}
+			_ => {
+				return Err(tls_error(format!(
+					"Both a client certificate and its key are needed for {}",
+					self.url
+				)));
+			}
+		}
+		Ok(config)
+	}
+
+	/// Check the certificate of the destination against the pins, if any
+	pub fn check_pins(&self, certificate: &[u8]) -> Result<(), Error> {
+		let pins = match self.pins {
+			Some(ref p) if !p.is_empty() => p,
+			_ => return Ok(()),
+		};
+		let fingerprint = util::to_hex(
+			digest::digest(&digest::SHA256, certificate)
+				.as_ref()
+				.to_vec(),
+		);
+		let pinned = pins
+			.iter()
+			.any(|p| p.replace(':', "").to_lowercase() == fingerprint);
+		if !pinned {
+			return Err(tls_error(format!(
+				"The certificate of {}, of fingerprint {}, is not pinned",
+				self.url, fingerprint
+			)));
+		}
+		Ok(())
+	}
+
+	/// Time to wait for the destination
+	pub fn timeout(&self) -> Duration {
+		Duration::from_secs(self.timeout_secs.unwrap_or(60))
+	}
+}
+
+fn tls_error(report: String) -> Error {
+	ErrorKind::ClientCallback(report).into()
+}
+
+fn open(file: &str) -> Result<BufReader<File>, Error> {
+	let f = File::open(file).map_err(client_error(&format!("Opening {}", file)))?;
+	Ok(BufReader::new(f))
+}
+
+/// Post `input` as json to an `https://` URL with the TLS settings of its
+/// destination, returning the body of the response
+pub fn tls_post<IN>(tls: &HttpTlsConfig, url: &str, input: &IN) -> Result<String, Error>
+where
+	IN: Serialize,
+{
+	let parts = UrlParts::parse(url)?;
+	if !parts.https {
+		return Err(tls_error(format!(
+			"TLS settings given for {}, which is not an https:// URL",
+			url
+		)));
+	}
+	let config = Arc::new(tls.client_config()?);
+	let dns_name = webpki::DNSNameRef::try_from_ascii_str(&parts.host).map_err(|_| {
+		tls_error(format!(
+			"Expected a host name in {}, the certificates being checked against it",
+			url
+		))
+	})?;
+	let session = ClientSession::new(&config, dns_name);
+
+	let addr = (parts.host.as_str(), parts.port)
+		.to_socket_addrs()
+		.map_err(client_error(&format!("Resolving {}", parts.host)))?
+		.next()
+		.ok_or_else(|| tls_error(format!("Unable to resolve {}", parts.host)))?;
+	let timeout = tls.timeout();
+	let socket = TcpStream::connect_timeout(&addr, timeout)
+		.map_err(client_error(&format!("Connecting to {}", url)))?;
+	socket
+		.set_read_timeout(Some(timeout))
+		.and_then(|_| socket.set_write_timeout(Some(timeout)))
+		.map_err(client_error(&format!("Connecting to {}", url)))?;
+	let mut stream = StreamOwned::new(session, socket);
+	while stream.sess.is_handshaking() {
+		stream
+			.sess
+			.complete_io(&mut stream.sock)
+			.map_err(client_error(&format!("TLS handshake with {}", url)))?;
+	}
+	let certificate = stream
+		.sess
+		.get_peer_certificates()
+		.and_then(|certs| certs.into_iter().next())
+		.ok_or_else(|| tls_error(format!("No certificate from {}", url)))?;
+	tls.check_pins(&certificate.0)?;
+
+	let body = serde_json::to_string(input)?;
+	post_over(&mut stream, &parts, &body)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn http_tls_config() {
+		let section = r#"
+			[[tls]]
+			url = "https://exchange.com"
+			pins = ["AB:CD"]
+
+			[[tls]]
+			url = "https://exchange.com/wallet/"
+			client_cert_file = "client.pem"
+		"#
+		.parse::<TransportConfig>()
+		.unwrap();
+		let config = HttpTransportConfig::from_config(Some(&section)).unwrap();
+		assert_eq!(config.tls.as_ref().unwrap().len(), 2);
+
+		let tls = config.tls_for("https://exchange.com/wallet/v2/foreign");
+		assert_eq!(tls.unwrap().client_cert_file, Some("client.pem".to_owned()));
+		let tls = config.tls_for("https://exchange.com/v2/foreign").unwrap();
+		assert_eq!(tls.url, "https://exchange.com");
+		assert!(config.tls_for("https://other.com").is_none());
+		assert!(config.tls_for("https://exchange.com.evil.org").is_none());
+		let tls = config
+			.tls_for("https://exchange.com:3415/v2/foreign")
+			.unwrap();
+		assert_eq!(tls.url, "https://exchange.com");
+		let tls = config.tls_for("https://exchange.com/wallets").unwrap();
+		assert_eq!(tls.url, "https://exchange.com");
+		assert!(HttpTransportConfig::from_config(None)
+			.unwrap()
+			.tls_for("https://exchange.com")
+			.is_none());
+
+		// a key is needed with a client certificate
+		let tls = config.tls_for("https://exchange.com/wallet").unwrap();
+		assert!(tls.client_config().is_err());
+		let missing = HttpTlsConfig {
+			url: "https://exchange.com".to_owned(),
+			ca_file: Some("no-such-ca.pem".to_owned()),
+			..Default::default()
+		};
+		assert!(missing.client_config().is_err());
+		assert!(tls_post(&missing, "http://exchange.com", &"{}").is_err());
+	}
+
+	#[test]
+	fn http_tls_pins() {
+		let certificate = b"certificate";
+		let fingerprint = util::to_hex(
+			digest::digest(&digest::SHA256, certificate)
+				.as_ref()
+				.to_vec(),
+		);
+		let mut tls = HttpTlsConfig {
+			url: "https://exchange.com".to_owned(),
+			..Default::default()
+		};
+		assert!(tls.check_pins(certificate).is_ok());
+		tls.pins = Some(vec!["00".to_owned()]);
+		assert!(tls.check_pins(certificate).is_err());
+		// colon separated and upper case, as printed by openssl
+		let colons = fingerprint
+			.to_uppercase()
+			.as_bytes()
+			.chunks(2)
+			.map(|c| String::from_utf8(c.to_vec()).unwrap())
+			.collect::<Vec<String>>()
+			.join(":");
+		tls.pins = Some(vec!["00".to_owned(), colons]);
+		assert!(tls.check_pins(certificate).is_ok());
+	}
+}
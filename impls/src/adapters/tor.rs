@@ -0,0 +1,299 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tor transport: slates posted to onion addresses through the SOCKS proxy of
+//! Tor, and received on an onion service published through its control port
+
+use crate::adapters::http::{client_error, post_over, UrlParts};
+use crate::config::TransportConfig;
+use crate::libwallet::{Error, ErrorKind};
+use crate::util;
+use ring::digest;
+use serde::Serialize;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Config of the Tor transport, the `[wallet.transports.tor]` section
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct TorConfig {
+	/// SOCKS proxy of Tor, 127.0.0.1:9050 if none
+	pub socks_proxy_addr: Option<String>,
+	/// Control port of Tor, 127.0.0.1:9051 if none
+	pub control_addr: Option<String>,
+	/// Password of the control port, as set by `HashedControlPassword`
+	pub control_password: Option<String>,
+	/// Cookie file of the control port, as set by `CookieAuthentication`
+	pub control_cookie_file: Option<String>,
+	/// Port of the onion service, 80 if none
+	pub onion_port: Option<u16>,
+	/// Seconds to wait for the other wallet, 120 if none
+	pub timeout_secs: Option<u64>,
+}
+
+impl TorConfig {
+	/// Read the config out of the section of the transport, the defaults
+	/// when there is none
+	pub fn from_config(config: Option<&TransportConfig>) -> Result<TorConfig, Error> {
+		match config {
+			Some(c) => c.clone().try_into().map_err(|e| {
+				ErrorKind::GenericError(format!("Invalid [wallet.transports.tor] config: {}", e))
+					.into()
+			}),
+			None => Ok(TorConfig::default()),
+		}
+	}
+
+	/// SOCKS proxy of Tor
+	pub fn socks_proxy_addr(&self) -> String {
+		self.socks_proxy_addr
+			.clone()
+			.unwrap_or_else(|| "127.0.0.1:9050".to_owned())
+	}
+
+	/// Control port of Tor
+	pub fn control_addr(&self) -> String {
+		self.control_addr
+			.clone()
+			.unwrap_or_else(|| "127.0.0.1:9051".to_owned())
+	}
+
+	/// Port of the onion service
+	pub fn onion_port(&self) -> u16 {
+		self.onion_port.unwrap_or(80)
+	}
+
+	/// Time to wait for the other wallet
+	pub fn timeout(&self) -> Duration {
+		Duration::from_secs(self.timeout_secs.unwrap_or(120))
+	}
+}
+
+/// Key of the onion service of a 32 bytes seed, as given to `ADD_ONION`: the
+/// expanded ed25519 secret key, so the onion address only depends on the seed
+pub fn onion_service_key(seed: &[u8; 32]) -> String {
+	let mut key = [0u8; 64];
+	key.copy_from_slice(digest::digest(&digest::SHA512, seed).as_ref());
+	key[0] &= 248;
+	key[31] &= 127;
+	key[31] |= 64;
+	format!("ED25519-V3:{}", base64::encode(&key[..]))
+}
+
+/// Connect to `host:port` through a SOCKS5 proxy, which resolves the host
+/// name, as needed for the onion addresses
+pub fn socks5_connect(
+	proxy: &str,
+	host: &str,
+	port: u16,
+	timeout: Duration,
+) -> Result<TcpStream, Error> {
+	let proxy_addr = proxy
+		.to_socket_addrs()
+		.map_err(client_error("Invalid SOCKS proxy address"))?
+		.next()
+		.ok_or_else(|| ErrorKind::ClientCallback(format!("Invalid SOCKS proxy {}", proxy)))?;
+	let mut stream = TcpStream::connect_timeout(&proxy_addr, timeout).map_err(client_error(
+		"Connecting to the SOCKS proxy (is Tor running?)",
+	))?;
+	stream
+		.set_read_timeout(Some(timeout))
+		.and_then(|_| stream.set_write_timeout(Some(timeout)))
+		.map_err(client_error("SOCKS proxy"))?;
+	if host.len() > 255 {
+		return Err(ErrorKind::ClientCallback(format!("Host name too long: {}", host)).into());
+	}
+
+	// no authentication
+	let mut reply = [0u8; 2];
+	stream
+		.write_all(&[5, 1, 0])
+		.and_then(|_| stream.read_exact(&mut reply))
+		.map_err(client_error("SOCKS proxy"))?;
+	if reply != [5, 0] {
+		return Err(ErrorKind::ClientCallback(
+			"The SOCKS proxy requires an authentication".to_owned(),
+		)
+		.into());
+	}
+
+	// connect, to a domain name
+	let mut request = vec![5, 1, 0, 3, host.len() as u8];
+	request.extend_from_slice(host.as_bytes());
+	request.extend_from_slice(&port.to_be_bytes());
+	let mut reply = [0u8; 4];
+	stream
+		.write_all(&request)
+		.and_then(|_| stream.read_exact(&mut reply))
+		.map_err(client_error("SOCKS proxy"))?;
+	if reply[1] != 0 {
+		return Err(ErrorKind::ClientCallback(format!(
+			"The SOCKS proxy failed to connect to {}:{} (error {})",
+			host, port, reply[1]
+		))
+		.into());
+	}
+	// skip the bound address
+	let addr_len = match reply[3] {
+		1 => 4,
+		4 => 16,
+		3 => {
+			let mut len = [0u8; 1];
+			stream
+				.read_exact(&mut len)
+				.map_err(client_error("SOCKS proxy"))?;
+			len[0] as usize
+		}
+		t => {
+			return Err(
+				ErrorKind::ClientCallback(format!("Unknown SOCKS address type {}", t)).into(),
+			);
+		}
+	};
+	let mut bound = vec![0u8; addr_len + 2];
+	stream
+		.read_exact(&mut bound)
+		.map_err(client_error("SOCKS proxy"))?;
+	Ok(stream)
+}
+
+/// Post `input` as json to an `http://` URL through the SOCKS proxy of Tor,
+/// returning the body of the response
+pub fn socks_post<IN>(config: &TorConfig, url: &str, input: &IN) -> Result<String, Error>
+where
+	IN: Serialize,
+{
+	let parts = UrlParts::parse(url)?;
+	if parts.https {
+		return Err(ErrorKind::ClientCallback(format!(
+			"Expected an http:// URL through Tor, the onion services being encrypted already: {}",
+			url
+		))
+		.into());
+	}
+	let body = serde_json::to_string(input)?;
+	let mut stream = socks5_connect(
+		&config.socks_proxy_addr(),
+		&parts.host,
+		parts.port,
+		config.timeout(),
+	)?;
+	post_over(&mut stream, &parts, &body)
+}
+
+/// Connection to the control port of Tor. The onion services added through
+/// it are removed by Tor once the connection closes.
+pub struct TorControl {
+	stream: TcpStream,
+	reader: BufReader<TcpStream>,
+}
+
+impl TorControl {
+	/// Connect to the control port, and authenticate with the password or the
+	/// cookie file of the config, if any
+	pub fn connect(config: &TorConfig) -> Result<TorControl, Error> {
+		let addr = config.control_addr();
+		let stream = TcpStream::connect(&addr).map_err(client_error(
+			"Connecting to the Tor control port (is Tor running?)",
+		))?;
+		let reader = BufReader::new(
+			stream
+				.try_clone()
+				.map_err(client_error("Tor control port"))?,
+		);
+		let mut control = TorControl { stream, reader };
+		let auth = match (&config.control_password, &config.control_cookie_file) {
+			(Some(password), _) => format!(
+				"AUTHENTICATE \"{}\"",
+				password.replace('\\', "\\\\").replace('"', "\\\"")
+			),
+			(None, Some(file)) => {
+				let cookie = fs::read(file).map_err(client_error("Reading the Tor cookie"))?;
+				format!("AUTHENTICATE {}", util::to_hex(cookie))
+			}
+			(None, None) => "AUTHENTICATE".to_owned(),
+		};
+		control.command(&auth)?;
+		Ok(control)
+	}
+
+	/// Send a command, returning the lines of the reply without their status
+	/// code, once successful
+	pub fn command(&mut self, command: &str) -> Result<Vec<String>, Error> {
+		self.stream
+			.write_all(format!("{}\r\n", command).as_bytes())
+			.map_err(client_error("Tor control port"))?;
+		let mut lines = vec![];
+		loop {
+			let mut line = String::new();
+			let n = self
+				.reader
+				.read_line(&mut line)
+				.map_err(client_error("Tor control port"))?;
+			if n == 0 {
+				return Err(ErrorKind::ClientCallback(
+					"The Tor control port closed the connection".to_owned(),
+				)
+				.into());
+			}
+			let line = line.trim_end();
+			if line.len() < 4 {
+				continue;
+			}
+			let (code, sep, text) = (&line[..3], &line[3..4], &line[4..]);
+			if !code.starts_with('2') {
+				let verb = command.split_whitespace().next().unwrap_or("");
+				return Err(ErrorKind::ClientCallback(format!(
+					"Tor refused {}: {} {}",
+					verb, code, text
+				))
+				.into());
+			}
+			lines.push(text.to_owned());
+			if sep == " " {
+				return Ok(lines);
+			}
+		}
+	}
+
+	/// Publish the onion service of `key` on `port`, forwarded to `target`,
+	/// returning its onion address
+	pub fn add_onion(&mut self, key: &str, port: u16, target: &str) -> Result<String, Error> {
+		let reply = self.command(&format!("ADD_ONION {} Port={},{}", key, port, target))?;
+		reply
+			.iter()
+			.find(|l| l.starts_with("ServiceID="))
+			.map(|l| format!("{}.onion", &l["ServiceID=".len()..]))
+			.ok_or_else(|| ErrorKind::ClientCallback("Tor gave no onion address".to_owned()).into())
+	}
+
+	/// Block while the connection stays open, keeping the onion services up
+	pub fn wait(mut self) -> Result<(), Error> {
+		let mut line = String::new();
+		loop {
+			line.clear();
+			let n = self
+				.reader
+				.read_line(&mut line)
+				.map_err(client_error("Tor control port"))?;
+			if n == 0 {
+				return Err(ErrorKind::ClientCallback(
+					"The Tor control port closed the connection".to_owned(),
+				)
+				.into());
+			}
+		}
+	}
+}
@@ -0,0 +1,187 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by all wallet database backends
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::blake2::blake2b::Blake2b;
+
+use crate::core;
+use crate::core::core::Transaction;
+use crate::keychain::{ExtKeychain, Identifier, Keychain, SwitchCommitmentType};
+use crate::libwallet::{Error, PaymentProof, TxLogEntry, TxProof};
+use crate::util;
+use crate::util::secp::constants::SECRET_KEY_SIZE;
+
+pub const DB_DIR: &'static str = "db";
+pub const TX_SAVE_DIR: &'static str = "saved_txs";
+pub const TX_PROOF_SAVE_DIR: &'static str = "saved_proofs";
+
+/// Create the directories shared by all backends under the wallet data dir
+pub fn create_data_dirs(data_file_dir: &str) {
+	let db_path = Path::new(data_file_dir).join(DB_DIR);
+	fs::create_dir_all(&db_path).expect("Couldn't create wallet backend directory!");
+
+	let stored_tx_path = Path::new(data_file_dir).join(TX_SAVE_DIR);
+	fs::create_dir_all(&stored_tx_path)
+		.expect("Couldn't create wallet backend tx storage directory!");
+
+	let stored_tx_proof_path = Path::new(data_file_dir).join(TX_PROOF_SAVE_DIR);
+	fs::create_dir_all(&stored_tx_proof_path)
+		.expect("Couldn't create wallet backend tx proof storage directory!");
+}
+
+/// return the default parent wallet path, corresponding to the default account
+/// in the BIP32 spec. Parent is account 0 at level 2, child output identifiers
+/// are all at level 3
+pub fn default_path() -> Identifier {
+	ExtKeychain::derive_key_id(2, 0, 0, 0, 0)
+}
+
+/// Helper to derive XOR keys for storing private transaction keys in the DB
+/// (blind_xor_key, nonce_xor_key)
+pub fn private_ctx_xor_keys<K>(
+	keychain: &K,
+	slate_id: &[u8],
+) -> Result<([u8; SECRET_KEY_SIZE], [u8; SECRET_KEY_SIZE]), Error>
+where
+	K: Keychain,
+{
+	let root_key = keychain.derive_key(0, &K::root_key_id(), &SwitchCommitmentType::Regular)?;
+
+	// derive XOR values for storing secret values in DB
+	// h(root_key|slate_id|"blind")
+	let mut hasher = Blake2b::new(SECRET_KEY_SIZE);
+	hasher.update(&root_key.0[..]);
+	hasher.update(&slate_id[..]);
+	hasher.update(&"blind".as_bytes()[..]);
+	let blind_xor_key = hasher.finalize();
+	let mut ret_blind = [0; SECRET_KEY_SIZE];
+	ret_blind.copy_from_slice(&blind_xor_key.as_bytes()[0..SECRET_KEY_SIZE]);
+
+	// h(root_key|slate_id|"nonce")
+	let mut hasher = Blake2b::new(SECRET_KEY_SIZE);
+	hasher.update(&root_key.0[..]);
+	hasher.update(&slate_id[..]);
+	hasher.update(&"nonce".as_bytes()[..]);
+	let nonce_xor_key = hasher.finalize();
+	let mut ret_nonce = [0; SECRET_KEY_SIZE];
+	ret_nonce.copy_from_slice(&nonce_xor_key.as_bytes()[0..SECRET_KEY_SIZE]);
+
+	Ok((ret_blind, ret_nonce))
+}
+
+/// Stores a transaction as a hex file in the saved txs dir
+pub fn store_tx(data_file_dir: &str, uuid: &str, tx: &Transaction) -> Result<(), Error> {
+	let filename = format!("{}.grintx", uuid);
+	let path = Path::new(data_file_dir).join(TX_SAVE_DIR).join(filename);
+	let path_buf = Path::new(&path).to_path_buf();
+	let mut stored_tx = File::create(path_buf)?;
+	let tx_hex = util::to_hex(core::ser::ser_vec(tx, core::ser::ProtocolVersion::local()).unwrap());
+	stored_tx.write_all(&tx_hex.as_bytes())?;
+	stored_tx.sync_all()?;
+	Ok(())
+}
+
+/// Stores a transaction proof as a json file in the saved proofs dir
+pub fn store_tx_proof(data_file_dir: &str, uuid: &str, tx_proof: &TxProof) -> Result<(), Error> {
+	let filename = format!("{}.proof", uuid);
+	let path = Path::new(data_file_dir)
+		.join(TX_PROOF_SAVE_DIR)
+		.join(filename);
+	let path_buf = Path::new(&path).to_path_buf();
+	let mut stored_tx_proof = File::create(path_buf)?;
+	let proof_ser = serde_json::to_string(tx_proof)?;
+	stored_tx_proof.write_all(&proof_ser.as_bytes())?;
+	stored_tx_proof.sync_all()?;
+	Ok(())
+}
+
+/// Stores a payment proof as a json file in the saved proofs dir
+pub fn store_payment_proof(
+	data_file_dir: &str,
+	uuid: &str,
+	proof: &PaymentProof,
+) -> Result<(), Error> {
+	let filename = format!("{}.payment_proof", uuid);
+	let path = Path::new(data_file_dir)
+		.join(TX_PROOF_SAVE_DIR)
+		.join(filename);
+	let mut stored_proof = File::create(path)?;
+	let proof_ser = serde_json::to_string(proof)?;
+	stored_proof.write_all(&proof_ser.as_bytes())?;
+	stored_proof.sync_all()?;
+	Ok(())
+}
+
+/// Retrieves a stored transaction from a TxLogEntry
+pub fn get_stored_tx(
+	data_file_dir: &str,
+	entry: &TxLogEntry,
+) -> Result<Option<Transaction>, Error> {
+	let filename = match entry.stored_tx.clone() {
+		Some(f) => f,
+		None => return Ok(None),
+	};
+	let path = Path::new(data_file_dir).join(TX_SAVE_DIR).join(filename);
+	let tx_file = Path::new(&path).to_path_buf();
+	let mut tx_f = File::open(tx_file)?;
+	let mut content = String::new();
+	tx_f.read_to_string(&mut content)?;
+	let tx_bin = util::from_hex(content).unwrap();
+	Ok(Some(
+		core::ser::deserialize::<Transaction>(
+			&mut &tx_bin[..],
+			core::ser::ProtocolVersion::local(),
+		)
+		.unwrap(),
+	))
+}
+
+/// Retrieves a stored transaction proof by the tx slate uuid
+pub fn get_stored_tx_proof(data_file_dir: &str, uuid: &str) -> Result<Option<TxProof>, Error> {
+	let filename = format!("{}.proof", uuid);
+	let path = Path::new(data_file_dir)
+		.join(TX_PROOF_SAVE_DIR)
+		.join(filename);
+	let tx_proof_file = Path::new(&path).to_path_buf();
+	if !tx_proof_file.exists() {
+		return Ok(None);
+	}
+	let mut tx_proof_f = File::open(tx_proof_file)?;
+	let mut content = String::new();
+	tx_proof_f.read_to_string(&mut content)?;
+	Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// Retrieves a stored payment proof by the tx slate uuid
+pub fn get_stored_payment_proof(
+	data_file_dir: &str,
+	uuid: &str,
+) -> Result<Option<PaymentProof>, Error> {
+	let filename = format!("{}.payment_proof", uuid);
+	let path = Path::new(data_file_dir)
+		.join(TX_PROOF_SAVE_DIR)
+		.join(filename);
+	if !path.exists() {
+		return Ok(None);
+	}
+	let mut proof_f = File::open(path)?;
+	let mut content = String::new();
+	proof_f.read_to_string(&mut content)?;
+	Ok(Some(serde_json::from_str(&content)?))
+}
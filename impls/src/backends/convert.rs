@@ -0,0 +1,105 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of the wallet data between database backends
+
+use std::collections::HashMap;
+
+use crate::keychain::{Identifier, Keychain};
+use crate::libwallet::{Context, Error, ErrorKind, NodeClient, TxLogEntryType, WalletBackend};
+
+/// Copies all the wallet data stored in `src` into `dst`, which must be empty.
+/// Both backends must have been opened with credentials, since the private
+/// transaction contexts of unfinished transactions are re-encrypted for the
+/// destination. The saved transaction and proof files stay where they are,
+/// both backends share the same wallet data dir.
+pub fn copy_wallet_data<S: ?Sized, D: ?Sized, C, K>(src: &mut S, dst: &mut D) -> Result<(), Error>
+where
+	S: WalletBackend<C, K>,
+	D: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	if dst.iter()?.next().is_some() || dst.tx_log_iter()?.next().is_some() {
+		return Err(ErrorKind::GenericError(
+			"destination wallet database is not empty".to_owned(),
+		))?;
+	}
+
+	let accounts: Vec<_> = src.acct_path_iter()?.collect();
+	let outputs: Vec<_> = src.iter()?.collect();
+	let payments: Vec<_> = src.payment_entries_iter_all()?.collect();
+	let tx_entries: Vec<_> = src.tx_log_iter()?.collect();
+
+	// the tx log id counters aren't readable, rebuild them from the entries
+	let mut next_tx_log_ids: HashMap<Identifier, u32> = HashMap::new();
+	for t in &tx_entries {
+		let next = next_tx_log_ids.entry(t.parent_key_id.clone()).or_insert(0);
+		if t.id + 1 > *next {
+			*next = t.id + 1;
+		}
+	}
+
+	// per account child index and last confirmed height
+	let orig_parent_key_id = src.parent_key_id();
+	let mut account_state = vec![];
+	for a in &accounts {
+		src.set_parent_key_id(a.path.clone());
+		let child_index = src.current_child_index(&a.path)?;
+		let height = src.last_confirmed_height()?;
+		account_state.push((a.path.clone(), child_index, height));
+	}
+	src.set_parent_key_id(orig_parent_key_id);
+
+	// private contexts of transactions still in flight
+	let mut contexts: Vec<(Vec<u8>, usize, Context)> = vec![];
+	for t in &tx_entries {
+		let in_flight = !t.confirmed
+			&& (t.tx_type == TxLogEntryType::TxSent || t.tx_type == TxLogEntryType::TxReceived);
+		if let (true, Some(slate_id)) = (in_flight, t.tx_slate_id) {
+			for participant_id in 0..2 {
+				if let Ok(ctx) = src.get_private_context(slate_id.as_bytes(), participant_id) {
+					contexts.push((slate_id.as_bytes().to_vec(), participant_id, ctx));
+				}
+			}
+		}
+	}
+
+	let mut batch = dst.batch()?;
+	for a in accounts {
+		batch.save_acct_path(a)?;
+	}
+	for (path, child_index, height) in account_state {
+		batch.save_child_index(&path, child_index)?;
+		batch.save_last_confirmed_height(&path, height)?;
+	}
+	for o in outputs {
+		batch.save(o)?;
+	}
+	for p in payments {
+		batch.save_payment(p)?;
+	}
+	for t in tx_entries {
+		let parent_key_id = t.parent_key_id.clone();
+		batch.save_tx_log_entry(t, &parent_key_id)?;
+	}
+	for (parent_key_id, next_id) in next_tx_log_ids {
+		batch.save_tx_log_id(&parent_key_id, next_id)?;
+	}
+	for (slate_id, participant_id, ctx) in contexts {
+		batch.save_private_context(&slate_id, participant_id, &ctx)?;
+	}
+	batch.commit()?;
+	Ok(())
+}
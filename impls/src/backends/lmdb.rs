@@ -14,37 +14,28 @@
 // limitations under the License.
 
 use std::cell::RefCell;
-use std::{fs, path::Path};
-
-// for writing stored transaction files
-use std::fs::File;
-use std::io::{Read, Write};
+use std::path::Path;
 
 use failure::ResultExt;
 use uuid::Uuid;
 
-use crate::blake2::blake2b::Blake2b;
-
+use super::common::{self, private_ctx_xor_keys, DB_DIR};
 use super::wallet_store::{self, option_to_not_found};
-use crate::keychain::{ChildNumber, ExtKeychain, Identifier, Keychain, SwitchCommitmentType};
+use crate::keychain::{ChildNumber, Identifier, Keychain, SwitchCommitmentType};
 use crate::store::{to_key, to_key_u64};
 
 use crate::core::core::Transaction;
-use crate::core::{self, global};
+use crate::core::global;
 use crate::libwallet::{check_repair, check_repair_batch, restore, restore_batch};
 use crate::libwallet::{
 	AcctPathMapping, Context, Error, ErrorKind, Listener, NodeClient, OutputData, PaymentData,
-	TxLogEntry, TxProof, WalletBackend, WalletOutputBatch,
+	PaymentProof, TxLogEntry, TxProof, WalletBackend, WalletOutputBatch,
 };
 use crate::util;
 use crate::util::secp::constants::SECRET_KEY_SIZE;
 use crate::util::ZeroingString;
 use crate::WalletSeed;
-use config::WalletConfig;
-
-pub const DB_DIR: &'static str = "db";
-pub const TX_SAVE_DIR: &'static str = "saved_txs";
-pub const TX_PROOF_SAVE_DIR: &'static str = "saved_proofs";
+use config::{ReceivePolicy, StaleTxPolicy, TransportConfig, WalletConfig};
 
 const OUTPUT_PREFIX: u8 = 'o' as u8;
 const PAYMENT_PREFIX: u8 = 'P' as u8;
@@ -62,39 +53,6 @@ pub fn wallet_db_exists(config: WalletConfig) -> bool {
 	db_path.exists()
 }
 
-/// Helper to derive XOR keys for storing private transaction keys in the DB
-/// (blind_xor_key, nonce_xor_key)
-fn private_ctx_xor_keys<K>(
-	keychain: &K,
-	slate_id: &[u8],
-) -> Result<([u8; SECRET_KEY_SIZE], [u8; SECRET_KEY_SIZE]), Error>
-where
-	K: Keychain,
-{
-	let root_key = keychain.derive_key(0, &K::root_key_id(), &SwitchCommitmentType::Regular)?;
-
-	// derive XOR values for storing secret values in DB
-	// h(root_key|slate_id|"blind")
-	let mut hasher = Blake2b::new(SECRET_KEY_SIZE);
-	hasher.update(&root_key.0[..]);
-	hasher.update(&slate_id[..]);
-	hasher.update(&"blind".as_bytes()[..]);
-	let blind_xor_key = hasher.finalize();
-	let mut ret_blind = [0; SECRET_KEY_SIZE];
-	ret_blind.copy_from_slice(&blind_xor_key.as_bytes()[0..SECRET_KEY_SIZE]);
-
-	// h(root_key|slate_id|"nonce")
-	let mut hasher = Blake2b::new(SECRET_KEY_SIZE);
-	hasher.update(&root_key.0[..]);
-	hasher.update(&slate_id[..]);
-	hasher.update(&"nonce".as_bytes()[..]);
-	let nonce_xor_key = hasher.finalize();
-	let mut ret_nonce = [0; SECRET_KEY_SIZE];
-	ret_nonce.copy_from_slice(&nonce_xor_key.as_bytes()[0..SECRET_KEY_SIZE]);
-
-	Ok((ret_blind, ret_nonce))
-}
-
 pub struct LMDBBackend<C, K> {
 	db: wallet_store::Store,
 	config: WalletConfig,
@@ -108,21 +66,14 @@ pub struct LMDBBackend<C, K> {
 	w2n_client: C,
 	/// Grin Relay Listener
 	pub grinrelay_listener: Option<Box<dyn Listener>>,
+	/// Open the database without loading the seed
+	watch_only: bool,
 }
 
 impl<C, K> LMDBBackend<C, K> {
 	pub fn new(config: WalletConfig, passphrase: &str, n_client: C) -> Result<Self, Error> {
+		common::create_data_dirs(&config.data_file_dir);
 		let db_path = Path::new(&config.data_file_dir).join(DB_DIR);
-		fs::create_dir_all(&db_path).expect("Couldn't create wallet backend directory!");
-
-		let stored_tx_path = Path::new(&config.data_file_dir).join(TX_SAVE_DIR);
-		fs::create_dir_all(&stored_tx_path)
-			.expect("Couldn't create wallet backend tx storage directory!");
-
-		let stored_tx_proof_path = Path::new(&config.data_file_dir).join(TX_PROOF_SAVE_DIR);
-		fs::create_dir_all(&stored_tx_proof_path)
-			.expect("Couldn't create wallet backend tx proof storage directory!");
-
 		let store = wallet_store::Store::new(db_path.to_str().unwrap(), None, Some(DB_DIR), None)?;
 
 		// Make sure default wallet derivation path always exists
@@ -151,15 +102,13 @@ impl<C, K> LMDBBackend<C, K> {
 			parent_key_id: LMDBBackend::<C, K>::default_path(),
 			w2n_client: n_client,
 			grinrelay_listener: None,
+			watch_only: false,
 		};
 		Ok(res)
 	}
 
 	fn default_path() -> Identifier {
-		// return the default parent wallet path, corresponding to the default account
-		// in the BIP32 spec. Parent is account 0 at level 2, child output identifiers
-		// are all at level 3
-		ExtKeychain::derive_key_id(2, 0, 0, 0, 0)
+		common::default_path()
 	}
 
 	/// Just test to see if database files exist in the current directory. If
@@ -177,6 +126,9 @@ where
 {
 	/// Initialise with whatever stored credentials we have
 	fn open_with_credentials(&mut self) -> Result<(), Error> {
+		if self.watch_only {
+			return Ok(());
+		}
 		let wallet_seed =
 			WalletSeed::from_file(&self.config.data_file_dir.as_str(), &self.passphrase)
 				.context(ErrorKind::CallbackImpl("Error opening wallet"))?;
@@ -199,6 +151,19 @@ where
 		self.keychain.as_mut().unwrap()
 	}
 
+	/// Set or clear watch-only mode
+	fn set_watch_only(&mut self, watch_only: bool) {
+		self.watch_only = watch_only;
+		if watch_only {
+			self.keychain = None;
+		}
+	}
+
+	/// Whether the wallet is opened watch-only
+	fn is_watch_only(&self) -> bool {
+		self.watch_only
+	}
+
 	/// Return the node client being used
 	fn w2n_client(&mut self) -> &mut C {
 		&mut self.w2n_client
@@ -228,6 +193,31 @@ where
 		}
 	}
 
+	/// Set the policy cancelling the stale outgoing transactions
+	fn set_stale_tx_policy(&mut self, policy: Option<StaleTxPolicy>) {
+		self.config.stale_tx_policy = policy;
+	}
+
+	/// Return the policy cancelling the stale outgoing transactions
+	fn stale_tx_policy(&self) -> Option<StaleTxPolicy> {
+		self.config.stale_tx_policy.clone()
+	}
+
+	/// Set the acceptance policy of the incoming payments
+	fn set_receive_policy(&mut self, policy: Option<ReceivePolicy>) {
+		self.config.receive_policy = policy;
+	}
+
+	/// Return the acceptance policy of the incoming payments
+	fn receive_policy(&self) -> Option<ReceivePolicy> {
+		self.config.receive_policy.clone()
+	}
+
+	/// Return the config section of a transport
+	fn transport_config(&self, name: &str) -> Option<TransportConfig> {
+		self.config.transports.as_ref()?.get(name).cloned()
+	}
+
 	/// return the version of the commit for caching
 	fn calc_commit_for_cache(
 		&mut self,
@@ -249,7 +239,7 @@ where
 	/// Set parent path by account name
 	fn set_parent_key_id_by_name(&mut self, label: &str) -> Result<(), Error> {
 		let label = label.to_owned();
-		let res = self.acct_path_iter().find(|l| l.label == label);
+		let res = self.acct_path_iter()?.find(|l| l.label == label);
 		if let Some(a) = res {
 			self.set_parent_key_id(a.path);
 			Ok(())
@@ -275,20 +265,22 @@ where
 		option_to_not_found(self.db.get_ser(&key), &format!("Key Id: {}", id)).map_err(|e| e.into())
 	}
 
-	fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = OutputData> + 'a> {
-		Box::new(self.db.iter(&[OUTPUT_PREFIX]).unwrap().map(|o| o.1))
+	fn iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = OutputData> + 'a>, Error> {
+		Ok(Box::new(self.db.iter(&[OUTPUT_PREFIX])?.map(|o| o.1)))
 	}
 
 	fn payment_entries_iter_tx<'a>(
 		&'a self,
 		u: &Uuid,
-	) -> Box<dyn Iterator<Item = PaymentData> + 'a> {
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error> {
 		let key = to_key(PAYMENT_PREFIX, &mut u.as_bytes().to_vec());
-		Box::new(self.db.iter(&key).unwrap().map(|o| o.1))
+		Ok(Box::new(self.db.iter(&key)?.map(|o| o.1)))
 	}
 
-	fn payment_entries_iter_all<'a>(&'a self) -> Box<dyn Iterator<Item = PaymentData> + 'a> {
-		Box::new(self.db.iter(&[PAYMENT_PREFIX]).unwrap().map(|o| o.1))
+	fn payment_entries_iter_all<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error> {
+		Ok(Box::new(self.db.iter(&[PAYMENT_PREFIX])?.map(|o| o.1)))
 	}
 
 	fn get_tx_log_entry(&self, u: &Uuid) -> Result<Option<TxLogEntry>, Error> {
@@ -296,8 +288,8 @@ where
 		self.db.get_ser(&key).map_err(|e| e.into())
 	}
 
-	fn tx_log_iter<'a>(&'a self) -> Box<dyn Iterator<Item = TxLogEntry> + 'a> {
-		Box::new(self.db.iter(&[TX_LOG_ENTRY_PREFIX]).unwrap().map(|o| o.1))
+	fn tx_log_iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = TxLogEntry> + 'a>, Error> {
+		Ok(Box::new(self.db.iter(&[TX_LOG_ENTRY_PREFIX])?.map(|o| o.1)))
 	}
 
 	fn get_private_context(
@@ -325,13 +317,12 @@ where
 		Ok(ctx)
 	}
 
-	fn acct_path_iter<'a>(&'a self) -> Box<dyn Iterator<Item = AcctPathMapping> + 'a> {
-		Box::new(
-			self.db
-				.iter(&[ACCOUNT_PATH_MAPPING_PREFIX])
-				.unwrap()
-				.map(|o| o.1),
-		)
+	fn acct_path_iter<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = AcctPathMapping> + 'a>, Error> {
+		Ok(Box::new(
+			self.db.iter(&[ACCOUNT_PATH_MAPPING_PREFIX])?.map(|o| o.1),
+		))
 	}
 
 	fn get_acct_path(&self, label: String) -> Result<Option<AcctPathMapping>, Error> {
@@ -340,67 +331,27 @@ where
 	}
 
 	fn store_tx(&self, uuid: &str, tx: &Transaction) -> Result<(), Error> {
-		let filename = format!("{}.grintx", uuid);
-		let path = Path::new(&self.config.data_file_dir)
-			.join(TX_SAVE_DIR)
-			.join(filename);
-		let path_buf = Path::new(&path).to_path_buf();
-		let mut stored_tx = File::create(path_buf)?;
-		let tx_hex =
-			util::to_hex(core::ser::ser_vec(tx, core::ser::ProtocolVersion::local()).unwrap());;
-		stored_tx.write_all(&tx_hex.as_bytes())?;
-		stored_tx.sync_all()?;
-		Ok(())
+		common::store_tx(&self.config.data_file_dir, uuid, tx)
 	}
 
 	fn store_tx_proof(&self, uuid: &str, tx_proof: &TxProof) -> Result<(), Error> {
-		let filename = format!("{}.proof", uuid);
-		let path = Path::new(&self.config.data_file_dir)
-			.join(TX_PROOF_SAVE_DIR)
-			.join(filename);
-		let path_buf = Path::new(&path).to_path_buf();
-		let mut stored_tx_proof = File::create(path_buf)?;
-		let proof_ser = serde_json::to_string(tx_proof)?;
-		stored_tx_proof.write_all(&proof_ser.as_bytes())?;
-		stored_tx_proof.sync_all()?;
-		Ok(())
+		common::store_tx_proof(&self.config.data_file_dir, uuid, tx_proof)
 	}
 
 	fn get_stored_tx(&self, entry: &TxLogEntry) -> Result<Option<Transaction>, Error> {
-		let filename = match entry.stored_tx.clone() {
-			Some(f) => f,
-			None => return Ok(None),
-		};
-		let path = Path::new(&self.config.data_file_dir)
-			.join(TX_SAVE_DIR)
-			.join(filename);
-		let tx_file = Path::new(&path).to_path_buf();
-		let mut tx_f = File::open(tx_file)?;
-		let mut content = String::new();
-		tx_f.read_to_string(&mut content)?;
-		let tx_bin = util::from_hex(content).unwrap();
-		Ok(Some(
-			core::ser::deserialize::<Transaction>(
-				&mut &tx_bin[..],
-				core::ser::ProtocolVersion::local(),
-			)
-			.unwrap(),
-		))
+		common::get_stored_tx(&self.config.data_file_dir, entry)
 	}
 
 	fn get_stored_tx_proof(&self, uuid: &str) -> Result<Option<TxProof>, Error> {
-		let filename = format!("{}.proof", uuid);
-		let path = Path::new(&self.config.data_file_dir)
-			.join(TX_PROOF_SAVE_DIR)
-			.join(filename);
-		let tx_proof_file = Path::new(&path).to_path_buf();
-		if !tx_proof_file.exists() {
-			return Ok(None);
-		}
-		let mut tx_proof_f = File::open(tx_proof_file)?;
-		let mut content = String::new();
-		tx_proof_f.read_to_string(&mut content)?;
-		Ok(Some(serde_json::from_str(&content)?))
+		common::get_stored_tx_proof(&self.config.data_file_dir, uuid)
+	}
+
+	fn store_payment_proof(&self, uuid: &str, proof: &PaymentProof) -> Result<(), Error> {
+		common::store_payment_proof(&self.config.data_file_dir, uuid, proof)
+	}
+
+	fn get_stored_payment_proof(&self, uuid: &str) -> Result<Option<PaymentProof>, Error> {
+		common::get_stored_payment_proof(&self.config.data_file_dir, uuid)
 	}
 
 	fn batch<'a>(&'a mut self) -> Result<Box<dyn WalletOutputBatch<K> + 'a>, Error> {
@@ -411,16 +362,18 @@ where
 		}))
 	}
 
+	fn current_child_index(&mut self, parent_key_id: &Identifier) -> Result<u32, Error> {
+		let batch = self.db.batch()?;
+		let deriv_key = to_key(DERIV_PREFIX, &mut parent_key_id.to_bytes().to_vec());
+		match batch.get_ser(&deriv_key)? {
+			Some(idx) => Ok(idx),
+			None => Ok(0),
+		}
+	}
+
 	fn next_child<'a>(&mut self) -> Result<Identifier, Error> {
 		let parent_key_id = self.parent_key_id.clone();
-		let mut deriv_idx = {
-			let batch = self.db.batch()?;
-			let deriv_key = to_key(DERIV_PREFIX, &mut self.parent_key_id.to_bytes().to_vec());
-			match batch.get_ser(&deriv_key)? {
-				Some(idx) => idx,
-				None => 0,
-			}
-		};
+		let mut deriv_idx = self.current_child_index(&parent_key_id)?;
 		let mut return_path = self.parent_key_id.to_path();
 		return_path.depth = return_path.depth + 1;
 		return_path.path[return_path.depth as usize - 1] = ChildNumber::from(deriv_idx);
@@ -542,29 +495,25 @@ where
 		.map_err(|e| e.into())
 	}
 
-	fn payment_entries_iter_tx(&self, u: &Uuid) -> Box<dyn Iterator<Item = PaymentData>> {
+	fn payment_entries_iter_tx(
+		&self,
+		u: &Uuid,
+	) -> Result<Box<dyn Iterator<Item = PaymentData>>, Error> {
 		let key = to_key(PAYMENT_PREFIX, &mut u.as_bytes().to_vec());
-		Box::new(
-			self.db
-				.borrow()
-				.as_ref()
-				.unwrap()
-				.iter(&key)
-				.unwrap()
-				.map(|o| o.1),
-		)
+		Ok(Box::new(
+			self.db.borrow().as_ref().unwrap().iter(&key)?.map(|o| o.1),
+		))
 	}
 
-	fn iter(&self) -> Box<dyn Iterator<Item = OutputData>> {
-		Box::new(
+	fn iter(&self) -> Result<Box<dyn Iterator<Item = OutputData>>, Error> {
+		Ok(Box::new(
 			self.db
 				.borrow()
 				.as_ref()
 				.unwrap()
-				.iter(&[OUTPUT_PREFIX])
-				.unwrap()
+				.iter(&[OUTPUT_PREFIX])?
 				.map(|o| o.1),
-		)
+		))
 	}
 
 	fn delete(&mut self, id: &Identifier, mmr_index: &Option<u64>) -> Result<(), Error> {
@@ -594,16 +543,25 @@ where
 		Ok(last_tx_log_id)
 	}
 
-	fn tx_log_iter(&self) -> Box<dyn Iterator<Item = TxLogEntry>> {
-		Box::new(
+	fn save_tx_log_id(&mut self, parent_key_id: &Identifier, next_id: u32) -> Result<(), Error> {
+		let tx_id_key = to_key(TX_LOG_ID_PREFIX, &mut parent_key_id.to_bytes().to_vec());
+		self.db
+			.borrow()
+			.as_ref()
+			.unwrap()
+			.put_ser(&tx_id_key, &next_id)?;
+		Ok(())
+	}
+
+	fn tx_log_iter(&self) -> Result<Box<dyn Iterator<Item = TxLogEntry>>, Error> {
+		Ok(Box::new(
 			self.db
 				.borrow()
 				.as_ref()
 				.unwrap()
-				.iter(&[TX_LOG_ENTRY_PREFIX])
-				.unwrap()
+				.iter(&[TX_LOG_ENTRY_PREFIX])?
 				.map(|o| o.1),
-		)
+		))
 	}
 
 	fn save_last_confirmed_height(
@@ -664,16 +622,15 @@ where
 		Ok(())
 	}
 
-	fn acct_path_iter(&self) -> Box<dyn Iterator<Item = AcctPathMapping>> {
-		Box::new(
+	fn acct_path_iter(&self) -> Result<Box<dyn Iterator<Item = AcctPathMapping>>, Error> {
+		Ok(Box::new(
 			self.db
 				.borrow()
 				.as_ref()
 				.unwrap()
-				.iter(&[ACCOUNT_PATH_MAPPING_PREFIX])
-				.unwrap()
+				.iter(&[ACCOUNT_PATH_MAPPING_PREFIX])?
 				.map(|o| o.1),
-		)
+		))
 	}
 
 	fn lock_output(&mut self, out: &mut OutputData) -> Result<(), Error> {
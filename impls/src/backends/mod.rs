@@ -13,7 +13,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod common;
+mod convert;
 mod lmdb;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 pub mod wallet_store;
 
+pub use self::convert::copy_wallet_data;
 pub use self::lmdb::{wallet_db_exists, LMDBBackend};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteBackend;
@@ -0,0 +1,947 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of wallet types using SQLite.
+//!
+//! Every record is kept as its JSON serialization in a `data` column, the
+//! same representation the LMDB backend stores, so conversion between the two
+//! backends is lossless. The fields useful for querying (status, value,
+//! heights, slate ids, ...) are duplicated into plain columns so the wallet
+//! data can be inspected with any SQLite client.
+
+use std::cell::Cell;
+use std::path::Path;
+use std::time::Duration;
+
+use failure::ResultExt;
+use rusqlite::types::ToSql;
+use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::common::{self, private_ctx_xor_keys, DB_DIR};
+use crate::keychain::{ChildNumber, Identifier, Keychain, SwitchCommitmentType};
+use crate::store;
+
+use crate::core::core::Transaction;
+use crate::core::global;
+use crate::libwallet::{check_repair, check_repair_batch, restore, restore_batch};
+use crate::libwallet::{
+	AcctPathMapping, Context, Error, ErrorKind, Listener, NodeClient, OutputData, PaymentData,
+	PaymentProof, TxLogEntry, TxProof, WalletBackend, WalletOutputBatch,
+};
+use crate::util;
+use crate::util::secp::constants::SECRET_KEY_SIZE;
+use crate::util::{Mutex, ZeroingString};
+use crate::WalletSeed;
+use config::{ReceivePolicy, StaleTxPolicy, TransportConfig, WalletConfig};
+
+/// File name of the SQLite database, under the wallet `db` dir
+pub const SQLITE_DB_FILE: &'static str = "wallet.sqlite";
+
+/// How long a connection waits for another process holding the write lock
+const BUSY_TIMEOUT_MS: u64 = 5_000;
+
+const SCHEMA: &'static str = "
+CREATE TABLE IF NOT EXISTS outputs (
+	key_id TEXT NOT NULL,
+	mmr_index INTEGER NOT NULL,
+	root_key_id TEXT NOT NULL,
+	n_child INTEGER NOT NULL,
+	commit_hex TEXT,
+	value INTEGER NOT NULL,
+	status TEXT NOT NULL,
+	height INTEGER NOT NULL,
+	lock_height INTEGER NOT NULL,
+	is_coinbase INTEGER NOT NULL,
+	tx_log_entry INTEGER,
+	slate_id TEXT,
+	is_change INTEGER,
+	data TEXT NOT NULL,
+	PRIMARY KEY (key_id, mmr_index)
+);
+CREATE INDEX IF NOT EXISTS outputs_root_key_id ON outputs (root_key_id);
+CREATE INDEX IF NOT EXISTS outputs_status ON outputs (status);
+CREATE INDEX IF NOT EXISTS outputs_slate_id ON outputs (slate_id);
+
+CREATE TABLE IF NOT EXISTS payments (
+	slate_id TEXT NOT NULL,
+	commit_hex TEXT NOT NULL,
+	value INTEGER NOT NULL,
+	status TEXT NOT NULL,
+	height INTEGER NOT NULL,
+	lock_height INTEGER NOT NULL,
+	tx_log_entry INTEGER,
+	data TEXT NOT NULL,
+	PRIMARY KEY (slate_id, commit_hex)
+);
+
+CREATE TABLE IF NOT EXISTS tx_log (
+	parent_key_id TEXT NOT NULL,
+	id INTEGER NOT NULL,
+	tx_slate_id TEXT,
+	tx_type TEXT NOT NULL,
+	creation_ts TEXT NOT NULL,
+	confirmation_ts TEXT,
+	confirmed INTEGER NOT NULL,
+	height INTEGER,
+	amount_credited INTEGER NOT NULL,
+	amount_debited INTEGER NOT NULL,
+	fee INTEGER,
+	kernel_excess TEXT,
+	data TEXT NOT NULL,
+	PRIMARY KEY (parent_key_id, id)
+);
+CREATE INDEX IF NOT EXISTS tx_log_tx_slate_id ON tx_log (tx_slate_id);
+
+CREATE TABLE IF NOT EXISTS tx_log_ids (
+	parent_key_id TEXT PRIMARY KEY,
+	next_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS child_indices (
+	parent_key_id TEXT PRIMARY KEY,
+	child_n INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS confirmed_heights (
+	parent_key_id TEXT PRIMARY KEY,
+	height INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS private_contexts (
+	slate_id BLOB NOT NULL,
+	participant_id INTEGER NOT NULL,
+	data TEXT NOT NULL,
+	PRIMARY KEY (slate_id, participant_id)
+);
+
+CREATE TABLE IF NOT EXISTS acct_paths (
+	label TEXT PRIMARY KEY,
+	path TEXT NOT NULL,
+	data TEXT NOT NULL
+);
+";
+
+/// Converts a SQLite error into a wallet backend error
+fn sql_err(e: rusqlite::Error) -> Error {
+	ErrorKind::Backend(format!("sqlite: {}", e)).into()
+}
+
+/// `None` mmr index is stored as -1, so it can be part of the primary key
+fn mmr_index_to_sql(mmr_index: &Option<u64>) -> i64 {
+	match mmr_index {
+		Some(i) => *i as i64,
+		None => -1,
+	}
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, Error> {
+	Ok(serde_json::to_string(value)?)
+}
+
+/// Runs a query whose first column is a json `data` column and deserializes
+/// all the rows
+fn query_json<T: DeserializeOwned>(
+	conn: &Connection,
+	sql: &str,
+	params: &[&dyn ToSql],
+) -> Result<Vec<T>, Error> {
+	let mut stmt = conn.prepare(sql).map_err(sql_err)?;
+	let rows = stmt
+		.query_map(params, |row| row.get::<_, String>(0))
+		.map_err(sql_err)?;
+	let mut ret = vec![];
+	for row in rows {
+		let data = row.map_err(sql_err)?;
+		ret.push(serde_json::from_str(&data)?);
+	}
+	Ok(ret)
+}
+
+/// Runs a query returning at most one json `data` row
+fn query_json_opt<T: DeserializeOwned>(
+	conn: &Connection,
+	sql: &str,
+	params: &[&dyn ToSql],
+) -> Result<Option<T>, Error> {
+	let data: Option<String> = conn
+		.query_row(sql, params, |row| row.get(0))
+		.optional()
+		.map_err(sql_err)?;
+	match data {
+		Some(d) => Ok(Some(serde_json::from_str(&d)?)),
+		None => Ok(None),
+	}
+}
+
+fn query_u64_opt(
+	conn: &Connection,
+	sql: &str,
+	params: &[&dyn ToSql],
+) -> Result<Option<u64>, Error> {
+	let res: Option<i64> = conn
+		.query_row(sql, params, |row| row.get(0))
+		.optional()
+		.map_err(sql_err)?;
+	Ok(res.map(|v| v as u64))
+}
+
+fn save_output(conn: &Connection, out: &OutputData) -> Result<(), Error> {
+	conn.execute(
+		"INSERT OR REPLACE INTO outputs (key_id, mmr_index, root_key_id, n_child, commit_hex, \
+		 value, status, height, lock_height, is_coinbase, tx_log_entry, slate_id, is_change, data) \
+		 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+		params![
+			out.key_id.to_hex(),
+			mmr_index_to_sql(&out.mmr_index),
+			out.root_key_id.to_hex(),
+			out.n_child,
+			out.commit,
+			out.value as i64,
+			out.status.to_string(),
+			out.height as i64,
+			out.lock_height as i64,
+			out.is_coinbase,
+			out.tx_log_entry,
+			out.slate_id.map(|u| u.to_string()),
+			out.is_change,
+			to_json(out)?,
+		],
+	)
+	.map_err(sql_err)?;
+	Ok(())
+}
+
+fn get_output(
+	conn: &Connection,
+	id: &Identifier,
+	mmr_index: &Option<u64>,
+) -> Result<OutputData, Error> {
+	let res = query_json_opt(
+		conn,
+		"SELECT data FROM outputs WHERE key_id = ?1 AND mmr_index = ?2",
+		params![id.to_hex(), mmr_index_to_sql(mmr_index)],
+	)?;
+	match res {
+		Some(o) => Ok(o),
+		None => Err(store::Error::NotFoundErr(format!("Key Id: {}", id)).into()),
+	}
+}
+
+/// SQLite-backed wallet storage. All writes are done through a Batch, which
+/// maps onto a SQLite transaction.
+pub struct SqliteBackend<C, K> {
+	/// rusqlite connections are Send but not Sync
+	conn: Mutex<Connection>,
+	config: WalletConfig,
+	/// passphrase: TODO better ways of dealing with this other than storing
+	passphrase: ZeroingString,
+	/// Keychain
+	pub keychain: Option<K>,
+	/// Parent path to use by default for output operations
+	parent_key_id: Identifier,
+	/// wallet to node client
+	w2n_client: C,
+	/// Grin Relay Listener
+	pub grinrelay_listener: Option<Box<dyn Listener>>,
+	/// Open the database without loading the seed
+	watch_only: bool,
+}
+
+impl<C, K> SqliteBackend<C, K> {
+	pub fn new(config: WalletConfig, passphrase: &str, n_client: C) -> Result<Self, Error> {
+		common::create_data_dirs(&config.data_file_dir);
+		let db_file = Path::new(&config.data_file_dir)
+			.join(DB_DIR)
+			.join(SQLITE_DB_FILE);
+
+		let conn = Connection::open(&db_file).map_err(sql_err)?;
+		conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MS))
+			.map_err(sql_err)?;
+		// WAL allows readers in other processes while this wallet writes
+		let _: String = conn
+			.query_row("PRAGMA journal_mode=WAL", NO_PARAMS, |row| row.get(0))
+			.map_err(sql_err)?;
+		conn.execute_batch(SCHEMA).map_err(sql_err)?;
+
+		// Make sure default wallet derivation path always exists
+		// as well as path (so it can be retrieved by batches to know where to store
+		// completed transactions, for reference
+		let default_account = AcctPathMapping {
+			label: "default".to_owned(),
+			path: common::default_path(),
+		};
+		conn.execute(
+			"INSERT OR REPLACE INTO acct_paths (label, path, data) VALUES (?1, ?2, ?3)",
+			params![
+				default_account.label,
+				default_account.path.to_hex(),
+				to_json(&default_account)?,
+			],
+		)
+		.map_err(sql_err)?;
+
+		let res = SqliteBackend {
+			conn: Mutex::new(conn),
+			config: config.clone(),
+			passphrase: ZeroingString::from(passphrase),
+			keychain: None,
+			parent_key_id: common::default_path(),
+			w2n_client: n_client,
+			grinrelay_listener: None,
+			watch_only: false,
+		};
+		Ok(res)
+	}
+
+	/// Just test to see if the SQLite database file exists in the wallet
+	/// data dir
+	pub fn exists(config: WalletConfig) -> bool {
+		Path::new(&config.data_file_dir)
+			.join(DB_DIR)
+			.join(SQLITE_DB_FILE)
+			.exists()
+	}
+}
+
+impl<C, K> WalletBackend<C, K> for SqliteBackend<C, K>
+where
+	C: NodeClient,
+	K: Keychain,
+{
+	/// Initialise with whatever stored credentials we have
+	fn open_with_credentials(&mut self) -> Result<(), Error> {
+		if self.watch_only {
+			return Ok(());
+		}
+		let wallet_seed =
+			WalletSeed::from_file(&self.config.data_file_dir.as_str(), &self.passphrase)
+				.context(ErrorKind::CallbackImpl("Error opening wallet"))?;
+		self.keychain = Some(
+			wallet_seed
+				.derive_keychain(global::is_floonet())
+				.context(ErrorKind::CallbackImpl("Error deriving keychain"))?,
+		);
+		Ok(())
+	}
+
+	/// Close wallet and remove any stored credentials (TBD)
+	fn close(&mut self) -> Result<(), Error> {
+		self.keychain = None;
+		Ok(())
+	}
+
+	/// Return the keychain being used
+	fn keychain(&mut self) -> &mut K {
+		self.keychain.as_mut().unwrap()
+	}
+
+	/// Set or clear watch-only mode
+	fn set_watch_only(&mut self, watch_only: bool) {
+		self.watch_only = watch_only;
+		if watch_only {
+			self.keychain = None;
+		}
+	}
+
+	/// Whether the wallet is opened watch-only
+	fn is_watch_only(&self) -> bool {
+		self.watch_only
+	}
+
+	/// Return the node client being used
+	fn w2n_client(&mut self) -> &mut C {
+		&mut self.w2n_client
+	}
+
+	/// Return the wallet data file dir
+	fn wallet_data_dir(&self) -> &str {
+		self.config.data_file_dir.as_str()
+	}
+
+	/// Update passphrase
+	fn update_passphrase(&mut self, new_password: &str) {
+		self.passphrase = ZeroingString::from(new_password);
+	}
+
+	/// Set the Grin Relay listener
+	fn set_grinrelay_listener(&mut self, listener: Box<dyn Listener>) {
+		self.grinrelay_listener = Some(listener);
+	}
+
+	/// Return the Grin Relay listener
+	fn grinrelay_listener(&self) -> Result<Box<dyn Listener>, Error> {
+		if let Some(ref listener) = self.grinrelay_listener {
+			Ok(listener.clone().into())
+		} else {
+			Err(ErrorKind::GenericError("Grin Relay listener not set!".to_string()).into())
+		}
+	}
+
+	/// Set the policy cancelling the stale outgoing transactions
+	fn set_stale_tx_policy(&mut self, policy: Option<StaleTxPolicy>) {
+		self.config.stale_tx_policy = policy;
+	}
+
+	/// Return the policy cancelling the stale outgoing transactions
+	fn stale_tx_policy(&self) -> Option<StaleTxPolicy> {
+		self.config.stale_tx_policy.clone()
+	}
+
+	/// Set the acceptance policy of the incoming payments
+	fn set_receive_policy(&mut self, policy: Option<ReceivePolicy>) {
+		self.config.receive_policy = policy;
+	}
+
+	/// Return the acceptance policy of the incoming payments
+	fn receive_policy(&self) -> Option<ReceivePolicy> {
+		self.config.receive_policy.clone()
+	}
+
+	/// Return the config section of a transport
+	fn transport_config(&self, name: &str) -> Option<TransportConfig> {
+		self.config.transports.as_ref()?.get(name).cloned()
+	}
+
+	/// return the version of the commit for caching
+	fn calc_commit_for_cache(
+		&mut self,
+		amount: u64,
+		id: &Identifier,
+	) -> Result<Option<String>, Error> {
+		if self.config.no_commit_cache == Some(true) {
+			Ok(None)
+		} else {
+			Ok(Some(util::to_hex(
+				self.keychain()
+					.commit(amount, &id, &SwitchCommitmentType::Regular)?
+					.0
+					.to_vec(), // TODO: proper support for different switch commitment schemes
+			)))
+		}
+	}
+
+	/// Set parent path by account name
+	fn set_parent_key_id_by_name(&mut self, label: &str) -> Result<(), Error> {
+		match self.get_acct_path(label.to_owned())? {
+			Some(a) => {
+				self.set_parent_key_id(a.path);
+				Ok(())
+			}
+			None => Err(ErrorKind::UnknownAccountLabel(label.to_owned()).into()),
+		}
+	}
+
+	/// set parent path
+	fn set_parent_key_id(&mut self, id: Identifier) {
+		self.parent_key_id = id;
+	}
+
+	fn parent_key_id(&mut self) -> Identifier {
+		self.parent_key_id.clone()
+	}
+
+	fn get(&self, id: &Identifier, mmr_index: &Option<u64>) -> Result<OutputData, Error> {
+		get_output(&self.conn.lock(), id, mmr_index)
+	}
+
+	fn iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = OutputData> + 'a>, Error> {
+		let outputs: Vec<OutputData> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM outputs ORDER BY key_id, mmr_index",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(outputs.into_iter()))
+	}
+
+	fn payment_entries_iter_tx<'a>(
+		&'a self,
+		u: &Uuid,
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error> {
+		let payments: Vec<PaymentData> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM payments WHERE slate_id = ?1 ORDER BY commit_hex",
+			params![u.to_string()],
+		)?;
+		Ok(Box::new(payments.into_iter()))
+	}
+
+	fn payment_entries_iter_all<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error> {
+		let payments: Vec<PaymentData> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM payments ORDER BY slate_id, commit_hex",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(payments.into_iter()))
+	}
+
+	fn get_tx_log_entry(&self, u: &Uuid) -> Result<Option<TxLogEntry>, Error> {
+		query_json_opt(
+			&self.conn.lock(),
+			"SELECT data FROM tx_log WHERE tx_slate_id = ?1 LIMIT 1",
+			params![u.to_string()],
+		)
+	}
+
+	fn tx_log_iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = TxLogEntry> + 'a>, Error> {
+		let entries: Vec<TxLogEntry> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM tx_log ORDER BY parent_key_id, id",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(entries.into_iter()))
+	}
+
+	fn get_private_context(
+		&mut self,
+		slate_id: &[u8],
+		participant_id: usize,
+	) -> Result<Context, Error> {
+		let (blind_xor_key, nonce_xor_key) = private_ctx_xor_keys(self.keychain(), slate_id)?;
+
+		let ctx: Option<Context> = query_json_opt(
+			&self.conn.lock(),
+			"SELECT data FROM private_contexts WHERE slate_id = ?1 AND participant_id = ?2",
+			params![slate_id.to_vec(), participant_id as i64],
+		)?;
+		let mut ctx = match ctx {
+			Some(c) => c,
+			None => {
+				return Err(store::Error::NotFoundErr(format!(
+					"Slate id: {:x?}",
+					slate_id.to_vec()
+				))
+				.into());
+			}
+		};
+
+		for i in 0..SECRET_KEY_SIZE {
+			ctx.sec_key.0[i] = ctx.sec_key.0[i] ^ blind_xor_key[i];
+			ctx.sec_nonce.0[i] = ctx.sec_nonce.0[i] ^ nonce_xor_key[i];
+		}
+
+		Ok(ctx)
+	}
+
+	fn acct_path_iter<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = AcctPathMapping> + 'a>, Error> {
+		let accounts: Vec<AcctPathMapping> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM acct_paths ORDER BY label",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(accounts.into_iter()))
+	}
+
+	fn get_acct_path(&self, label: String) -> Result<Option<AcctPathMapping>, Error> {
+		query_json_opt(
+			&self.conn.lock(),
+			"SELECT data FROM acct_paths WHERE label = ?1",
+			params![label],
+		)
+	}
+
+	fn store_tx(&self, uuid: &str, tx: &Transaction) -> Result<(), Error> {
+		common::store_tx(&self.config.data_file_dir, uuid, tx)
+	}
+
+	fn store_tx_proof(&self, uuid: &str, tx_proof: &TxProof) -> Result<(), Error> {
+		common::store_tx_proof(&self.config.data_file_dir, uuid, tx_proof)
+	}
+
+	fn get_stored_tx(&self, entry: &TxLogEntry) -> Result<Option<Transaction>, Error> {
+		common::get_stored_tx(&self.config.data_file_dir, entry)
+	}
+
+	fn get_stored_tx_proof(&self, uuid: &str) -> Result<Option<TxProof>, Error> {
+		common::get_stored_tx_proof(&self.config.data_file_dir, uuid)
+	}
+
+	fn store_payment_proof(&self, uuid: &str, proof: &PaymentProof) -> Result<(), Error> {
+		common::store_payment_proof(&self.config.data_file_dir, uuid, proof)
+	}
+
+	fn get_stored_payment_proof(&self, uuid: &str) -> Result<Option<PaymentProof>, Error> {
+		common::get_stored_payment_proof(&self.config.data_file_dir, uuid)
+	}
+
+	fn batch<'a>(&'a mut self) -> Result<Box<dyn WalletOutputBatch<K> + 'a>, Error> {
+		Ok(Box::new(Batch::new(&self.conn, self.keychain.clone())?))
+	}
+
+	fn current_child_index(&mut self, parent_key_id: &Identifier) -> Result<u32, Error> {
+		let idx = query_u64_opt(
+			&self.conn.lock(),
+			"SELECT child_n FROM child_indices WHERE parent_key_id = ?1",
+			params![parent_key_id.to_hex()],
+		)?;
+		Ok(idx.unwrap_or(0) as u32)
+	}
+
+	fn next_child<'a>(&mut self) -> Result<Identifier, Error> {
+		let parent_key_id = self.parent_key_id.clone();
+		// read and increment in the same write transaction, so two processes
+		// sharing the database can't derive the same child
+		let mut batch = Batch::new(&self.conn, self.keychain.clone())?;
+		let deriv_idx = query_u64_opt(
+			&batch.conn.lock(),
+			"SELECT child_n FROM child_indices WHERE parent_key_id = ?1",
+			params![parent_key_id.to_hex()],
+		)?
+		.unwrap_or(0) as u32;
+		let mut return_path = self.parent_key_id.to_path();
+		return_path.depth = return_path.depth + 1;
+		return_path.path[return_path.depth as usize - 1] = ChildNumber::from(deriv_idx);
+		batch.save_child_index(&parent_key_id, deriv_idx + 1)?;
+		batch.commit()?;
+		Ok(Identifier::from_path(&return_path))
+	}
+
+	fn last_confirmed_height<'a>(&mut self) -> Result<u64, Error> {
+		let height = query_u64_opt(
+			&self.conn.lock(),
+			"SELECT height FROM confirmed_heights WHERE parent_key_id = ?1",
+			params![self.parent_key_id.to_hex()],
+		)?;
+		Ok(height.unwrap_or(0))
+	}
+
+	fn restore(&mut self) -> Result<(), Error> {
+		restore(self).context(ErrorKind::Restore)?;
+		Ok(())
+	}
+
+	fn restore_batch(
+		&mut self,
+		start_index: u64,
+		batch_size: u64,
+	) -> Result<(u64, u64, u64), Error> {
+		let res = restore_batch(self, start_index, batch_size).context(ErrorKind::Restore)?;
+		Ok(res)
+	}
+
+	fn check_repair(&mut self, delete_unconfirmed: bool) -> Result<(), Error> {
+		check_repair(self, delete_unconfirmed).context(ErrorKind::Restore)?;
+		Ok(())
+	}
+
+	fn check_repair_batch(
+		&mut self,
+		delete_unconfirmed: bool,
+		start_index: u64,
+		batch_size: u64,
+	) -> Result<(u64, u64), Error> {
+		let res = check_repair_batch(self, delete_unconfirmed, start_index, batch_size)
+			.context(ErrorKind::Restore)?;
+		Ok(res)
+	}
+}
+
+/// An atomic batch in which all changes can be committed all at once or
+/// discarded on error. Backed by a SQLite transaction which is rolled back
+/// if the batch is dropped without being committed.
+pub struct Batch<'a, K>
+where
+	K: Keychain,
+{
+	conn: &'a Mutex<Connection>,
+	/// Whether the underlying transaction is still open
+	open: Cell<bool>,
+	/// Keychain
+	keychain: Option<K>,
+}
+
+impl<'a, K> Batch<'a, K>
+where
+	K: Keychain,
+{
+	fn new(conn: &'a Mutex<Connection>, keychain: Option<K>) -> Result<Self, Error> {
+		// take the write lock up front, so concurrent writers wait on the
+		// busy timeout instead of failing midway through the batch
+		conn.lock()
+			.execute_batch("BEGIN IMMEDIATE")
+			.map_err(sql_err)?;
+		Ok(Batch {
+			conn,
+			open: Cell::new(true),
+			keychain,
+		})
+	}
+}
+
+impl<'a, K> Drop for Batch<'a, K>
+where
+	K: Keychain,
+{
+	fn drop(&mut self) {
+		if self.open.get() {
+			if let Err(e) = self.conn.lock().execute_batch("ROLLBACK") {
+				error!("sqlite batch rollback failed: {}", e);
+			}
+		}
+	}
+}
+
+#[allow(missing_docs)]
+impl<'a, K> WalletOutputBatch<K> for Batch<'a, K>
+where
+	K: Keychain,
+{
+	fn keychain(&mut self) -> &mut K {
+		self.keychain.as_mut().unwrap()
+	}
+
+	fn save(&mut self, out: OutputData) -> Result<(), Error> {
+		save_output(&self.conn.lock(), &out)
+	}
+
+	fn save_payment(&mut self, out: PaymentData) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO payments (slate_id, commit_hex, value, status, height, \
+				 lock_height, tx_log_entry, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+				params![
+					out.slate_id.to_string(),
+					util::to_hex(out.commit.0.to_vec()),
+					out.value as i64,
+					out.status.to_string(),
+					out.height as i64,
+					out.lock_height as i64,
+					out.id,
+					to_json(&out)?,
+				],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn delete_payment(&mut self, u: &Uuid) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"DELETE FROM payments WHERE slate_id = ?1",
+				params![u.to_string()],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn get(&self, id: &Identifier, mmr_index: &Option<u64>) -> Result<OutputData, Error> {
+		get_output(&self.conn.lock(), id, mmr_index)
+	}
+
+	fn payment_entries_iter_tx(
+		&self,
+		u: &Uuid,
+	) -> Result<Box<dyn Iterator<Item = PaymentData>>, Error> {
+		let payments: Vec<PaymentData> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM payments WHERE slate_id = ?1 ORDER BY commit_hex",
+			params![u.to_string()],
+		)?;
+		Ok(Box::new(payments.into_iter()))
+	}
+
+	fn iter(&self) -> Result<Box<dyn Iterator<Item = OutputData>>, Error> {
+		let outputs: Vec<OutputData> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM outputs ORDER BY key_id, mmr_index",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(outputs.into_iter()))
+	}
+
+	fn delete(&mut self, id: &Identifier, mmr_index: &Option<u64>) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"DELETE FROM outputs WHERE key_id = ?1 AND mmr_index = ?2",
+				params![id.to_hex(), mmr_index_to_sql(mmr_index)],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn next_tx_log_id(&mut self, parent_key_id: &Identifier) -> Result<u32, Error> {
+		let last_tx_log_id = query_u64_opt(
+			&self.conn.lock(),
+			"SELECT next_id FROM tx_log_ids WHERE parent_key_id = ?1",
+			params![parent_key_id.to_hex()],
+		)?
+		.unwrap_or(0);
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO tx_log_ids (parent_key_id, next_id) VALUES (?1, ?2)",
+				params![parent_key_id.to_hex(), (last_tx_log_id + 1) as i64],
+			)
+			.map_err(sql_err)?;
+		Ok(last_tx_log_id as u32)
+	}
+
+	fn save_tx_log_id(&mut self, parent_key_id: &Identifier, next_id: u32) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO tx_log_ids (parent_key_id, next_id) VALUES (?1, ?2)",
+				params![parent_key_id.to_hex(), next_id],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn tx_log_iter(&self) -> Result<Box<dyn Iterator<Item = TxLogEntry>>, Error> {
+		let entries: Vec<TxLogEntry> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM tx_log ORDER BY parent_key_id, id",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(entries.into_iter()))
+	}
+
+	fn save_last_confirmed_height(
+		&mut self,
+		parent_key_id: &Identifier,
+		height: u64,
+	) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO confirmed_heights (parent_key_id, height) VALUES (?1, ?2)",
+				params![parent_key_id.to_hex(), height as i64],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn save_child_index(&mut self, parent_id: &Identifier, child_n: u32) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO child_indices (parent_key_id, child_n) VALUES (?1, ?2)",
+				params![parent_id.to_hex(), child_n],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn save_tx_log_entry(
+		&mut self,
+		tx_in: TxLogEntry,
+		parent_id: &Identifier,
+	) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO tx_log (parent_key_id, id, tx_slate_id, tx_type, \
+				 creation_ts, confirmation_ts, confirmed, height, amount_credited, \
+				 amount_debited, fee, kernel_excess, data) \
+				 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+				params![
+					parent_id.to_hex(),
+					tx_in.id,
+					tx_in.tx_slate_id.map(|u| u.to_string()),
+					tx_in.tx_type.to_string(),
+					tx_in.creation_ts.to_rfc3339(),
+					tx_in.confirmation_ts.map(|t| t.to_rfc3339()),
+					tx_in.confirmed,
+					tx_in.height.map(|h| h as i64),
+					tx_in.amount_credited as i64,
+					tx_in.amount_debited as i64,
+					tx_in.fee.map(|f| f as i64),
+					tx_in.kernel_excess,
+					to_json(&tx_in)?,
+				],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn save_acct_path(&mut self, mapping: AcctPathMapping) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO acct_paths (label, path, data) VALUES (?1, ?2, ?3)",
+				params![mapping.label, mapping.path.to_hex(), to_json(&mapping)?],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn acct_path_iter(&self) -> Result<Box<dyn Iterator<Item = AcctPathMapping>>, Error> {
+		let accounts: Vec<AcctPathMapping> = query_json(
+			&self.conn.lock(),
+			"SELECT data FROM acct_paths ORDER BY label",
+			NO_PARAMS,
+		)?;
+		Ok(Box::new(accounts.into_iter()))
+	}
+
+	fn lock_output(&mut self, out: &mut OutputData) -> Result<(), Error> {
+		out.lock();
+		self.save(out.clone())
+	}
+
+	fn save_private_context(
+		&mut self,
+		slate_id: &[u8],
+		participant_id: usize,
+		ctx: &Context,
+	) -> Result<(), Error> {
+		let (blind_xor_key, nonce_xor_key) = private_ctx_xor_keys(self.keychain(), slate_id)?;
+
+		let mut s_ctx = ctx.clone();
+		for i in 0..SECRET_KEY_SIZE {
+			s_ctx.sec_key.0[i] = s_ctx.sec_key.0[i] ^ blind_xor_key[i];
+			s_ctx.sec_nonce.0[i] = s_ctx.sec_nonce.0[i] ^ nonce_xor_key[i];
+		}
+
+		self.conn
+			.lock()
+			.execute(
+				"INSERT OR REPLACE INTO private_contexts (slate_id, participant_id, data) \
+				 VALUES (?1, ?2, ?3)",
+				params![slate_id.to_vec(), participant_id as i64, to_json(&s_ctx)?],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn delete_private_context(
+		&mut self,
+		slate_id: &[u8],
+		participant_id: usize,
+	) -> Result<(), Error> {
+		self.conn
+			.lock()
+			.execute(
+				"DELETE FROM private_contexts WHERE slate_id = ?1 AND participant_id = ?2",
+				params![slate_id.to_vec(), participant_id as i64],
+			)
+			.map_err(sql_err)?;
+		Ok(())
+	}
+
+	fn commit(&self) -> Result<(), Error> {
+		self.conn.lock().execute_batch("COMMIT").map_err(sql_err)?;
+		self.open.set(false);
+		Ok(())
+	}
+}
@@ -27,6 +27,7 @@ use failure;
 
 use crate::core::global;
 use grin_wallet_libwallet as libwallet;
+use grin_wallet_relay as grinrelay;
 use grin_wallet_util::grin_api as api;
 use grin_wallet_util::grin_chain as chain;
 use grin_wallet_util::grin_core as core;
@@ -43,10 +44,15 @@ mod seed;
 pub mod test_framework;
 
 pub use crate::adapters::{
-	FileWalletCommAdapter, GrinrelayWalletCommAdapter, HTTPWalletCommAdapter,
-	KeybaseWalletCommAdapter, NullWalletCommAdapter, WalletCommAdapter,
+	onion_service_key, socks5_connect, socks_post, tls_post, DirConfig, DirScan, DirStage,
+	DirTransport, DirWalletCommAdapter, FileWalletCommAdapter, GrinrelayWalletCommAdapter,
+	HTTPWalletCommAdapter, HttpTlsConfig, HttpTransportConfig, KeybaseApi, KeybaseListener,
+	KeybaseMessage, KeybaseWalletCommAdapter, NullWalletCommAdapter, TorConfig, TorControl,
+	Transport, TransportCapabilities, TransportFactory, TransportRegistry, WalletCommAdapter,
 };
-pub use crate::backends::{wallet_db_exists, LMDBBackend};
+#[cfg(feature = "sqlite")]
+pub use crate::backends::SqliteBackend;
+pub use crate::backends::{copy_wallet_data, wallet_db_exists, LMDBBackend};
 pub use crate::error::{Error, ErrorKind};
 pub use crate::node_clients::HTTPNodeClient;
 pub use crate::seed::{EncryptedWalletSeed, WalletSeed, SEED_FILE};
@@ -54,15 +60,20 @@ pub use crate::seed::{EncryptedWalletSeed, WalletSeed, SEED_FILE};
 use crate::util::Mutex;
 use std::sync::Arc;
 
+use config::WalletBackendType;
 use libwallet::{NodeClient, WalletBackend, WalletInst};
 
-/// Helper to create an instance of the LMDB wallet
-pub fn instantiate_wallet(
+/// Helper to create an instance of the wallet, on the backend selected
+/// by the wallet config
+pub fn instantiate_wallet<C>(
 	wallet_config: config::WalletConfig,
-	node_client: impl NodeClient + 'static,
+	node_client: C,
 	passphrase: &str,
 	account: &str,
-) -> Result<Arc<Mutex<WalletInst<impl NodeClient, keychain::ExtKeychain>>>, Error> {
+) -> Result<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>, Error>
+where
+	C: NodeClient + 'static,
+{
 	// Set Chain Type
 	let chain_type = wallet_config.chain_type.clone();
 	if let Some(chain_type) = chain_type {
@@ -71,8 +82,152 @@ pub fn instantiate_wallet(
 
 	// First test decryption, so we can abort early if we have the wrong password
 	let _ = WalletSeed::from_file(wallet_config.data_file_dir.as_str(), passphrase)?;
-	let mut db_wallet = LMDBBackend::new(wallet_config.clone(), passphrase, node_client)?;
-	db_wallet.set_parent_key_id_by_name(account)?;
-	info!("A Wallet instance instantiated");
-	Ok(Arc::new(Mutex::new(db_wallet)))
+	open_wallet_db(wallet_config, node_client, passphrase, account)
+}
+
+/// Helper to create an instance of the wallet in watch-only mode. The seed
+/// is never loaded, balances and history are served from the database and
+/// its cached output commitments, and any operation needing the wallet keys
+/// is refused.
+pub fn instantiate_watch_only_wallet<C>(
+	wallet_config: config::WalletConfig,
+	node_client: C,
+	account: &str,
+) -> Result<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>, Error>
+where
+	C: NodeClient + 'static,
+{
+	let chain_type = wallet_config.chain_type.clone();
+	if let Some(chain_type) = chain_type {
+		global::set_mining_mode(chain_type);
+	}
+
+	if wallet_config.no_commit_cache == Some(true) {
+		warn!("The commit cache is disabled, outputs without a cached commit can't be shown in watch-only mode");
+	}
+	let wallet = open_wallet_db(wallet_config, node_client, "", account)?;
+	wallet.lock().set_watch_only(true);
+	Ok(wallet)
+}
+
+/// Helper to create an instance of a wallet which has no seed yet, so the
+/// Owner API can create or recover its seed. Any other operation fails
+/// until then.
+pub fn instantiate_wallet_without_seed<C>(
+	wallet_config: config::WalletConfig,
+	node_client: C,
+	account: &str,
+) -> Result<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>, Error>
+where
+	C: NodeClient + 'static,
+{
+	let chain_type = wallet_config.chain_type.clone();
+	if let Some(chain_type) = chain_type {
+		global::set_mining_mode(chain_type);
+	}
+
+	open_wallet_db(wallet_config, node_client, "", account)
+}
+
+fn open_wallet_db<C>(
+	wallet_config: config::WalletConfig,
+	node_client: C,
+	passphrase: &str,
+	account: &str,
+) -> Result<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>, Error>
+where
+	C: NodeClient + 'static,
+{
+	let wallet: Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>> = match wallet_config
+		.wallet_backend()
+	{
+		WalletBackendType::Lmdb => {
+			let mut db_wallet = LMDBBackend::new(wallet_config.clone(), passphrase, node_client)?;
+			db_wallet.set_parent_key_id_by_name(account)?;
+			Arc::new(Mutex::new(db_wallet))
+		}
+		#[cfg(feature = "sqlite")]
+		WalletBackendType::Sqlite => {
+			let mut db_wallet = SqliteBackend::new(wallet_config.clone(), passphrase, node_client)?;
+			db_wallet.set_parent_key_id_by_name(account)?;
+			Arc::new(Mutex::new(db_wallet))
+		}
+		#[cfg(not(feature = "sqlite"))]
+		WalletBackendType::Sqlite => return Err(sqlite_not_supported()),
+	};
+	info!(
+		"A Wallet instance instantiated, on {} backend",
+		wallet_config.wallet_backend()
+	);
+	Ok(wallet)
+}
+
+/// Helper to create the database of the backend selected by the wallet config
+pub fn init_wallet_db<C>(
+	wallet_config: &config::WalletConfig,
+	node_client: C,
+	passphrase: &str,
+) -> Result<(), Error>
+where
+	C: NodeClient + 'static,
+{
+	match wallet_config.wallet_backend() {
+		WalletBackendType::Lmdb => {
+			let _: LMDBBackend<C, keychain::ExtKeychain> =
+				LMDBBackend::new(wallet_config.clone(), passphrase, node_client)?;
+		}
+		#[cfg(feature = "sqlite")]
+		WalletBackendType::Sqlite => {
+			let _: SqliteBackend<C, keychain::ExtKeychain> =
+				SqliteBackend::new(wallet_config.clone(), passphrase, node_client)?;
+		}
+		#[cfg(not(feature = "sqlite"))]
+		WalletBackendType::Sqlite => return Err(sqlite_not_supported()),
+	}
+	Ok(())
+}
+
+/// Helper to copy the wallet database from the other backend into the `to`
+/// backend. The wallet config still has to be updated to use the converted
+/// database afterwards.
+pub fn convert_wallet_db<C>(
+	wallet_config: &config::WalletConfig,
+	node_client: C,
+	passphrase: &str,
+	to: WalletBackendType,
+) -> Result<(), Error>
+where
+	C: NodeClient + 'static,
+{
+	let _ = WalletSeed::from_file(wallet_config.data_file_dir.as_str(), passphrase)?;
+	#[cfg(feature = "sqlite")]
+	{
+		let mut lmdb: LMDBBackend<C, keychain::ExtKeychain> =
+			LMDBBackend::new(wallet_config.clone(), passphrase, node_client.clone())?;
+		let mut sqlite: SqliteBackend<C, keychain::ExtKeychain> =
+			SqliteBackend::new(wallet_config.clone(), passphrase, node_client)?;
+		lmdb.open_with_credentials()?;
+		sqlite.open_with_credentials()?;
+		match to {
+			WalletBackendType::Sqlite => copy_wallet_data(&mut lmdb, &mut sqlite)?,
+			WalletBackendType::Lmdb => copy_wallet_data(&mut sqlite, &mut lmdb)?,
+		}
+		lmdb.close()?;
+		sqlite.close()?;
+		info!("Wallet database converted to {} backend", to);
+		Ok(())
+	}
+	#[cfg(not(feature = "sqlite"))]
+	{
+		let _ = (node_client, to);
+		Err(sqlite_not_supported())
+	}
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_not_supported() -> Error {
+	ErrorKind::GenericError(
+		"SQLite wallet backend not supported, wallet built without the 'sqlite' feature".to_owned(),
+	)
+	.into()
 }
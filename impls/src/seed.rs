@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::Path;
@@ -32,12 +33,39 @@ use failure::ResultExt;
 
 pub const SEED_FILE: &'static str = "wallet.seed";
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct WalletSeed(Vec<u8>);
+/// Number of PBKDF2 rounds of the BIP39 seed derivation
+const BIP39_PBKDF2_ROUNDS: u32 = 2048;
+
+/// Wallet seed, with the optional BIP39 passphrase (the "25th word") used
+/// along with it in the keychain derivation
+#[derive(Clone, PartialEq)]
+pub struct WalletSeed(Vec<u8>, Option<util::ZeroingString>);
+
+impl fmt::Debug for WalletSeed {
+	// neither the seed nor the passphrase ever end up in a log
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("WalletSeed")
+			.field("len", &self.0.len())
+			.field("has_passphrase", &self.has_passphrase())
+			.finish()
+	}
+}
 
 impl WalletSeed {
 	pub fn from_bytes(bytes: &[u8]) -> WalletSeed {
-		WalletSeed(bytes.to_vec())
+		WalletSeed(bytes.to_vec(), None)
+	}
+
+	/// Set the BIP39 passphrase of the seed. An empty passphrase is no
+	/// passphrase.
+	pub fn with_passphrase(mut self, passphrase: Option<util::ZeroingString>) -> WalletSeed {
+		self.1 = passphrase.filter(|p| !p.is_empty());
+		self
+	}
+
+	/// Whether the seed has a BIP39 passphrase
+	pub fn has_passphrase(&self) -> bool {
+		self.1.is_some()
 	}
 
 	pub fn from_mnemonic(word_list: &str) -> Result<WalletSeed, Error> {
@@ -71,18 +99,40 @@ impl WalletSeed {
 		seed.as_bytes().to_vec()
 	}
 
+	/// Derive the keychain of the wallet. Without a passphrase the keychain
+	/// is derived from the seed bytes as it has always been, with one it is
+	/// derived from the BIP39 seed of the recovery phrase and passphrase.
 	pub fn derive_keychain<K: Keychain>(&self, is_floonet: bool) -> Result<K, Error> {
-		let result = K::from_seed(&self.0, is_floonet)?;
+		let result = match self.1 {
+			Some(ref passphrase) => K::from_seed(&self.to_bip39_seed(passphrase)?, is_floonet)?,
+			None => K::from_seed(&self.0, is_floonet)?,
+		};
 		Ok(result)
 	}
 
+	/// BIP39 seed: PBKDF2-HMAC-SHA512 of the recovery phrase, salted with
+	/// "mnemonic" and the passphrase
+	fn to_bip39_seed(&self, passphrase: &str) -> Result<[u8; 64], Error> {
+		let phrase = util::ZeroingString::from(self.to_mnemonic()?);
+		let salt = util::ZeroingString::from(format!("mnemonic{}", passphrase));
+		let mut seed = [0; 64];
+		pbkdf2::derive(
+			&digest::SHA512,
+			BIP39_PBKDF2_ROUNDS,
+			salt.as_bytes(),
+			phrase.as_bytes(),
+			&mut seed,
+		);
+		Ok(seed)
+	}
+
 	pub fn init_new(seed_length: usize) -> WalletSeed {
 		let mut seed: Vec<u8> = vec![];
 		let mut rng = thread_rng();
 		for _ in 0..seed_length {
 			seed.push(rng.gen());
 		}
-		WalletSeed(seed)
+		WalletSeed(seed, None)
 	}
 
 	pub fn seed_file_exists(data_file_dir: &str) -> Result<(), Error> {
@@ -129,6 +179,7 @@ impl WalletSeed {
 	pub fn recover_from_phrase(
 		data_file_dir: &str,
 		word_list: &str,
+		passphrase: Option<util::ZeroingString>,
 		password: &str,
 	) -> Result<(), Error> {
 		let seed_file_path = &format!("{}{}{}", data_file_dir, MAIN_SEPARATOR, SEED_FILE,);
@@ -142,7 +193,7 @@ impl WalletSeed {
 					.to_owned(),
 			))?;
 		}
-		let seed = WalletSeed::from_mnemonic(word_list)?;
+		let seed = WalletSeed::from_mnemonic(word_list)?.with_passphrase(passphrase);
 		let enc_seed = EncryptedWalletSeed::from_seed(&seed, password)?;
 		let enc_seed_json = serde_json::to_string_pretty(&enc_seed).context(ErrorKind::Format)?;
 		let mut file = File::create(seed_file_path).context(ErrorKind::IO)?;
@@ -158,6 +209,10 @@ impl WalletSeed {
 		println!("{}", self.to_mnemonic()?);
 		println!();
 		println!("Please back-up these words in a non-digital format.");
+		if self.has_passphrase() {
+			println!("This wallet also has a BIP39 passphrase, needed along with these words");
+			println!("to recover it.");
+		}
 		Ok(())
 	}
 
@@ -165,6 +220,7 @@ impl WalletSeed {
 		data_file_dir: &str,
 		seed_length: usize,
 		recovery_phrase: Option<util::ZeroingString>,
+		passphrase: Option<util::ZeroingString>,
 		password: &str,
 		remind_phrase_backup: bool,
 	) -> Result<WalletSeed, Error> {
@@ -179,7 +235,8 @@ impl WalletSeed {
 		let seed = match recovery_phrase {
 			Some(p) => WalletSeed::from_mnemonic(&p)?,
 			None => WalletSeed::init_new(seed_length),
-		};
+		}
+		.with_passphrase(passphrase);
 
 		let enc_seed = EncryptedWalletSeed::from_seed(&seed, password)?;
 		let enc_seed_json = serde_json::to_string_pretty(&enc_seed).context(ErrorKind::Format)?;
@@ -249,6 +306,12 @@ pub struct EncryptedWalletSeed {
 	pub salt: String,
 	/// Nonce
 	pub nonce: String,
+	/// Encrypted BIP39 passphrase, only for seeds with a passphrase
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	encrypted_passphrase: Option<String>,
+	/// Nonce of the passphrase encryption
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub passphrase_nonce: Option<String>,
 }
 
 impl EncryptedWalletSeed {
@@ -256,49 +319,115 @@ impl EncryptedWalletSeed {
 	pub fn from_seed(seed: &WalletSeed, password: &str) -> Result<EncryptedWalletSeed, Error> {
 		let salt: [u8; 8] = thread_rng().gen();
 		let nonce: [u8; 12] = thread_rng().gen();
-		let password = password.as_bytes();
-		let mut key = [0; 32];
-		pbkdf2::derive(&digest::SHA512, 100, &salt, password, &mut key);
-		let content = seed.0.to_vec();
-		let mut enc_bytes = content.clone();
-		let suffix_len = aead::CHACHA20_POLY1305.tag_len();
-		for _ in 0..suffix_len {
-			enc_bytes.push(0);
-		}
-		let sealing_key =
-			aead::SealingKey::new(&aead::CHACHA20_POLY1305, &key).context(ErrorKind::Encryption)?;
-		aead::seal_in_place(&sealing_key, &nonce, &[], &mut enc_bytes, suffix_len)
-			.context(ErrorKind::Encryption)?;
+		let key = EncryptedWalletSeed::derive_key(password, &salt);
+		let encrypted_seed = EncryptedWalletSeed::seal(&key, &nonce, &seed.0)?;
+		let (encrypted_passphrase, passphrase_nonce) = match seed.1 {
+			Some(ref passphrase) => {
+				let nonce: [u8; 12] = thread_rng().gen();
+				let enc_bytes = EncryptedWalletSeed::seal(&key, &nonce, passphrase.as_bytes())?;
+				(
+					Some(util::to_hex(enc_bytes)),
+					Some(util::to_hex(nonce.to_vec())),
+				)
+			}
+			None => (None, None),
+		};
 		Ok(EncryptedWalletSeed {
-			encrypted_seed: util::to_hex(enc_bytes.to_vec()),
+			encrypted_seed: util::to_hex(encrypted_seed),
 			salt: util::to_hex(salt.to_vec()),
 			nonce: util::to_hex(nonce.to_vec()),
+			encrypted_passphrase,
+			passphrase_nonce,
 		})
 	}
 
 	/// Decrypt seed
 	pub fn decrypt(&self, password: &str) -> Result<WalletSeed, Error> {
-		let mut encrypted_seed = match util::from_hex(self.encrypted_seed.clone()) {
-			Ok(s) => s,
-			Err(_) => return Err(ErrorKind::Encryption)?,
+		let encrypted_seed = EncryptedWalletSeed::from_hex(&self.encrypted_seed)?;
+		let salt = EncryptedWalletSeed::from_hex(&self.salt)?;
+		let nonce = EncryptedWalletSeed::from_hex(&self.nonce)?;
+		let key = EncryptedWalletSeed::derive_key(password, &salt);
+		let decrypted_data = EncryptedWalletSeed::open(&key, &nonce, encrypted_seed)?;
+
+		let passphrase = match (&self.encrypted_passphrase, &self.passphrase_nonce) {
+			(Some(p), Some(n)) => {
+				let encrypted_passphrase = EncryptedWalletSeed::from_hex(p)?;
+				let nonce = EncryptedWalletSeed::from_hex(n)?;
+				let passphrase = EncryptedWalletSeed::open(&key, &nonce, encrypted_passphrase)?;
+				let passphrase =
+					String::from_utf8(passphrase).map_err(|_| ErrorKind::Encryption)?;
+				Some(util::ZeroingString::from(passphrase))
+			}
+			(None, None) => None,
+			_ => return Err(ErrorKind::Encryption)?,
 		};
-		let salt = match util::from_hex(self.salt.clone()) {
-			Ok(s) => s,
-			Err(_) => return Err(ErrorKind::Encryption)?,
-		};
-		let nonce = match util::from_hex(self.nonce.clone()) {
-			Ok(s) => s,
-			Err(_) => return Err(ErrorKind::Encryption)?,
-		};
-		let password = password.as_bytes();
+
+		Ok(WalletSeed::from_bytes(&decrypted_data).with_passphrase(passphrase))
+	}
+
+	fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
 		let mut key = [0; 32];
-		pbkdf2::derive(&digest::SHA512, 100, &salt, password, &mut key);
+		pbkdf2::derive(&digest::SHA512, 100, salt, password.as_bytes(), &mut key);
+		key
+	}
 
+	fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+		match util::from_hex(hex.to_owned()) {
+			Ok(s) => Ok(s),
+			Err(_) => Err(ErrorKind::Encryption)?,
+		}
+	}
+
+	fn seal(key: &[u8; 32], nonce: &[u8], content: &[u8]) -> Result<Vec<u8>, Error> {
+		let mut enc_bytes = content.to_vec();
+		let suffix_len = aead::CHACHA20_POLY1305.tag_len();
+		for _ in 0..suffix_len {
+			enc_bytes.push(0);
+		}
+		let sealing_key =
+			aead::SealingKey::new(&aead::CHACHA20_POLY1305, key).context(ErrorKind::Encryption)?;
+		aead::seal_in_place(&sealing_key, nonce, &[], &mut enc_bytes, suffix_len)
+			.context(ErrorKind::Encryption)?;
+		Ok(enc_bytes)
+	}
+
+	fn open(key: &[u8; 32], nonce: &[u8], mut encrypted: Vec<u8>) -> Result<Vec<u8>, Error> {
 		let opening_key =
-			aead::OpeningKey::new(&aead::CHACHA20_POLY1305, &key).context(ErrorKind::Encryption)?;
-		let decrypted_data = aead::open_in_place(&opening_key, &nonce, &[], 0, &mut encrypted_seed)
+			aead::OpeningKey::new(&aead::CHACHA20_POLY1305, key).context(ErrorKind::Encryption)?;
+		let decrypted_data = aead::open_in_place(&opening_key, nonce, &[], 0, &mut encrypted)
 			.context(ErrorKind::Encryption)?;
+		Ok(decrypted_data.to_vec())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bip39_seed() {
+		// first test vector of the BIP39 reference implementation
+		let seed = WalletSeed::from_bytes(&[0; 16]);
+		let bip39_seed = seed.to_bip39_seed("TREZOR").unwrap();
+		assert_eq!(
+			util::to_hex(bip39_seed.to_vec()),
+			"c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+			 1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+		);
+	}
 
-		Ok(WalletSeed::from_bytes(&decrypted_data))
+	#[test]
+	fn encrypted_passphrase() {
+		let seed = WalletSeed::init_new(32);
+		let enc_seed = EncryptedWalletSeed::from_seed(&seed, "password").unwrap();
+		assert!(enc_seed.passphrase_nonce.is_none());
+		assert_eq!(enc_seed.decrypt("password").unwrap(), seed);
+
+		let seed = seed.with_passphrase(Some(util::ZeroingString::from("hidden")));
+		let enc_seed = EncryptedWalletSeed::from_seed(&seed, "password").unwrap();
+		let dec_seed = enc_seed.decrypt("password").unwrap();
+		assert!(dec_seed.has_passphrase());
+		assert_eq!(dec_seed, seed);
+		assert!(enc_seed.decrypt("wrong").is_err());
 	}
 }
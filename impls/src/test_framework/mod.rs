@@ -16,7 +16,7 @@
 use crate::api;
 use crate::chain;
 use crate::chain::Chain;
-use crate::config::WalletConfig;
+use crate::config::{WalletBackendType, WalletConfig};
 use crate::core;
 use crate::core::core::{OutputFeatures, OutputIdentifier, Transaction};
 use crate::core::{consensus, global, pow};
@@ -30,6 +30,8 @@ use crate::util;
 use crate::util::secp::pedersen;
 use crate::util::Mutex;
 use crate::LMDBBackend;
+#[cfg(feature = "sqlite")]
+use crate::SqliteBackend;
 use crate::WalletSeed;
 use chrono::Duration;
 use std::sync::Arc;
@@ -161,6 +163,20 @@ pub fn create_wallet<C, K>(
 	n_client: C,
 	rec_phrase: Option<&str>,
 ) -> Arc<Mutex<dyn WalletInst<C, K>>>
+where
+	C: NodeClient + 'static,
+	K: keychain::Keychain + 'static,
+{
+	create_wallet_with_backend(dir, n_client, rec_phrase, WalletBackendType::Lmdb)
+}
+
+/// dispatch a db wallet stored in the given database backend
+pub fn create_wallet_with_backend<C, K>(
+	dir: &str,
+	n_client: C,
+	rec_phrase: Option<&str>,
+	backend: WalletBackendType,
+) -> Arc<Mutex<dyn WalletInst<C, K>>>
 where
 	C: NodeClient + 'static,
 	K: keychain::Keychain + 'static,
@@ -171,8 +187,40 @@ where
 	};
 	let mut wallet_config = WalletConfig::default();
 	wallet_config.data_file_dir = String::from(dir);
-	let _ = WalletSeed::init_file(wallet_config.data_file_dir.as_str(), 32, z_string, "", true);
-	let mut wallet = LMDBBackend::new(wallet_config.clone(), "", n_client)
+	wallet_config.wallet_backend = Some(backend.clone());
+	let _ = WalletSeed::init_file(
+		wallet_config.data_file_dir.as_str(),
+		32,
+		z_string,
+		None,
+		"",
+		true,
+	);
+	match backend {
+		WalletBackendType::Lmdb => open_wallet(
+			LMDBBackend::new(wallet_config.clone(), "", n_client),
+			&wallet_config,
+		),
+		#[cfg(feature = "sqlite")]
+		WalletBackendType::Sqlite => open_wallet(
+			SqliteBackend::new(wallet_config.clone(), "", n_client),
+			&wallet_config,
+		),
+		#[cfg(not(feature = "sqlite"))]
+		WalletBackendType::Sqlite => panic!("wallet built without the 'sqlite' feature"),
+	}
+}
+
+fn open_wallet<W, C, K>(
+	wallet: Result<W, libwallet::Error>,
+	wallet_config: &WalletConfig,
+) -> Arc<Mutex<dyn WalletInst<C, K>>>
+where
+	W: WalletInst<C, K>,
+	C: NodeClient + 'static,
+	K: keychain::Keychain + 'static,
+{
+	let mut wallet = wallet
 		.unwrap_or_else(|e| panic!("Error creating wallet: {:?} Config: {:?}", e, wallet_config));
 	wallet.open_with_credentials().unwrap_or_else(|e| {
 		panic!(
@@ -25,9 +25,8 @@ use crate::core::core::verifier_cache::LruVerifierCache;
 use crate::core::core::{Transaction, TxKernelApiEntry};
 use crate::core::global::{set_mining_mode, ChainTypes};
 use crate::core::{self, pow};
+use crate::grinrelay::receive_tx_with_proof;
 use crate::keychain::Keychain;
-use crate::libwallet::api_impl::foreign;
-use crate::libwallet::slate_versions::v2::SlateV2;
 use crate::libwallet::{NodeClient, NodeVersionInfo, Slate, TxProof, TxWrapper, WalletInst};
 use crate::util;
 use crate::util::secp::pedersen;
@@ -212,22 +211,22 @@ where
 			Some(w) => w,
 		};
 
-		let slate: SlateV2 = serde_json::from_str(&m.body).context(
+		let slate: Slate = serde_json::from_str(&m.body).context(
 			libwallet::ErrorKind::ClientCallback("Error parsing TxWrapper".to_owned()),
 		)?;
 
 		let slate: Slate = {
 			let mut w = wallet.1.lock();
 			w.open_with_credentials()?;
-			// receive tx
-			foreign::receive_tx(&mut *w, &Slate::from(slate), None, None, None, false)?
+			// receive tx, signing its payment proof
+			receive_tx_with_proof(&mut *w, &slate, None, None, None, None, false)?
 		};
 
 		Ok(WalletProxyMessage {
 			sender_id: m.dest,
 			dest: m.sender_id,
 			method: m.method,
-			body: serde_json::to_string(&SlateV2::from(slate)).unwrap(),
+			body: serde_json::to_string(&slate).unwrap(),
 		})
 	}
 
@@ -356,7 +355,7 @@ impl LocalWalletClient {
 			sender_id: self.id.clone(),
 			dest: dest.to_owned(),
 			method: "send_tx_slate".to_owned(),
-			body: serde_json::to_string(&SlateV2::from(slate)).unwrap(),
+			body: serde_json::to_string(slate).unwrap(),
 		};
 		{
 			let p = self.proxy_tx.lock();
@@ -367,10 +366,10 @@ impl LocalWalletClient {
 		let r = self.rx.lock();
 		let m = r.recv().unwrap();
 		trace!("Received send_tx_slate response: {:?}", m.clone());
-		let slate: SlateV2 = serde_json::from_str(&m.body).context(
+		let slate: Slate = serde_json::from_str(&m.body).context(
 			libwallet::ErrorKind::ClientCallback("Parsing send_tx_slate response".to_owned()),
 		)?;
-		Ok(Slate::from(slate))
+		Ok(slate)
 	}
 }
 
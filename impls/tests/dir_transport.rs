@@ -0,0 +1,191 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Directory transport tests, receiving and finalizing through closures
+extern crate grin_wallet_config as config;
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use grin_wallet_util::grin_util::secp::key::{PublicKey, SecretKey};
+use grin_wallet_util::grin_util::secp::Secp256k1;
+
+use config::TransportConfig;
+use impls::{DirConfig, DirScan, DirStage, DirTransport, TransportRegistry};
+use libwallet::{ErrorKind, ParticipantData, Slate};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Add the data of a participant, as when signing the slate
+fn sign(slate: &Slate) -> Slate {
+	let secp = Secp256k1::new();
+	let key = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&secp, &[1; 32]).unwrap())
+		.unwrap();
+	let mut slate = slate.clone();
+	slate.participant_data.push(ParticipantData {
+		id: slate.participant_data.len() as u64,
+		public_blind_excess: key,
+		public_nonce: key,
+		part_sig: None,
+		message: None,
+		message_sig: None,
+	});
+	slate
+}
+
+fn drop_slate(dir: &str, name: &str, slate: &Slate) {
+	fs::write(
+		Path::new(dir).join(name),
+		serde_json::to_string(slate).unwrap(),
+	)
+	.unwrap();
+}
+
+fn count(dir: &Path) -> usize {
+	fs::read_dir(dir).map(|d| d.count()).unwrap_or(0)
+}
+
+#[test]
+fn dir_transport() {
+	let test_dir = "test_output/dir_transport";
+	let _ = fs::remove_dir_all(test_dir);
+	let inbox = format!("{}/inbox", test_dir);
+	let outbox = format!("{}/outbox", test_dir);
+	let archive = format!("{}/archive", test_dir);
+	fs::create_dir_all(&inbox).unwrap();
+
+	// sending through the registry, out of the config section
+	let mut registry = TransportRegistry::new();
+	assert!(registry.adapter("dir").is_err());
+	let section = format!("inbox = \"{}\"\noutbox = \"{}\"\n", inbox, outbox);
+	let mut sections = BTreeMap::new();
+	sections.insert(
+		"dir".to_owned(),
+		section.parse::<TransportConfig>().unwrap(),
+	);
+	registry.set_config(sections);
+	let (transport, dest) = registry.resolve("dir", "dir://partner").unwrap();
+	assert!(transport.capabilities.listen && !transport.capabilities.send_sync);
+	let sent = Slate::blank(2);
+	registry
+		.adapter("dir")
+		.unwrap()
+		.send_tx_async(&dest, &sent)
+		.unwrap();
+	assert!(Path::new(&outbox)
+		.join(format!("partner/{}.tx", sent.id))
+		.exists());
+
+	// the destination stays inside the outbox
+	let adapter = registry.adapter("dir").unwrap();
+	assert!(adapter.send_tx_async("../partner", &sent).is_err());
+	assert!(adapter.send_tx_async("partner/../../x", &sent).is_err());
+	assert!(adapter.send_tx_sync("partner", &sent).is_err());
+
+	let transport = DirTransport::new(DirConfig {
+		inbox: inbox.clone(),
+		outbox: outbox.clone(),
+		archive: Some(archive.clone()),
+		poll_secs: None,
+	});
+	let mut received = vec![];
+	let mut finalized = vec![];
+
+	// a new slate, a slate being written and a broken one
+	let incoming = Slate::blank(2);
+	assert_eq!(DirStage::of(&incoming), DirStage::Received);
+	drop_slate(&inbox, "incoming.tx", &incoming);
+	drop_slate(&inbox, "later.tx.part", &Slate::blank(2));
+	fs::write(Path::new(&inbox).join("broken.tx"), "not a slate").unwrap();
+	let scan = transport
+		.scan(
+			|s| {
+				received.push(s.id);
+				Ok(sign(s))
+			},
+			|s| {
+				finalized.push(s.id);
+				Ok(())
+			},
+		)
+		.unwrap();
+	assert_eq!(
+		scan,
+		DirScan {
+			processed: 1,
+			duplicates: 0,
+			failed: 1,
+		}
+	);
+	assert_eq!(received, vec![incoming.id]);
+	let response = Path::new(&outbox).join(format!("{}.response", incoming.id));
+	let signed = fs::read_to_string(&response).unwrap();
+	assert_eq!(
+		Slate::deserialize_upgrade(&signed)
+			.unwrap()
+			.participant_data
+			.len(),
+		1
+	);
+	assert!(Path::new(&archive)
+		.join(format!("{}.received", incoming.id))
+		.exists());
+	assert_eq!(count(&Path::new(&archive).join("failed")), 2);
+	assert_eq!(transport.pending().unwrap().len(), 0);
+	assert!(Path::new(&inbox).join("later.tx.part").exists());
+
+	// the same slate again is not received twice, but answered again
+	fs::remove_file(&response).unwrap();
+	drop_slate(&inbox, "incoming-again.tx", &incoming);
+	let scan = transport.scan(|s| Ok(sign(s)), |_| Ok(())).unwrap();
+	assert_eq!((scan.processed, scan.duplicates), (0, 1));
+	assert_eq!(fs::read_to_string(&response).unwrap(), signed);
+
+	// the response to the slate we sent is finalized, once
+	let answer = sign(&sign(&sent));
+	assert_eq!(DirStage::of(&answer), DirStage::Finalized);
+	drop_slate(&inbox, "answer.tx", &answer);
+	drop_slate(&inbox, "answer-again.tx", &answer);
+	let scan = transport
+		.scan(
+			|_| Err(ErrorKind::GenericError("unexpected".to_owned()))?,
+			|s| {
+				finalized.push(s.id);
+				Ok(())
+			},
+		)
+		.unwrap();
+	assert_eq!((scan.processed, scan.duplicates), (1, 1));
+	assert_eq!(finalized, vec![sent.id]);
+
+	// a slate failing to finalize is set aside with its error
+	let other = sign(&sign(&Slate::blank(2)));
+	drop_slate(&inbox, "other.tx", &other);
+	let scan = transport
+		.scan(
+			|s| Ok(sign(s)),
+			|_| Err(ErrorKind::GenericError("not ours".to_owned()))?,
+		)
+		.unwrap();
+	assert_eq!((scan.processed, scan.failed), (0, 1));
+	let failed = Path::new(&archive).join("failed");
+	assert_eq!(count(&failed), 4);
+	let errors: Vec<String> = fs::read_dir(&failed)
+		.unwrap()
+		.map(|e| e.unwrap().path())
+		.filter(|p| p.extension().map_or(false, |e| e == "error"))
+		.map(|p| fs::read_to_string(p).unwrap())
+		.collect();
+	assert!(errors.iter().any(|e| e.contains("not ours")));
+}
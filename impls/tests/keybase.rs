@@ -0,0 +1,196 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keybase chat API client tests, against a fake keybase
+#![cfg(unix)]
+extern crate grin_wallet_impls as impls;
+extern crate grin_wallet_libwallet as libwallet;
+
+use impls::{KeybaseApi, KeybaseMessage, KeybaseWalletCommAdapter, WalletCommAdapter};
+use libwallet::Slate;
+use serde_json::json;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+/// Write a fake keybase, pushing `notifications` to `api-listen` and
+/// answering each `api` request as sent
+fn fake_keybase(test_dir: &str, notifications: &str) -> String {
+	let _ = fs::remove_dir_all(test_dir);
+	fs::create_dir_all(test_dir).unwrap();
+	let dir = fs::canonicalize(test_dir).unwrap();
+	let dir = dir.to_str().unwrap();
+	fs::write(format!("{}/notifications", dir), notifications).unwrap();
+	let script = format!(
+		r#"#!/bin/sh
+case "$1 $2" in
+"chat api-listen")
+	cat {dir}/notifications
+	;;
+"chat api")
+	echo started >> {dir}/starts
+	while read -r line; do
+		printf "%s\n" "$line" >> {dir}/requests
+		echo '{{"result": {{"message": "message sent"}}}}'
+	done
+	;;
+"status -json")
+	echo '{{"Username": "bob"}}'
+	;;
+esac
+"#,
+		dir = dir
+	);
+	let program = format!("{}/keybase", dir);
+	fs::write(&program, script).unwrap();
+	fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+	program
+}
+
+fn notification(
+	id: u64,
+	channel: &str,
+	topic: &str,
+	sender: &str,
+	content: serde_json::Value,
+) -> String {
+	serde_json::to_string_pretty(&json!({
+		"type": "chat",
+		"source": "remote",
+		"msg": {
+			"id": id,
+			"channel": {"name": channel, "topic_type": "dev", "topic_name": topic},
+			"sender": {"username": sender},
+			"content": content,
+		}
+	}))
+	.unwrap()
+}
+
+#[test]
+fn keybase_listen() {
+	let text = |body: &str| json!({"type": "text", "text": {"body": body}});
+	// pretty printed and back to back notifications, and a reaction to skip
+	let notifications = format!(
+		"{}{}\n{}\n",
+		notification(1, "alice,bob", "grin_slate_new", "alice", text("first")),
+		notification(
+			2,
+			"alice,bob",
+			"grin_slate_new",
+			"alice",
+			json!({"type": "reaction"})
+		),
+		notification(3, "alice,bob", "grin_slate_signed", "alice", text("second")),
+	);
+	let program = fake_keybase("test_output/keybase_listen", &notifications);
+	let api = KeybaseApi::new(&program);
+
+	let listener = api.listen().unwrap();
+	assert_eq!(
+		listener.recv(),
+		Some(KeybaseMessage {
+			id: 1,
+			channel: "alice,bob".to_owned(),
+			topic: "grin_slate_new".to_owned(),
+			sender: "alice".to_owned(),
+			body: "first".to_owned(),
+		})
+	);
+	let second = listener.recv().unwrap();
+	assert_eq!((second.id, second.body.as_str()), (3, "second"));
+	// the stream ends with keybase
+	assert_eq!(listener.recv(), None);
+
+	// the requests all go through a single keybase process
+	for _ in 0..3 {
+		let res = api.call(&json!({"method": "list"})).unwrap();
+		assert_eq!(res["result"]["message"], "message sent");
+	}
+	api.mark_read(&second).unwrap();
+	let starts = fs::read_to_string("test_output/keybase_listen/starts").unwrap();
+	assert_eq!(starts.lines().count(), 1);
+	let requests = fs::read_to_string("test_output/keybase_listen/requests").unwrap();
+	assert_eq!(requests.lines().count(), 4);
+	assert!(requests.lines().last().unwrap().contains("\"mark\""));
+
+	assert_eq!(api.whoami().unwrap(), "bob");
+}
+
+#[test]
+fn keybase_send() {
+	let slate = Slate::blank(2);
+	let signed = serde_json::to_string(&slate).unwrap();
+	let text = |body: &str| json!({"type": "text", "text": {"body": body}});
+	// our own slate, pushed back by keybase, and a reply from another channel
+	// come first
+	let own = serde_json::to_string(&Slate::blank(2)).unwrap();
+	let notifications = format!(
+		"{}\n{}\n{}\n",
+		notification(1, "alice", "grin_slate_signed", "bob", text(&own)),
+		notification(2, "carol", "grin_slate_signed", "carol", text(&signed)),
+		notification(3, "alice", "grin_slate_signed", "alice", text(&signed)),
+	);
+	let program = fake_keybase("test_output/keybase_send", &notifications);
+	let adapter = KeybaseWalletCommAdapter::with_api(KeybaseApi::new(&program));
+
+	let (received, proof) = adapter.send_tx_sync("alice", &slate).unwrap();
+	assert_eq!(received.id, slate.id);
+	assert!(proof.is_none());
+	let requests = fs::read_to_string("test_output/keybase_send/requests").unwrap();
+	assert!(requests.contains("grin_slate_new"));
+
+	// a single recipient only
+	assert!(adapter.send_tx_sync("alice,carol", &slate).is_err());
+}
+
+#[test]
+fn keybase_receive() {
+	let text = |body: &str| json!({"type": "text", "text": {"body": body}});
+	let own = Slate::blank(2);
+	let slate = Slate::blank(2);
+	// our own new slate, pushed back by keybase, comes first
+	let notifications = format!(
+		"{}\n{}\n",
+		notification(
+			1,
+			"alice,bob",
+			"grin_slate_new",
+			"bob",
+			text(&serde_json::to_string(&own).unwrap())
+		),
+		notification(
+			2,
+			"alice,bob",
+			"grin_slate_new",
+			"alice",
+			text(&serde_json::to_string(&slate).unwrap())
+		),
+	);
+	let program = fake_keybase("test_output/keybase_receive", &notifications);
+	let adapter = KeybaseWalletCommAdapter::with_api(KeybaseApi::new(&program));
+
+	let mut received = vec![];
+	let res = adapter.receive_loop(
+		|channel, s| {
+			received.push((channel.to_owned(), s.id));
+			Ok(s.clone())
+		},
+		0,
+	);
+	// the loop ends with keybase
+	assert!(res.is_err());
+	assert_eq!(received, vec![("alice,bob".to_owned(), slate.id)]);
+	let requests = fs::read_to_string("test_output/keybase_receive/requests").unwrap();
+	assert!(requests.contains("grin_slate_signed"));
+}
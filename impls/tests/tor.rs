@@ -0,0 +1,168 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tor transport tests, against a fake SOCKS proxy and control port
+extern crate grin_wallet_impls as impls;
+
+use impls::{onion_service_key, socks_post, TorConfig, TorControl};
+use serde_json::json;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// SOCKS5 proxy answering a single connection with an HTTP `response`,
+/// and returning the host, port and HTTP request it got
+fn fake_proxy(response: &'static str) -> (String, thread::JoinHandle<(String, u16, String)>) {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap().to_string();
+	let handle = thread::spawn(move || {
+		let (mut stream, _) = listener.accept().unwrap();
+		let mut greeting = [0u8; 3];
+		stream.read_exact(&mut greeting).unwrap();
+		assert_eq!(greeting, [5, 1, 0]);
+		stream.write_all(&[5, 0]).unwrap();
+		let mut request = [0u8; 5];
+		stream.read_exact(&mut request).unwrap();
+		assert_eq!(request[..4], [5, 1, 0, 3]);
+		let mut host = vec![0u8; request[4] as usize];
+		stream.read_exact(&mut host).unwrap();
+		let mut port = [0u8; 2];
+		stream.read_exact(&mut port).unwrap();
+		stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+		let mut reader = BufReader::new(stream.try_clone().unwrap());
+		let mut http = String::new();
+		let mut length = 0;
+		loop {
+			let mut line = String::new();
+			reader.read_line(&mut line).unwrap();
+			if line.to_lowercase().starts_with("content-length:") {
+				length = line[15..].trim().parse().unwrap();
+			}
+			http.push_str(&line);
+			if line == "\r\n" {
+				break;
+			}
+		}
+		let mut body = vec![0u8; length];
+		reader.read_exact(&mut body).unwrap();
+		http.push_str(&String::from_utf8(body).unwrap());
+		stream.write_all(response.as_bytes()).unwrap();
+		(
+			String::from_utf8(host).unwrap(),
+			u16::from_be_bytes(port),
+			http,
+		)
+	});
+	(addr, handle)
+}
+
+#[test]
+fn tor_socks_post() {
+	let onion = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+	let (proxy, handle) = fake_proxy(
+		"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\n{\"ok\": \r\n5\r\ntrue}\r\n0\r\n\r\n",
+	);
+	let config = TorConfig {
+		socks_proxy_addr: Some(proxy),
+		..Default::default()
+	};
+	let url = format!("http://{}/v2/foreign", onion);
+	let res = socks_post(&config, &url, &json!({"method": "check_version"})).unwrap();
+	assert_eq!(res, "{\"ok\": true}");
+	// the proxy resolves the onion address
+	let (host, port, http) = handle.join().unwrap();
+	assert_eq!((host.as_str(), port), (onion, 80));
+	assert!(http.starts_with("POST /v2/foreign HTTP/1.1\r\n"));
+	assert!(http.ends_with("{\"method\":\"check_version\"}"));
+
+	// an error status, and no TLS over onion services
+	let (proxy, _) = fake_proxy("HTTP/1.1 500 Internal Server Error\r\n\r\n");
+	let config = TorConfig {
+		socks_proxy_addr: Some(proxy),
+		..Default::default()
+	};
+	assert!(socks_post(&config, &format!("http://{}:3415", onion), &json!({})).is_err());
+	assert!(socks_post(&config, &format!("https://{}", onion), &json!({})).is_err());
+}
+
+#[test]
+fn tor_control() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap().to_string();
+	let handle = thread::spawn(move || {
+		let (stream, _) = listener.accept().unwrap();
+		let mut writer = stream.try_clone().unwrap();
+		let mut commands = vec![];
+		for line in BufReader::new(stream).lines() {
+			let line = line.unwrap();
+			let reply = if line.starts_with("AUTHENTICATE") {
+				"250 OK\r\n"
+			} else if line.starts_with("ADD_ONION") {
+				"250-ServiceID=2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid\r\n250 OK\r\n"
+			} else {
+				"510 Unrecognized command\r\n"
+			};
+			writer.write_all(reply.as_bytes()).unwrap();
+			commands.push(line);
+			if commands.len() == 3 {
+				break;
+			}
+		}
+		commands
+	});
+
+	let config = TorConfig {
+		control_addr: Some(addr),
+		control_password: Some("pass\"word".to_owned()),
+		..Default::default()
+	};
+	let key = onion_service_key(&[7; 32]);
+	assert_eq!(key, onion_service_key(&[7; 32]));
+	assert_ne!(key, onion_service_key(&[8; 32]));
+	assert!(key.starts_with("ED25519-V3:"));
+
+	let mut control = TorControl::connect(&config).unwrap();
+	let onion = control.add_onion(&key, 80, "127.0.0.1:3415").unwrap();
+	assert_eq!(
+		onion,
+		"2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion"
+	);
+	assert!(control.command("SIGNAL NEWNYM").is_err());
+	// the service goes away with the control connection
+	let commands = handle.join().unwrap();
+	assert_eq!(commands[0], "AUTHENTICATE \"pass\\\"word\"");
+	assert_eq!(
+		commands[1],
+		format!("ADD_ONION {} Port=80,127.0.0.1:3415", key)
+	);
+	assert!(control.wait().is_err());
+}
+
+#[test]
+fn tor_unreachable() {
+	// nothing listens on the proxy port
+	let port = TcpListener::bind("127.0.0.1:0")
+		.unwrap()
+		.local_addr()
+		.unwrap()
+		.port();
+	let config = TorConfig {
+		socks_proxy_addr: Some(format!("127.0.0.1:{}", port)),
+		control_addr: Some(format!("127.0.0.1:{}", port)),
+		..Default::default()
+	};
+	assert!(socks_post(&config, "http://abc.onion", &json!({})).is_err());
+	assert!(TorControl::connect(&config).is_err());
+}
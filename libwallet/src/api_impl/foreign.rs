@@ -14,10 +14,12 @@
 // limitations under the License.
 
 //! Generic implementation of owner API functions
+use chrono::{Duration, Utc};
 use strum::IntoEnumIterator;
 
-use crate::grin_keychain::Keychain;
-use crate::internal::{tx, updater};
+use crate::grin_core::core::amount_to_hr_string;
+use crate::grin_keychain::{Identifier, Keychain};
+use crate::internal::{keys, tx, updater};
 use crate::slate_versions::SlateVersion;
 use crate::{
 	BlockFees, CbData, Error, ErrorKind, NodeClient, Slate, TxLogEntryType, VersionInfo,
@@ -46,6 +48,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "build coinbase")?;
 	updater::build_coinbase(&mut *w, block_fees, test_mode)
 }
 
@@ -54,10 +57,12 @@ pub fn verify_slate_messages(slate: &Slate) -> Result<(), Error> {
 	slate.verify_messages()
 }
 
-/// Receive a tx as recipient
+/// Receive a tx as recipient. `sender` is the Grin Relay address of the
+/// sender, when the transport knows it.
 pub fn receive_tx<T: ?Sized, C, K>(
 	w: &mut T,
 	slate: &Slate,
+	sender: Option<&str>,
 	dest_acct_name: Option<&str>,
 	message: Option<String>,
 	grinrelay_key_path: Option<u64>,
@@ -68,17 +73,9 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "receive tx")?;
 	let mut ret_slate = slate.clone();
-	let parent_key_id = match dest_acct_name {
-		Some(d) => {
-			let pm = w.get_acct_path(d.to_owned())?;
-			match pm {
-				Some(p) => p.path,
-				None => w.parent_key_id(),
-			}
-		}
-		None => w.parent_key_id(),
-	};
+	let parent_key_id = receive_parent_key_id(&mut *w, dest_acct_name)?;
 	// Don't do this multiple times
 	let tx = updater::retrieve_txs(
 		&mut *w,
@@ -93,6 +90,8 @@ where
 			return Err(ErrorKind::TransactionAlreadyReceived(ret_slate.id.to_string()).into());
 		}
 	}
+	tx::check_ttl(&mut *w, &ret_slate)?;
+	check_receive_policy(&mut *w, &ret_slate, sender)?;
 
 	let message = match message {
 		Some(mut m) => {
@@ -115,6 +114,122 @@ where
 	Ok(ret_slate)
 }
 
+/// Cancel a received transaction which couldn't be completed, with the
+/// output added for it
+pub fn cancel_receive_tx<T: ?Sized, C, K>(
+	w: &mut T,
+	slate: &Slate,
+	dest_acct_name: Option<&str>,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let parent_key_id = receive_parent_key_id(&mut *w, dest_acct_name)?;
+	tx::cancel_tx(&mut *w, &parent_key_id, None, Some(slate.id))
+}
+
+/// Account a payment is received into
+fn receive_parent_key_id<T: ?Sized, C, K>(
+	w: &mut T,
+	dest_acct_name: Option<&str>,
+) -> Result<Identifier, Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let parent_key_id = match dest_acct_name {
+		Some(d) => match w.get_acct_path(d.to_owned())? {
+			Some(p) => p.path,
+			None => w.parent_key_id(),
+		},
+		None => w.parent_key_id(),
+	};
+	Ok(parent_key_id)
+}
+
+/// Refuse a payment the receive policy of the wallet doesn't accept, before
+/// any output is added for it. A payment with no known sender is refused
+/// when only some senders are allowed.
+fn check_receive_policy<T: ?Sized, C, K>(
+	w: &mut T,
+	slate: &Slate,
+	sender: Option<&str>,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let policy = match w.receive_policy() {
+		Some(p) => p,
+		None => return Ok(()),
+	};
+	let refuse =
+		|reason: String| -> Result<(), Error> { Err(ErrorKind::ReceivePolicy(reason).into()) };
+	match sender {
+		Some(s) if !policy.accepts_sender(s) => {
+			return refuse(format!("sender {} not accepted", s));
+		}
+		None if policy.allowed_senders.is_some() => {
+			return refuse("sender unknown, only listed senders are accepted".to_owned());
+		}
+		_ => (),
+	}
+	if let Some(min) = policy.min_amount {
+		if slate.amount < min {
+			return refuse(format!("amount below {}", amount_to_hr_string(min, false)));
+		}
+	}
+	if let Some(max) = policy.max_amount {
+		if slate.amount > max {
+			return refuse(format!("amount above {}", amount_to_hr_string(max, false)));
+		}
+	}
+	if policy.require_message == Some(true) {
+		let has_message = slate
+			.participant_data
+			.iter()
+			.any(|p| p.message.as_ref().map_or(false, |m| !m.trim().is_empty()));
+		if !has_message {
+			return refuse("no sender message".to_owned());
+		}
+	}
+	if policy.daily_cap.is_none() && policy.max_pending_receives.is_none() {
+		return Ok(());
+	}
+	let received = updater::retrieve_txs(
+		&mut *w,
+		None,
+		None,
+		None,
+		false,
+		Some(TxLogEntryType::TxReceived),
+	)?;
+	if let Some(max) = policy.max_pending_receives {
+		if received.iter().filter(|t| !t.confirmed).count() >= max as usize {
+			return refuse(format!("{} payments already pending", max));
+		}
+	}
+	if let Some(cap) = policy.daily_cap {
+		let since = Utc::now() - Duration::hours(24);
+		let today: u64 = received
+			.iter()
+			.filter(|t| t.creation_ts > since)
+			.map(|t| t.amount_credited)
+			.sum();
+		if today + slate.amount > cap {
+			return refuse(format!(
+				"daily cap of {} reached",
+				amount_to_hr_string(cap, false)
+			));
+		}
+	}
+	Ok(())
+}
+
 /// Receive an tx that this wallet has issued
 pub fn finalize_invoice_tx<T: ?Sized, C, K>(w: &mut T, slate: &Slate) -> Result<Slate, Error>
 where
@@ -122,6 +237,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "finalize invoice tx")?;
 	let mut sl = slate.clone();
 	let context = w.get_private_context(sl.id.as_bytes(), 1)?;
 	tx::complete_tx(&mut *w, &mut sl, 1, &context)?;
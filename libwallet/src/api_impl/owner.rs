@@ -28,7 +28,7 @@ use crate::types::{AcctPathMapping, NodeClient, TxLogEntry, TxWrapper, WalletBac
 use crate::{Error, ErrorKind};
 use crate::{
 	InitTxArgs, IssueInvoiceTxArgs, NodeHeightResult, OutputCommitMapping, PaymentData,
-	TxLogEntryType, TxProof,
+	PaymentProof, TxLogEntryType, TxProof,
 };
 
 const USER_MESSAGE_MAX_LEN: usize = 256;
@@ -172,6 +172,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "init send tx")?;
 	let parent_key_id = match args.src_acct_name {
 		Some(d) => {
 			let pm = w.get_acct_path(d)?;
@@ -192,6 +193,14 @@ where
 	};
 
 	let mut slate = tx::new_tx_slate(&mut *w, args.amount, 2, use_test_rng)?;
+	if let Some(ttl_blocks) = args.ttl_blocks {
+		if args.target_slate_version.map_or(false, |v| v < 3) {
+			return Err(ErrorKind::GenericError(
+				"Only V3 slates carry a TTL to the receiver".to_owned(),
+			))?;
+		}
+		slate.ttl_cutoff_height = Some(slate.height + ttl_blocks);
+	}
 
 	// if we just want to estimate, don't save a context, just send the results
 	// back
@@ -233,6 +242,9 @@ where
 	}
 	if let Some(v) = args.target_slate_version {
 		slate.version_info.orig_version = v;
+	} else if slate.ttl_cutoff_height.is_some() {
+		// only V3 carries the TTL to the receiver
+		slate.version_info.orig_version = 3;
 	}
 	Ok(slate)
 }
@@ -248,6 +260,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "issue invoice tx")?;
 	let parent_key_id = match args.dest_acct_name {
 		Some(d) => {
 			let pm = w.get_acct_path(d)?;
@@ -306,6 +319,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "process invoice tx")?;
 	let mut ret_slate = slate.clone();
 	let parent_key_id = match args.src_acct_name {
 		Some(d) => {
@@ -383,6 +397,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "tx lock outputs")?;
 	let context = w.get_private_context(slate.id.as_bytes(), participant_id)?;
 	selection::lock_tx_context(&mut *w, slate, &context)
 }
@@ -399,6 +414,8 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "finalize tx")?;
+	tx::check_ttl(&mut *w, slate)?;
 	let mut sl = slate.clone();
 	let context = w.get_private_context(sl.id.as_bytes(), 0)?;
 
@@ -607,6 +624,30 @@ where
 	}
 }
 
+/// get stored payment proof
+pub fn get_stored_payment_proof<T: ?Sized, C, K>(
+	w: &mut T,
+	tx_id: Option<u32>,
+	tx_slate_id: Option<Uuid>,
+) -> Result<Option<PaymentProof>, Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let slate_id = match tx_slate_id {
+		Some(id) => Some(id),
+		None => retrieve_txs(w, false, tx_id, None)?
+			.1
+			.first()
+			.and_then(|t| t.tx_slate_id),
+	};
+	match slate_id {
+		Some(slate_id) => w.get_stored_payment_proof(&slate_id.to_string()),
+		None => Ok(None),
+	}
+}
+
 /// Attempt to restore contents of wallet
 pub fn restore<T: ?Sized, C, K>(w: &mut T) -> Result<(), Error>
 where
@@ -614,6 +655,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "restore")?;
 	w.restore()
 }
 
@@ -628,6 +670,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "restore batch")?;
 	w.restore_batch(start_index, batch_size)
 }
 
@@ -638,6 +681,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "check repair")?;
 	update_outputs(w, true);
 	w.check_repair(delete_unconfirmed)
 }
@@ -655,6 +699,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	keys::check_not_watch_only(w, "check repair batch")?;
 	if is_update_outputs {
 		update_outputs(w, true);
 	}
@@ -21,6 +21,7 @@ use crate::grin_keychain::Identifier;
 use crate::grin_util::secp::pedersen;
 use crate::slate_versions::SlateVersion;
 use crate::types::OutputData;
+use chrono::prelude::*;
 
 /// Send TX API Args
 // TODO: This is here to ensure the legacy V1 API remains intact
@@ -95,6 +96,19 @@ pub struct InitTxArgs {
 	/// 'true', the amount field in the slate will contain the total amount locked, not the provided
 	/// transaction amount
 	pub estimate_only: Option<bool>,
+	/// If true, ask the receiver to sign a payment proof for the transaction, whatever
+	/// the transport the slate goes through. The proof is stored with the transaction
+	/// when it is finalized, see [`get_stored_payment_proof`](../grin_wallet_api/owner/struct.Owner.html#method.get_stored_payment_proof).
+	/// Receivers with an older wallet just leave the request unsigned.
+	#[serde(default)]
+	pub payment_proof: Option<bool>,
+	/// Optional number of blocks the transaction stays valid for. Past this many blocks
+	/// from now, the receiver refuses the slate, this wallet refuses to finalize it and
+	/// cancels it on the next refresh, unlocking its inputs. Only V3 slates carry it to
+	/// the receiver, so a slate with a TTL is sent as V3 unless `target_slate_version`
+	/// asks for an older version, which is an error.
+	#[serde(default)]
+	pub ttl_blocks: Option<u64>,
 	/// Sender arguments. If present, the underlying function will also attempt to send the
 	/// transaction to a destination and optionally finalize the result
 	pub send_args: Option<InitTxSendArgs>,
@@ -128,6 +142,8 @@ impl Default for InitTxArgs {
 			message: None,
 			target_slate_version: None,
 			estimate_only: Some(false),
+			payment_proof: None,
+			ttl_blocks: None,
 			send_args: None,
 		}
 	}
@@ -216,6 +232,39 @@ pub struct NodeHeightResult {
 	pub updated_from_node: bool,
 }
 
+/// A wallet hosted by the owner API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WalletListing {
+	/// Name selecting the wallet on owner API calls
+	pub name: String,
+	/// Whether the wallet is open and can serve owner API calls
+	pub open: bool,
+}
+
+/// State of a listener run by the listener supervisor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ListenerState {
+	/// Started, and not failed since
+	Running,
+	/// Failed or stopped, waiting to be restarted
+	Restarting,
+}
+
+/// Status of a transport listener, as reported by the owner API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListenerStatus {
+	/// Transport name, e.g. "http", "relay" or "keybase"
+	pub transport: String,
+	/// Current state
+	pub state: ListenerState,
+	/// Time of the last state change
+	pub since: DateTime<Utc>,
+	/// Number of restarts after a failure
+	pub restarts: u32,
+	/// Why the listener last failed
+	pub last_error: Option<String>,
+}
+
 /// Version request result
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VersionInfo {
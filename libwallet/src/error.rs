@@ -134,6 +134,18 @@ pub enum ErrorKind {
 	#[fail(display = "Wallet seed decryption error")]
 	WalletSeedDecryption,
 
+	/// Invalid BIP39 recovery phrase
+	#[fail(display = "BIP39 Mnemonic (word list) Error")]
+	Mnemonic,
+
+	/// Hosted wallet is not open
+	#[fail(display = "Wallet '{}' is not open", _0)]
+	WalletNotOpen(String),
+
+	/// Hosted wallet name can't be used
+	#[fail(display = "Invalid wallet name '{}'", _0)]
+	InvalidWalletName(String),
+
 	/// Transaction doesn't exist
 	#[fail(display = "Transaction {} doesn't exist", _0)]
 	TransactionDoesntExist(String),
@@ -158,6 +170,14 @@ pub enum ErrorKind {
 	#[fail(display = "Transaction {} has already been received", _0)]
 	TransactionAlreadyReceived(String),
 
+	/// Transaction TTL cutoff height has been reached
+	#[fail(display = "Transaction {} has expired", _0)]
+	TransactionExpired(String),
+
+	/// Incoming payment refused by the receive policy
+	#[fail(display = "Payment refused by the receive policy: {}", _0)]
+	ReceivePolicy(String),
+
 	/// Attempt to repost a transaction that's not completed and stored
 	#[fail(display = "Transaction building not completed: {}", _0)]
 	TransactionBuildingNotCompleted(u32),
@@ -206,6 +226,10 @@ pub enum ErrorKind {
 	#[fail(display = "Transaction Proof Signing Fail: {}", _0)]
 	SignProof(String),
 
+	/// Operation needs the wallet keys, refused in watch-only mode
+	#[fail(display = "Not available in watch-only mode: {}", _0)]
+	WatchOnly(String),
+
 	/// Other
 	#[fail(display = "Generic error: {}", _0)]
 	GenericError(String),
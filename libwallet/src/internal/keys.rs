@@ -18,6 +18,20 @@ use crate::error::{Error, ErrorKind};
 use crate::grin_keychain::{ChildNumber, ExtKeychain, Identifier, Keychain};
 use crate::types::{AcctPathMapping, NodeClient, WalletBackend};
 
+/// Make sure the wallet keys are loaded for an operation which needs them,
+/// refusing it when the wallet is opened watch-only
+pub fn check_not_watch_only<T: ?Sized, C, K>(wallet: &T, operation: &str) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	if wallet.is_watch_only() {
+		return Err(ErrorKind::WatchOnly(operation.to_owned()).into());
+	}
+	Ok(())
+}
+
 /// Get next available key in the wallet for a given parent
 pub fn next_available_key<T: ?Sized, C, K>(wallet: &mut T) -> Result<Identifier, Error>
 where
@@ -53,7 +67,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
-	Ok(wallet.acct_path_iter().collect())
+	Ok(wallet.acct_path_iter()?.collect())
 }
 
 /// Adds an new parent account path with a given label
@@ -64,7 +78,7 @@ where
 	K: Keychain,
 {
 	let label = label.to_owned();
-	if let Some(_) = wallet.acct_path_iter().find(|l| l.label == label) {
+	if let Some(_) = wallet.acct_path_iter()?.find(|l| l.label == label) {
 		return Err(ErrorKind::AccountLabelAlreadyExists(label.clone()).into());
 	}
 
@@ -72,7 +86,7 @@ where
 	// so find the highest of those, then increment (to conform with external/internal
 	// derivation chains in BIP32 spec)
 
-	let highest_entry = wallet.acct_path_iter().max_by(|a, b| {
+	let highest_entry = wallet.acct_path_iter()?.max_by(|a, b| {
 		<u32>::from(a.path.to_path().path[0]).cmp(&<u32>::from(b.path.to_path().path[0]))
 	});
 
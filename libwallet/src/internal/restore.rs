@@ -529,7 +529,7 @@ where
 	K: Keychain,
 {
 	// Don't proceed if wallet_data has anything in it
-	let is_empty = wallet.iter().next().is_none();
+	let is_empty = wallet.iter()?.next().is_none();
 	if !is_empty {
 		error!("Not restoring. Please back up and remove existing db directory first.");
 		return Ok(());
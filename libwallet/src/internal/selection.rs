@@ -134,6 +134,7 @@ where
 		let filename = format!("{}.grintx", slate_id);
 		t.stored_tx = Some(filename);
 		t.fee = Some(slate.fee);
+		t.ttl_cutoff_height = slate.ttl_cutoff_height;
 		let mut amount_debited = 0;
 		t.num_inputs = lock_inputs.len();
 		for id in lock_inputs {
@@ -224,6 +225,7 @@ where
 	t.amount_credited = amount;
 	t.num_outputs = 1;
 	t.messages = messages;
+	t.ttl_cutoff_height = slate.ttl_cutoff_height;
 	batch.save(OutputData {
 		root_key_id: parent_key_id.clone(),
 		key_id: key_id_inner.clone(),
@@ -347,7 +349,7 @@ where
 		max_outputs,
 		selection_strategy.clone(),
 		parent_key_id,
-	);
+	)?;
 
 	// sender is responsible for setting the fee on the partial tx
 	// recipient should double check the fee calculation and not blindly trust the
@@ -409,7 +411,7 @@ where
 				max_outputs,
 				selection_strategy.clone(),
 				parent_key_id,
-			)
+			)?
 			.1;
 			fee = tx_fee(coins.len(), num_outputs, 1, None);
 			total = coins.iter().map(|c| c.value).sum();
@@ -516,7 +518,7 @@ pub fn select_coins<T: ?Sized, C, K>(
 	max_outputs: usize,
 	selection_strategy: String,
 	parent_key_id: &Identifier,
-) -> (usize, Vec<OutputData>)
+) -> Result<(usize, Vec<OutputData>), Error>
 //    max_outputs_available, Outputs
 where
 	T: WalletBackend<C, K>,
@@ -525,7 +527,7 @@ where
 {
 	// first find all eligible outputs based on number of confirmations
 	let mut eligible = wallet
-		.iter()
+		.iter()?
 		.filter(|out| {
 			out.root_key_id == *parent_key_id
 				&& out.eligible_to_spend(current_height, minimum_confirmations)
@@ -556,7 +558,7 @@ where
 			if let Some(outputs) =
 				select_from(amount, selection_strategy.clone(), windowed_eligible)
 			{
-				return (max_available, outputs);
+				return Ok((max_available, outputs));
 			}
 		}
 		// Not exist in any window of which total amount >= amount.
@@ -575,11 +577,11 @@ where
 				"Extending maximum number of outputs. {} outputs selected.",
 				outputs.len()
 			);
-			return (max_available, outputs);
+			return Ok((max_available, outputs));
 		}
 	} else {
 		if let Some(outputs) = select_from(amount, selection_strategy, eligible.clone()) {
-			return (max_available, outputs);
+			return Ok((max_available, outputs));
 		}
 	}
 
@@ -587,10 +589,10 @@ where
 	// so return the largest amount we can so we can provide guidance on what is
 	// possible
 	eligible.reverse();
-	(
+	Ok((
 		max_available,
 		eligible.iter().take(max_outputs).cloned().collect(),
-	)
+	))
 }
 
 fn select_from(
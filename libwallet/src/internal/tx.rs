@@ -19,11 +19,13 @@ use uuid::Uuid;
 
 use crate::grin_core::consensus::valid_header_version;
 use crate::grin_core::core::HeaderVersion;
-use crate::grin_keychain::{Identifier, Keychain};
+use crate::grin_core::libtx::{proof, ProofBuilder};
+use crate::grin_keychain::{Identifier, Keychain, SwitchCommitmentType};
 use crate::grin_util::secp::pedersen;
 use crate::grin_util::{to_hex, Mutex};
 use crate::internal::{selection, updater};
 use crate::slate::Slate;
+use crate::slate_versions::v3::is_zero_rangeproof;
 use crate::types::{
 	Context, NodeClient, OutputStatus, PaymentData, TxLogEntryType, TxProof, WalletBackend,
 };
@@ -233,6 +235,37 @@ where
 	Ok(context)
 }
 
+/// Rebuild the range proofs of our outputs, left out of the initial leg of a
+/// V3 slate
+fn restore_output_proofs<T: ?Sized, C, K>(
+	wallet: &mut T,
+	slate: &mut Slate,
+	context: &Context,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let keychain = wallet.keychain();
+	let builder = ProofBuilder::new(keychain);
+	let switch = SwitchCommitmentType::Regular;
+	for output in slate.tx.body.outputs.iter_mut() {
+		if !is_zero_rangeproof(&output.proof) {
+			continue;
+		}
+		for (key_id, _, amount) in context.get_outputs() {
+			let commit = keychain.commit(amount, &key_id, &switch)?;
+			if commit == output.commit {
+				output.proof =
+					proof::create(keychain, &builder, amount, &key_id, &switch, commit, None)?;
+				break;
+			}
+		}
+	}
+	Ok(())
+}
+
 /// Complete a transaction
 pub fn complete_tx<T: ?Sized, C, K>(
 	wallet: &mut T,
@@ -245,6 +278,7 @@ where
 	C: NodeClient,
 	K: Keychain,
 {
+	restore_output_proofs(wallet, slate, context)?;
 	let _ = slate.fill_round_2(
 		wallet.keychain(),
 		&context.sec_key,
@@ -278,7 +312,7 @@ where
 		// sender save the payment output
 		let mut batch = wallet.batch()?;
 		let tx_id = if let Some(tx_entry) = batch
-			.tx_log_iter()
+			.tx_log_iter()?
 			.find(|t| t.tx_slate_id == Some(slate.id) && t.parent_key_id == context.parent_key_id)
 		{
 			Some(tx_entry.id)
@@ -437,6 +471,29 @@ where
 	Ok(())
 }
 
+/// Refuse a slate past its TTL cutoff height, either the one it carries or the
+/// one stored with its transaction when this wallet created it
+pub fn check_ttl<T: ?Sized, C, K>(wallet: &mut T, slate: &Slate) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let stored = updater::retrieve_txs(wallet, None, Some(slate.id), None, false, None)?;
+	let cutoff = slate
+		.ttl_cutoff_height
+		.into_iter()
+		.chain(stored.iter().filter_map(|t| t.ttl_cutoff_height))
+		.min();
+	if let Some(cutoff) = cutoff {
+		let height = wallet.w2n_client().get_chain_height()?;
+		if height >= cutoff {
+			return Err(ErrorKind::TransactionExpired(slate.id.to_string()))?;
+		}
+	}
+	Ok(())
+}
+
 #[cfg(test)]
 mod test {
 	use crate::grin_core::libtx::{build, ProofBuilder};
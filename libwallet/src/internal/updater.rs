@@ -16,11 +16,12 @@
 //! Utilities to check the status of all the outputs we have stored in
 //! the wallet storage and update them.
 
+use chrono::prelude::*;
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::error::Error;
-use crate::grin_core::consensus::reward;
+use crate::error::{Error, ErrorKind};
+use crate::grin_core::consensus::{reward, BLOCK_TIME_SEC};
 use crate::grin_core::core::{Output, TxKernel, TxKernelApiEntry};
 use crate::grin_core::global;
 use crate::grin_core::libtx::proof::ProofBuilder;
@@ -30,9 +31,11 @@ use crate::grin_util as util;
 use crate::grin_util::secp::pedersen;
 use crate::internal::keys;
 use crate::types::{
-	NodeClient, OutputData, OutputStatus, TxLogEntry, TxLogEntryType, WalletBackend, WalletInfo,
+	NodeClient, OutputData, OutputStatus, TxCancellationReason, TxLogEntry, TxLogEntryType,
+	WalletBackend, WalletInfo,
 };
 use crate::{BlockFees, CbData, OutputCommitMapping, PaymentData};
+use grin_wallet_config::StaleTxPolicy;
 
 /// Retrieve all of the outputs (doesn't attempt to update from node)
 pub fn retrieve_outputs<T: ?Sized, C, K>(
@@ -49,7 +52,7 @@ where
 {
 	// just read the wallet here, no need for a write lock
 	let mut outputs = wallet
-		.iter()
+		.iter()?
 		.filter(|out| show_spent || out.status != OutputStatus::Spent)
 		.collect::<Vec<_>>();
 
@@ -78,23 +81,37 @@ where
 	}
 
 	outputs.sort_by_key(|out| out.n_child);
-	let keychain = wallet.keychain().clone();
-
-	let res = outputs
-		.into_iter()
-		.map(|output| {
-			let commit = match output.commit.clone() {
-				Some(c) => pedersen::Commitment::from_vec(util::from_hex(c).unwrap()),
-				None => keychain
-					.commit(output.value, &output.key_id, &SwitchCommitmentType::Regular)
-					.unwrap(), // TODO: proper support for different switch commitment schemes
-			};
-			OutputCommitMapping { output, commit }
-		})
-		.collect();
+	let keychain = match wallet.is_watch_only() {
+		true => None,
+		false => Some(wallet.keychain().clone()),
+	};
+
+	let mut res = vec![];
+	for output in outputs {
+		let commit = output_commit(keychain.as_ref(), &output)?;
+		res.push(OutputCommitMapping { output, commit });
+	}
 	Ok(res)
 }
 
+/// Commitment of an output, taken from the commit cache when there is one.
+/// Rebuilding a missing commitment needs the keychain, so a watch-only
+/// wallet can only use outputs stored with a cached commit.
+fn output_commit<K>(keychain: Option<&K>, out: &OutputData) -> Result<pedersen::Commitment, Error>
+where
+	K: Keychain,
+{
+	match (out.commit.clone(), keychain) {
+		(Some(c), _) => Ok(pedersen::Commitment::from_vec(util::from_hex(c).unwrap())),
+		// TODO: proper support for different switch commitment schemes
+		(None, Some(k)) => Ok(k.commit(out.value, &out.key_id, &SwitchCommitmentType::Regular)?),
+		(None, None) => Err(ErrorKind::WatchOnly(format!(
+			"output {} has no cached commit",
+			out.key_id
+		)))?,
+	}
+}
+
 /// Retrieve all of the payment outputs (doesn't attempt to update from node)
 pub fn retrieve_payments<T: ?Sized, C, K>(
 	wallet: &mut T,
@@ -109,10 +126,10 @@ where
 
 	let mut payments = if let Some(slate_id) = tx_id {
 		wallet
-			.payment_entries_iter_tx(&slate_id)
+			.payment_entries_iter_tx(&slate_id)?
 			.collect::<Vec<_>>()
 	} else {
-		wallet.payment_entries_iter_all().collect::<Vec<_>>()
+		wallet.payment_entries_iter_all()?.collect::<Vec<_>>()
 	};
 
 	payments.sort_by(|a, b| a.height.cmp(&b.height).then(a.id.cmp(&b.id)));
@@ -135,7 +152,7 @@ where
 	K: Keychain,
 {
 	let mut txs: Vec<TxLogEntry> = wallet
-		.tx_log_iter()
+		.tx_log_iter()?
 		.filter(|tx_entry| {
 			let f_pk = match parent_key_id {
 				Some(k) => tx_entry.parent_key_id == *k,
@@ -200,9 +217,12 @@ where
 {
 	let mut wallet_outputs: HashMap<pedersen::Commitment, (Identifier, Option<u64>)> =
 		HashMap::new();
-	let keychain = wallet.keychain().clone();
+	let keychain = match wallet.is_watch_only() {
+		true => None,
+		false => Some(wallet.keychain().clone()),
+	};
 	let unspents: Vec<OutputData> = wallet
-		.iter()
+		.iter()?
 		.filter(|x| x.root_key_id == *parent_key_id && x.status != OutputStatus::Spent)
 		.collect();
 
@@ -227,12 +247,7 @@ where
 	};
 
 	for out in unspents {
-		let commit = match out.commit.clone() {
-			Some(c) => pedersen::Commitment::from_vec(util::from_hex(c).unwrap()),
-			None => keychain
-				.commit(out.value, &out.key_id, &SwitchCommitmentType::Regular)
-				.unwrap(), // TODO: proper support for different switch commitment schemes
-		};
+		let commit = output_commit(keychain.as_ref(), &out)?;
 		wallet_outputs.insert(commit, (out.key_id.clone(), out.mmr_index));
 	}
 	Ok(wallet_outputs)
@@ -346,7 +361,7 @@ where
 						// note that one involved input/output confirmation SHOULD be enough
 						// to reliably confirm the tx
 						if !output.is_coinbase && output.status == OutputStatus::Unconfirmed {
-							let tx = batch.tx_log_iter().find(|t| {
+							let tx = batch.tx_log_iter()?.find(|t| {
 								Some(t.id) == output.tx_log_entry
 									&& t.parent_key_id == *parent_key_id
 							});
@@ -370,7 +385,7 @@ where
 								// if there's a related payment output being confirmed, refresh that payment log
 								if let Some(slate_id) = output.slate_id {
 									let payment_entries = batch
-										.payment_entries_iter_tx(&slate_id)
+										.payment_entries_iter_tx(&slate_id)?
 										.collect::<Vec<_>>();
 
 									for mut payment in payment_entries {
@@ -430,7 +445,7 @@ where
 						Some(tx_kernel_api_entry) => {
 							// mark the output/s involved as confirmed
 							let outputs = batch
-								.iter()
+								.iter()?
 								.filter(|out| out.tx_log_entry == Some(tx_entry.id))
 								.collect::<Vec<_>>();
 							for mut output in outputs {
@@ -453,7 +468,7 @@ where
 								// refresh the payment log
 								if let Some(slate_id) = tx_entry.tx_slate_id {
 									let payment_entries = batch
-										.payment_entries_iter_tx(&slate_id)
+										.payment_entries_iter_tx(&slate_id)?
 										.collect::<Vec<_>>();
 
 									for mut payment in payment_entries {
@@ -549,9 +564,115 @@ where
 		apply_api_outputs(wallet, &wallet_outputs, &api_outputs, height, parent_key_id)?;
 	}
 	clean_old_unconfirmed(wallet, height)?;
+	cancel_stale_txs(wallet, height, parent_key_id)?;
+	Ok(())
+}
+
+/// Cancel the transactions not completed before their TTL cutoff height, and
+/// the outgoing ones the stale transaction policy gives up on, unlocking
+/// their inputs. The reason is recorded in the transaction log.
+fn cancel_stale_txs<T: ?Sized, C, K>(
+	wallet: &mut T,
+	height: u64,
+	parent_key_id: &Identifier,
+) -> Result<(), Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let policy = wallet.stale_tx_policy();
+	let now = Utc::now();
+	let stale: Vec<(TxLogEntry, TxCancellationReason)> =
+		retrieve_txs(wallet, None, None, Some(parent_key_id), true, None)?
+			.into_iter()
+			.filter(|t| {
+				(t.tx_type == TxLogEntryType::TxSent || t.tx_type == TxLogEntryType::TxReceived)
+					&& !t.confirmed
+			})
+			.filter_map(|t| {
+				let reason = stale_tx_reason(&t, policy.as_ref(), height, now)?;
+				Some((t, reason))
+			})
+			.collect();
+	for (mut tx, reason) in stale {
+		let outputs: Vec<OutputData> =
+			retrieve_outputs(wallet, true, Some(tx.id), None, Some(parent_key_id))?
+				.into_iter()
+				.map(|m| m.output)
+				.collect();
+		// a posted transaction can still confirm, unless its inputs are gone
+		// from the pool and still unspent
+		if let TxCancellationReason::Unconfirmed(_) = reason {
+			if !inputs_unspent(wallet, &outputs)? {
+				continue;
+			}
+		}
+		info!("Cancelling transaction {}, {}", tx.id, reason);
+		tx.cancellation_reason = Some(reason);
+		cancel_tx_and_outputs(wallet, tx.clone(), outputs, parent_key_id)?;
+		if tx.tx_type == TxLogEntryType::TxSent {
+			if let Some(tx_slate_id) = tx.tx_slate_id {
+				cancel_payments(wallet, tx_slate_id)?;
+			}
+		}
+	}
 	Ok(())
 }
 
+/// Whether the node shows the inputs locked by a transaction still unspent
+fn inputs_unspent<T: ?Sized, C, K>(wallet: &mut T, outputs: &[OutputData]) -> Result<bool, Error>
+where
+	T: WalletBackend<C, K>,
+	C: NodeClient,
+	K: Keychain,
+{
+	let keychain = match wallet.is_watch_only() {
+		true => None,
+		false => Some(wallet.keychain().clone()),
+	};
+	let inputs = outputs
+		.iter()
+		.filter(|o| o.status == OutputStatus::Locked)
+		.map(|o| output_commit(keychain.as_ref(), o))
+		.collect::<Result<Vec<_>, _>>()?;
+	if inputs.is_empty() {
+		return Ok(false);
+	}
+	let unspent = wallet.w2n_client().get_outputs_from_node(inputs.clone())?;
+	Ok(inputs.iter().all(|c| unspent.contains_key(c)))
+}
+
+/// Why an unconfirmed transaction is stale, if it is
+fn stale_tx_reason(
+	tx: &TxLogEntry,
+	policy: Option<&StaleTxPolicy>,
+	height: u64,
+	now: DateTime<Utc>,
+) -> Option<TxCancellationReason> {
+	let posted = tx.posted == Some(true);
+	if let Some(cutoff) = tx.ttl_cutoff_height {
+		if !posted && height >= cutoff {
+			return Some(TxCancellationReason::Expired(cutoff));
+		}
+	}
+	// the policy only gives up on our own sends
+	let policy = match policy {
+		Some(p) if tx.tx_type == TxLogEntryType::TxSent => p,
+		_ => return None,
+	};
+	let age = now.signed_duration_since(tx.creation_ts);
+	match (posted, policy.unconfirmed_blocks, policy.unposted_hours) {
+		(true, Some(blocks), _) if age.num_seconds() >= (blocks * BLOCK_TIME_SEC) as i64 => {
+			Some(TxCancellationReason::Unconfirmed(blocks))
+		}
+		(false, _, Some(hours)) if age.num_hours() >= hours as i64 => {
+			Some(TxCancellationReason::NotPosted(hours))
+		}
+		_ => None,
+	}
+}
+
 /// Only for miner wallet.
 /// Clean the unconfirmed coinbase output which was created at 50 minutes ago.
 fn clean_old_unconfirmed<T: ?Sized, C, K>(wallet: &mut T, height: u64) -> Result<(), Error>
@@ -564,7 +685,7 @@ where
 		return Ok(());
 	}
 	let mut ids_to_del = vec![];
-	for out in wallet.iter() {
+	for out in wallet.iter()? {
 		if out.status == OutputStatus::Unconfirmed
 			&& out.height > 0
 			&& out.height < height - 50
@@ -598,7 +719,7 @@ where
 {
 	let current_height = wallet.last_confirmed_height()?;
 	let outputs = wallet
-		.iter()
+		.iter()?
 		.filter(|out| out.root_key_id == *parent_key_id);
 
 	let mut unspent_total = 0;
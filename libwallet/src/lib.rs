@@ -54,18 +54,20 @@ mod types;
 pub mod wallet_ser;
 
 pub use crate::error::{Error, ErrorKind};
-pub use crate::slate::{ParticipantData, ParticipantMessageData, Slate};
+pub use crate::slate::{ParticipantData, ParticipantMessageData, PaymentInfo, Slate};
 pub use crate::slate_versions::{
-	SlateVersion, VersionedSlate, CURRENT_SLATE_VERSION, GRIN_BLOCK_HEADER_VERSION,
+	SlateVersion, VersionedSlate, CURRENT_SLATE_VERSION, DEFAULT_SLATE_VERSION,
+	GRIN_BLOCK_HEADER_VERSION, MIN_SLATE_VERSION,
 };
 pub use api_impl::types::{
-	BlockFees, CbData, InitTxArgs, InitTxSendArgs, IssueInvoiceTxArgs, NodeHeightResult,
-	OutputCommitMapping, SendTXArgs, VersionInfo,
+	BlockFees, CbData, InitTxArgs, InitTxSendArgs, IssueInvoiceTxArgs, ListenerState,
+	ListenerStatus, NodeHeightResult, OutputCommitMapping, SendTXArgs, VersionInfo, WalletListing,
 };
 pub use internal::restore::{check_repair, check_repair_batch, restore, restore_batch};
 pub use listener::Listener;
 pub use types::{
-	AcctPathMapping, BlockIdentifier, Context, NodeClient, NodeVersionInfo, OutputData,
-	OutputStatus, PaymentData, TxLogEntry, TxLogEntryType, TxProof, TxProofVerified, TxWrapper,
+	payment_proof_message, AcctPathMapping, BlockIdentifier, Context, NodeClient, NodeVersionInfo,
+	OutputData, OutputStatus, PaymentData, PaymentProof, TxCancellationReason, TxLogEntry,
+	TxLogEntryType, TxProof, TxProofReport, TxProofStatus, TxProofVerified, TxWrapper,
 	WalletBackend, WalletInfo, WalletInst, WalletOutputBatch,
 };
@@ -28,6 +28,7 @@ use crate::grin_core::libtx::{aggsig, build, proof::ProofBuild, secp_ser, tx_fee
 use crate::grin_core::map_vec;
 use crate::grin_keychain::{BlindSum, BlindingFactor, Keychain};
 use crate::grin_util::secp::key::{PublicKey, SecretKey};
+use crate::grin_util::secp::pedersen::Commitment;
 use crate::grin_util::secp::Signature;
 use crate::grin_util::{self, secp, RwLock};
 use failure::ResultExt;
@@ -40,10 +41,16 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::slate_versions::v2::{
-	InputV2, OutputV2, ParticipantDataV2, SlateV2, TransactionBodyV2, TransactionV2, TxKernelV2,
-	VersionCompatInfoV2,
+	InputV2, OutputV2, ParticipantDataV2, PaymentInfoV2, SlateV2, TransactionBodyV2, TransactionV2,
+	TxKernelV2, VersionCompatInfoV2,
+};
+use crate::slate_versions::v3::{
+	zero_rangeproof, InputV3, OutputV3, ParticipantDataV3, PaymentInfoV3, SlateV3,
+	TransactionBodyV3, TransactionV3, TxKernelV3, VersionCompatInfoV3,
+};
+use crate::slate_versions::{
+	binary, VersionedSlate, CURRENT_SLATE_VERSION, DEFAULT_SLATE_VERSION, GRIN_BLOCK_HEADER_VERSION,
 };
-use crate::slate_versions::{CURRENT_SLATE_VERSION, GRIN_BLOCK_HEADER_VERSION};
 
 /// Public data for each participant in the slate
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -146,8 +153,10 @@ impl fmt::Display for ParticipantMessageData {
 /// transaction data needed to create a finalized transaction. Callers can pass
 /// the slate around by whatever means they choose, (but we can provide some
 /// binary or JSON serialization helpers here).
+/// A slate is read as whichever version it was written in.
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(from = "VersionedSlate")]
 pub struct Slate {
 	/// Versioning info
 	pub version_info: VersionCompatInfo,
@@ -174,6 +183,26 @@ pub struct Slate {
 	/// insert their public data here. For now, 0 is sender and 1
 	/// is receiver, though this will change for multi-party
 	pub participant_data: Vec<ParticipantData>,
+	/// Payment proof requested by the sender, signed by the receiver
+	#[serde(default)]
+	pub payment_proof: Option<PaymentInfo>,
+	/// Height after which the transaction expires, carried by V3 slates only
+	#[serde(default)]
+	pub ttl_cutoff_height: Option<u64>,
+}
+
+/// Payment proof data carried by the slate. The sender asks for a proof by
+/// giving its address, the receiver signs the payment statement (amount,
+/// kernel excess, sender address) with the key of its own address.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentInfo {
+	/// Sender payment proof address
+	pub sender_address: String,
+	/// Receiver payment proof address, set by the receiver
+	pub receiver_address: Option<String>,
+	/// Receiver signature of the payment statement
+	#[serde(with = "secp_ser::option_sig_serde")]
+	pub receiver_signature: Option<Signature>,
 }
 
 /// Versioning and compatibility info about this slate
@@ -202,10 +231,22 @@ impl Slate {
 		Ok(probe.version())
 	}
 
-	/// Receive a slate, upgrade it to the latest version internally
+	/// Receive a slate, upgrade it to the latest version internally. The slate
+	/// can be either json or in the armored text form of
+	/// [`binary`](../slate_versions/binary/index.html). The raw binary form
+	/// isn't text, use [`deserialize_upgrade_bytes`](#method.deserialize_upgrade_bytes)
+	/// where it may be found.
 	pub fn deserialize_upgrade(slate_json: &str) -> Result<Slate, Error> {
+		if binary::is_armored_slate(slate_json) {
+			return Ok(VersionedSlate::from_armored(slate_json)?.into());
+		}
 		let version = Slate::parse_slate_version(slate_json)?;
 		let v2: SlateV2 = match version {
+			3 => {
+				let v3: SlateV3 =
+					serde_json::from_str(slate_json).context(ErrorKind::SlateDeser)?;
+				return Ok(v3.into());
+			}
 			2 => serde_json::from_str(slate_json).context(ErrorKind::SlateDeser)?,
 			// left as a reminder
 			/*0 => {
@@ -219,6 +260,16 @@ impl Slate {
 		Ok(v2.into())
 	}
 
+	/// As [`deserialize_upgrade`](#method.deserialize_upgrade), also detecting
+	/// the raw binary form of a slate
+	pub fn deserialize_upgrade_bytes(bytes: &[u8]) -> Result<Slate, Error> {
+		if binary::is_binary_slate(bytes) {
+			return Ok(VersionedSlate::from_binary(bytes)?.into());
+		}
+		let text = std::str::from_utf8(bytes).map_err(|_| ErrorKind::SlateDeser)?;
+		Slate::deserialize_upgrade(text)
+	}
+
 	/// Create a new slate
 	pub fn blank(num_participants: usize) -> Slate {
 		Slate {
@@ -230,9 +281,11 @@ impl Slate {
 			height: 0,
 			lock_height: 0,
 			participant_data: vec![],
+			payment_proof: None,
+			ttl_cutoff_height: None,
 			version_info: VersionCompatInfo {
 				version: CURRENT_SLATE_VERSION,
-				orig_version: CURRENT_SLATE_VERSION,
+				orig_version: DEFAULT_SLATE_VERSION,
 				block_header_version: GRIN_BLOCK_HEADER_VERSION,
 			},
 		}
@@ -622,6 +675,26 @@ impl Slate {
 		Ok(final_sig)
 	}
 
+	/// Kernel excess of the transaction: the sum of its input and output
+	/// commitments, minus the kernel offset. It is known as soon as all the
+	/// participants have added their inputs and outputs, before the kernel
+	/// is signed.
+	pub fn calc_excess<K>(&self, keychain: &K) -> Result<Commitment, Error>
+	where
+		K: Keychain,
+	{
+		let overage = self.tx.fee() as i64;
+		let tx_excess = self.tx.sum_commitments(overage)?;
+
+		// subtract the kernel_excess (built from kernel_offset)
+		let offset_excess = keychain
+			.secp()
+			.commit(0, self.tx.offset.secret_key(&keychain.secp())?)?;
+		Ok(keychain
+			.secp()
+			.commit_sum(vec![tx_excess], vec![offset_excess])?)
+	}
+
 	/// builds a final transaction after the aggregated sig exchange
 	fn finalize_transaction<K>(
 		&mut self,
@@ -631,26 +704,12 @@ impl Slate {
 	where
 		K: Keychain,
 	{
-		let kernel_offset = &self.tx.offset;
-
 		self.check_fees()?;
 
 		let mut final_tx = self.tx.clone();
 
 		// build the final excess based on final tx and offset
-		let final_excess = {
-			// sum the input/output commitments on the final tx
-			let overage = final_tx.fee() as i64;
-			let tx_excess = final_tx.sum_commitments(overage)?;
-
-			// subtract the kernel_excess (built from kernel_offset)
-			let offset_excess = keychain
-				.secp()
-				.commit(0, kernel_offset.secret_key(&keychain.secp())?)?;
-			keychain
-				.secp()
-				.commit_sum(vec![tx_excess], vec![offset_excess])?
-		};
+		let final_excess = self.calc_excess(keychain)?;
 
 		// update the tx kernel to reflect the offset excess and sig
 		assert_eq!(final_tx.kernels().len(), 1);
@@ -678,9 +737,9 @@ impl Serialize for Slate {
 	{
 		use serde::ser::Error;
 
-		let v2 = SlateV2::from(self);
 		match self.version_info.orig_version {
-			2 => v2.serialize(serializer),
+			3 => SlateV3::from(self).serialize(serializer),
+			2 => SlateV2::from(self).serialize(serializer),
 			// left as a reminder
 			/*0 => {
 				let v1 = SlateV1::from(v2);
@@ -730,9 +789,12 @@ impl From<Slate> for SlateV2 {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
+			ttl_cutoff_height: _,
 		} = slate;
 		let participant_data = map_vec!(participant_data, |data| ParticipantDataV2::from(data));
+		let payment_proof = payment_proof.as_ref().map(PaymentInfoV2::from);
 		let version_info = VersionCompatInfoV2::from(&version_info);
 		let tx = TransactionV2::from(tx);
 		SlateV2 {
@@ -744,6 +806,7 @@ impl From<Slate> for SlateV2 {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
 		}
 	}
@@ -760,7 +823,9 @@ impl From<&Slate> for SlateV2 {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
+			ttl_cutoff_height: _,
 		} = slate;
 		let num_participants = *num_participants;
 		let id = *id;
@@ -770,6 +835,7 @@ impl From<&Slate> for SlateV2 {
 		let height = *height;
 		let lock_height = *lock_height;
 		let participant_data = map_vec!(participant_data, |data| ParticipantDataV2::from(data));
+		let payment_proof = payment_proof.as_ref().map(PaymentInfoV2::from);
 		let version_info = VersionCompatInfoV2::from(version_info);
 		SlateV2 {
 			num_participants,
@@ -780,6 +846,7 @@ impl From<&Slate> for SlateV2 {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
 		}
 	}
@@ -812,14 +879,32 @@ impl From<&ParticipantData> for ParticipantDataV2 {
 	}
 }
 
+impl From<&PaymentInfo> for PaymentInfoV2 {
+	fn from(data: &PaymentInfo) -> PaymentInfoV2 {
+		let PaymentInfo {
+			sender_address,
+			receiver_address,
+			receiver_signature,
+		} = data;
+		let sender_address = sender_address.clone();
+		let receiver_address = receiver_address.clone();
+		let receiver_signature = *receiver_signature;
+		PaymentInfoV2 {
+			sender_address,
+			receiver_address,
+			receiver_signature,
+		}
+	}
+}
+
 impl From<&VersionCompatInfo> for VersionCompatInfoV2 {
 	fn from(data: &VersionCompatInfo) -> VersionCompatInfoV2 {
 		let VersionCompatInfo {
-			version,
+			version: _,
 			orig_version,
 			block_header_version,
 		} = data;
-		let version = *version;
+		let version = 2;
 		let orig_version = *orig_version;
 		let block_header_version = *block_header_version;
 		VersionCompatInfoV2 {
@@ -919,9 +1004,11 @@ impl From<SlateV2> for Slate {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
 		} = slate;
 		let participant_data = map_vec!(participant_data, |data| ParticipantData::from(data));
+		let payment_proof = payment_proof.as_ref().map(PaymentInfo::from);
 		let version_info = VersionCompatInfo::from(&version_info);
 		let tx = Transaction::from(tx);
 		Slate {
@@ -933,6 +1020,8 @@ impl From<SlateV2> for Slate {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
+			ttl_cutoff_height: None,
 			version_info,
 		}
 	}
@@ -949,6 +1038,7 @@ impl From<&SlateV2> for Slate {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
 			version_info,
 		} = slate;
 		let num_participants = *num_participants;
@@ -959,6 +1049,7 @@ impl From<&SlateV2> for Slate {
 		let height = *height;
 		let lock_height = *lock_height;
 		let participant_data = map_vec!(participant_data, |data| ParticipantData::from(data));
+		let payment_proof = payment_proof.as_ref().map(PaymentInfo::from);
 		let version_info = VersionCompatInfo::from(version_info);
 
 		Slate {
@@ -970,6 +1061,8 @@ impl From<&SlateV2> for Slate {
 			height,
 			lock_height,
 			participant_data,
+			payment_proof,
+			ttl_cutoff_height: None,
 			version_info,
 		}
 	}
@@ -1002,6 +1095,24 @@ impl From<&ParticipantDataV2> for ParticipantData {
 	}
 }
 
+impl From<&PaymentInfoV2> for PaymentInfo {
+	fn from(data: &PaymentInfoV2) -> PaymentInfo {
+		let PaymentInfoV2 {
+			sender_address,
+			receiver_address,
+			receiver_signature,
+		} = data;
+		let sender_address = sender_address.clone();
+		let receiver_address = receiver_address.clone();
+		let receiver_signature = *receiver_signature;
+		PaymentInfo {
+			sender_address,
+			receiver_address,
+			receiver_signature,
+		}
+	}
+}
+
 impl From<&VersionCompatInfoV2> for VersionCompatInfo {
 	fn from(data: &VersionCompatInfoV2) -> VersionCompatInfo {
 		let VersionCompatInfoV2 {
@@ -1094,6 +1205,194 @@ impl From<&TxKernelV2> for TxKernel {
 	}
 }
 
+// Current slate to V3
+impl From<Slate> for SlateV3 {
+	fn from(slate: Slate) -> SlateV3 {
+		SlateV3::from(&slate)
+	}
+}
+
+impl From<&Slate> for SlateV3 {
+	fn from(slate: &Slate) -> SlateV3 {
+		let Slate {
+			num_participants,
+			id,
+			tx,
+			amount,
+			fee,
+			height,
+			lock_height,
+			participant_data,
+			payment_proof,
+			ttl_cutoff_height,
+			version_info,
+		} = slate;
+		let mut tx = TransactionV3::from(tx);
+		// nobody signed yet: the initial leg goes without the range proofs,
+		// rebuilt by the party finalizing the slate, who started it
+		if participant_data.iter().all(|p| p.part_sig.is_none()) {
+			for out in tx.body.outputs.iter_mut() {
+				out.proof = zero_rangeproof();
+			}
+		}
+		let participant_data = map_vec!(participant_data, |data| ParticipantDataV3::from(data));
+		let payment_proof = payment_proof.as_ref().map(PaymentInfoV3::from);
+		let version_info = VersionCompatInfoV3 {
+			version: 3,
+			orig_version: version_info.orig_version,
+			block_header_version: version_info.block_header_version,
+		};
+		SlateV3 {
+			num_participants: *num_participants,
+			id: *id,
+			tx,
+			amount: *amount,
+			fee: *fee,
+			height: *height,
+			lock_height: *lock_height,
+			ttl_cutoff_height: *ttl_cutoff_height,
+			participant_data,
+			payment_proof,
+			version_info,
+		}
+	}
+}
+
+impl From<&ParticipantData> for ParticipantDataV3 {
+	fn from(data: &ParticipantData) -> ParticipantDataV3 {
+		ParticipantDataV3 {
+			id: data.id,
+			public_blind_excess: data.public_blind_excess,
+			public_nonce: data.public_nonce,
+			part_sig: data.part_sig,
+			message: data.message.clone(),
+			message_sig: data.message_sig,
+		}
+	}
+}
+
+impl From<&PaymentInfo> for PaymentInfoV3 {
+	fn from(data: &PaymentInfo) -> PaymentInfoV3 {
+		PaymentInfoV3 {
+			sender_address: data.sender_address.clone(),
+			receiver_address: data.receiver_address.clone(),
+			receiver_signature: data.receiver_signature,
+		}
+	}
+}
+
+impl From<&Transaction> for TransactionV3 {
+	fn from(tx: &Transaction) -> TransactionV3 {
+		let TransactionBody {
+			inputs,
+			outputs,
+			kernels,
+		} = &tx.body;
+		let body = TransactionBodyV3 {
+			inputs: map_vec!(inputs, |inp| InputV3 {
+				features: inp.features,
+				commit: inp.commit,
+			}),
+			outputs: map_vec!(outputs, |out| OutputV3 {
+				features: out.features,
+				commit: out.commit,
+				proof: out.proof,
+			}),
+			kernels: map_vec!(kernels, |kern| TxKernelV3 {
+				excess: kern.excess,
+				excess_sig: kern.excess_sig,
+			}),
+		};
+		TransactionV3 {
+			offset: tx.offset.clone(),
+			body,
+		}
+	}
+}
+
+// V3 to current slate
+impl From<SlateV3> for Slate {
+	fn from(slate: SlateV3) -> Slate {
+		Slate::from(&slate)
+	}
+}
+
+impl From<&SlateV3> for Slate {
+	fn from(slate: &SlateV3) -> Slate {
+		let SlateV3 {
+			num_participants,
+			id,
+			tx,
+			amount,
+			fee,
+			height,
+			lock_height,
+			ttl_cutoff_height,
+			participant_data,
+			payment_proof,
+			version_info,
+		} = slate;
+		// the kernels are the ones of the slate
+		let features = match *lock_height {
+			0 => KernelFeatures::Plain { fee: *fee },
+			_ => KernelFeatures::HeightLocked {
+				fee: *fee,
+				lock_height: *lock_height,
+			},
+		};
+		let body = TransactionBody {
+			inputs: map_vec!(tx.body.inputs, |inp| Input {
+				features: inp.features,
+				commit: inp.commit,
+			}),
+			outputs: map_vec!(tx.body.outputs, |out| Output {
+				features: out.features,
+				commit: out.commit,
+				proof: out.proof,
+			}),
+			kernels: map_vec!(tx.body.kernels, |kern| TxKernel {
+				features,
+				excess: kern.excess,
+				excess_sig: kern.excess_sig,
+			}),
+		};
+		let participant_data = map_vec!(participant_data, |data| ParticipantData {
+			id: data.id,
+			public_blind_excess: data.public_blind_excess,
+			public_nonce: data.public_nonce,
+			part_sig: data.part_sig,
+			message: data.message.clone(),
+			message_sig: data.message_sig,
+		});
+		let payment_proof = payment_proof.as_ref().map(|p| PaymentInfo {
+			sender_address: p.sender_address.clone(),
+			receiver_address: p.receiver_address.clone(),
+			receiver_signature: p.receiver_signature,
+		});
+		let version_info = VersionCompatInfo {
+			version: version_info.version,
+			orig_version: version_info.orig_version,
+			block_header_version: version_info.block_header_version,
+		};
+		Slate {
+			num_participants: *num_participants,
+			id: *id,
+			tx: Transaction {
+				offset: tx.offset.clone(),
+				body,
+			},
+			amount: *amount,
+			fee: *fee,
+			height: *height,
+			lock_height: *lock_height,
+			participant_data,
+			payment_proof,
+			ttl_cutoff_height: *ttl_cutoff_height,
+			version_info,
+		}
+	}
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum CompatKernelFeatures {
 	Plain,
@@ -0,0 +1,308 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compact binary encoding of the slates, for QR codes and other constrained
+//! transports, and its armored text form.
+//!
+//! The binary form starts with the `GS` magic bytes and the slate version,
+//! followed by the slate fields, the transaction being in its consensus
+//! binary encoding. The armored form is the base58 of the binary form and
+//! of a checksum, prefixed with `grinslate`, on a single line.
+
+use crate::blake2::blake2b::blake2b;
+use crate::error::{Error, ErrorKind};
+use crate::grin_core::core::Transaction;
+use crate::grin_core::ser as core_ser;
+use crate::grin_util::secp::key::PublicKey;
+use crate::grin_util::secp::Signature;
+use crate::slate::Slate;
+use crate::slate_versions::v2::{
+	ParticipantDataV2, PaymentInfoV2, SlateV2, TransactionV2, VersionCompatInfoV2,
+};
+use crate::slate_versions::v3::SlateV3;
+use crate::slate_versions::VersionedSlate;
+use crate::wallet_ser::{self as ser, BinReader, BinWriter, Readable, Reader, Writeable, Writer};
+use uuid::Uuid;
+
+/// Magic bytes a binary slate starts with
+pub const BINARY_SLATE_MAGIC: [u8; 2] = [b'G', b'S'];
+
+/// Prefix of an armored slate
+pub const ARMORED_SLATE_PREFIX: &str = "grinslate";
+
+const CHECKSUM_LEN: usize = 4;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+impl VersionedSlate {
+	/// Compact binary encoding of this slate
+	pub fn to_binary(&self) -> Result<Vec<u8>, Error> {
+		let mut bytes = BINARY_SLATE_MAGIC.to_vec();
+		{
+			let mut writer = BinWriter::default(&mut bytes);
+			match self {
+				VersionedSlate::V3(s) => {
+					// the binary form of V2 is already compact, V3 only adds the TTL
+					writer.write_u16(3)?;
+					SlateV2::from(Slate::from(s)).write(&mut writer)?;
+					write_opt(&mut writer, &s.ttl_cutoff_height, |w, h| w.write_u64(*h))?;
+				}
+				VersionedSlate::V2(s) => {
+					writer.write_u16(2)?;
+					s.write(&mut writer)?;
+				}
+			}
+		}
+		Ok(bytes)
+	}
+
+	/// Decode a slate from its compact binary encoding
+	pub fn from_binary(bytes: &[u8]) -> Result<VersionedSlate, Error> {
+		if !is_binary_slate(bytes) {
+			return Err(ErrorKind::SlateDeser.into());
+		}
+		let mut source = &bytes[BINARY_SLATE_MAGIC.len()..];
+		let mut reader = BinReader::new(&mut source, core_ser::ProtocolVersion::local());
+		let slate = match reader.read_u16()? {
+			3 => {
+				let mut slate = Slate::from(SlateV2::read(&mut reader)?);
+				slate.ttl_cutoff_height = read_opt(&mut reader, |r| r.read_u64())?;
+				VersionedSlate::V3(SlateV3::from(slate))
+			}
+			2 => VersionedSlate::V2(SlateV2::read(&mut reader)?),
+			v => return Err(ErrorKind::SlateVersion(v).into()),
+		};
+		Ok(slate)
+	}
+
+	/// Armored text form of this slate, a single line fit for a QR code
+	pub fn to_armored(&self) -> Result<String, Error> {
+		let mut bytes = self.to_binary()?;
+		let checksum = blake2b(CHECKSUM_LEN, &[], &bytes);
+		bytes.extend_from_slice(checksum.as_bytes());
+		Ok(format!("{}{}", ARMORED_SLATE_PREFIX, base58_encode(&bytes)))
+	}
+
+	/// Decode a slate from its armored text form
+	pub fn from_armored(text: &str) -> Result<VersionedSlate, Error> {
+		let text = text.trim();
+		if !is_armored_slate(text) {
+			return Err(ErrorKind::SlateDeser.into());
+		}
+		let bytes =
+			base58_decode(&text[ARMORED_SLATE_PREFIX.len()..]).ok_or(ErrorKind::SlateDeser)?;
+		if bytes.len() < CHECKSUM_LEN {
+			return Err(ErrorKind::SlateDeser.into());
+		}
+		let (bytes, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+		if blake2b(CHECKSUM_LEN, &[], bytes).as_bytes() != checksum {
+			return Err(ErrorKind::SlateDeser.into());
+		}
+		VersionedSlate::from_binary(bytes)
+	}
+}
+
+/// Whether these bytes look like a binary slate
+pub fn is_binary_slate(bytes: &[u8]) -> bool {
+	bytes.starts_with(&BINARY_SLATE_MAGIC)
+}
+
+/// Whether this text looks like an armored slate
+pub fn is_armored_slate(text: &str) -> bool {
+	text.trim_start().starts_with(ARMORED_SLATE_PREFIX)
+}
+
+/// Participant counts are a single byte in the binary form, refuse rather
+/// than truncate anything larger
+fn count_u8(count: usize) -> Result<u8, ser::Error> {
+	if count > u8::max_value() as usize {
+		return Err(ser::Error::CountError);
+	}
+	Ok(count as u8)
+}
+
+impl Writeable for SlateV2 {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u16(self.version_info.orig_version)?;
+		writer.write_u16(self.version_info.block_header_version)?;
+		writer.write_u8(count_u8(self.num_participants)?)?;
+		writer.write_fixed_bytes(&self.id.as_bytes().to_vec())?;
+		writer.write_u64(self.amount)?;
+		writer.write_u64(self.fee)?;
+		writer.write_u64(self.height)?;
+		writer.write_u64(self.lock_height)?;
+		let tx: Transaction = (&self.tx).into();
+		let tx = core_ser::ser_vec(&tx, core_ser::ProtocolVersion::local())
+			.map_err(|_| ser::Error::CorruptedData)?;
+		writer.write_bytes(&tx)?;
+		writer.write_u8(count_u8(self.participant_data.len())?)?;
+		for p in &self.participant_data {
+			p.write(writer)?;
+		}
+		write_opt(writer, &self.payment_proof, |w, p| p.write(w))
+	}
+}
+
+impl Readable for SlateV2 {
+	fn read(reader: &mut dyn Reader) -> Result<SlateV2, ser::Error> {
+		let version_info = VersionCompatInfoV2 {
+			version: 2,
+			orig_version: reader.read_u16()?,
+			block_header_version: reader.read_u16()?,
+		};
+		let num_participants = reader.read_u8()? as usize;
+		let id = Uuid::from_slice(&reader.read_fixed_bytes(16)?)
+			.map_err(|_| ser::Error::CorruptedData)?;
+		let amount = reader.read_u64()?;
+		let fee = reader.read_u64()?;
+		let height = reader.read_u64()?;
+		let lock_height = reader.read_u64()?;
+		let tx = reader.read_bytes_len_prefix()?;
+		let tx: Transaction =
+			core_ser::deserialize(&mut &tx[..], core_ser::ProtocolVersion::local())
+				.map_err(|_| ser::Error::CorruptedData)?;
+		let mut participant_data = vec![];
+		for _ in 0..reader.read_u8()? {
+			participant_data.push(ParticipantDataV2::read(reader)?);
+		}
+		let payment_proof = read_opt(reader, PaymentInfoV2::read)?;
+		Ok(SlateV2 {
+			version_info,
+			num_participants,
+			id,
+			tx: TransactionV2::from(tx),
+			amount,
+			fee,
+			height,
+			lock_height,
+			participant_data,
+			payment_proof,
+		})
+	}
+}
+
+impl Writeable for ParticipantDataV2 {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.id)?;
+		self.public_blind_excess.write(writer)?;
+		self.public_nonce.write(writer)?;
+		write_opt(writer, &self.part_sig, |w, s| s.write(w))?;
+		write_opt(writer, &self.message, |w, m| w.write_bytes(m))?;
+		write_opt(writer, &self.message_sig, |w, s| s.write(w))
+	}
+}
+
+impl Readable for ParticipantDataV2 {
+	fn read(reader: &mut dyn Reader) -> Result<ParticipantDataV2, ser::Error> {
+		Ok(ParticipantDataV2 {
+			id: reader.read_u64()?,
+			public_blind_excess: PublicKey::read(reader)?,
+			public_nonce: PublicKey::read(reader)?,
+			part_sig: read_opt(reader, Signature::read)?,
+			message: read_opt(reader, read_string)?,
+			message_sig: read_opt(reader, Signature::read)?,
+		})
+	}
+}
+
+impl Writeable for PaymentInfoV2 {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_bytes(&self.sender_address)?;
+		write_opt(writer, &self.receiver_address, |w, a| w.write_bytes(a))?;
+		write_opt(writer, &self.receiver_signature, |w, s| s.write(w))
+	}
+}
+
+impl Readable for PaymentInfoV2 {
+	fn read(reader: &mut dyn Reader) -> Result<PaymentInfoV2, ser::Error> {
+		Ok(PaymentInfoV2 {
+			sender_address: read_string(reader)?,
+			receiver_address: read_opt(reader, read_string)?,
+			receiver_signature: read_opt(reader, Signature::read)?,
+		})
+	}
+}
+
+fn write_opt<W, T, F>(writer: &mut W, value: &Option<T>, write: F) -> Result<(), ser::Error>
+where
+	W: Writer,
+	F: Fn(&mut W, &T) -> Result<(), ser::Error>,
+{
+	match value {
+		Some(v) => {
+			writer.write_u8(1)?;
+			write(writer, v)
+		}
+		None => writer.write_u8(0),
+	}
+}
+
+fn read_opt<T, F>(reader: &mut dyn Reader, read: F) -> Result<Option<T>, ser::Error>
+where
+	F: Fn(&mut dyn Reader) -> Result<T, ser::Error>,
+{
+	match reader.read_u8()? {
+		0 => Ok(None),
+		1 => Ok(Some(read(reader)?)),
+		_ => Err(ser::Error::CorruptedData),
+	}
+}
+
+fn read_string(reader: &mut dyn Reader) -> Result<String, ser::Error> {
+	String::from_utf8(reader.read_bytes_len_prefix()?).map_err(|_| ser::Error::CorruptedData)
+}
+
+fn base58_encode(data: &[u8]) -> String {
+	let zeros = data.iter().take_while(|&&b| b == 0).count();
+	// base58 digits, least significant first
+	let mut digits: Vec<u8> = vec![];
+	for &byte in &data[zeros..] {
+		let mut carry = byte as u32;
+		for d in digits.iter_mut() {
+			carry += (*d as u32) << 8;
+			*d = (carry % 58) as u8;
+			carry /= 58;
+		}
+		while carry > 0 {
+			digits.push((carry % 58) as u8);
+			carry /= 58;
+		}
+	}
+	let mut text = "1".repeat(zeros);
+	for &d in digits.iter().rev() {
+		text.push(BASE58_ALPHABET[d as usize] as char);
+	}
+	text
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+	let zeros = text.bytes().take_while(|&c| c == b'1').count();
+	// bytes, least significant first
+	let mut bytes: Vec<u8> = vec![];
+	for c in text.bytes().skip(zeros) {
+		let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+		for b in bytes.iter_mut() {
+			carry += (*b as u32) * 58;
+			*b = (carry & 0xff) as u8;
+			carry >>= 8;
+		}
+		while carry > 0 {
+			bytes.push((carry & 0xff) as u8);
+			carry >>= 8;
+		}
+	}
+	let mut data = vec![0; zeros];
+	data.extend(bytes.iter().rev());
+	Some(data)
+}
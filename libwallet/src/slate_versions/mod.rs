@@ -19,12 +19,24 @@
 //! remains for future needs
 use crate::slate::Slate;
 use crate::slate_versions::v2::SlateV2;
+use crate::slate_versions::v3::SlateV3;
+use serde::de::{Deserialize, Deserializer, Error};
 
+pub mod binary;
 #[allow(missing_docs)]
 pub mod v2;
+#[allow(missing_docs)]
+pub mod v3;
 
 /// The most recent version of the slate
-pub const CURRENT_SLATE_VERSION: u16 = 2;
+pub const CURRENT_SLATE_VERSION: u16 = 3;
+
+/// The version new slates are sent in, unless the receiver advertises a newer
+/// one or another is asked for, so that older wallets can still read them
+pub const DEFAULT_SLATE_VERSION: u16 = 2;
+
+/// The oldest version of the slate this wallet still reads
+pub const MIN_SLATE_VERSION: u16 = 2;
 
 /// The grin block header this slate is intended to be compatible with
 pub const GRIN_BLOCK_HEADER_VERSION: u16 = 2;
@@ -32,16 +44,20 @@ pub const GRIN_BLOCK_HEADER_VERSION: u16 = 2;
 /// Existing versions of the slate
 #[derive(EnumIter, Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum SlateVersion {
-	/// V2 (most current)
+	/// V3 (most current)
+	V3,
+	/// V2 (Grin 1.1.0 - 2.x)
 	V2,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize)]
 #[serde(untagged)]
-/// Versions are ordered newest to oldest so serde attempts to
-/// deserialize newer versions first, then falls back to older versions.
+/// Versions are ordered newest to oldest. A slate is deserialized as the
+/// version given by its `version_info`.
 pub enum VersionedSlate {
-	/// Current (Grin 1.1.0 - 2.x (current))
+	/// Current
+	V3(SlateV3),
+	/// Grin 1.1.0 - 2.x
 	V2(SlateV2),
 }
 
@@ -49,6 +65,7 @@ impl VersionedSlate {
 	/// Return slate version
 	pub fn version(&self) -> SlateVersion {
 		match *self {
+			VersionedSlate::V3(_) => SlateVersion::V3,
 			VersionedSlate::V2(_) => SlateVersion::V2,
 		}
 	}
@@ -56,6 +73,7 @@ impl VersionedSlate {
 	/// convert this slate type to a specified older version
 	pub fn into_version(slate: Slate, version: SlateVersion) -> VersionedSlate {
 		match version {
+			SlateVersion::V3 => VersionedSlate::V3(slate.into()),
 			SlateVersion::V2 => VersionedSlate::V2(slate.into()),
 			// Left here as a reminder of what needs to be inserted on
 			// the release of a new slate
@@ -67,11 +85,39 @@ impl VersionedSlate {
 			}*/
 		}
 	}
+
+	/// convert this slate to the version it was created in, or converted from
+	pub fn into_orig_version(slate: Slate) -> VersionedSlate {
+		let version = match slate.version_info.orig_version {
+			2 => SlateVersion::V2,
+			_ => SlateVersion::V3,
+		};
+		VersionedSlate::into_version(slate, version)
+	}
+}
+
+impl<'de> Deserialize<'de> for VersionedSlate {
+	fn deserialize<D>(deserializer: D) -> Result<VersionedSlate, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let value = serde_json::Value::deserialize(deserializer)?;
+		match value["version_info"]["version"].as_u64() {
+			Some(3) => serde_json::from_value(value)
+				.map(VersionedSlate::V3)
+				.map_err(D::Error::custom),
+			Some(2) => serde_json::from_value(value)
+				.map(VersionedSlate::V2)
+				.map_err(D::Error::custom),
+			v => Err(D::Error::custom(format!("Unknown slate version {:?}", v))),
+		}
+	}
 }
 
 impl From<VersionedSlate> for Slate {
 	fn from(slate: VersionedSlate) -> Slate {
 		match slate {
+			VersionedSlate::V3(s) => Slate::from(s),
 			VersionedSlate::V2(s) => {
 				let s = SlateV2::from(s);
 				Slate::from(s)
@@ -89,6 +135,7 @@ impl From<VersionedSlate> for Slate {
 impl From<&VersionedSlate> for Slate {
 	fn from(slate: &VersionedSlate) -> Slate {
 		match slate {
+			VersionedSlate::V3(s) => Slate::from(s),
 			VersionedSlate::V2(s) => Slate::from(s),
 		}
 	}
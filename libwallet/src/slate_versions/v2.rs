@@ -73,6 +73,22 @@ pub struct SlateV2 {
 	/// insert their public data here. For now, 0 is sender and 1
 	/// is receiver, though this will change for multi-party
 	pub participant_data: Vec<ParticipantDataV2>,
+	/// Payment proof requested by the sender, signed by the receiver.
+	/// Left out unless a proof is requested, so other wallets see a
+	/// plain V2 slate.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub payment_proof: Option<PaymentInfoV2>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentInfoV2 {
+	/// Sender payment proof address
+	pub sender_address: String,
+	/// Receiver payment proof address, set by the receiver
+	pub receiver_address: Option<String>,
+	/// Receiver signature of the payment statement
+	#[serde(with = "secp_ser::option_sig_serde")]
+	pub receiver_signature: Option<Signature>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
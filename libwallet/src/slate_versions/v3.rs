@@ -0,0 +1,250 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains V3 of the slate
+//! Changes from V2:
+//! * TxKernel features, fee and lock_height removed, being the ones of the slate
+//! * TxKernel excess and excess_sig left out until the kernel is signed, so the
+//!   initial leg carries no placeholder kernel data
+//! * Output range proofs left out of the initial leg, before any participant
+//!   signed: the party who started the slate rebuilds the ones of its outputs
+//!   when finalizing, the others travel with their owner's signature
+//! * lock_height left out when 0
+//! * ParticipantData part_sig, message and message_sig left out when empty
+//! * payment_proof field added, optional
+//! * ttl_cutoff_height field added, optional: the height after which the
+//!   transaction should not be received or finalized
+
+use crate::grin_core::core::transaction::OutputFeatures;
+use crate::grin_core::libtx::secp_ser;
+use crate::grin_keychain::BlindingFactor;
+use crate::grin_util::secp;
+use crate::grin_util::secp::key::PublicKey;
+use crate::grin_util::secp::pedersen::{Commitment, RangeProof};
+use crate::grin_util::secp::Signature;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SlateV3 {
+	/// Versioning info
+	pub version_info: VersionCompatInfoV3,
+	/// The number of participants intended to take part in this transaction
+	pub num_participants: usize,
+	/// Unique transaction ID, selected by sender
+	pub id: Uuid,
+	/// The core transaction data:
+	/// inputs, outputs, kernels, kernel offset
+	pub tx: TransactionV3,
+	/// base amount (excluding fee)
+	#[serde(with = "secp_ser::string_or_u64")]
+	pub amount: u64,
+	/// fee amount
+	#[serde(with = "secp_ser::string_or_u64")]
+	pub fee: u64,
+	/// Block height for the transaction
+	#[serde(with = "secp_ser::string_or_u64")]
+	pub height: u64,
+	/// Lock height
+	#[serde(
+		with = "secp_ser::string_or_u64",
+		default,
+		skip_serializing_if = "is_zero"
+	)]
+	pub lock_height: u64,
+	/// Height after which the transaction expires
+	#[serde(
+		with = "secp_ser::opt_string_or_u64",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub ttl_cutoff_height: Option<u64>,
+	/// Participant data, each participant in the transaction will
+	/// insert their public data here. For now, 0 is sender and 1
+	/// is receiver, though this will change for multi-party
+	pub participant_data: Vec<ParticipantDataV3>,
+	/// Payment proof requested by the sender, signed by the receiver
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub payment_proof: Option<PaymentInfoV3>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentInfoV3 {
+	/// Sender payment proof address
+	pub sender_address: String,
+	/// Receiver payment proof address, set by the receiver
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub receiver_address: Option<String>,
+	/// Receiver signature of the payment statement
+	#[serde(
+		with = "secp_ser::option_sig_serde",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub receiver_signature: Option<Signature>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionCompatInfoV3 {
+	/// The current version of the slate format
+	pub version: u16,
+	/// Original version this slate was converted from
+	pub orig_version: u16,
+	/// Version of grin block header this slate is compatible with
+	pub block_header_version: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParticipantDataV3 {
+	/// Id of participant in the transaction. (For now, 0=sender, 1=rec)
+	#[serde(with = "secp_ser::string_or_u64")]
+	pub id: u64,
+	/// Public key corresponding to private blinding factor
+	#[serde(with = "secp_ser::pubkey_serde")]
+	pub public_blind_excess: PublicKey,
+	/// Public key corresponding to private nonce
+	#[serde(with = "secp_ser::pubkey_serde")]
+	pub public_nonce: PublicKey,
+	/// Public partial signature
+	#[serde(
+		with = "secp_ser::option_sig_serde",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub part_sig: Option<Signature>,
+	/// A message for other participants
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub message: Option<String>,
+	/// Signature, created with private key corresponding to 'public_blind_excess'
+	#[serde(
+		with = "secp_ser::option_sig_serde",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub message_sig: Option<Signature>,
+}
+
+/// A transaction
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionV3 {
+	/// The kernel "offset" k2
+	/// excess is k1G after splitting the key k = k1 + k2
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::blind_from_hex"
+	)]
+	pub offset: BlindingFactor,
+	/// The transaction body - inputs/outputs/kernels
+	pub body: TransactionBodyV3,
+}
+
+/// TransactionBody is a common abstraction for transaction and block
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionBodyV3 {
+	/// List of inputs spent by the transaction.
+	pub inputs: Vec<InputV3>,
+	/// List of outputs the transaction produces.
+	pub outputs: Vec<OutputV3>,
+	/// List of kernels that make up this transaction (usually a single kernel).
+	pub kernels: Vec<TxKernelV3>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputV3 {
+	/// The features of the output being spent.
+	/// We will check maturity for coinbase output.
+	pub features: OutputFeatures,
+	/// The commit referencing the output being spent.
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::commitment_from_hex"
+	)]
+	pub commit: Commitment,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct OutputV3 {
+	/// Options for an output's structure or use
+	pub features: OutputFeatures,
+	/// The homomorphic commitment representing the output amount
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::commitment_from_hex"
+	)]
+	pub commit: Commitment,
+	/// A proof that the commitment is in the right range, left out when
+	/// rebuilt by the owner of the output
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::rangeproof_from_hex",
+		default = "zero_rangeproof",
+		skip_serializing_if = "is_zero_rangeproof"
+	)]
+	pub proof: RangeProof,
+}
+
+/// A kernel, whose features, fee and lock height are the ones of the slate
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxKernelV3 {
+	/// Remainder of the sum of all transaction commitments. If the transaction
+	/// is well formed, amounts components should sum to zero and the excess
+	/// is hence a valid public key.
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::commitment_from_hex",
+		default = "zero_commitment",
+		skip_serializing_if = "is_zero_commitment"
+	)]
+	pub excess: Commitment,
+	/// The signature proving the excess is a valid public key, which signs
+	/// the transaction fee.
+	#[serde(
+		with = "secp_ser::sig_serde",
+		default = "zero_signature",
+		skip_serializing_if = "is_zero_signature"
+	)]
+	pub excess_sig: secp::Signature,
+}
+
+fn is_zero(n: &u64) -> bool {
+	*n == 0
+}
+
+fn zero_commitment() -> Commitment {
+	Commitment::from_vec(vec![0; secp::constants::PEDERSEN_COMMITMENT_SIZE])
+}
+
+fn is_zero_commitment(commit: &Commitment) -> bool {
+	commit.as_ref().iter().all(|b| *b == 0)
+}
+
+fn zero_signature() -> secp::Signature {
+	secp::Signature::from_raw_data(&[0; secp::constants::AGG_SIGNATURE_SIZE]).unwrap()
+}
+
+fn is_zero_signature(sig: &secp::Signature) -> bool {
+	sig.to_raw_data().iter().all(|b| *b == 0)
+}
+
+/// Placeholder of a range proof left out of the slate
+pub(crate) fn zero_rangeproof() -> RangeProof {
+	RangeProof {
+		proof: [0; secp::constants::MAX_PROOF_SIZE],
+		plen: 0,
+	}
+}
+
+/// Whether a range proof is the placeholder of one left out of the slate
+pub(crate) fn is_zero_rangeproof(proof: &RangeProof) -> bool {
+	proof.proof.iter().all(|b| *b == 0)
+}
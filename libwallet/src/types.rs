@@ -31,6 +31,7 @@ use crate::slate_versions::VersionedSlate;
 use crate::wallet_ser as ser;
 use chrono::prelude::*;
 use failure::ResultExt;
+use grin_wallet_config::{ReceivePolicy, StaleTxPolicy, TransportConfig};
 use rustc_serialize::hex::{FromHex, ToHex};
 use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json;
@@ -71,6 +72,14 @@ where
 	/// Return the keychain being used
 	fn keychain(&mut self) -> &mut K;
 
+	/// Switch watch-only mode on or off. A watch-only wallet never loads the
+	/// seed, so only operations that can be served from the cached output
+	/// commitments are available.
+	fn set_watch_only(&mut self, watch_only: bool);
+
+	/// Whether the wallet is in watch-only mode
+	fn is_watch_only(&self) -> bool;
+
 	/// Return the client being used to communicate with the node
 	fn w2n_client(&mut self) -> &mut C;
 
@@ -86,6 +95,21 @@ where
 	/// Return the Grin Relay listener
 	fn grinrelay_listener(&self) -> Result<Box<dyn Listener>, Error>;
 
+	/// Set the policy cancelling the stale outgoing transactions on refresh
+	fn set_stale_tx_policy(&mut self, policy: Option<StaleTxPolicy>);
+
+	/// Return the policy cancelling the stale outgoing transactions, if any
+	fn stale_tx_policy(&self) -> Option<StaleTxPolicy>;
+
+	/// Set the acceptance policy of the incoming payments
+	fn set_receive_policy(&mut self, policy: Option<ReceivePolicy>);
+
+	/// Return the acceptance policy of the incoming payments, if any
+	fn receive_policy(&self) -> Option<ReceivePolicy>;
+
+	/// Return the config section of a transport, if any
+	fn transport_config(&self, name: &str) -> Option<TransportConfig>;
+
 	/// return the commit for caching if allowed, none otherwise
 	fn calc_commit_for_cache(
 		&mut self,
@@ -104,16 +128,18 @@ where
 	fn parent_key_id(&mut self) -> Identifier;
 
 	/// Iterate over all self output data stored by the backend
-	fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = OutputData> + 'a>;
+	fn iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = OutputData> + 'a>, Error>;
 
 	/// Get payment output data entries by slate id
 	fn payment_entries_iter_tx<'a>(
 		&'a self,
 		u: &Uuid,
-	) -> Box<dyn Iterator<Item = PaymentData> + 'a>;
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error>;
 
 	/// Iterate over all payment output data stored by the backend
-	fn payment_entries_iter_all<'a>(&'a self) -> Box<dyn Iterator<Item = PaymentData> + 'a>;
+	fn payment_entries_iter_all<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = PaymentData> + 'a>, Error>;
 
 	/// Get self owned output data by id
 	fn get(&self, id: &Identifier, mmr_index: &Option<u64>) -> Result<OutputData, Error>;
@@ -129,10 +155,12 @@ where
 	) -> Result<Context, Error>;
 
 	/// Iterate over all output data stored by the backend
-	fn tx_log_iter<'a>(&'a self) -> Box<dyn Iterator<Item = TxLogEntry> + 'a>;
+	fn tx_log_iter<'a>(&'a self) -> Result<Box<dyn Iterator<Item = TxLogEntry> + 'a>, Error>;
 
 	/// Iterate over all stored account paths
-	fn acct_path_iter<'a>(&'a self) -> Box<dyn Iterator<Item = AcctPathMapping> + 'a>;
+	fn acct_path_iter<'a>(
+		&'a self,
+	) -> Result<Box<dyn Iterator<Item = AcctPathMapping> + 'a>, Error>;
 
 	/// Gets an account path for a given label
 	fn get_acct_path(&self, label: String) -> Result<Option<AcctPathMapping>, Error>;
@@ -149,9 +177,19 @@ where
 	/// Get a stored transaction proof
 	fn get_stored_tx_proof(&self, uuid: &str) -> Result<Option<TxProof>, Error>;
 
+	/// Stores a payment proof
+	fn store_payment_proof(&self, uuid: &str, proof: &PaymentProof) -> Result<(), Error>;
+
+	/// Get a stored payment proof
+	fn get_stored_payment_proof(&self, uuid: &str) -> Result<Option<PaymentProof>, Error>;
+
 	/// Create a new write batch to update or remove output data
 	fn batch<'a>(&'a mut self) -> Result<Box<dyn WalletOutputBatch<K> + 'a>, Error>;
 
+	/// Current child index of the given parent, i.e. the index the next
+	/// derived child will use
+	fn current_child_index(&mut self, parent_key_id: &Identifier) -> Result<u32, Error>;
+
 	/// Next child ID when we want to create a new output, based on current parent
 	fn next_child<'a>(&mut self) -> Result<Identifier, Error>;
 
@@ -205,10 +243,13 @@ where
 	fn get(&self, id: &Identifier, mmr_index: &Option<u64>) -> Result<OutputData, Error>;
 
 	/// Gets payment output data entries by slate id
-	fn payment_entries_iter_tx(&self, u: &Uuid) -> Box<dyn Iterator<Item = PaymentData>>;
+	fn payment_entries_iter_tx(
+		&self,
+		u: &Uuid,
+	) -> Result<Box<dyn Iterator<Item = PaymentData>>, Error>;
 
 	/// Iterate over all output data stored by the backend
-	fn iter(&self) -> Box<dyn Iterator<Item = OutputData>>;
+	fn iter(&self) -> Result<Box<dyn Iterator<Item = OutputData>>, Error>;
 
 	/// Delete data about an output from the backend
 	fn delete(&mut self, id: &Identifier, mmr_index: &Option<u64>) -> Result<(), Error>;
@@ -226,8 +267,11 @@ where
 	/// get next tx log entry for the parent
 	fn next_tx_log_id(&mut self, parent_key_id: &Identifier) -> Result<u32, Error>;
 
+	/// Save the next tx log entry id to use for the parent
+	fn save_tx_log_id(&mut self, parent_key_id: &Identifier, next_id: u32) -> Result<(), Error>;
+
 	/// Iterate over tx log data stored by the backend
-	fn tx_log_iter(&self) -> Box<dyn Iterator<Item = TxLogEntry>>;
+	fn tx_log_iter(&self) -> Result<Box<dyn Iterator<Item = TxLogEntry>>, Error>;
 
 	/// save a tx log entry
 	fn save_tx_log_entry(&mut self, t: TxLogEntry, parent_id: &Identifier) -> Result<(), Error>;
@@ -236,7 +280,7 @@ where
 	fn save_acct_path(&mut self, mapping: AcctPathMapping) -> Result<(), Error>;
 
 	/// Iterate over account names stored in backend
-	fn acct_path_iter(&self) -> Box<dyn Iterator<Item = AcctPathMapping>>;
+	fn acct_path_iter(&self) -> Result<Box<dyn Iterator<Item = AcctPathMapping>>, Error>;
 
 	/// Save an output as locked in the backend
 	fn lock_output(&mut self, out: &mut OutputData) -> Result<(), Error>;
@@ -775,6 +819,27 @@ impl fmt::Display for TxLogEntryType {
 	}
 }
 
+/// Why the wallet cancelled a transaction by itself
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum TxCancellationReason {
+	/// Not completed before its TTL cutoff height
+	Expired(u64),
+	/// Posted, but still unconfirmed this many blocks after its creation
+	Unconfirmed(u64),
+	/// Not posted this many hours after its creation
+	NotPosted(u64),
+}
+
+impl fmt::Display for TxCancellationReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match *self {
+			TxCancellationReason::Expired(h) => write!(f, "expired at height {}", h),
+			TxCancellationReason::Unconfirmed(b) => write!(f, "unconfirmed after {} blocks", b),
+			TxCancellationReason::NotPosted(h) => write!(f, "not posted after {} hours", h),
+		}
+	}
+}
+
 /// Optional transaction information, recorded when an event happens
 /// to add or remove funds from a wallet. One Transaction log entry
 /// maps to one or many outputs
@@ -824,6 +889,16 @@ pub struct TxLogEntry {
 	pub kernel_excess: Option<String>,
 	/// Whether this transaction has been posted to the node
 	pub posted: Option<bool>,
+	/// Height after which this transaction expires, if it is not completed by then
+	#[serde(
+		default,
+		with = "secp_ser::opt_string_or_u64",
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub ttl_cutoff_height: Option<u64>,
+	/// Why the wallet cancelled this transaction by itself, none if the user did
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cancellation_reason: Option<TxCancellationReason>,
 }
 
 impl ser::Writeable for TxLogEntry {
@@ -861,6 +936,8 @@ impl TxLogEntry {
 			grinrelay_key_path: None,
 			kernel_excess: None,
 			posted: Some(false),
+			ttl_cutoff_height: None,
+			cancellation_reason: None,
 		}
 	}
 
@@ -1005,6 +1082,48 @@ pub struct TxProof {
 	pub prover_signature: Option<Signature>,
 }
 
+/// Payment proof signed by the receiver of a transaction, whatever the
+/// transport the slate went through. The receiver signs the payment
+/// statement, see [`payment_proof_message`](fn.payment_proof_message.html),
+/// with the key of its Grin Relay address.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaymentProof {
+	/// Transaction amount
+	#[serde(with = "secp_ser::string_or_u64")]
+	pub amount: u64,
+	/// Transaction kernel excess
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::commitment_from_hex"
+	)]
+	pub excess: Commitment,
+	/// Sender Grin Relay address
+	pub sender_address: String,
+	/// Receiver Grin Relay address
+	pub receiver_address: String,
+	/// Receiver signature of the payment statement
+	#[serde(with = "secp_ser::sig_serde")]
+	pub receiver_signature: Signature,
+}
+
+impl PaymentProof {
+	/// The payment statement signed by the receiver
+	pub fn message(&self) -> String {
+		payment_proof_message(self.amount, &self.excess, &self.sender_address)
+	}
+}
+
+/// Payment statement signed by the receiver of a transaction: amount, kernel
+/// excess and sender address
+pub fn payment_proof_message(amount: u64, excess: &Commitment, sender_address: &str) -> String {
+	format!(
+		"{}{}{}",
+		amount,
+		excess.as_ref().to_vec().to_hex(),
+		sender_address
+	)
+}
+
 /// Transaction Proof verified result (when using Grin Relay service)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TxProofVerified {
@@ -1022,6 +1141,76 @@ pub struct TxProofVerified {
 	pub confirmed: bool,
 	/// Block height in which this transaction included
 	pub height: Option<u64>,
+	/// Whether this proof was verified without a node, in which case the
+	/// confirmation is either unknown or taken from a kernel inclusion record
+	/// supplied by the user
+	#[serde(default)]
+	pub offline: bool,
+}
+
+/// Outcome of the verification of one proof file in a batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TxProofStatus {
+	/// Valid proof, with its kernel on the chain
+	Confirmed,
+	/// Valid proof, with its kernel not on the chain (yet)
+	Unconfirmed,
+	/// Valid proof, verified offline: whether its kernel is on the chain is
+	/// unknown
+	ConfirmationUnknown,
+	/// Not a valid proof
+	Invalid,
+}
+
+impl fmt::Display for TxProofStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match *self {
+			TxProofStatus::Confirmed => write!(f, "Confirmed"),
+			TxProofStatus::Unconfirmed => write!(f, "Unconfirmed"),
+			TxProofStatus::ConfirmationUnknown => write!(f, "ConfirmationUnknown"),
+			TxProofStatus::Invalid => write!(f, "Invalid"),
+		}
+	}
+}
+
+/// One entry of a batch proof verification report, one per proof file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxProofReport {
+	/// Name of the verified proof file
+	pub file: String,
+	/// Whether the proof is valid, and whether its kernel is on the chain
+	pub status: TxProofStatus,
+	/// Sender's Grin Relay address
+	pub sender: Option<String>,
+	/// Receiver's Grin Relay address
+	pub receiver: Option<String>,
+	/// Transaction amount
+	#[serde(with = "secp_ser::opt_string_or_u64")]
+	pub amount: Option<u64>,
+	/// Transaction kernel excess, hex encoded
+	pub excess: Option<String>,
+	/// Block height in which this transaction included, none if not confirmed
+	/// or if the confirmation is unknown
+	#[serde(with = "secp_ser::opt_string_or_u64")]
+	pub height: Option<u64>,
+	/// Why this proof could not be verified, none if it is valid
+	pub error: Option<String>,
+}
+
+impl TxProofReport {
+	/// A report entry for a proof file which failed to verify
+	pub fn failed(file: &str, error: String) -> TxProofReport {
+		TxProofReport {
+			file: file.to_owned(),
+			status: TxProofStatus::Invalid,
+			sender: None,
+			receiver: None,
+			amount: None,
+			excess: None,
+			height: None,
+			error: Some(error),
+		}
+	}
 }
 
 /// Serializes a TxProof message into a readable string (without those \\\ escapes)
@@ -1041,6 +1230,8 @@ where
 		message: slate,
 		salt: decrypted_message.salt,
 		nonce: decrypted_message.nonce,
+		version: decrypted_message.version,
+		ephemeral_key: decrypted_message.ephemeral_key,
 	};
 
 	serializer.serialize_newtype_struct("message", &exp_message)
@@ -1059,6 +1250,8 @@ where
 				.map_err(|err| Error::custom(err.to_string()))?,
 			salt: exp_message.salt,
 			nonce: exp_message.nonce,
+			version: exp_message.version,
+			ephemeral_key: exp_message.ephemeral_key,
 		};
 
 		Ok(serde_json::to_string(&decrypted_message)
@@ -1082,6 +1275,10 @@ struct TxProofMessage {
 	pub message: String,
 	pub salt: String,
 	pub nonce: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<u8>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ephemeral_key: Option<String>,
 }
 
 /// Dummy wrapper for the TxProofMessage with explicit VersionedSlate.
@@ -1091,4 +1288,8 @@ struct TxProofMessageExp {
 	pub message: VersionedSlate,
 	pub salt: String,
 	pub nonce: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<u8>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ephemeral_key: Option<String>,
 }
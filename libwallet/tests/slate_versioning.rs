@@ -14,7 +14,8 @@
 // limitations under the License.
 
 //! core::libtx specific tests
-//use grin_wallet_libwallet::Slate;
+use grin_wallet_libwallet::{Slate, SlateVersion, VersionedSlate};
+use grin_wallet_util::grin_util::secp;
 
 // test all slate conversions
 /* TODO: Turn back on upon release of new slate version
@@ -96,3 +97,109 @@ fn slate_conversions() {
 	println!("v2 -> v0: {}", s);
 }
 */
+
+// test the compact binary and armored slates
+#[test]
+fn binary_slate() {
+	let v2 = include_str!("slates/v2.slate");
+	let slate = Slate::deserialize_upgrade(&v2).unwrap();
+	let versioned = VersionedSlate::into_version(slate.clone(), SlateVersion::V2);
+	let json = serde_json::to_string(&versioned).unwrap();
+
+	let bytes = versioned.to_binary().unwrap();
+	assert!(bytes.len() < json.len());
+	let res = VersionedSlate::from_binary(&bytes).unwrap();
+	assert_eq!(serde_json::to_string(&res).unwrap(), json);
+
+	// raw binary slates are detected from bytes, not from text
+	let res = Slate::deserialize_upgrade_bytes(&bytes).unwrap();
+	assert_eq!(
+		serde_json::to_string(&res).unwrap(),
+		serde_json::to_string(&slate).unwrap()
+	);
+	let res = Slate::deserialize_upgrade_bytes(v2.as_bytes()).unwrap();
+	assert_eq!(
+		serde_json::to_string(&res).unwrap(),
+		serde_json::to_string(&slate).unwrap()
+	);
+
+	// participant counts that don't fit the binary form are refused
+	let mut v = VersionedSlate::into_version(slate.clone(), SlateVersion::V2);
+	if let VersionedSlate::V2(ref mut s) = v {
+		s.num_participants = 256;
+	}
+	assert!(v.to_binary().is_err());
+
+	// armored slates are detected on deserialization
+	let armored = versioned.to_armored().unwrap();
+	assert!(!armored.contains('\n'));
+	let res = Slate::deserialize_upgrade(&armored).unwrap();
+	assert_eq!(
+		serde_json::to_string(&res).unwrap(),
+		serde_json::to_string(&slate).unwrap()
+	);
+
+	// with their checksum checked
+	let last = armored.chars().last().unwrap();
+	let corrupted = format!(
+		"{}{}",
+		&armored[..armored.len() - 1],
+		if last == '2' { '3' } else { '2' }
+	);
+	assert!(Slate::deserialize_upgrade(&corrupted).is_err());
+}
+
+// test the V3 slate and its negotiation with V2
+#[test]
+fn v3_slate() {
+	let v2 = include_str!("slates/v2.slate");
+	let mut slate = Slate::deserialize_upgrade(&v2).unwrap();
+	let v2_json = serde_json::to_string(&slate).unwrap();
+
+	// V3 drops the redundant kernel fields, and the range proofs of the
+	// initial leg, rebuilt by the sender when finalizing
+	slate.version_info.orig_version = 3;
+	let v3_json = serde_json::to_string(&slate).unwrap();
+	assert!(v3_json.len() < v2_json.len() / 2);
+	assert_eq!(Slate::parse_slate_version(&v3_json).unwrap(), 3);
+	let versioned: VersionedSlate = serde_json::from_str(&v3_json).unwrap();
+	assert_eq!(versioned.version(), SlateVersion::V3);
+	let res = Slate::from(versioned);
+	assert_eq!(res.tx.kernels()[0].features, slate.tx.kernels()[0].features);
+	assert_eq!(res.tx.outputs()[0].commit, slate.tx.outputs()[0].commit);
+	slate.version_info.orig_version = 2;
+	let mut res = Slate::deserialize_upgrade(&v3_json).unwrap();
+	res.version_info.orig_version = 2;
+	res.tx.body.outputs[0].proof = slate.tx.outputs()[0].proof;
+	assert_eq!(serde_json::to_string(&res).unwrap(), v2_json);
+
+	// once signed, the outputs go with their range proofs
+	let mut signed = slate.clone();
+	signed.participant_data[0].part_sig =
+		Some(secp::Signature::from_raw_data(&[1; secp::constants::AGG_SIGNATURE_SIZE]).unwrap());
+	signed.version_info.orig_version = 3;
+	let value = serde_json::to_value(&signed).unwrap();
+	assert!(value["tx"]["body"]["outputs"][0].get("proof").is_some());
+	let value = serde_json::to_value(&slate).unwrap();
+	assert!(value["tx"]["body"]["outputs"][0].get("proof").is_some());
+
+	// V2 slates are still read as such
+	let versioned: VersionedSlate = serde_json::from_str(&v2_json).unwrap();
+	assert_eq!(versioned.version(), SlateVersion::V2);
+
+	// the TTL is only carried by V3
+	slate.ttl_cutoff_height = Some(1000);
+	let res = Slate::from(VersionedSlate::into_version(
+		slate.clone(),
+		SlateVersion::V2,
+	));
+	assert_eq!(res.ttl_cutoff_height, None);
+	let versioned = VersionedSlate::into_version(slate.clone(), SlateVersion::V3);
+	let res: Slate = serde_json::from_str(&serde_json::to_string(&versioned).unwrap()).unwrap();
+	assert_eq!(res.ttl_cutoff_height, Some(1000));
+
+	// as well as by its binary form
+	let res = VersionedSlate::from_binary(&versioned.to_binary().unwrap()).unwrap();
+	assert_eq!(res.version(), SlateVersion::V3);
+	assert_eq!(Slate::from(res).ttl_cutoff_height, Some(1000));
+}
@@ -20,14 +20,18 @@ use crate::util::{Mutex, ZeroingString};
 use clap::ArgMatches;
 use colored::*;
 use failure::Fail;
-use grin_wallet_config::WalletConfig;
+use grin_wallet_config::{WalletBackendType, WalletConfig};
 use grin_wallet_controller::command;
 use grin_wallet_controller::{DateTime, Error, ErrorKind};
-use grin_wallet_impls::{instantiate_wallet, WalletSeed};
+use grin_wallet_impls::{
+	instantiate_wallet, instantiate_wallet_without_seed, instantiate_watch_only_wallet,
+	TransportRegistry, WalletSeed, SEED_FILE,
+};
 use grin_wallet_libwallet::{
 	IssueInvoiceTxArgs, NodeClient, OutputStatus, TxLogEntryType, WalletInst,
 };
 use grin_wallet_relay::grinrelay_address::GRINRELAY_SHORT_ADDRESS_REGEX;
+use grin_wallet_relay::GrinboxAddress;
 use grin_wallet_util::grin_core as core;
 use grin_wallet_util::grin_keychain as keychain;
 use linefeed::terminal::Signal;
@@ -47,6 +51,10 @@ use grin_wallet_libwallet::Slate;
 #[cfg(not(test))]
 use grin_wallet_util::grin_core::core::amount_to_hr_string;
 
+/// Commands which can be served without the wallet keys
+const WATCH_ONLY_COMMANDS: [&str; 6] =
+	["account", "info", "outputs", "payments", "txs", "owner_api"];
+
 macro_rules! usage {
 	( $r:expr ) => {
 		return Err(ErrorKind::Usage($r.usage().to_owned()));
@@ -60,8 +68,8 @@ macro_rules! arg_parse {
 			Ok(res) => res,
 			Err(e) => {
 				return Err(ErrorKind::ArgumentError(format!("{}", e)).into());
-				}
 			}
+		}
 	};
 }
 /// Simple error definition, just so we can return errors from all commands
@@ -128,6 +136,24 @@ fn prompt_password_confirm() -> ZeroingString {
 	first
 }
 
+fn prompt_mnemonic_passphrase() -> Option<ZeroingString> {
+	println!("Please enter the BIP39 passphrase of the wallet. The same recovery phrase with");
+	println!("another passphrase is another wallet, the passphrase must be backed up as well.");
+	let mut first = ZeroingString::from("first");
+	let mut second = ZeroingString::from("second");
+	while first != second {
+		first = prompt_password_stdout("Passphrase: ");
+		second = prompt_password_stdout("Confirm Passphrase: ");
+		if first != second {
+			println!("Passphrase and confirm passphrase don't match. Please try again.");
+		}
+	}
+	match first.is_empty() {
+		true => None,
+		false => Some(first),
+	}
+}
+
 fn prompt_replace_seed() -> Result<bool, ParseError> {
 	let interface = Arc::new(Interface::new("replace_seed")?);
 	interface.set_report_signal(Signal::Interrupt, true);
@@ -246,17 +272,23 @@ fn prompt_pay_invoice(slate: &Slate, method: &str, dest: &str) -> Result<bool, P
 
 // instantiate wallet (needed by most functions)
 
-pub fn inst_wallet(
+pub fn inst_wallet<C>(
 	config: WalletConfig,
 	g_args: &command::GlobalArgs,
-	node_client: impl NodeClient + 'static,
-) -> Result<Arc<Mutex<WalletInst<impl NodeClient + 'static, keychain::ExtKeychain>>>, ParseError> {
-	let res = instantiate_wallet(
-		config.clone(),
-		node_client,
-		&g_args.password.clone().unwrap(),
-		&g_args.account,
-	);
+	node_client: C,
+) -> Result<Arc<Mutex<WalletInst<C, keychain::ExtKeychain>>>, ParseError>
+where
+	C: NodeClient + 'static,
+{
+	let res = match g_args.watch_only {
+		true => instantiate_watch_only_wallet(config.clone(), node_client, &g_args.account),
+		false => instantiate_wallet(
+			config.clone(),
+			node_client,
+			&g_args.password.clone().unwrap(),
+			&g_args.account,
+		),
+	};
 	match res {
 		Ok(p) => Ok(p),
 		Err(e) => {
@@ -397,6 +429,7 @@ pub fn parse_global_args(
 		node_api_secret: config.node_api_secret.clone(),
 		password: password,
 		tls_conf: tls_conf,
+		watch_only: args.is_present("watch_only"),
 	})
 }
 
@@ -417,6 +450,10 @@ pub fn parse_init_args(
 		true => Some(prompt_recovery_phrase()?),
 		false => None,
 	};
+	let mnemonic_passphrase = match args.is_present("passphrase") {
+		true => prompt_mnemonic_passphrase(),
+		false => None,
+	};
 
 	if recovery_phrase.is_some() {
 		println!("Please provide a new password for the recovered wallet");
@@ -434,6 +471,7 @@ pub fn parse_init_args(
 		password: password,
 		config: config.clone(),
 		recovery_phrase: recovery_phrase,
+		mnemonic_passphrase: mnemonic_passphrase,
 		restore: false,
 	})
 }
@@ -443,9 +481,9 @@ pub fn parse_recover_args(
 	g_args: &command::GlobalArgs,
 	args: &ArgMatches,
 ) -> Result<command::RecoverArgs, ParseError> {
-	let (passphrase, recovery_phrase) = {
+	let (passphrase, recovery_phrase, mnemonic_passphrase) = {
 		match args.is_present("display") {
-			true => (prompt_password(&g_args.password), None),
+			true => (prompt_password(&g_args.password), None, None),
 			false => {
 				let cont = {
 					match WalletSeed::seed_file_exists(config.data_file_dir.as_str()) {
@@ -457,14 +495,23 @@ pub fn parse_recover_args(
 					return Err(ParseError::CancelledError);
 				}
 				let phrase = prompt_recovery_phrase()?;
+				let mnemonic_passphrase = match args.is_present("passphrase") {
+					true => prompt_mnemonic_passphrase(),
+					false => None,
+				};
 				println!("Please provide a new password for the recovered wallet");
-				(prompt_password_confirm(), Some(phrase.to_owned()))
+				(
+					prompt_password_confirm(),
+					Some(phrase.to_owned()),
+					mnemonic_passphrase,
+				)
 			}
 		}
 	};
 	Ok(command::RecoverArgs {
 		passphrase,
 		recovery_phrase,
+		mnemonic_passphrase,
 	})
 }
 
@@ -472,6 +519,7 @@ pub fn parse_listen_args(
 	config: &mut WalletConfig,
 	g_args: &mut command::GlobalArgs,
 	args: &ArgMatches,
+	transports: &TransportRegistry,
 ) -> Result<command::ListenArgs, ParseError> {
 	// listen args
 	let pass = match g_args.password.clone() {
@@ -482,9 +530,20 @@ pub fn parse_listen_args(
 	if let Some(port) = args.value_of("port") {
 		config.api_listen_port = port.parse().unwrap();
 	}
-	let method = parse_required(args, "method")?;
+	let methods: Vec<String> = parse_required(args, "method")?
+		.split(',')
+		.map(|m| m.trim().to_owned())
+		.filter(|m| !m.is_empty())
+		.collect();
+	for m in &methods {
+		if !transports.get(m).map_or(false, |t| t.capabilities.listen) {
+			let msg = format!("Unknown listen method: {}", m);
+			return Err(ParseError::ArgumentError(msg));
+		}
+	}
 	Ok(command::ListenArgs {
-		method: method.to_owned(),
+		methods,
+		owner_api: args.is_present("owner_api"),
 	})
 }
 
@@ -496,7 +555,10 @@ pub fn parse_account_args(account_args: &ArgMatches) -> Result<command::AccountA
 	Ok(command::AccountArgs { create: create })
 }
 
-pub fn parse_send_args(args: &ArgMatches) -> Result<command::SendArgs, ParseError> {
+pub fn parse_send_args(
+	args: &ArgMatches,
+	transports: &TransportRegistry,
+) -> Result<command::SendArgs, ParseError> {
 	// amount
 	let amount = parse_required(args, "amount")?;
 	let amount = core::core::amount_from_hr_string(amount);
@@ -546,6 +608,13 @@ pub fn parse_send_args(args: &ArgMatches) -> Result<command::SendArgs, ParseErro
 		}
 	};
 
+	// the scheme of the destination picks the method when none is given
+	if args.occurrences_of("method") == 0 {
+		if let Some(t) = transports.by_scheme(dest) {
+			method = &t.name;
+		}
+	}
+
 	if dest.starts_with("grinrelay://tn1") || dest.starts_with("grinrelay://gn1") {
 		method = "relay";
 	}
@@ -575,6 +644,22 @@ pub fn parse_send_args(args: &ArgMatches) -> Result<command::SendArgs, ParseErro
 		return Err(ParseError::ArgumentError(msg));
 	}
 
+	// armored slate, optionally encrypted
+	let armor = args.is_present("armor");
+	let encrypt_to = args.value_of("encrypt_to").map(|a| a.to_owned());
+	if armor || encrypt_to.is_some() {
+		if method != "file" {
+			let msg = "Armored slates are only written with the file method".to_owned();
+			return Err(ParseError::ArgumentError(msg));
+		}
+	}
+	if let Some(ref addr) = encrypt_to {
+		if GrinboxAddress::from_str(addr).is_err() {
+			let msg = format!("Invalid Grin Relay address to encrypt to: {}", addr);
+			return Err(ParseError::ArgumentError(msg));
+		}
+	}
+
 	// change_outputs
 	let change_outputs = parse_required(args, "change_outputs")?;
 	let change_outputs = parse_u64(change_outputs, "change_outputs")? as usize;
@@ -596,6 +681,12 @@ pub fn parse_send_args(args: &ArgMatches) -> Result<command::SendArgs, ParseErro
 		}
 	};
 
+	// ttl_blocks
+	let ttl_blocks = match args.value_of("ttl_blocks") {
+		Some(b) => Some(parse_u64(b, "ttl_blocks")?),
+		None => None,
+	};
+
 	Ok(command::SendArgs {
 		amount: amount,
 		message: message,
@@ -608,6 +699,9 @@ pub fn parse_send_args(args: &ArgMatches) -> Result<command::SendArgs, ParseErro
 		fluff: fluff,
 		max_outputs: max_outputs,
 		target_slate_version: target_slate_version,
+		armor,
+		encrypt_to,
+		ttl_blocks,
 	})
 }
 
@@ -787,6 +881,21 @@ pub fn parse_check_args(args: &ArgMatches) -> Result<command::CheckArgs, ParseEr
 	})
 }
 
+pub fn parse_convert_args(args: &ArgMatches) -> Result<command::ConvertArgs, ParseError> {
+	let to = match parse_required(args, "to")? {
+		"lmdb" => WalletBackendType::Lmdb,
+		"sqlite" => WalletBackendType::Sqlite,
+		t => {
+			let msg = format!(
+				"Unknown wallet backend '{}', expected 'lmdb' or 'sqlite'",
+				t
+			);
+			return Err(ParseError::ArgumentError(msg));
+		}
+	};
+	Ok(command::ConvertArgs { to })
+}
+
 pub fn parse_pwdupdate_args(_args: &ArgMatches) -> Result<command::PwdUpdateArgs, ParseError> {
 	let mut stdout = std::io::stdout();
 	write!(
@@ -866,6 +975,17 @@ pub fn parse_txs_args(args: &ArgMatches) -> Result<command::TxsArgs, ParseError>
 	})
 }
 
+pub fn proof_batch_command<'a>(
+	args: &'a ArgMatches,
+) -> Result<command::ProofBatchArgs<'a>, ErrorKind> {
+	Ok(command::ProofBatchArgs {
+		dir: required(args, "dir")?,
+		format: required(args, "format")?,
+		output: args.value_of("output"),
+		offline: args.is_present("offline"),
+	})
+}
+
 pub fn proof_command<'a>(args: &'a ArgMatches) -> Result<command::ProofArgs<'a>, ErrorKind> {
 	let proof_args = match args.subcommand() {
 		("export", Some(args)) => command::ProofArgs::Export(
@@ -876,7 +996,11 @@ pub fn proof_command<'a>(args: &'a ArgMatches) -> Result<command::ProofArgs<'a>,
 				Some(msg) => msg,
 			},
 		),
-		("verify", Some(args)) => command::ProofArgs::Verify(required(args, "filename")?),
+		("verify", Some(args)) => command::ProofArgs::Verify(
+			required(args, "filename")?,
+			args.is_present("offline"),
+			args.value_of("kernel"),
+		),
 		(_, _) => {
 			usage!(args);
 		}
@@ -959,12 +1083,46 @@ pub fn wallet_command(
 	node_client.set_node_url(&wallet_config.check_node_api_http_addr);
 	node_client.set_node_api_secret(global_wallet_args.node_api_secret.clone());
 
+	if global_wallet_args.watch_only && !WATCH_ONLY_COMMANDS.contains(&wallet_args.subcommand().0) {
+		let msg = format!(
+			"'{}' is not available in watch-only mode, use one of: {}",
+			wallet_args.subcommand().0,
+			WATCH_ONLY_COMMANDS.join(", ")
+		);
+		return Err(ErrorKind::ArgumentError(msg).into());
+	}
+
+	// the owner api also runs before the wallet seed is created, which its
+	// create_wallet or recover_wallet calls do
+	let without_seed = match wallet_args.subcommand().0 {
+		"owner_api" | "web" => {
+			!global_wallet_args.watch_only
+				&& !Path::new(&wallet_config.data_file_dir)
+					.join(SEED_FILE)
+					.exists()
+		}
+		_ => false,
+	};
+	// verifying a batch of proofs needs no wallet
+	let proof_batch = wallet_args
+		.subcommand_matches("proof")
+		.map_or(false, |a| a.subcommand_name() == Some("batch"));
+
 	// prompt to input password
-	if global_wallet_args.password.is_none() {
+	if global_wallet_args.password.is_none()
+		&& !global_wallet_args.watch_only
+		&& !without_seed
+		&& !proof_batch
+	{
 		global_wallet_args.password = Some(prompt_password(&global_wallet_args.password));
 	}
 
+	// the transports slates are sent over, with their config sections
+	let mut transports = TransportRegistry::new();
+	transports.set_config(wallet_config.transports.clone().unwrap_or_default());
+
 	// closure to instantiate wallet as needed by each subcommand
+	let owner_node_client = node_client.clone();
 	let inst_wallet = || {
 		let res = inst_wallet(wallet_config.clone(), &global_wallet_args, node_client);
 		res.unwrap_or_else(|e| {
@@ -972,6 +1130,17 @@ pub fn wallet_command(
 			std::process::exit(1);
 		})
 	};
+	let inst_wallet_without_seed = || {
+		let res = instantiate_wallet_without_seed(
+			wallet_config.clone(),
+			owner_node_client.clone(),
+			&global_wallet_args.account,
+		);
+		res.unwrap_or_else(|e| {
+			println!("Error instantiating wallet: {}", e);
+			std::process::exit(1);
+		})
+	};
 
 	let res = match wallet_args.subcommand() {
 		("init", Some(args)) => {
@@ -989,30 +1158,45 @@ pub fn wallet_command(
 		("listen", Some(args)) => {
 			let mut c = wallet_config.clone();
 			let mut g = global_wallet_args.clone();
-			let a = arg_parse!(parse_listen_args(&mut c, &mut g, &args));
+			let a = arg_parse!(parse_listen_args(&mut c, &mut g, &args, &transports));
 			command::listen(&c, &a, &g)
 		}
 		("owner_api", Some(_)) => {
 			let mut g = global_wallet_args.clone();
 			g.tls_conf = None;
-			command::owner_api(inst_wallet(), &wallet_config, &g)
+			let wallet = match without_seed {
+				true => inst_wallet_without_seed(),
+				false => inst_wallet(),
+			};
+			command::owner_api(Some(wallet), owner_node_client, &wallet_config, &g)
+		}
+		("web", Some(_)) => {
+			let wallet = match without_seed {
+				true => inst_wallet_without_seed(),
+				false => inst_wallet(),
+			};
+			command::owner_api(
+				Some(wallet),
+				owner_node_client,
+				&wallet_config,
+				&global_wallet_args,
+			)
 		}
-		("web", Some(_)) => command::owner_api(inst_wallet(), &wallet_config, &global_wallet_args),
 		("account", Some(args)) => {
 			let a = arg_parse!(parse_account_args(&args));
 			command::account(inst_wallet(), a)
 		}
 		("send", Some(args)) => {
-			let a = arg_parse!(parse_send_args(&args));
-			command::send(inst_wallet(), a, &wallet_config)
+			let a = arg_parse!(parse_send_args(&args, &transports));
+			command::send(inst_wallet(), a, &wallet_config, &transports)
 		}
 		("receive", Some(args)) => {
 			let a = arg_parse!(parse_receive_args(&args));
-			command::receive(inst_wallet(), &global_wallet_args, a)
+			command::receive(inst_wallet(), &global_wallet_args, a, &wallet_config)
 		}
 		("finalize", Some(args)) => {
 			let a = arg_parse!(parse_finalize_args(&args));
-			command::finalize(inst_wallet(), a)
+			command::finalize(inst_wallet(), a, &wallet_config)
 		}
 		("invoice", Some(args)) => {
 			let a = arg_parse!(parse_issue_invoice_args(&args));
@@ -1024,6 +1208,7 @@ pub fn wallet_command(
 				inst_wallet(),
 				a,
 				wallet_config.dark_background_color_scheme.unwrap_or(true),
+				&transports,
 			)
 		}
 		("info", Some(args)) => {
@@ -1062,6 +1247,10 @@ pub fn wallet_command(
 				wallet_config.dark_background_color_scheme.unwrap_or(true),
 			)
 		}
+		("proof", Some(args)) if proof_batch => command::proof_batch(
+			proof_batch_command(args.subcommand_matches("batch").unwrap())?,
+			&wallet_config,
+		),
 		("proof", Some(args)) => command::proof(
 			inst_wallet(),
 			&global_wallet_args,
@@ -1092,6 +1281,10 @@ pub fn wallet_command(
 			let a = arg_parse!(parse_pwdupdate_args(&args));
 			command::change_password(wallet, &global_wallet_args, a)
 		}
+		("convert", Some(args)) => {
+			let a = arg_parse!(parse_convert_args(&args));
+			command::convert(&wallet_config, &global_wallet_args, a)
+		}
 		_ => {
 			let msg = format!("Unknown wallet command, use 'grin help wallet' for details");
 			return Err(ErrorKind::ArgumentError(msg).into());
@@ -98,7 +98,17 @@ fn real_main() -> i32 {
 	});
 
 	if !cmd::seed_exists(w.members.as_ref().unwrap().wallet.clone()) {
-		if "init" == args.subcommand().0 || "recover" == args.subcommand().0 {
+		// the owner api creates or recovers the seed itself
+		let owner_api = "owner_api" == args.subcommand().0 || "web" == args.subcommand().0;
+		let proof_batch = args
+			.subcommand_matches("proof")
+			.map_or(false, |a| a.subcommand_name() == Some("batch"));
+		if "init" == args.subcommand().0
+			|| "recover" == args.subcommand().0
+			|| args.is_present("watch_only")
+			|| owner_api
+			|| proof_batch
+		{
 		} else {
 			println!("Wallet seed file doesn't exist. Run `grin-wallet init` first");
 			exit(1);